@@ -0,0 +1,128 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Benchmarks for loading a repository with many targets, and for looking up and listing them.
+
+use chrono::{Duration, Utc};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::collections::HashMap;
+use std::num::NonZeroU64;
+use std::path::Path;
+use tempfile::TempDir;
+use test_utils::{dir_url, key, load, root_path};
+use tough::editor::RepositoryEditor;
+use tough::schema::decoded::Decoded;
+use tough::schema::{Hashes, PathSet, Target};
+use tough::FilesystemTransport;
+
+#[path = "../tests/test_utils.rs"]
+mod test_utils;
+
+/// The number of delegated roles in the generated repository.
+const ROLES: usize = 10;
+/// The number of targets listed by each delegated role.
+const TARGETS_PER_ROLE: usize = 10_000;
+
+fn target_name(role: usize, target: usize) -> String {
+    format!("role{}/target-{}.txt", role, target)
+}
+
+/// Returns metadata for a target. The targets are never fetched, so the hash doesn't matter.
+fn target(length: u64) -> Target {
+    Target {
+        length,
+        hashes: Hashes {
+            sha256: Decoded::from(vec![0; 32]),
+            _extra: HashMap::new(),
+        },
+        custom: HashMap::new(),
+        _extra: HashMap::new(),
+    }
+}
+
+/// Writes the metadata of a repository in which "targets" delegates `role{n}/*` to each of the
+/// roles "role0" to "role9", each of which lists 10,000 targets.
+fn create_repo(outdir: &Path) {
+    let expiration = Utc::now().checked_add_signed(Duration::days(21)).unwrap();
+    let version = NonZeroU64::new(1).unwrap();
+
+    let mut editor = RepositoryEditor::<FilesystemTransport>::new(root_path()).unwrap();
+    editor
+        .targets_expires(expiration)
+        .unwrap()
+        .targets_version(version)
+        .unwrap()
+        .snapshot_expires(expiration)
+        .snapshot_version(version)
+        .timestamp_expires(expiration)
+        .timestamp_version(version);
+    for role in 0..ROLES {
+        editor
+            .delegate_role(
+                &format!("role{}", role),
+                &key("targetskey"),
+                PathSet::Paths(vec![format!("role{}/*", role)]),
+                NonZeroU64::new(1).unwrap(),
+                expiration,
+                version,
+            )
+            .unwrap();
+    }
+    editor.sign_targets_editor(&key("snakeoil.pem")).unwrap();
+    for role in 0..ROLES {
+        editor
+            .change_delegated_targets(&format!("role{}", role))
+            .unwrap()
+            .targets_version(version)
+            .unwrap()
+            .targets_expires(expiration)
+            .unwrap();
+        for i in 0..TARGETS_PER_ROLE {
+            editor
+                .add_target(&target_name(role, i), target(i as u64))
+                .unwrap();
+        }
+        editor.sign_targets_editor(&key("targetskey")).unwrap();
+    }
+    editor
+        .change_delegated_targets("targets")
+        .unwrap()
+        .targets_version(version)
+        .unwrap()
+        .targets_expires(expiration)
+        .unwrap();
+
+    let signed_repo = editor.sign(&key("snakeoil.pem")).unwrap();
+    signed_repo.write(outdir.join("metadata")).unwrap();
+}
+
+fn targets(c: &mut Criterion) {
+    let repo_dir = TempDir::new().unwrap();
+    let datastore = TempDir::new().unwrap();
+    create_repo(repo_dir.path());
+    let metadata_base_url = dir_url(repo_dir.path().join("metadata"));
+    let targets_base_url = dir_url(repo_dir.path().join("targets"));
+
+    let mut group = c.benchmark_group("100k targets");
+    group.sample_size(10);
+    group.bench_function("load", |b| {
+        b.iter(|| load(&metadata_base_url, &targets_base_url, datastore.path()))
+    });
+    group.finish();
+
+    let repo = load(&metadata_base_url, &targets_base_url, datastore.path());
+    let last = target_name(ROLES - 1, TARGETS_PER_ROLE - 1);
+    c.bench_function("find_target", |b| {
+        b.iter(|| repo.find_target(black_box(&last)).unwrap())
+    });
+    c.bench_function("all_targets", |b| {
+        b.iter(|| {
+            repo.all_targets()
+                .map(|(_, target)| target.length)
+                .sum::<u64>()
+        })
+    });
+}
+
+criterion_group!(benches, targets);
+criterion_main!(benches);
@@ -0,0 +1,163 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Benchmarks for loading a repository with many delegated roles, which is dominated by verifying
+//! their signatures.
+//!
+//! The keys of a `Delegations` are parsed the first time a role is verified with them and kept for
+//! verifying later roles. "verify, parsing keys for each role" verifies each delegated role with
+//! a copy of the delegations whose keys have not been parsed yet, which is how every role was
+//! verified before the parsed keys were kept; "verify, parsing keys once" verifies all of them
+//! with one copy. "verify together" verifies them all with `Delegations::verify_roles`.
+//!
+//! To see the gain from verifying sibling roles in parallel, run this benchmark without and then
+//! with the `parallel` feature; criterion reports the change from the first run:
+//!
+//! ```text
+//! cargo bench --bench verify
+//! cargo bench --bench verify --features parallel
+//! ```
+
+use chrono::{Duration, Utc};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use std::num::NonZeroU64;
+use std::path::Path;
+use tempfile::TempDir;
+use test_utils::{dir_url, key, load, root_path, test_data};
+use tough::editor::RepositoryEditor;
+use tough::schema::{Delegations, PathSet, Signed, Targets};
+use tough::FilesystemTransport;
+
+#[path = "../tests/test_utils.rs"]
+mod test_utils;
+
+/// The number of delegated roles in the generated repository.
+const ROLES: usize = 200;
+
+/// Writes the metadata of a repository in which "targets" delegates `role{n}/*` to each of the
+/// roles "role0" to "role199", each of which lists one target.
+fn create_repo(outdir: &Path) {
+    let expiration = Utc::now().checked_add_signed(Duration::days(21)).unwrap();
+    let version = NonZeroU64::new(1).unwrap();
+    let target = test_data()
+        .join("tuf-reference-impl")
+        .join("targets")
+        .join("file1.txt");
+
+    let mut editor = RepositoryEditor::<FilesystemTransport>::new(root_path()).unwrap();
+    editor
+        .targets_expires(expiration)
+        .unwrap()
+        .targets_version(version)
+        .unwrap()
+        .snapshot_expires(expiration)
+        .snapshot_version(version)
+        .timestamp_expires(expiration)
+        .timestamp_version(version);
+    for role in 0..ROLES {
+        editor
+            .delegate_role(
+                &format!("role{}", role),
+                &key("targetskey"),
+                PathSet::Paths(vec![format!("role{}/*", role)]),
+                NonZeroU64::new(1).unwrap(),
+                expiration,
+                version,
+            )
+            .unwrap();
+    }
+    editor.sign_targets_editor(&key("snakeoil.pem")).unwrap();
+    for role in 0..ROLES {
+        editor
+            .change_delegated_targets(&format!("role{}", role))
+            .unwrap()
+            .targets_version(version)
+            .unwrap()
+            .targets_expires(expiration)
+            .unwrap()
+            .add_target_path(&target)
+            .unwrap()
+            .sign_targets_editor(&key("targetskey"))
+            .unwrap();
+    }
+    editor
+        .change_delegated_targets("targets")
+        .unwrap()
+        .targets_version(version)
+        .unwrap()
+        .targets_expires(expiration)
+        .unwrap();
+
+    let signed_repo = editor.sign(&key("snakeoil.pem")).unwrap();
+    signed_repo.write(outdir.join("metadata")).unwrap();
+}
+
+fn verify(c: &mut Criterion) {
+    let repo_dir = TempDir::new().unwrap();
+    let datastore = TempDir::new().unwrap();
+    create_repo(repo_dir.path());
+    let metadata_base_url = dir_url(repo_dir.path().join("metadata"));
+    let targets_base_url = dir_url(repo_dir.path().join("targets"));
+
+    let mut group = c.benchmark_group("200 delegated roles");
+    group.sample_size(10);
+    group.bench_function("load", |b| {
+        b.iter(|| load(&metadata_base_url, &targets_base_url, datastore.path()))
+    });
+
+    let read_targets = |name: &str| -> Signed<Targets> {
+        let path = repo_dir
+            .path()
+            .join("metadata")
+            .join(format!("1.{}.json", name));
+        serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap()
+    };
+    let delegations = read_targets("targets").signed.delegations.unwrap();
+    let roles = (0..ROLES)
+        .map(|role| {
+            let name = format!("role{}", role);
+            let targets = read_targets(&name);
+            (name, targets)
+        })
+        .collect::<Vec<_>>();
+    // A copy of `delegations` that has not yet parsed its keys.
+    let unparsed = || {
+        let mut unparsed = Delegations::new();
+        unparsed.keys = delegations.keys.clone();
+        unparsed.roles = delegations.roles.clone();
+        unparsed
+    };
+    group.bench_function("verify, parsing keys for each role", |b| {
+        b.iter_batched(
+            || roles.iter().map(|_| unparsed()).collect::<Vec<_>>(),
+            |copies| {
+                for ((name, role), delegations) in roles.iter().zip(copies) {
+                    delegations.verify_role(role, name).unwrap();
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("verify, parsing keys once", |b| {
+        b.iter_batched(
+            unparsed,
+            |delegations| {
+                for (name, role) in &roles {
+                    delegations.verify_role(role, name).unwrap();
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    let roles = roles
+        .iter()
+        .map(|(name, role)| (name.as_str(), role))
+        .collect::<Vec<_>>();
+    group.bench_function("verify together", |b| {
+        b.iter(|| delegations.verify_roles(&roles).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, verify);
+criterion_main!(benches);
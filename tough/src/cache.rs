@@ -1,11 +1,37 @@
 use crate::error::{self, Result};
 use crate::fetch::{fetch_max_size, fetch_sha256};
-use crate::schema::{RoleType, Target};
+use crate::root_chain::verify_root_update;
+use crate::schema::{Role, RoleType, Root, Signed, Snapshot, SnapshotMerkleProof, Target, Targets};
+use crate::target_name;
 use crate::{Repository, Transport};
-use snafu::{OptionExt, ResultExt};
-use std::fs::OpenOptions;
+use serde::de::DeserializeOwned;
+use snafu::{ensure, OptionExt, ResultExt};
+use std::collections::HashSet;
 use std::io::{Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+use walkdir::WalkDir;
+
+/// The result of checking a cached repository with [`Repository::verify_cache`].
+///
+/// All paths are relative to the metadata or targets directory they were found in (or expected
+/// to be found in).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheReport {
+    /// Files referenced by the repository metadata that are not present in the cache.
+    pub missing: Vec<PathBuf>,
+    /// Files present in the cache whose contents do not match the repository metadata.
+    pub corrupt: Vec<PathBuf>,
+    /// Files present in the cache that are not referenced by the repository metadata.
+    pub extraneous: Vec<PathBuf>,
+}
+
+impl CacheReport {
+    /// Returns `true` if no missing, corrupt or extraneous files were found.
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty() && self.corrupt.is_empty() && self.extraneous.is_empty()
+    }
+}
 
 impl<'a, T: Transport> Repository<'a, T> {
     /// Cache an entire or partial repository to disk, including all required metadata.
@@ -16,6 +42,15 @@ impl<'a, T: Transport> Repository<'a, T> {
     /// * `targets_subset` is the list of targets to include in the cached repo. If no subset is
     /// specified (`None`), then *all* targets are included in the cache.
     /// * `cache_root_chain` specifies whether or not we will cache all versions of `root.json`.
+    /// The versions verified while loading are written exactly as they were verified; older
+    /// versions are fetched and checked to lead up to the trusted root.
+    ///
+    /// Every file is downloaded to a temporary file and then moved into place, so an existing
+    /// file is only replaced once its new contents have been fully fetched and verified.
+    ///
+    /// If the repository was loaded with [`UserTargets`](crate::UserTargets), the targets selected
+    /// by the user-selected targets role are cached. The delegated roles it reaches are cached
+    /// along with the repository's own, since loading the cache fetches both.
     pub fn cache<P1, P2, S>(
         &self,
         metadata_outdir: P1,
@@ -28,33 +63,184 @@ impl<'a, T: Transport> Repository<'a, T> {
         P2: AsRef<Path>,
         S: AsRef<str>,
     {
+        self.cache_impl(
+            metadata_outdir.as_ref(),
+            targets_outdir.as_ref(),
+            targets_subset,
+            cache_root_chain,
+            false,
+        )
+    }
+
+    /// Like [`cache`](Self::cache), but targets that are already present in `targets_outdir`
+    /// with the length and sha256 listed in the repository metadata are not downloaded again.
+    /// Metadata files are always downloaded, because they are small and may have changed.
+    pub fn cache_incremental<P1, P2, S>(
+        &self,
+        metadata_outdir: P1,
+        targets_outdir: P2,
+        targets_subset: Option<&[S]>,
+        cache_root_chain: bool,
+    ) -> Result<()>
+    where
+        P1: AsRef<Path>,
+        P2: AsRef<Path>,
+        S: AsRef<str>,
+    {
+        self.cache_impl(
+            metadata_outdir.as_ref(),
+            targets_outdir.as_ref(),
+            targets_subset,
+            cache_root_chain,
+            true,
+        )
+    }
+
+    /// Checks a cache previously written by [`cache`](Self::cache) against the loaded repository
+    /// metadata, without fetching anything.
+    ///
+    /// * Targets are checked against the length and sha256 listed in the targets metadata.
+    /// * Timestamp, snapshot, targets and delegated targets metadata files are checked against
+    /// the metadata this `Repository` loaded. Versions of `root.json` are checked if they are
+    /// present, but are not reported as missing.
+    ///
+    /// Every target in the repository is expected, so a cache written with a `targets_subset`
+    /// will report the other targets as missing. As with [`cache`](Self::cache), targets are
+    /// expected from the user-selected targets role if there is one. `metadata_dir` and `targets_dir` must be
+    /// different directories.
+    pub fn verify_cache<P1, P2>(&self, metadata_dir: P1, targets_dir: P2) -> Result<CacheReport>
+    where
+        P1: AsRef<Path>,
+        P2: AsRef<Path>,
+    {
+        let metadata_dir = metadata_dir.as_ref();
+        let targets_dir = targets_dir.as_ref();
+        let mut report = CacheReport::default();
+
+        // Check the metadata files.
+        let mut metadata = vec![(
+            "timestamp.json".to_owned(),
+            cached_role_matches(&metadata_dir.join("timestamp.json"), &self.timestamp),
+        )];
+        if let Some(snapshot) = &self.snapshot {
+            let filename = self.snapshot_filename(snapshot);
+            let matches = cached_role_matches(&metadata_dir.join(&filename), snapshot);
+            metadata.push((filename, matches));
+        }
+        metadata.push((
+            self.targets_filename(),
+            cached_role_matches(&metadata_dir.join(self.targets_filename()), &self.targets),
+        ));
+        for (name, filename) in self.snapshot_merkle_proof_filenames() {
+            let matches = self.cached_proof_matches(&metadata_dir.join(&filename), &name);
+            metadata.push((filename, matches));
+        }
+        for name in self.delegated_role_names() {
+            if let Some(filename) = self.delegated_filename(name) {
+                let matches = match self.loaded_delegated_targets(name) {
+                    Some(role) => cached_role_matches(&metadata_dir.join(&filename), role),
+                    None => false,
+                };
+                metadata.push((filename, matches));
+            }
+        }
+        let mut expected_metadata = HashSet::new();
+        for (filename, matches) in metadata {
+            let filename = PathBuf::from(filename);
+            if !metadata_dir.join(&filename).is_file() {
+                report.missing.push(filename.clone());
+            } else if !matches {
+                report.corrupt.push(filename.clone());
+            }
+            expected_metadata.insert(filename);
+        }
+        // The versions of root.json are only cached if `cache_root_chain` was requested, so they
+        // are never missing. If they are present, the versions verified while loading must be
+        // byte-for-byte what was verified, and older versions must at least parse.
+        for ver in 1..=self.root.signed.version.get() {
+            let filename = PathBuf::from(format!("{}.root.json", ver));
+            let path = metadata_dir.join(&filename);
+            let verified = self
+                .root_chain
+                .iter()
+                .find(|verified| verified.signed().signed.version.get() == ver);
+            let matches = match verified {
+                Some(verified) => std::fs::read(&path).ok().as_deref() == Some(verified.buffer()),
+                None => parse_cached::<Signed<Root>>(&path).is_some(),
+            };
+            if path.is_file() && !matches {
+                report.corrupt.push(filename.clone());
+            }
+            expected_metadata.insert(filename);
+        }
+        report
+            .extraneous
+            .extend(extraneous_files(metadata_dir, &expected_metadata)?);
+
+        // Check the targets.
+        let mut expected_targets = HashSet::new();
+        for (name, target) in self.all_targets() {
+            let (_, filename) = self.target_digest_and_filename(target, name);
+            let path = targets_dir.join(&filename);
+            if !path.is_file() {
+                report.missing.push(PathBuf::from(&filename));
+            } else if !cached_target_matches(&path, target) {
+                report.corrupt.push(PathBuf::from(&filename));
+            }
+            expected_targets.insert(PathBuf::from(filename));
+        }
+        report
+            .extraneous
+            .extend(extraneous_files(targets_dir, &expected_targets)?);
+
+        Ok(report)
+    }
+
+    fn cache_impl<S: AsRef<str>>(
+        &self,
+        metadata_outdir: &Path,
+        targets_outdir: &Path,
+        targets_subset: Option<&[S]>,
+        cache_root_chain: bool,
+        incremental: bool,
+    ) -> Result<()> {
         // Create the output directories if the do not exist.
-        std::fs::create_dir_all(metadata_outdir.as_ref()).context(error::CacheDirectoryCreate {
-            path: metadata_outdir.as_ref(),
+        std::fs::create_dir_all(metadata_outdir).context(error::CacheDirectoryCreate {
+            path: metadata_outdir,
         })?;
-        std::fs::create_dir_all(targets_outdir.as_ref()).context(error::CacheDirectoryCreate {
-            path: targets_outdir.as_ref(),
+        std::fs::create_dir_all(targets_outdir).context(error::CacheDirectoryCreate {
+            path: targets_outdir,
         })?;
 
         // Fetch targets and save them to the outdir
         if let Some(target_list) = targets_subset {
             for target_name in target_list.iter() {
-                self.cache_target(&targets_outdir, target_name.as_ref())?;
+                self.cache_target(&targets_outdir, target_name.as_ref(), incremental)?;
             }
         } else {
-            let targets = &self.targets.signed.targets_map();
-            for target_name in targets.keys() {
-                self.cache_target(&targets_outdir, target_name)?;
+            for (target_name, _) in self.all_targets() {
+                self.cache_target(&targets_outdir, target_name, incremental)?;
             }
         }
 
-        // Save the snapshot, targets and timestamp metadata files, and (optionally) the root files.
-        self.cache_file_from_transport(
-            self.snapshot_filename().as_str(),
-            self.max_snapshot_size()?,
-            "timestamp.json",
-            &metadata_outdir,
-        )?;
+        // Save the snapshot (or snapshot Merkle proofs), targets and timestamp metadata files, and
+        // (optionally) the root files.
+        if let Some(snapshot) = &self.snapshot {
+            self.cache_file_from_transport(
+                self.snapshot_filename(snapshot).as_str(),
+                self.max_snapshot_size()?,
+                "timestamp.json",
+                &metadata_outdir,
+            )?;
+        }
+        for (_, filename) in self.snapshot_merkle_proof_filenames() {
+            self.cache_file_from_transport(
+                filename.as_str(),
+                self.limits.max_timestamp_size,
+                "max_timestamp_size argument",
+                metadata_outdir,
+            )?;
+        }
         self.cache_file_from_transport(
             self.targets_filename().as_str(),
             self.limits.max_targets_size,
@@ -68,41 +254,65 @@ impl<'a, T: Transport> Repository<'a, T> {
             &metadata_outdir,
         )?;
 
-        for name in self.targets.signed.role_names() {
+        for name in self.delegated_role_names() {
             if let Some(filename) = self.delegated_filename(name) {
                 self.cache_file_from_transport(
                     filename.as_str(),
-                    self.limits.max_targets_size,
-                    "max_targets_size argument",
+                    self.limits.max_delegated_targets_size,
+                    "max_delegated_targets_size argument",
                     &metadata_outdir,
                 )?;
             }
         }
 
         if cache_root_chain {
-            // Copy all versions of root.json less than or equal to the current version.
-            for ver in (1..=self.root.signed.version.get()).rev() {
-                let root_json_filename = format!("{}.root.json", ver);
-                self.cache_file_from_transport(
-                    root_json_filename.as_str(),
-                    self.limits.max_root_size,
-                    "max_root_size argument",
-                    &metadata_outdir,
-                )?;
-            }
+            self.cache_root_chain(metadata_outdir)?;
         }
         Ok(())
     }
 
     /// Prepends the version number to the snapshot.json filename if using consistent snapshot mode.
-    fn snapshot_filename(&self) -> String {
+    fn snapshot_filename(&self, snapshot: &Signed<Snapshot>) -> String {
         if self.root.signed.consistent_snapshot {
-            format!("{}.snapshot.json", self.snapshot.signed.version)
+            format!("{}.snapshot.json", snapshot.signed.version)
         } else {
             "snapshot.json".to_owned()
         }
     }
 
+    /// Returns the name and proof filename of each loaded targets role, if the repository
+    /// publishes a snapshot Merkle tree.
+    fn snapshot_merkle_proof_filenames(&self) -> Vec<(String, String)> {
+        if self.timestamp.signed.merkle_root.is_none() {
+            return Vec::new();
+        }
+        std::iter::once("targets")
+            .chain(self.delegated_role_names())
+            .map(|name| {
+                let filename = SnapshotMerkleProof::filename(
+                    name,
+                    self.timestamp.signed.version,
+                    self.root.signed.consistent_snapshot,
+                );
+                (name.to_owned(), filename)
+            })
+            .collect()
+    }
+
+    /// Checks that a cached snapshot Merkle proof is for the named role and leads to the Merkle
+    /// root in the loaded timestamp metadata.
+    fn cached_proof_matches(&self, path: &Path, name: &str) -> bool {
+        match (
+            parse_cached::<SnapshotMerkleProof>(path),
+            &self.timestamp.signed.merkle_root,
+        ) {
+            (Some(proof), Some(merkle_root)) => {
+                proof.leaf_contents.name == name && proof.verify(merkle_root).is_ok()
+            }
+            _ => false,
+        }
+    }
+
     /// Prepends the version number to the targets.json filename if using consistent snapshot mode.
     fn targets_filename(&self) -> String {
         if self.root.signed.consistent_snapshot {
@@ -112,16 +322,36 @@ impl<'a, T: Transport> Repository<'a, T> {
         }
     }
 
+    /// Returns the names of the delegated roles that loading the repository reaches: those
+    /// delegated to by the repository's top-level targets role, and by the user-selected targets
+    /// role if there is one.
+    fn delegated_role_names(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        for targets in std::iter::once(&self.targets).chain(self.user_targets()) {
+            for name in targets.signed.role_names() {
+                if !names.contains(&name.as_str()) {
+                    names.push(name.as_str());
+                }
+            }
+        }
+        names
+    }
+
+    /// Returns the loaded metadata of a delegated role named by
+    /// [`delegated_role_names`](Self::delegated_role_names).
+    fn loaded_delegated_targets(&self, name: &str) -> Option<&Signed<Targets>> {
+        std::iter::once(&self.targets)
+            .chain(self.user_targets())
+            .find_map(|targets| targets.signed.delegated_targets(name).ok())
+    }
+
     /// Prepends the version number to the role.json filename if using consistent snapshot mode.
     fn delegated_filename(&self, name: &str) -> Option<String> {
         if self.root.signed.consistent_snapshot {
+            // The loaded role's version was checked against the snapshot (or its proof).
             Some(format!(
                 "{}.{}.json",
-                self.snapshot
-                    .signed
-                    .meta
-                    .get(&format!("{}.json", name))?
-                    .version,
+                self.loaded_delegated_targets(name)?.signed.version,
                 name
             ))
         } else {
@@ -137,6 +367,17 @@ impl<'a, T: Transport> Repository<'a, T> {
         max_size_specifier: &'static str,
         outdir: P,
     ) -> Result<()> {
+        let data = self.fetch_metadata_file(filename, max_size, max_size_specifier)?;
+        write_cached_file(outdir.as_ref().join(&filename), &data)
+    }
+
+    /// Fetches a metadata file using `Transport`.
+    fn fetch_metadata_file(
+        &self,
+        filename: &str,
+        max_size: u64,
+        max_size_specifier: &'static str,
+    ) -> Result<Vec<u8>> {
         let mut read = fetch_max_size(
             self.transport,
             self.metadata_base_url
@@ -148,39 +389,86 @@ impl<'a, T: Transport> Repository<'a, T> {
             max_size,
             max_size_specifier,
         )?;
-        let outpath = outdir.as_ref().join(&filename);
-        let mut file = std::fs::File::create(&outpath).context(error::CacheFileWrite {
-            path: outpath.clone(),
+        let mut data = Vec::new();
+        read.read_to_end(&mut data).context(error::CacheFileRead {
+            url: self.metadata_base_url.to_owned(),
         })?;
-        let mut root_file_data = Vec::new();
-        read.read_to_end(&mut root_file_data)
-            .context(error::CacheFileRead {
-                url: self.metadata_base_url.to_owned(),
-            })?;
-        file.write_all(&root_file_data)
-            .context(error::CacheFileWrite { path: outpath })
+        Ok(data)
+    }
+
+    /// Saves every version of root.json up to the current version to `outdir`.
+    ///
+    /// The versions that were verified while loading the repository are written exactly as they
+    /// were verified. Older versions, from before the trusted root provided to `load`, are fetched
+    /// and checked to form an unbroken chain up to the trusted root: each version must be signed by
+    /// a threshold of its own keys, and the following version by a threshold of its keys.
+    fn cache_root_chain(&self, outdir: &Path) -> Result<()> {
+        for verified in &self.root_chain {
+            let filename = format!("{}.root.json", verified.signed().signed.version);
+            write_cached_file(outdir.join(filename), verified.buffer())?;
+        }
+
+        let mut next = self.root_chain[0].signed().clone();
+        for ver in (1..next.signed.version.get()).rev() {
+            let filename = format!("{}.root.json", ver);
+            let data = self.fetch_metadata_file(
+                &filename,
+                self.limits.max_root_size,
+                "max_root_size argument",
+            )?;
+            let root: Signed<Root> =
+                serde_json::from_slice(&data).context(error::ParseMetadata {
+                    role: RoleType::Root,
+                })?;
+            ensure!(
+                root.signed.version.get() == ver,
+                error::VersionMismatch {
+                    role: RoleType::Root,
+                    fetched: root.signed.version,
+                    expected: ver,
+                }
+            );
+            verify_root_update(&root, &next)?;
+            write_cached_file(outdir.join(filename), &data)?;
+            next = root;
+        }
+        // The oldest version has no predecessor, but must still be signed by its own keys.
+        next.signed
+            .verify_role(&next)
+            .context(error::VerifyMetadata {
+                role: RoleType::Root,
+            })
     }
 
     /// Saves a signed target to the specified `outdir`. Retains the digest-prepended filename if
-    /// consistent snapshots are used.
-    fn cache_target<P: AsRef<Path>>(&self, outdir: P, name: &str) -> Result<()> {
+    /// consistent snapshots are used. If `incremental` is set and the target is already present
+    /// with the expected length and hash, it is left alone.
+    fn cache_target<P: AsRef<Path>>(&self, outdir: P, name: &str, incremental: bool) -> Result<()> {
         let t = self
-            .targets
-            .signed
             .find_target(name)
+            .map(|found| found.target)
+            .ok_or_else(|| crate::schema::Error::TargetNotFound {
+                target_file: name.to_owned(),
+            })
             .context(error::CacheTargetMissing {
                 target_name: name.to_owned(),
             })?;
+        // Target names may be paths, so make sure the name stays within the outdir.
+        target_name::validate(name)?;
         let (sha, filename) = self.target_digest_and_filename(&t, name);
+        let path = outdir.as_ref().join(filename.as_str());
+        if incremental && cached_target_matches(&path, t) {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .context(error::CacheDirectoryCreate { path: parent })?;
+        }
         let mut reader = self.fetch_target(t, &sha, filename.as_str())?;
-        let path = outdir.as_ref().join(filename);
-        let mut f = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .open(&path)
+        let mut f = temp_file_for(&path)?;
+        let _ = std::io::copy(&mut reader, &mut f)
             .context(error::CacheTargetWrite { path: path.clone() })?;
-        let _ = std::io::copy(&mut reader, &mut f).context(error::CacheTargetWrite { path })?;
-        Ok(())
+        persist(f, path)
     }
 
     /// Gets the max size of the snapshot.json file as specified by the timestamp file.
@@ -197,8 +485,8 @@ impl<'a, T: Transport> Repository<'a, T> {
         Ok(snapshot_meta.length)
     }
 
-    /// Prepends the target digest to the name if using consistent snapshots. Returns both the
-    /// digest and the filename.
+    /// Prepends the target digest to the final component of the name if using consistent
+    /// snapshots. Returns both the digest and the filename.
     pub(crate) fn target_digest_and_filename(
         &self,
         target: &Target,
@@ -206,7 +494,10 @@ impl<'a, T: Transport> Repository<'a, T> {
     ) -> (Vec<u8>, String) {
         let sha256 = &target.hashes.sha256.clone().into_vec();
         if self.consistent_snapshot {
-            (sha256.clone(), format!("{}.{}", hex::encode(sha256), name))
+            (
+                sha256.clone(),
+                target_name::consistent_filename(name, sha256),
+            )
         } else {
             (sha256.clone(), name.to_owned())
         }
@@ -234,3 +525,87 @@ impl<'a, T: Transport> Repository<'a, T> {
         )
     }
 }
+
+/// Writes `data` to `path`, replacing any existing file atomically.
+fn write_cached_file(path: PathBuf, data: &[u8]) -> Result<()> {
+    let mut file = temp_file_for(&path)?;
+    file.write_all(data)
+        .context(error::CacheFileWrite { path: path.clone() })?;
+    persist(file, path)
+}
+
+/// Creates a temporary file next to `path`, so that it can later be renamed over `path`.
+fn temp_file_for(path: &Path) -> Result<NamedTempFile> {
+    let parent = path.parent().unwrap_or_else(|| Path::new("."));
+    NamedTempFile::new_in(parent).context(error::CacheFileWrite { path })
+}
+
+/// Atomically replaces `path` with the fully written temporary `file`.
+fn persist(file: NamedTempFile, path: PathBuf) -> Result<()> {
+    file.persist(&path)
+        .context(error::CacheFilePersist { path })?;
+    Ok(())
+}
+
+/// Returns `true` if the file at `path` has the length and sha256 listed for `target`.
+fn cached_target_matches(path: &Path, target: &Target) -> bool {
+    match Target::from_path(path) {
+        Ok(cached) => {
+            cached.length == target.length && cached.hashes.sha256 == target.hashes.sha256
+        }
+        Err(_) => false,
+    }
+}
+
+/// Parses a cached metadata file, returning `None` if it can't be read or parsed.
+fn parse_cached<R: DeserializeOwned>(path: &Path) -> Option<R> {
+    let file = std::fs::File::open(path).ok()?;
+    serde_json::from_reader(file).ok()
+}
+
+/// Returns `true` if the metadata file at `path` is the same signed role as `loaded`.
+fn cached_role_matches<R>(path: &Path, loaded: &Signed<R>) -> bool
+where
+    R: Role + DeserializeOwned,
+{
+    let cached = match parse_cached::<Signed<R>>(path) {
+        Some(cached) => cached,
+        None => return false,
+    };
+    // Compare the canonical form rather than the structs, since loaded targets metadata has its
+    // delegated roles filled in.
+    match (
+        cached.signed.canonical_form(),
+        loaded.signed.canonical_form(),
+    ) {
+        (Ok(cached_form), Ok(loaded_form)) => {
+            cached_form == loaded_form
+                && cached.signatures == loaded.signatures
+                && cached.dsse == loaded.dsse
+        }
+        _ => false,
+    }
+}
+
+/// Returns the files under `dir`, relative to `dir`, that are not in `expected`.
+fn extraneous_files(dir: &Path, expected: &HashSet<PathBuf>) -> Result<Vec<PathBuf>> {
+    let mut extraneous = Vec::new();
+    if !dir.exists() {
+        return Ok(extraneous);
+    }
+    for entry in WalkDir::new(dir) {
+        let entry = entry.context(error::WalkDir { directory: dir })?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative = entry
+            .path()
+            .strip_prefix(dir)
+            .unwrap_or_else(|_| entry.path());
+        if !expected.contains(relative) {
+            extraneous.push(relative.to_owned());
+        }
+    }
+    extraneous.sort();
+    Ok(extraneous)
+}
@@ -0,0 +1,38 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use chrono::{DateTime, Utc};
+use std::fmt::Debug;
+
+/// A trait to abstract over the source of the current time, which [`Repository`] uses to check
+/// metadata expiration.
+///
+/// The time returned by a `Clock` is still recorded in the datastore, and loading fails if it is
+/// ever earlier than the latest recorded time.
+///
+/// [`Repository`]: crate::Repository
+pub trait Clock: Debug {
+    /// Returns the current time.
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// Provides a `Clock` that uses the system clock. This is the clock you should use unless your
+/// system clock cannot be trusted, such as on devices without a real-time clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Provides a `Clock` that always returns the same time. This is mostly useful for tests.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedClock(pub DateTime<Utc>);
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
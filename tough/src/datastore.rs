@@ -2,31 +2,64 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::error::{self, Result};
+use fs2::FileExt;
 use log::debug;
 use serde::Serialize;
 use snafu::ResultExt;
-use std::fs::{self, File};
-use std::io::{ErrorKind, Read};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, ErrorKind, Read, Write};
 use std::path::Path;
 use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use tempfile::NamedTempFile;
+
+/// The name of the file used to lock the datastore against other processes.
+const LOCK_FILE: &str = ".lock";
 
 #[derive(Debug, Clone)]
-pub(crate) struct Datastore<'a>(Arc<RwLock<&'a Path>>);
+pub(crate) struct Datastore<'a> {
+    path: Arc<RwLock<&'a Path>>,
+    history: bool,
+}
 
 impl<'a> Datastore<'a> {
-    pub(crate) fn new(path: &'a Path) -> Self {
-        Self(Arc::new(RwLock::new(path)))
+    pub(crate) fn new(path: &'a Path, history: bool) -> Self {
+        Self {
+            path: Arc::new(RwLock::new(path)),
+            history,
+        }
+    }
+
+    /// Whether newly trusted metadata is recorded in the history log.
+    pub(crate) fn history(&self) -> bool {
+        self.history
     }
 
     // Because we are not actually changing the underlying data in the lock, we can ignore when a
     // lock is poisoned.
 
     fn read(&self) -> RwLockReadGuard<'_, &'a Path> {
-        self.0.read().unwrap_or_else(PoisonError::into_inner)
+        self.path.read().unwrap_or_else(PoisonError::into_inner)
     }
 
     fn write(&self) -> RwLockWriteGuard<'_, &'a Path> {
-        self.0.write().unwrap_or_else(PoisonError::into_inner)
+        self.path.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Takes an exclusive lock on the datastore, blocking until any other process (or other
+    /// `Datastore` for the same directory) holding the lock releases it. The lock is released when
+    /// the returned `DatastoreLock` is dropped.
+    pub(crate) fn lock(&self) -> Result<DatastoreLock> {
+        let path = self.read().join(LOCK_FILE);
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(&path)
+            .context(error::DatastoreLock { path: &path })?;
+        debug!("locking '{}'", path.display());
+        file.lock_exclusive()
+            .context(error::DatastoreLock { path: &path })?;
+        Ok(DatastoreLock(file))
     }
 
     pub(crate) fn reader(&self, file: &str) -> Result<Option<impl Read>> {
@@ -40,16 +73,61 @@ impl<'a> Datastore<'a> {
         }
     }
 
+    /// Writes `value` to `file`. The value is written to a temporary file which is then renamed
+    /// over `file`, so readers never see a partially written file.
     pub(crate) fn create<T: Serialize>(&self, file: &str, value: &T) -> Result<()> {
+        let dir = self.write();
+        let path = dir.join(file);
+        let mut temp =
+            NamedTempFile::new_in(*dir).context(error::DatastoreCreate { path: &path })?;
+        let mut writer = BufWriter::new(&mut temp);
+        serde_json::to_writer_pretty(&mut writer, value).context(error::DatastoreSerialize {
+            what: format!("{} in datastore", file),
+            path: &path,
+        })?;
+        writer
+            .flush()
+            .context(error::DatastoreWrite { path: &path })?;
+        drop(writer);
+        temp.as_file()
+            .sync_all()
+            .context(error::DatastoreWrite { path: &path })?;
+        temp.persist(&path)
+            .context(error::DatastorePersist { path })?;
+        Ok(())
+    }
+
+    /// Appends `value` to `file` as a single line of JSON, creating the file if needed, and
+    /// syncs the file to disk. Returns the line written, without its newline.
+    pub(crate) fn append<T: Serialize>(&self, file: &str, value: &T) -> Result<String> {
         let path = self.write().join(file);
-        serde_json::to_writer_pretty(
-            File::create(&path).context(error::DatastoreCreate { path: &path })?,
-            value,
-        )
-        .context(error::DatastoreSerialize {
+        let mut line = serde_json::to_string(value).context(error::DatastoreSerialize {
             what: format!("{} in datastore", file),
-            path,
-        })
+            path: &path,
+        })?;
+        line.push('\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .context(error::DatastoreCreate { path: &path })?;
+        file.write_all(line.as_bytes())
+            .context(error::DatastoreWrite { path: &path })?;
+        file.sync_all().context(error::DatastoreWrite { path })?;
+        line.pop();
+        Ok(line)
+    }
+
+    /// Returns the size of `file` in bytes, or `None` if it doesn't exist.
+    pub(crate) fn size(&self, file: &str) -> Result<Option<u64>> {
+        let path = self.read().join(file);
+        match fs::metadata(&path) {
+            Ok(metadata) => Ok(Some(metadata.len())),
+            Err(err) => match err.kind() {
+                ErrorKind::NotFound => Ok(None),
+                _ => Err(err).context(error::DatastoreOpen { path: &path }),
+            },
+        }
     }
 
     pub(crate) fn remove(&self, file: &str) -> Result<()> {
@@ -64,3 +142,14 @@ impl<'a> Datastore<'a> {
         }
     }
 }
+
+/// An exclusive lock on a datastore, held until this is dropped.
+#[derive(Debug)]
+pub(crate) struct DatastoreLock(File);
+
+impl Drop for DatastoreLock {
+    fn drop(&mut self) {
+        // Closing the file releases the lock too; this only makes it explicit.
+        let _ = self.0.unlock();
+    }
+}
@@ -4,6 +4,7 @@
 use crate::error::{self, Result};
 use crate::key_source::KeySource;
 use crate::schema::decoded::{Decoded, Hex};
+use crate::schema::key::Key;
 use crate::schema::{Delegations, KeyHolder, RoleId, RoleKeys, Root, Signed, Targets};
 use crate::sign::Sign;
 use snafu::{ensure, OptionExt, ResultExt};
@@ -52,6 +53,14 @@ impl KeyHolder {
         Err(error::Error::SigningKeysNotFound { role })
     }
 
+    /// Returns the public keys, by key ID, that the `KeyHolder` lists
+    pub(crate) fn keys(&self) -> &HashMap<Decoded<Hex>, Key> {
+        match self {
+            Self::Delegations(delegations) => &delegations.keys,
+            Self::Root(root) => &root.keys,
+        }
+    }
+
     /// Verifies the role using `KeyHolder`'s keys
     pub(crate) fn verify_role(&self, targets: &Signed<Targets>, name: &str) -> Result<()> {
         match self {
@@ -5,27 +5,34 @@
 //! Provides a `RepositoryEditor` object for building and editing TUF repositories.
 
 mod keys;
+pub mod prune;
+pub mod publish;
+pub mod refresh;
+mod root;
 pub mod signed;
 pub mod targets;
 mod test;
 
-use crate::editor::signed::{SignedDelegatedTargets, SignedRepository, SignedRole};
-use crate::editor::targets::TargetsEditor;
+use crate::editor::signed::{PendingRole, SignedDelegatedTargets, SignedRepository, SignedRole};
+use crate::editor::targets::{validate_custom_as, CustomValidator, TargetsEditor};
 use crate::error::{self, Result};
 use crate::fetch::fetch_max_size;
 use crate::key_source::KeySource;
 use crate::schema::decoded::{Decoded, Hex};
 use crate::schema::key::Key;
 use crate::schema::{
-    Hashes, KeyHolder, PathSet, Role, RoleType, Root, Signed, Snapshot, SnapshotMeta, Target,
-    Targets, Timestamp, TimestampMeta,
+    spec_version_supported, DelegatedTargets, Hashes, KeyHolder, MerkleLeaf, PathSet, Role,
+    RoleType, Root, Signed, Snapshot, SnapshotMerkleTree, SnapshotMeta, Target, Targets, Timestamp,
+    TimestampMeta, SPEC_VERSION,
 };
+use crate::target_name;
 use crate::transport::Transport;
 use crate::Limits;
 use crate::Repository;
 use chrono::{DateTime, Utc};
 use ring::digest::{SHA256, SHA256_OUTPUT_LEN};
 use ring::rand::SystemRandom;
+use serde::de::DeserializeOwned;
 use serde_json::Value;
 use snafu::{ensure, OptionExt, ResultExt};
 use std::borrow::Cow;
@@ -34,7 +41,7 @@ use std::num::NonZeroU64;
 use std::path::Path;
 use url::Url;
 
-const SPEC_VERSION: &str = "1.0.0";
+pub use crate::editor::root::RootEditor;
 
 /// `RepositoryEditor` contains the various bits of data needed to construct
 /// or edit a TUF repository.
@@ -66,6 +73,10 @@ const SPEC_VERSION: &str = "1.0.0";
 pub struct RepositoryEditor<'a, T: Transport> {
     signed_root: SignedRole<Root>,
 
+    spec_version: Option<String>,
+    dsse: bool,
+    custom_validator: Option<CustomValidator>,
+
     snapshot_version: Option<NonZeroU64>,
     snapshot_expires: Option<DateTime<Utc>>,
     snapshot_extra: Option<HashMap<String, Value>>,
@@ -74,6 +85,8 @@ pub struct RepositoryEditor<'a, T: Transport> {
     timestamp_expires: Option<DateTime<Utc>>,
     timestamp_extra: Option<HashMap<String, Value>>,
 
+    snapshot_merkle: bool,
+
     targets_editor: Option<TargetsEditor<'a, T>>,
 
     /// The signed top level targets, will be None if no top level targets have been signed
@@ -123,6 +136,9 @@ impl<'a, T: Transport> RepositoryEditor<'a, T> {
 
         Ok(RepositoryEditor {
             signed_root,
+            spec_version: None,
+            dsse: false,
+            custom_validator: None,
             targets_editor: Some(editor),
             snapshot_version: None,
             snapshot_expires: None,
@@ -130,6 +146,7 @@ impl<'a, T: Transport> RepositoryEditor<'a, T> {
             timestamp_version: None,
             timestamp_expires: None,
             timestamp_extra: None,
+            snapshot_merkle: false,
             signed_targets: None,
             transport: None,
             limits: None,
@@ -146,7 +163,22 @@ impl<'a, T: Transport> RepositoryEditor<'a, T> {
     {
         let mut editor = RepositoryEditor::new(root_path)?;
         editor.targets(repo.targets)?;
-        editor.snapshot(repo.snapshot.signed)?;
+        // A repository that publishes a snapshot Merkle tree is loaded without its snapshot
+        // metadata, but the editor keeps the snapshot's data, so fetch it.
+        let snapshot = match repo.snapshot {
+            Some(snapshot) => snapshot,
+            None => {
+                crate::fetch_snapshot(
+                    repo.transport,
+                    &repo.root,
+                    &repo.timestamp,
+                    &repo.metadata_base_url,
+                )?
+                .0
+            }
+        };
+        editor.snapshot(snapshot.signed)?;
+        editor.snapshot_merkle = repo.timestamp.signed.merkle_root.is_some();
         editor.timestamp(repo.timestamp.signed)?;
         editor.transport = Some(repo.transport);
         editor.limits = Some(repo.limits);
@@ -188,10 +220,15 @@ impl<'a, T: Transport> RepositoryEditor<'a, T> {
 
         let signed_snapshot = self
             .build_snapshot(&signed_targets, &signed_delegated_targets)
-            .and_then(|snapshot| SignedRole::new(snapshot, &root, keys, &rng))?;
+            .and_then(|snapshot| SignedRole::sign(snapshot, &root, keys, &rng, self.dsse))?;
+        let snapshot_merkle = if self.snapshot_merkle {
+            Some(Self::build_snapshot_merkle(&signed_snapshot)?)
+        } else {
+            None
+        };
         let signed_timestamp = self
-            .build_timestamp(&signed_snapshot)
-            .and_then(|timestamp| SignedRole::new(timestamp, &root, keys, &rng))?;
+            .build_timestamp(&signed_snapshot, snapshot_merkle.as_ref())
+            .and_then(|timestamp| SignedRole::sign(timestamp, &root, keys, &rng, self.dsse))?;
 
         Ok(SignedRepository {
             root: self.signed_root,
@@ -199,13 +236,14 @@ impl<'a, T: Transport> RepositoryEditor<'a, T> {
             snapshot: signed_snapshot,
             timestamp: signed_timestamp,
             delegated_targets: signed_delegated_targets,
+            snapshot_merkle,
         })
     }
 
     /// Add an existing `Targets` struct to the repository.
     pub fn targets(&mut self, targets: Signed<Targets>) -> Result<&mut Self> {
         ensure!(
-            targets.signed.spec_version == SPEC_VERSION,
+            spec_version_supported(&targets.signed.spec_version),
             error::SpecVersion {
                 given: targets.signed.spec_version,
                 supported: SPEC_VERSION
@@ -214,11 +252,15 @@ impl<'a, T: Transport> RepositoryEditor<'a, T> {
         // Save the existing targets
         self.signed_targets = Some(targets.clone());
         // Create a targets editor so that targets can be updated
-        self.targets_editor = Some(TargetsEditor::from_targets(
+        let mut editor = TargetsEditor::from_targets(
             "targets",
             targets.signed,
             KeyHolder::Root(self.signed_root.signed.signed.clone()),
-        ));
+        );
+        editor.spec_version.clone_from(&self.spec_version);
+        editor.dsse = self.dsse;
+        editor.custom_validator = self.custom_validator;
+        self.targets_editor = Some(editor);
         Ok(self)
     }
 
@@ -226,7 +268,7 @@ impl<'a, T: Transport> RepositoryEditor<'a, T> {
     /// is preserved
     pub fn snapshot(&mut self, snapshot: Snapshot) -> Result<&mut Self> {
         ensure!(
-            snapshot.spec_version == SPEC_VERSION,
+            spec_version_supported(&snapshot.spec_version),
             error::SpecVersion {
                 given: snapshot.spec_version,
                 supported: SPEC_VERSION
@@ -240,7 +282,7 @@ impl<'a, T: Transport> RepositoryEditor<'a, T> {
     /// is preserved
     pub fn timestamp(&mut self, timestamp: Timestamp) -> Result<&mut Self> {
         ensure!(
-            timestamp.spec_version == SPEC_VERSION,
+            spec_version_supported(&timestamp.spec_version),
             error::SpecVersion {
                 given: timestamp.spec_version,
                 supported: SPEC_VERSION
@@ -322,6 +364,21 @@ impl<'a, T: Transport> RepositoryEditor<'a, T> {
         Ok((target_name, target))
     }
 
+    /// Builds a target struct for the given path, named by its path relative to `indir` rather
+    /// than by its file name, so that `a/foo.bin` and `b/foo.bin` are different targets. See
+    /// [`target_name_from_path`].
+    pub fn build_target_relative<P1, P2>(indir: P1, target_path: P2) -> Result<(String, Target)>
+    where
+        P1: AsRef<Path>,
+        P2: AsRef<Path>,
+    {
+        let target_path = target_path.as_ref();
+        let target_name = target_name_from_path(indir, target_path)?;
+        let target =
+            Target::from_path(target_path).context(error::TargetFromPath { path: target_path })?;
+        Ok((target_name, target))
+    }
+
     /// Remove all targets from this repo
     pub fn clear_targets(&mut self) -> Result<&mut Self> {
         self.targets_editor_mut()?.clear_targets();
@@ -343,6 +400,11 @@ impl<'a, T: Transport> RepositoryEditor<'a, T> {
     ) -> Result<&mut Self> {
         // Create the new targets using targets editor
         let mut new_targets_editor = TargetsEditor::<'a, T>::new(name);
+        new_targets_editor
+            .spec_version
+            .clone_from(&self.spec_version);
+        new_targets_editor.dsse = self.dsse;
+        new_targets_editor.custom_validator = self.custom_validator;
         // Set the version and expiration
         new_targets_editor.version(version).expires(expiration);
         // Sign the new targets
@@ -373,6 +435,52 @@ impl<'a, T: Transport> RepositoryEditor<'a, T> {
         Ok(self)
     }
 
+    /// Set the `spec_version` written to the metadata this editor builds. Defaults to
+    /// [`SPEC_VERSION`]; other versions must have the same major version.
+    pub fn spec_version(&mut self, spec_version: &str) -> Result<&mut Self> {
+        ensure!(
+            spec_version_supported(spec_version),
+            error::SpecVersion {
+                given: spec_version,
+                supported: SPEC_VERSION
+            }
+        );
+        self.spec_version = Some(spec_version.to_owned());
+        if let Some(targets_editor) = self.targets_editor.as_mut() {
+            targets_editor.spec_version(spec_version)?;
+        }
+        Ok(self)
+    }
+
+    /// Sign the targets, snapshot and timestamp metadata (including delegated targets roles
+    /// edited through this editor) as DSSE envelopes instead of in the TUF JSON format. Clients
+    /// using this library read either format. Defaults to `false`; root metadata is not affected.
+    pub fn dsse(&mut self, dsse: bool) -> &mut Self {
+        self.dsse = dsse;
+        if let Some(targets_editor) = self.targets_editor.as_mut() {
+            targets_editor.dsse(dsse);
+        }
+        self
+    }
+
+    /// Declares the type of the custom metadata of every target, in the top-level targets role and
+    /// in delegated targets roles edited through this editor. See
+    /// [`TargetsEditor::validate_custom`].
+    pub fn validate_custom<C: DeserializeOwned>(&mut self) -> &mut Self {
+        self.custom_validator = Some(validate_custom_as::<C>);
+        if let Some(targets_editor) = self.targets_editor.as_mut() {
+            targets_editor.custom_validator = self.custom_validator;
+        }
+        self
+    }
+
+    /// Returns the `spec_version` to write to new metadata.
+    fn spec_version_or_default(&self) -> String {
+        self.spec_version
+            .clone()
+            .unwrap_or_else(|| SPEC_VERSION.to_string())
+    }
+
     /// Set the `Snapshot` version
     pub fn snapshot_version(&mut self, snapshot_version: NonZeroU64) -> &mut Self {
         self.snapshot_version = Some(snapshot_version);
@@ -409,28 +517,73 @@ impl<'a, T: Transport> RepositoryEditor<'a, T> {
         self
     }
 
+    /// Publish a snapshot Merkle tree (TAP 16) when signing. The root of the tree is listed in the
+    /// timestamp metadata, and a proof for each targets role is written next to the metadata, so
+    /// clients can verify a role without downloading the whole snapshot. The snapshot metadata is
+    /// still written for clients that don't support Merkle trees.
+    pub fn snapshot_merkle(&mut self, snapshot_merkle: bool) -> &mut Self {
+        self.snapshot_merkle = snapshot_merkle;
+        self
+    }
+
     /// Takes the current Targets from `targets_editor` and inserts the role to its proper place in `signed_targets`
     /// Sets `targets_editor` to None
     /// Must be called before `change_delegated_targets()`
     pub fn sign_targets_editor(&mut self, keys: &[Box<dyn KeySource>]) -> Result<&mut Self> {
         if let Some(targets_editor) = self.targets_editor.as_mut() {
-            let (name, targets) = targets_editor.create_signed(keys)?.targets();
-            if name == "targets" {
-                self.signed_targets = Some(targets);
-            } else {
-                self.signed_targets
-                    .as_mut()
-                    .context(error::NoTargets)?
-                    .signed
-                    .delegated_role_mut(&name)
-                    .context(error::DelegateMissing { name })?
-                    .targets = Some(targets);
-            }
+            let signed = targets_editor.create_signed(keys)?;
+            self.insert_signed_targets(signed)?;
         }
         self.targets_editor = None;
         Ok(self)
     }
 
+    /// Builds the `Targets` role from `targets_editor`, without signing it, so that it can be
+    /// signed by parties who each hold some of its keys. See [`PendingRole`].
+    ///
+    /// Once the role has enough signatures, pass it to `finalize_targets_editor()`.
+    pub fn pending_targets_editor(&self) -> Result<PendingRole<DelegatedTargets>> {
+        self.targets_editor
+            .as_ref()
+            .context(error::NoTargets)?
+            .create_pending()
+    }
+
+    /// Finalizes a role created by `pending_targets_editor()` and inserts it to its proper place
+    /// in `signed_targets`, as `sign_targets_editor()` does. This fails unless the role is signed
+    /// by a threshold of its keys.
+    /// Sets `targets_editor` to None
+    pub fn finalize_targets_editor(
+        &mut self,
+        pending: PendingRole<DelegatedTargets>,
+    ) -> Result<&mut Self> {
+        let signed = self
+            .targets_editor
+            .as_ref()
+            .context(error::NoTargets)?
+            .finalize_pending(pending)?;
+        self.insert_signed_targets(signed)?;
+        self.targets_editor = None;
+        Ok(self)
+    }
+
+    /// Inserts a signed `Targets` role to its proper place in `signed_targets`.
+    fn insert_signed_targets(&mut self, signed: Signed<DelegatedTargets>) -> Result<()> {
+        let (name, targets) = signed.targets();
+        if name == "targets" {
+            self.signed_targets = Some(targets);
+        } else {
+            self.signed_targets
+                .as_mut()
+                .context(error::NoTargets)?
+                .signed
+                .delegated_role_mut(&name)
+                .context(error::DelegateMissing { name })?
+                .targets = Some(targets);
+        }
+        Ok(())
+    }
+
     /// Changes the targets refered to in `targets_editor` to role
     /// All `Targets` related calls will now be called on the `Targets` role named `role`
     /// Throws error if the `targets_editor` was not cleared using `sign_targets_editor()`
@@ -464,7 +617,11 @@ impl<'a, T: Transport> RepositoryEditor<'a, T> {
                 .clone();
             (KeyHolder::Delegations(parent), targets.signed)
         };
-        self.targets_editor = Some(TargetsEditor::from_targets(role, targets, key_holder));
+        let mut editor = TargetsEditor::from_targets(role, targets, key_holder);
+        editor.spec_version.clone_from(&self.spec_version);
+        editor.dsse = self.dsse;
+        editor.custom_validator = self.custom_validator;
+        self.targets_editor = Some(editor);
 
         Ok(self)
     }
@@ -634,7 +791,8 @@ impl<'a, T: Transport> RepositoryEditor<'a, T> {
         })?;
         let _extra = self.snapshot_extra.clone().unwrap_or_else(HashMap::new);
 
-        let mut snapshot = Snapshot::new(SPEC_VERSION.to_string(), version, expires);
+        let mut snapshot = Snapshot::new(self.spec_version_or_default(), version, expires);
+        snapshot._extra = _extra;
 
         // Snapshot stores metadata about targets and root
         let targets_meta = Self::snapshot_meta(signed_targets);
@@ -672,8 +830,27 @@ impl<'a, T: Transport> RepositoryEditor<'a, T> {
         }
     }
 
+    /// Build a snapshot Merkle tree with a leaf for each role listed in the snapshot.
+    fn build_snapshot_merkle(signed_snapshot: &SignedRole<Snapshot>) -> Result<SnapshotMerkleTree> {
+        let leaves = signed_snapshot
+            .signed
+            .signed
+            .meta
+            .iter()
+            .map(|(filename, meta)| {
+                let name = filename.strip_suffix(".json").unwrap_or(filename);
+                MerkleLeaf::from_snapshot_meta(name.to_owned(), meta)
+            })
+            .collect();
+        SnapshotMerkleTree::new(leaves).context(error::SnapshotMerkleTree)
+    }
+
     /// Build the `Timestamp` struct
-    fn build_timestamp(&self, signed_snapshot: &SignedRole<Snapshot>) -> Result<Timestamp> {
+    fn build_timestamp(
+        &self,
+        signed_snapshot: &SignedRole<Snapshot>,
+        snapshot_merkle: Option<&SnapshotMerkleTree>,
+    ) -> Result<Timestamp> {
         let version = self.timestamp_version.context(error::Missing {
             field: "timestamp version",
         })?;
@@ -681,13 +858,14 @@ impl<'a, T: Transport> RepositoryEditor<'a, T> {
             field: "timestamp expiration",
         })?;
         let _extra = self.timestamp_extra.clone().unwrap_or_else(HashMap::new);
-        let mut timestamp = Timestamp::new(SPEC_VERSION.to_string(), version, expires);
+        let mut timestamp = Timestamp::new(self.spec_version_or_default(), version, expires);
 
         // Timestamp stores metadata about snapshot
         let snapshot_meta = Self::timestamp_meta(signed_snapshot);
         timestamp
             .meta
             .insert("snapshot.json".to_owned(), snapshot_meta);
+        timestamp.merkle_root = snapshot_merkle.map(|tree| tree.root().to_vec().into());
         timestamp._extra = _extra;
 
         Ok(timestamp)
@@ -711,6 +889,17 @@ impl<'a, T: Transport> RepositoryEditor<'a, T> {
     }
 }
 
+/// Returns the name of the target at `target_path` when named by its path relative to `indir`:
+/// the path components joined by `/`, as in `images/x86_64/root.img`. Fails if the path is not
+/// within `indir`.
+pub fn target_name_from_path<P1, P2>(indir: P1, target_path: P2) -> Result<String>
+where
+    P1: AsRef<Path>,
+    P2: AsRef<Path>,
+{
+    target_name::from_path(indir.as_ref(), target_path.as_ref())
+}
+
 fn parse_url(url: &str) -> Result<Url> {
     let mut url = Cow::from(url);
     if !url.ends_with('/') {
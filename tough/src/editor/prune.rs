@@ -0,0 +1,335 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Provides `prune`, which removes old versions of metadata and targets that are no longer
+//! referenced from a repository's output directories.
+
+use crate::error::{self, Result};
+use crate::schema::{Signed, Snapshot, SnapshotMerkleProof, Targets, Timestamp};
+use crate::target_name;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use snafu::ResultExt;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// The files removed by [`prune`], or that would be removed in a dry run.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PruneReport {
+    /// Metadata files that aren't referenced by the snapshots and Merkle proofs that were kept.
+    pub metadata: Vec<PathBuf>,
+    /// Target files that were only listed by targets metadata that was removed, and with
+    /// [`UnknownTargets::Remove`], target files that aren't listed by any metadata.
+    pub targets: Vec<PathBuf>,
+    /// Target files that aren't listed by any metadata in the metadata directory, and were left
+    /// in place because of [`UnknownTargets::Keep`]. They may belong to another repository that
+    /// shares the targets directory.
+    pub unknown_targets: Vec<PathBuf>,
+}
+
+impl PruneReport {
+    /// Returns true if there was nothing to remove.
+    pub fn is_empty(&self) -> bool {
+        self.metadata.is_empty() && self.targets.is_empty()
+    }
+}
+
+/// What [`prune`] does with files in the targets directory that aren't listed by any of the
+/// repository's metadata, not even by versions that are being removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnknownTargets {
+    /// Leave the files, and list them in [`PruneReport::unknown_targets`]. Use this if the
+    /// targets directory is shared with other repositories.
+    Keep,
+    /// Remove the files.
+    Remove,
+}
+
+/// Removes stale files from the output directories of a repository that uses consistent
+/// snapshots, where each update leaves behind the previous versions of its metadata and targets.
+///
+/// In `metadata_dir`, the `keep` most recent versions of the snapshot are kept, along with the
+/// snapshot that `timestamp.json` refers to. For repositories that publish a snapshot Merkle tree
+/// (TAP 16), the proofs for the `keep` most recent timestamp versions, and for the current
+/// timestamp, are kept instead. The versions of the targets roles that the kept snapshots and
+/// proofs refer to are kept, and every other versioned targets role, snapshot, and proof is
+/// removed. The root chain (`<version>.root.json` and `root.json`) and files without a version
+/// prefix, such as `timestamp.json`, are never removed.
+///
+/// In `targets_dir`, files that are only listed by the targets metadata being removed are
+/// removed, along with any directories left empty. Files that aren't listed by any metadata are
+/// handled as `unknown_targets` says.
+///
+/// `prune` shouldn't run while metadata is being written to `metadata_dir`, since metadata that
+/// isn't referenced yet would be removed; see [`SignedRepository::prune`].
+///
+/// If `dry_run` is true, nothing is removed; the returned report lists the files that would be.
+///
+/// [`SignedRepository::prune`]: crate::editor::signed::SignedRepository::prune
+pub fn prune<P1, P2>(
+    metadata_dir: P1,
+    targets_dir: P2,
+    keep: NonZeroUsize,
+    unknown_targets: UnknownTargets,
+    dry_run: bool,
+) -> Result<PruneReport>
+where
+    P1: AsRef<Path>,
+    P2: AsRef<Path>,
+{
+    Pruner::default().prune(
+        metadata_dir.as_ref(),
+        targets_dir.as_ref(),
+        keep,
+        unknown_targets,
+        dry_run,
+    )
+}
+
+/// Holds the files that must survive pruning regardless of their versions, such as the metadata
+/// of a `SignedRepository` that hasn't been written yet, and the targets it lists.
+#[derive(Debug, Default)]
+pub(crate) struct Pruner {
+    /// Names of metadata files to keep.
+    pub(crate) metadata: HashSet<String>,
+    /// Paths, relative to the targets directory, of target files to keep.
+    pub(crate) targets: HashSet<String>,
+}
+
+/// The metadata files found in a metadata directory, other than the root chain.
+#[derive(Debug, Default)]
+struct MetadataFiles {
+    /// Files that are always kept, such as those without a version prefix.
+    kept: Vec<PathBuf>,
+    /// Snapshots, by version.
+    snapshots: BTreeMap<u64, Vec<PathBuf>>,
+    /// Snapshot Merkle proofs, by the version of the timestamp they were published with.
+    proofs: BTreeMap<u64, Vec<PathBuf>>,
+    /// Targets roles, by filename.
+    roles: BTreeMap<String, PathBuf>,
+}
+
+impl Pruner {
+    /// Marks a target as referenced, under both its plain and consistent snapshot filenames.
+    pub(crate) fn reference_target(&mut self, name: &str, sha256: &[u8]) {
+        insert_target(&mut self.targets, name, sha256);
+    }
+
+    pub(crate) fn prune(
+        mut self,
+        metadata_dir: &Path,
+        targets_dir: &Path,
+        keep: NonZeroUsize,
+        unknown_targets: UnknownTargets,
+        dry_run: bool,
+    ) -> Result<PruneReport> {
+        let mut report = PruneReport::default();
+        let files = self.metadata_files(metadata_dir)?;
+        let mut kept = files.kept;
+
+        // Keep the most recent snapshots and proofs, and the ones the timestamp refers to.
+        let timestamp_path = metadata_dir.join("timestamp.json");
+        let timestamp = if timestamp_path.is_file() {
+            Some(read_json::<Signed<Timestamp>>(&timestamp_path)?.signed)
+        } else {
+            None
+        };
+        let current_snapshot = timestamp
+            .as_ref()
+            .and_then(|timestamp| timestamp.meta.get("snapshot.json"))
+            .map(|meta| meta.version.get());
+        let current_proofs = timestamp.as_ref().map(|timestamp| timestamp.version.get());
+        let mut referenced = HashSet::new();
+        for path in recent(files.snapshots, keep, current_snapshot, &mut report) {
+            let snapshot = read_json::<Signed<Snapshot>>(&path)?.signed;
+            for (name, meta) in &snapshot.meta {
+                referenced.insert(format!("{}.{}", meta.version, name));
+            }
+            kept.push(path);
+        }
+        for path in recent(files.proofs, keep, current_proofs, &mut report) {
+            let proof = read_json::<SnapshotMerkleProof>(&path)?;
+            referenced.insert(format!(
+                "{}.{}.json",
+                proof.leaf_contents.version, proof.leaf_contents.name
+            ));
+            kept.push(path);
+        }
+
+        // Keep the targets roles that are referenced, and note the targets that the rest list.
+        let mut known = HashSet::new();
+        for (filename, path) in files.roles {
+            if referenced.contains(&filename) {
+                kept.push(path);
+            } else {
+                // A removed role that can't be parsed only means fewer targets are known, so fewer
+                // are removed.
+                if let Ok(Some(targets)) = read_targets(&path) {
+                    for (name, target) in &targets.targets {
+                        insert_target(&mut known, name, &target.hashes.sha256);
+                    }
+                }
+                report.metadata.push(path);
+            }
+        }
+
+        // Every target listed by kept targets metadata is referenced.
+        for path in &kept {
+            if let Some(targets) = read_targets(path)? {
+                for (name, target) in &targets.targets {
+                    self.reference_target(name, &target.hashes.sha256);
+                }
+            }
+        }
+
+        if targets_dir.exists() {
+            for entry in WalkDir::new(targets_dir).min_depth(1) {
+                let entry = entry.context(error::WalkDir {
+                    directory: targets_dir,
+                })?;
+                if entry.file_type().is_dir() {
+                    continue;
+                }
+                let name = target_name::from_path(targets_dir, entry.path()).ok();
+                match name {
+                    Some(name) if self.targets.contains(&name) => {}
+                    Some(name) if known.contains(&name) => report.targets.push(entry.into_path()),
+                    _ => match unknown_targets {
+                        UnknownTargets::Keep => report.unknown_targets.push(entry.into_path()),
+                        UnknownTargets::Remove => report.targets.push(entry.into_path()),
+                    },
+                }
+            }
+        }
+
+        report.metadata.sort();
+        report.targets.sort();
+        report.unknown_targets.sort();
+        if !dry_run {
+            for path in report.metadata.iter().chain(&report.targets) {
+                fs::remove_file(path).context(error::FileRemove { path })?;
+            }
+            for path in &report.targets {
+                remove_empty_parents(path, targets_dir);
+            }
+        }
+        Ok(report)
+    }
+
+    /// Sorts the versioned metadata files in `metadata_dir` by kind. Files without a version
+    /// prefix, and the metadata this pruner must keep, are always kept.
+    fn metadata_files(&self, metadata_dir: &Path) -> Result<MetadataFiles> {
+        let mut files = MetadataFiles::default();
+        for entry in WalkDir::new(metadata_dir).min_depth(1).max_depth(1) {
+            let entry = entry.context(error::WalkDir {
+                directory: metadata_dir,
+            })?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            // Files with names that aren't UTF-8 aren't metadata, and are left alone.
+            let filename = entry.file_name().to_str().unwrap_or_default().to_owned();
+            let (version, role) = match parse_metadata_filename(&filename) {
+                // The root chain is never removed, and lists no targets.
+                Some((_, "root")) | None => continue,
+                Some((Some(version), role)) if !self.metadata.contains(&filename) => {
+                    (version, role)
+                }
+                Some(_) => {
+                    files.kept.push(entry.into_path());
+                    continue;
+                }
+            };
+            let path = entry.into_path();
+            if role == "snapshot" {
+                files.snapshots.entry(version).or_default().push(path);
+            } else if role.ends_with("-snapshot") && is_merkle_proof(&path)? {
+                files.proofs.entry(version).or_default().push(path);
+            } else {
+                files.roles.insert(filename, path);
+            }
+        }
+        Ok(files)
+    }
+}
+
+/// Returns the files of the `keep` most recent versions in `versions`, and of the `current`
+/// version, and adds the files of the other versions to the report's metadata to remove.
+fn recent(
+    versions: BTreeMap<u64, Vec<PathBuf>>,
+    keep: NonZeroUsize,
+    current: Option<u64>,
+    report: &mut PruneReport,
+) -> Vec<PathBuf> {
+    let mut kept = Vec::new();
+    for (index, (version, paths)) in versions.into_iter().rev().enumerate() {
+        if index < keep.get() || Some(version) == current {
+            kept.extend(paths);
+        } else {
+            report.metadata.extend(paths);
+        }
+    }
+    kept
+}
+
+/// Returns true if the file at `path` holds a snapshot Merkle proof.
+fn is_merkle_proof(path: &Path) -> Result<bool> {
+    let data = fs::read(path).context(error::FileRead { path })?;
+    Ok(serde_json::from_slice::<SnapshotMerkleProof>(&data).is_ok())
+}
+
+/// Reads the metadata file at `path`, and returns its targets role, if it holds one. Fails if the
+/// file can't be parsed, so that a damaged targets role can't cause the targets it lists to be
+/// removed.
+fn read_targets(path: &Path) -> Result<Option<Targets>> {
+    let signed = match read_json::<Signed<Value>>(path) {
+        Ok(signed) => signed,
+        // Snapshot Merkle proofs are published alongside the roles they prove.
+        Err(_) if is_merkle_proof(path)? => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    if signed.signed.get("_type").and_then(Value::as_str) != Some("targets") {
+        return Ok(None);
+    }
+    serde_json::from_value(signed.signed)
+        .map(Some)
+        .context(error::FileParseJson { path })
+}
+
+/// Reads and parses the JSON file at `path`.
+fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let data = fs::read(path).context(error::FileRead { path })?;
+    serde_json::from_slice(&data).context(error::FileParseJson { path })
+}
+
+/// Adds a target's plain and consistent snapshot filenames to `targets`.
+fn insert_target(targets: &mut HashSet<String>, name: &str, sha256: &[u8]) {
+    targets.insert(target_name::consistent_filename(name, sha256));
+    targets.insert(name.to_owned());
+}
+
+/// Splits a metadata filename like `3.snapshot.json` into its version, if it has one, and role
+/// name. Returns `None` for files that aren't JSON.
+fn parse_metadata_filename(filename: &str) -> Option<(Option<u64>, &str)> {
+    let role = filename.strip_suffix(".json")?;
+    Some(match role.split_once('.') {
+        Some((version, name)) => match version.parse::<u64>() {
+            Ok(version) => (Some(version), name),
+            Err(_) => (None, role),
+        },
+        None => (None, role),
+    })
+}
+
+/// Removes the directories between `path` and `root` that are left empty.
+fn remove_empty_parents(path: &Path, root: &Path) {
+    for dir in path.ancestors().skip(1) {
+        // `remove_dir` only removes empty directories.
+        if dir == root || !dir.starts_with(root) || fs::remove_dir(dir).is_err() {
+            break;
+        }
+    }
+}
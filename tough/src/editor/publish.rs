@@ -0,0 +1,263 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Provides a `Publisher` for writing a `SignedRepository` into a live repository, in an order
+//! that, for repositories using consistent snapshots, never lets a client see metadata referring
+//! to files that haven't been written yet.
+
+use crate::editor::signed::{write_atomic, PathExists, SignedRepository};
+use crate::error::{self, Result};
+use crate::{Repository, Settings, Transport};
+use snafu::{OptionExt, ResultExt};
+use std::fs::{self, File};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// `Publisher` writes a [`SignedRepository`] and its targets to the directories a repository is
+/// served from, while clients may be reading them.
+///
+/// The target files are written first, since no metadata refers to them yet. Then the metadata is
+/// written as by [`SignedRepository::write`]: each file is written to a temporary file and
+/// renamed into place, with the targets metadata before the snapshot, and the timestamp last.
+///
+/// If a staging directory is given with [`stage`](Publisher::stage), it is emptied, and the
+/// metadata is written there first and loaded with a client, falling back to the published
+/// metadata for roles that weren't re-signed. The client trusts the latest root already published
+/// in the metadata directory, if there is one, and updates to the staged root as a real client
+/// would. Only if the client accepts it is the metadata promoted, in the same order, to the
+/// metadata directory. The staging directory is left in place afterward.
+///
+/// ```no_run
+/// # use tough::editor::publish::Publisher;
+/// # use tough::editor::signed::{PathExists, SignedRepository};
+/// # fn publish(signed_repo: &SignedRepository) -> tough::error::Result<()> {
+/// Publisher::new(signed_repo, "repo/metadata")
+///     .link_targets("build/targets", "repo/targets", PathExists::Skip)
+///     .stage("staging/metadata")
+///     .publish()
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Publisher<'a> {
+    repo: &'a SignedRepository,
+    metadata_dir: PathBuf,
+    targets: Option<PublishTargets>,
+    staging_dir: Option<PathBuf>,
+}
+
+/// The target files to publish, and how.
+#[derive(Debug)]
+struct PublishTargets {
+    indir: PathBuf,
+    outdir: PathBuf,
+    link: bool,
+    replace_behavior: PathExists,
+}
+
+impl<'a> Publisher<'a> {
+    /// Creates a `Publisher` that writes the metadata of `repo` to `metadata_dir`.
+    pub fn new<P: AsRef<Path>>(repo: &'a SignedRepository, metadata_dir: P) -> Self {
+        Self {
+            repo,
+            metadata_dir: metadata_dir.as_ref().to_owned(),
+            targets: None,
+            staging_dir: None,
+        }
+    }
+
+    /// Symlinks the targets found in `indir` into `outdir` before any metadata is written, as
+    /// with [`SignedRepository::link_targets`].
+    pub fn link_targets<P1, P2>(
+        &mut self,
+        indir: P1,
+        outdir: P2,
+        replace_behavior: PathExists,
+    ) -> &mut Self
+    where
+        P1: AsRef<Path>,
+        P2: AsRef<Path>,
+    {
+        self.targets(indir.as_ref(), outdir.as_ref(), true, replace_behavior)
+    }
+
+    /// Copies the targets found in `indir` into `outdir` before any metadata is written, as with
+    /// [`SignedRepository::copy_targets`].
+    pub fn copy_targets<P1, P2>(
+        &mut self,
+        indir: P1,
+        outdir: P2,
+        replace_behavior: PathExists,
+    ) -> &mut Self
+    where
+        P1: AsRef<Path>,
+        P2: AsRef<Path>,
+    {
+        self.targets(indir.as_ref(), outdir.as_ref(), false, replace_behavior)
+    }
+
+    fn targets(
+        &mut self,
+        indir: &Path,
+        outdir: &Path,
+        link: bool,
+        replace_behavior: PathExists,
+    ) -> &mut Self {
+        self.targets = Some(PublishTargets {
+            indir: indir.to_owned(),
+            outdir: outdir.to_owned(),
+            link,
+            replace_behavior,
+        });
+        self
+    }
+
+    /// Writes the metadata to `staging_dir`, and verifies it with a client, before promoting it
+    /// to the metadata directory. Anything already in `staging_dir` is removed when publishing.
+    pub fn stage<P: AsRef<Path>>(&mut self, staging_dir: P) -> &mut Self {
+        self.staging_dir = Some(staging_dir.as_ref().to_owned());
+        self
+    }
+
+    /// Publishes the targets, then the metadata.
+    pub fn publish(&self) -> Result<()> {
+        if let Some(targets) = &self.targets {
+            if targets.link {
+                self.repo.link_targets(
+                    &targets.indir,
+                    &targets.outdir,
+                    targets.replace_behavior,
+                )?;
+            } else {
+                self.repo.copy_targets(
+                    &targets.indir,
+                    &targets.outdir,
+                    targets.replace_behavior,
+                )?;
+            }
+        }
+
+        match &self.staging_dir {
+            Some(staging_dir) => self.publish_staged(staging_dir),
+            None => self.repo.write(&self.metadata_dir),
+        }
+    }
+
+    /// Writes the metadata to `staging_dir`, verifies it, and promotes it.
+    fn publish_staged(&self, staging_dir: &Path) -> Result<()> {
+        // Files left from an earlier attempt would be read in place of published metadata that
+        // isn't staged this time, so start from an empty staging directory.
+        match fs::remove_dir_all(staging_dir) {
+            Err(err) if err.kind() != ErrorKind::NotFound => {
+                return Err(err).context(error::FileRemove { path: staging_dir });
+            }
+            _ => {}
+        }
+        self.repo.write(staging_dir)?;
+        self.verify_staged(staging_dir)?;
+
+        fs::create_dir_all(&self.metadata_dir).context(error::DirCreate {
+            path: &self.metadata_dir,
+        })?;
+        for filename in self.repo.metadata_filenames() {
+            let path = staging_dir.join(&filename);
+            let data = fs::read(&path).context(error::FileRead { path })?;
+            write_atomic(&self.metadata_dir.join(&filename), &data)?;
+        }
+        Ok(())
+    }
+
+    /// Loads the staged metadata with a client that trusts the latest published root, or the
+    /// repository's own root if none has been published yet.
+    fn verify_staged(&self, staging_dir: &Path) -> Result<()> {
+        let root = match self.latest_published_root()? {
+            Some(path) => fs::read(&path).context(error::FileRead { path })?,
+            None => self.repo.root.buffer().clone(),
+        };
+        let staging_dir =
+            fs::canonicalize(staging_dir).context(error::AbsolutePath { path: staging_dir })?;
+        let metadata_base_url = Url::from_directory_path(&staging_dir)
+            .ok() // dump unhelpful `()` error
+            .context(error::FileUrl { path: &staging_dir })?;
+        let datastore = tempfile::tempdir().context(error::DatastoreCreate {
+            path: std::env::temp_dir(),
+        })?;
+        let transport = StagedTransport {
+            staging_dir: &staging_dir,
+            metadata_dir: &self.metadata_dir,
+        };
+        Repository::load(
+            &transport,
+            Settings::new(
+                root.as_slice(),
+                datastore.path(),
+                metadata_base_url.as_str(),
+                // Targets aren't loaded, so the metadata URL stands in for the targets URL.
+                metadata_base_url.as_str(),
+            ),
+        )?;
+        Ok(())
+    }
+
+    /// Finds the root with the highest version in the metadata directory, if any.
+    fn latest_published_root(&self) -> Result<Option<PathBuf>> {
+        let entries = match fs::read_dir(&self.metadata_dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(err).context(error::DirRead {
+                    path: &self.metadata_dir,
+                })
+            }
+        };
+        let mut roots = Vec::new();
+        for entry in entries {
+            let entry = entry.context(error::DirRead {
+                path: &self.metadata_dir,
+            })?;
+            let version = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.strip_suffix(".root.json"))
+                .and_then(|version| version.parse::<u64>().ok());
+            if let Some(version) = version {
+                roots.push((version, entry.path()));
+            }
+        }
+        Ok(roots
+            .into_iter()
+            .max_by_key(|(version, _)| *version)
+            .map(|(_, path)| path))
+    }
+}
+
+/// A `Transport` that reads staged metadata, and falls back to the published metadata for files
+/// that weren't staged, such as delegated roles that weren't re-signed.
+#[derive(Debug)]
+struct StagedTransport<'a> {
+    staging_dir: &'a Path,
+    metadata_dir: &'a Path,
+}
+
+impl Transport for StagedTransport<'_> {
+    type Stream = File;
+    type Error = io::Error;
+
+    fn fetch(&self, url: Url) -> std::result::Result<Self::Stream, Self::Error> {
+        let path = url.to_file_path().map_err(|()| {
+            io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("unexpected URL for staged metadata: {}", url.as_str()),
+            )
+        })?;
+        match File::open(&path) {
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                match path.strip_prefix(self.staging_dir) {
+                    Ok(relative) => File::open(self.metadata_dir.join(relative)),
+                    Err(_) => Err(err),
+                }
+            }
+            result => result,
+        }
+    }
+}
@@ -0,0 +1,233 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Provides a `RefreshEditor` for re-signing the timestamp, and optionally the snapshot, of an
+//! existing repository without rebuilding it.
+
+use crate::editor::signed::SignedRole;
+use crate::error::{self, Result};
+use crate::key_source::KeySource;
+use crate::schema::{
+    Hashes, KeyHolder, RoleType, Root, Signed, Snapshot, Timestamp, TimestampMeta,
+};
+use crate::{Repository, Transport};
+use chrono::{DateTime, Utc};
+use ring::rand::SystemRandom;
+use snafu::{ensure, OptionExt};
+use std::collections::HashMap;
+use std::num::NonZeroU64;
+use std::path::Path;
+
+/// `RefreshEditor` re-signs the timestamp of a loaded repository, and optionally its snapshot,
+/// leaving every other role untouched. Only the keys for the roles being re-signed are needed, so
+/// a timestamp can be refreshed by an online signer while the other keys stay offline.
+///
+/// The new roles keep everything from the loaded roles except their versions and expirations.
+/// Expirations must be set for each role being signed; versions default to one more than the
+/// loaded version, and versions that are set must be greater than the loaded version.
+///
+/// Repositories that publish a snapshot Merkle tree (TAP 16) aren't supported, because the proofs
+/// are published alongside the timestamp and must be rebuilt from the whole repository.
+#[derive(Debug, Clone)]
+pub struct RefreshEditor {
+    root: Signed<Root>,
+    snapshot: Signed<Snapshot>,
+    timestamp: Signed<Timestamp>,
+
+    snapshot_version: Option<NonZeroU64>,
+    snapshot_expires: Option<DateTime<Utc>>,
+    timestamp_version: Option<NonZeroU64>,
+    timestamp_expires: Option<DateTime<Utc>>,
+}
+
+impl RefreshEditor {
+    /// Creates a `RefreshEditor` for the current root, snapshot, and timestamp of `repo`.
+    pub fn from_repo<T: Transport>(repo: &Repository<'_, T>) -> Result<Self> {
+        ensure!(
+            repo.timestamp().signed.merkle_root.is_none(),
+            error::RefreshSnapshotMerkle
+        );
+        let snapshot = repo.snapshot().context(error::RefreshSnapshotMerkle)?;
+        Ok(Self {
+            root: repo.root().clone(),
+            snapshot: snapshot.clone(),
+            timestamp: repo.timestamp().clone(),
+            snapshot_version: None,
+            snapshot_expires: None,
+            timestamp_version: None,
+            timestamp_expires: None,
+        })
+    }
+
+    /// Set the version of the snapshot, if it is re-signed.
+    pub fn snapshot_version(&mut self, snapshot_version: NonZeroU64) -> &mut Self {
+        self.snapshot_version = Some(snapshot_version);
+        self
+    }
+
+    /// Set the expiration of the snapshot, if it is re-signed.
+    pub fn snapshot_expires(&mut self, snapshot_expires: DateTime<Utc>) -> &mut Self {
+        self.snapshot_expires = Some(snapshot_expires);
+        self
+    }
+
+    /// Set the version of the timestamp.
+    pub fn timestamp_version(&mut self, timestamp_version: NonZeroU64) -> &mut Self {
+        self.timestamp_version = Some(timestamp_version);
+        self
+    }
+
+    /// Set the expiration of the timestamp.
+    pub fn timestamp_expires(&mut self, timestamp_expires: DateTime<Utc>) -> &mut Self {
+        self.timestamp_expires = Some(timestamp_expires);
+        self
+    }
+
+    /// Re-signs the timestamp with `keys`, keeping the current snapshot.
+    pub fn sign_timestamp(&self, keys: &[Box<dyn KeySource>]) -> Result<SignedRefresh> {
+        let rng = SystemRandom::new();
+        let mut timestamp = self.timestamp.signed.clone();
+        timestamp.version = new_version(
+            RoleType::Timestamp,
+            timestamp.version,
+            self.timestamp_version,
+        )?;
+        timestamp.expires = self.timestamp_expires.context(error::Missing {
+            field: "timestamp expiration",
+        })?;
+        let timestamp = SignedRole::sign(
+            timestamp,
+            &self.key_holder(),
+            keys,
+            &rng,
+            self.timestamp.dsse.is_some(),
+        )?;
+
+        Ok(SignedRefresh {
+            snapshot: None,
+            timestamp,
+            consistent_snapshot: self.root.signed.consistent_snapshot,
+        })
+    }
+
+    /// Re-signs the snapshot and then the timestamp with `keys`, keeping the current targets.
+    pub fn sign_snapshot_and_timestamp(
+        &self,
+        keys: &[Box<dyn KeySource>],
+    ) -> Result<SignedRefresh> {
+        let rng = SystemRandom::new();
+        let key_holder = self.key_holder();
+
+        let mut snapshot = self.snapshot.signed.clone();
+        snapshot.version =
+            new_version(RoleType::Snapshot, snapshot.version, self.snapshot_version)?;
+        snapshot.expires = self.snapshot_expires.context(error::Missing {
+            field: "snapshot expiration",
+        })?;
+        let snapshot = SignedRole::sign(
+            snapshot,
+            &key_holder,
+            keys,
+            &rng,
+            self.snapshot.dsse.is_some(),
+        )?;
+
+        let mut timestamp = self.timestamp.signed.clone();
+        timestamp.version = new_version(
+            RoleType::Timestamp,
+            timestamp.version,
+            self.timestamp_version,
+        )?;
+        timestamp.expires = self.timestamp_expires.context(error::Missing {
+            field: "timestamp expiration",
+        })?;
+        timestamp.meta.insert(
+            "snapshot.json".to_owned(),
+            TimestampMeta {
+                hashes: Hashes {
+                    sha256: snapshot.sha256.to_vec().into(),
+                    _extra: HashMap::new(),
+                },
+                length: snapshot.length,
+                version: snapshot.signed.signed.version,
+                _extra: HashMap::new(),
+            },
+        );
+        let timestamp = SignedRole::sign(
+            timestamp,
+            &key_holder,
+            keys,
+            &rng,
+            self.timestamp.dsse.is_some(),
+        )?;
+
+        Ok(SignedRefresh {
+            snapshot: Some(snapshot),
+            timestamp,
+            consistent_snapshot: self.root.signed.consistent_snapshot,
+        })
+    }
+
+    fn key_holder(&self) -> KeyHolder {
+        KeyHolder::Root(self.root.signed.clone())
+    }
+}
+
+/// The roles re-signed by a [`RefreshEditor`].
+#[derive(Debug)]
+pub struct SignedRefresh {
+    snapshot: Option<SignedRole<Snapshot>>,
+    timestamp: SignedRole<Timestamp>,
+    consistent_snapshot: bool,
+}
+
+impl SignedRefresh {
+    /// Provides access to the re-signed snapshot, if it was re-signed.
+    pub fn snapshot(&self) -> Option<&SignedRole<Snapshot>> {
+        self.snapshot.as_ref()
+    }
+
+    /// Provides access to the re-signed timestamp.
+    pub fn timestamp(&self) -> &SignedRole<Timestamp> {
+        &self.timestamp
+    }
+
+    /// Writes the re-signed roles to the given metadata directory. The snapshot is written before
+    /// the timestamp that refers to it.
+    pub fn write<P>(&self, outdir: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        if let Some(snapshot) = &self.snapshot {
+            snapshot.write(&outdir, self.consistent_snapshot)?;
+        }
+        self.timestamp.write(&outdir, self.consistent_snapshot)
+    }
+}
+
+/// Returns the version for a re-signed role: `requested` if it's greater than `current`, or the
+/// version after `current` if no version was requested.
+fn new_version(
+    role: RoleType,
+    current: NonZeroU64,
+    requested: Option<NonZeroU64>,
+) -> Result<NonZeroU64> {
+    match requested {
+        Some(version) => {
+            ensure!(
+                version > current,
+                error::VersionNotIncreased {
+                    role,
+                    version: version.get(),
+                    current: current.get(),
+                }
+            );
+            Ok(version)
+        }
+        None => current
+            .get()
+            .checked_add(1)
+            .and_then(NonZeroU64::new)
+            .context(error::VersionOverflow { role }),
+    }
+}
@@ -0,0 +1,231 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Provides a `RootEditor` for creating and changing root metadata.
+
+use crate::editor::signed::{PendingRole, SignedRole};
+use crate::error::{self, Result};
+use crate::key_source::KeySource;
+use crate::schema::decoded::{Decoded, Hex};
+use crate::schema::key::Key;
+use crate::schema::{KeyCache, KeyHolder, RoleKeys, RoleType, Root, SPEC_VERSION};
+use chrono::{DateTime, Timelike, Utc};
+use ring::rand::SystemRandom;
+use serde_json::Value;
+use snafu::{ensure, OptionExt, ResultExt};
+use std::collections::HashMap;
+use std::num::NonZeroU64;
+
+/// The threshold given to roles that are added without one; it's absurdly high so that someone
+/// realizes they need to set it.
+const UNSET_THRESHOLD: u64 = 1507;
+
+/// `RootEditor` builds root metadata: the keys the repository trusts, which of them sign each
+/// top-level role, and how many signatures each role needs.
+///
+/// Any change invalidates the signatures of a root, so the editor holds unsigned root metadata,
+/// which is signed with [`sign`](RootEditor::sign), or with [`pending`](RootEditor::pending)
+/// when no one holds a threshold of the root keys.
+///
+/// Roles that are added without a threshold get one that's impossibly high. A root can't be
+/// signed until each of its roles lists at least as many keys as its threshold, since a client
+/// could never load a repository with such a root.
+#[derive(Debug, Clone)]
+pub struct RootEditor {
+    root: Root,
+}
+
+impl Default for RootEditor {
+    fn default() -> Self {
+        Self {
+            root: Root {
+                spec_version: SPEC_VERSION.to_owned(),
+                consistent_snapshot: true,
+                version: NonZeroU64::new(1).unwrap(),
+                expires: round_time(Utc::now()),
+                keys: HashMap::new(),
+                roles: [
+                    RoleType::Root,
+                    RoleType::Snapshot,
+                    RoleType::Targets,
+                    RoleType::Timestamp,
+                ]
+                .iter()
+                .map(|role| (*role, unset_role_keys()))
+                .collect(),
+                _extra: HashMap::new(),
+                verifying_keys: KeyCache::default(),
+            },
+        }
+    }
+}
+
+impl RootEditor {
+    /// Creates root metadata at version 1 that uses consistent snapshots and expires now. Each
+    /// top-level role is listed without keys or a threshold.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a `RootEditor` that starts from existing root metadata.
+    pub fn from_root(root: Root) -> Self {
+        Self { root }
+    }
+
+    /// Returns the root metadata as edited so far.
+    pub fn root(&self) -> &Root {
+        &self.root
+    }
+
+    /// Set the version of the root.
+    pub fn version(&mut self, version: NonZeroU64) -> &mut Self {
+        self.root.version = version;
+        self
+    }
+
+    /// Increment the version of the root, as is needed for each new root.
+    pub fn bump_version(&mut self) -> Result<&mut Self> {
+        self.root.version = self
+            .root
+            .version
+            .get()
+            .checked_add(1)
+            .and_then(NonZeroU64::new)
+            .context(error::VersionOverflow {
+                role: RoleType::Root,
+            })?;
+        Ok(self)
+    }
+
+    /// Set the expiration of the root. The time is truncated to the second.
+    pub fn expires(&mut self, expires: DateTime<Utc>) -> &mut Self {
+        self.root.expires = round_time(expires);
+        self
+    }
+
+    /// Set whether the repository uses consistent snapshots.
+    pub fn consistent_snapshot(&mut self, consistent_snapshot: bool) -> &mut Self {
+        self.root.consistent_snapshot = consistent_snapshot;
+        self
+    }
+
+    /// Set the number of signatures required for `role`, adding the role if it isn't listed.
+    pub fn threshold(&mut self, role: RoleType, threshold: NonZeroU64) -> &mut Self {
+        self.root
+            .roles
+            .entry(role)
+            .and_modify(|role_keys| role_keys.threshold = threshold)
+            .or_insert_with(|| role_keys(threshold));
+        self
+    }
+
+    /// Adds a key to the root if it isn't present, and adds its key ID to each of `roles`. Roles
+    /// that aren't listed yet are added without a threshold.
+    pub fn add_key(&mut self, key: Key, roles: &[RoleType]) -> Result<&mut Self> {
+        let key_id = if let Some((key_id, _)) = self
+            .root
+            .keys
+            .iter()
+            .find(|(_, candidate_key)| key.eq(candidate_key))
+        {
+            key_id.clone()
+        } else {
+            // Key isn't present yet, so we need to add it
+            let key_id = key.key_id().context(error::JsonSerialization)?;
+            ensure!(
+                !self.root.keys.contains_key(&key_id),
+                error::KeyDuplicate {
+                    key_id: hex::encode(&key_id)
+                }
+            );
+            self.root.keys.insert(key_id.clone(), key);
+            key_id
+        };
+
+        for role in roles {
+            let role_keys = self.root.roles.entry(*role).or_insert_with(unset_role_keys);
+            if !role_keys.keyids.contains(&key_id) {
+                role_keys.keyids.push(key_id.clone());
+            }
+        }
+        Ok(self)
+    }
+
+    /// Removes a key ID from `role`, leaving the key listed in the root, or if no role is given,
+    /// removes the key from every role and from the root.
+    pub fn remove_key(&mut self, key_id: &Decoded<Hex>, role: Option<RoleType>) -> &mut Self {
+        if let Some(role) = role {
+            if let Some(role_keys) = self.root.roles.get_mut(&role) {
+                role_keys.keyids.retain(|candidate| candidate != key_id);
+            }
+        } else {
+            for role_keys in self.root.roles.values_mut() {
+                role_keys.keyids.retain(|candidate| candidate != key_id);
+            }
+            self.root.keys.remove(key_id);
+        }
+        self
+    }
+
+    /// Set an extra field in the root, which is signed along with the rest of the root.
+    pub fn extra(&mut self, key: &str, value: Value) -> &mut Self {
+        self.root._extra.insert(key.to_owned(), value);
+        self
+    }
+
+    /// Removes an extra field from the root.
+    pub fn remove_extra(&mut self, key: &str) -> &mut Self {
+        self.root._extra.remove(key);
+        self
+    }
+
+    /// Signs the root with `keys`, which must include a threshold of the root role's keys.
+    pub fn sign(&self, keys: &[Box<dyn KeySource>]) -> Result<SignedRole<Root>> {
+        self.check_stable()?;
+        SignedRole::new(
+            self.root.clone(),
+            &KeyHolder::Root(self.root.clone()),
+            keys,
+            &SystemRandom::new(),
+        )
+    }
+
+    /// Creates a `PendingRole` for the root, so that it can be signed by parties who each hold
+    /// some of the root keys.
+    pub fn pending(&self) -> Result<PendingRole<Root>> {
+        self.check_stable()?;
+        PendingRole::new(self.root.clone(), KeyHolder::Root(self.root.clone()))
+    }
+
+    /// Checks that each role lists enough keys to meet its threshold.
+    fn check_stable(&self) -> Result<()> {
+        for (role, role_keys) in &self.root.roles {
+            ensure!(
+                role_keys.threshold.get() <= role_keys.keyids.len() as u64,
+                error::UnstableRoot {
+                    role: *role,
+                    threshold: role_keys.threshold.get(),
+                    actual: role_keys.keyids.len(),
+                }
+            );
+        }
+        Ok(())
+    }
+}
+
+fn role_keys(threshold: NonZeroU64) -> RoleKeys {
+    RoleKeys {
+        keyids: Vec::new(),
+        threshold,
+        _extra: HashMap::new(),
+    }
+}
+
+fn unset_role_keys() -> RoleKeys {
+    role_keys(NonZeroU64::new(UNSET_THRESHOLD).unwrap())
+}
+
+fn round_time(time: DateTime<Utc>) -> DateTime<Utc> {
+    // `Timelike::with_nanosecond` returns None only when passed a value >= 2_000_000_000
+    time.with_nanosecond(0).unwrap()
+}
@@ -6,23 +6,28 @@
 //! Provides the `SignedDelegatedTargets` object which represents the output of `TargetsEditor` after
 //! signing, ready to be written to disk.
 
+use crate::editor::prune::{PruneReport, Pruner, UnknownTargets};
 use crate::error::{self, Result};
 use crate::io::DigestAdapter;
 use crate::key_source::KeySource;
 use crate::schema::{
-    DelegatedTargets, KeyHolder, Role, Root, Signature, Signed, Snapshot, Target, Targets,
-    Timestamp,
+    DelegatedTargets, DssePayload, KeyHolder, Role, RoleId, Root, Signature, Signed, Snapshot,
+    SnapshotMerkleProof, SnapshotMerkleTree, Target, Targets, Timestamp, VerifyingKeys,
 };
+use crate::target_name;
 use olpc_cjson::CanonicalFormatter;
 use ring::digest::{digest, SHA256, SHA256_OUTPUT_LEN};
-use ring::rand::SecureRandom;
+use ring::rand::{SecureRandom, SystemRandom};
 use serde::{Deserialize, Serialize};
 use serde_plain::forward_from_str_to_serde;
 use snafu::{ensure, OptionExt, ResultExt};
 use std::collections::HashMap;
 use std::fs;
+use std::io::Write;
+use std::num::NonZeroUsize;
 use std::os::unix::fs::symlink;
 use std::path::{Path, PathBuf};
+use tempfile::TempDir;
 use url::Url;
 use walkdir::WalkDir;
 
@@ -51,6 +56,28 @@ where
         key_holder: &KeyHolder,
         keys: &[Box<dyn KeySource>],
         rng: &dyn SecureRandom,
+    ) -> Result<Self> {
+        Self::sign(role, key_holder, keys, rng, false)
+    }
+
+    /// Creates a new `SignedRole` that is written as a DSSE envelope instead of in the TUF JSON
+    /// format. See [`DssePayload`].
+    pub fn new_dsse(
+        role: T,
+        key_holder: &KeyHolder,
+        keys: &[Box<dyn KeySource>],
+        rng: &dyn SecureRandom,
+    ) -> Result<Self> {
+        Self::sign(role, key_holder, keys, rng, true)
+    }
+
+    /// Signs a role, as a DSSE envelope if `dsse` is set.
+    pub(crate) fn sign(
+        role: T,
+        key_holder: &KeyHolder,
+        keys: &[Box<dyn KeySource>],
+        rng: &dyn SecureRandom,
+        dsse: bool,
     ) -> Result<Self> {
         let root_keys = key_holder.get_keys(keys)?;
 
@@ -64,34 +91,23 @@ where
 
         // Create the `Signed` struct for this role. This struct will be
         // mutated later to contain the signatures.
-        let mut role = Signed {
-            signed: role,
-            signatures: Vec::new(),
-        };
-
-        let mut data = Vec::new();
-        let mut ser = serde_json::Serializer::with_formatter(&mut data, CanonicalFormatter::new());
-        role.signed
-            .serialize(&mut ser)
-            .context(error::SerializeRole {
-                role: T::TYPE.to_string(),
-            })?;
+        let mut role = PendingRole::create(role, key_holder.clone(), dsse)?;
         for (signing_key_id, signing_key) in valid_keys {
-            let sig = signing_key.sign(&data, rng)?;
+            let sig = signing_key.sign(&role.payload, rng)?;
 
             // Add the signatures to the `Signed` struct for this role
-            role.signatures.push(Signature {
+            role.signed.signatures.push(Signature {
                 keyid: signing_key_id.clone(),
                 sig: sig.into(),
             });
         }
-        if role_keys.threshold.get() > role.signatures.len() as u64 {
+        if role_keys.threshold.get() > role.signed.signatures.len() as u64 {
             return Err(error::Error::SigningKeysNotFound {
                 role: T::TYPE.to_string(),
             });
         }
 
-        SignedRole::from_signed(role)
+        SignedRole::from_signed(role.signed)
     }
 
     /// Creates a `SignedRole<Role>` from a `Signed<Role>`.
@@ -152,8 +168,202 @@ where
 
         let filename = self.signed.signed.filename(consistent_snapshot);
 
-        let path = outdir.join(filename);
-        std::fs::write(&path, &self.buffer).context(error::FileWrite { path })
+        write_atomic(&outdir.join(filename), &self.buffer)
+    }
+}
+
+/// Writes `data` to a temporary file next to `path`, syncs it to disk, and renames it to `path`,
+/// so that readers never see a partially written file. On Unix, the directory is synced after the
+/// rename.
+///
+/// The temporary file is created in its own temporary directory, rather than with
+/// `NamedTempFile`, so that it gets the same mode (subject to the umask) that writing `path`
+/// directly would give it.
+pub(crate) fn write_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    let parent = path.parent().unwrap_or_else(|| Path::new("."));
+    let temp_dir = TempDir::new_in(parent).context(error::FileWrite { path })?;
+    let temp_path = temp_dir.path().join("data");
+    let mut file = fs::File::create(&temp_path).context(error::FileWrite { path })?;
+    file.write_all(data).context(error::FileWrite { path })?;
+    file.sync_all().context(error::FileWrite { path })?;
+    fs::rename(&temp_path, path).context(error::FilePersist { path })?;
+    // Sync the directory as well, so that the rename itself is on disk.
+    #[cfg(unix)]
+    fs::File::open(parent)
+        .and_then(|dir| dir.sync_all())
+        .context(error::FilePersist { path })?;
+    Ok(())
+}
+
+// =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=
+
+/// A role waiting to be signed by parties who each hold some of its keys, for when no one has
+/// enough of the keys to sign it alone with `SignedRole::new`.
+///
+/// The [`payload`](PendingRole::payload) is given to each party, who signs it offline with
+/// [`sign_payload`]. Their signatures are merged with
+/// [`add_signature`](PendingRole::add_signature), which checks each one against the role's keys
+/// in the `KeyHolder` the pending role was created with. Once a threshold of the role's keys have
+/// signed, [`finalize`](PendingRole::finalize) produces the `SignedRole`.
+///
+/// To keep a pending role between signing sessions, write out [`signed`](PendingRole::signed)
+/// and read it back with [`from_signed`](PendingRole::from_signed).
+#[derive(Debug, Clone)]
+pub struct PendingRole<T> {
+    signed: Signed<T>,
+    payload: Vec<u8>,
+    key_holder: KeyHolder,
+}
+
+impl<T> PendingRole<T>
+where
+    T: Role + Serialize,
+{
+    /// Creates a `PendingRole` with no signatures, to be signed by the keys that `key_holder`
+    /// authorizes to sign the role.
+    pub fn new(role: T, key_holder: KeyHolder) -> Result<Self> {
+        Self::create(role, key_holder, false)
+    }
+
+    /// Creates a `PendingRole` with no signatures, which is written as a DSSE envelope instead of
+    /// in the TUF JSON format. See [`DssePayload`].
+    pub fn new_dsse(role: T, key_holder: KeyHolder) -> Result<Self> {
+        Self::create(role, key_holder, true)
+    }
+
+    /// Creates a `PendingRole` with no signatures, as a DSSE envelope if `dsse` is set.
+    pub(crate) fn create(role: T, key_holder: KeyHolder, dsse: bool) -> Result<Self> {
+        let mut signed = Signed {
+            signed: role,
+            signatures: Vec::new(),
+            dsse: None,
+        };
+        let payload = if dsse {
+            let payload =
+                DssePayload::new(&signed.signed).context(error::SerializeDssePayload {
+                    role: T::TYPE.to_string(),
+                })?;
+            let data = payload.pae();
+            signed.dsse = Some(payload);
+            data
+        } else {
+            let mut data = Vec::new();
+            let mut ser =
+                serde_json::Serializer::with_formatter(&mut data, CanonicalFormatter::new());
+            signed
+                .signed
+                .serialize(&mut ser)
+                .context(error::SerializeRole {
+                    role: T::TYPE.to_string(),
+                })?;
+            data
+        };
+        Ok(Self {
+            signed,
+            payload,
+            key_holder,
+        })
+    }
+
+    /// Creates a `PendingRole` from signed metadata, keeping the signatures it already has. The
+    /// signatures are checked when the role is finalized.
+    pub fn from_signed(signed: Signed<T>, key_holder: KeyHolder) -> Result<Self> {
+        let payload =
+            signed
+                .signed_data(&format!("{} role", T::TYPE))
+                .context(error::SigningPayload {
+                    role: T::TYPE.to_string(),
+                })?;
+        Ok(Self {
+            signed,
+            payload,
+            key_holder,
+        })
+    }
+
+    /// Returns the data that each party signs: the canonical JSON form of the role, or the DSSE
+    /// pre-authentication encoding of its payload.
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    /// Provides access to the role and the signatures merged so far.
+    pub fn signed(&self) -> &Signed<T> {
+        &self.signed
+    }
+
+    /// Merges a signature made by one of the parties. The signature must be made over the
+    /// [`payload`](PendingRole::payload) by a key that the `KeyHolder` authorizes to sign the
+    /// role. If the key has already signed, its earlier signature is replaced.
+    pub fn add_signature(&mut self, signature: Signature) -> Result<&mut Self> {
+        let key_holder = &self.key_holder;
+        let role = role_name(self.signed.signed.role_id());
+        let role_keys = key_holder.role_keys(self.signed.signed.role_id())?;
+        let key = key_holder
+            .keys()
+            .get(&signature.keyid)
+            .filter(|_| role_keys.keyids.contains(&signature.keyid))
+            .context(error::SignatureNotAuthorized {
+                role: role.clone(),
+                keyid: hex::encode(&signature.keyid),
+            })?;
+        ensure!(
+            key.verify(&self.payload, &signature.sig),
+            error::SignatureInvalid {
+                role,
+                keyid: hex::encode(&signature.keyid),
+            }
+        );
+        self.signed
+            .signatures
+            .retain(|existing| existing.keyid != signature.keyid);
+        self.signed.signatures.push(signature);
+        Ok(self)
+    }
+
+    /// Creates the `SignedRole`, if the role is signed by a threshold of the keys that
+    /// `KeyHolder` authorizes to sign it.
+    pub fn finalize(self) -> Result<SignedRole<T>> {
+        let key_holder = &self.key_holder;
+        let role_keys = key_holder.role_keys(self.signed.signed.role_id())?;
+        VerifyingKeys::new(key_holder.keys())
+            .verify_threshold(
+                T::TYPE,
+                &role_keys.keyids,
+                role_keys.threshold,
+                &self.payload,
+                &self.signed.signatures,
+            )
+            .context(error::VerifyRoleMetadata {
+                role: role_name(self.signed.signed.role_id()),
+            })?;
+        SignedRole::from_signed(self.signed)
+    }
+}
+
+/// Signs the [`payload`](PendingRole::payload) of a pending role with each of `keys`, returning
+/// the signatures to be merged into the role with [`PendingRole::add_signature`].
+pub fn sign_payload(payload: &[u8], keys: &[Box<dyn KeySource>]) -> Result<Vec<Signature>> {
+    let rng = SystemRandom::new();
+    let mut signatures = Vec::new();
+    for source in keys {
+        let key_pair = source.as_sign().context(error::KeyPairFromKeySource)?;
+        signatures.push(Signature {
+            keyid: key_pair
+                .tuf_key()
+                .key_id()
+                .context(error::JsonSerialization)?,
+            sig: key_pair.sign(payload, &rng)?.into(),
+        });
+    }
+    Ok(signatures)
+}
+
+/// Returns the name of a role, as used in error messages.
+fn role_name(role_id: RoleId) -> String {
+    match role_id {
+        RoleId::StandardRole(role) => role.to_string(),
+        RoleId::DelegatedRole(name) => name,
     }
 }
 
@@ -201,34 +411,147 @@ pub struct SignedRepository {
     pub(crate) snapshot: SignedRole<Snapshot>,
     pub(crate) timestamp: SignedRole<Timestamp>,
     pub(crate) delegated_targets: Option<SignedDelegatedTargets>,
+    pub(crate) snapshot_merkle: Option<SnapshotMerkleTree>,
 }
 
 impl SignedRepository {
     /// Writes the metadata to the given directory. If consistent snapshots
     /// are used, the appropriate files are prefixed with their version.
+    ///
+    /// If a snapshot Merkle tree was requested, a proof for each targets role is written as well;
+    /// see [`SnapshotMerkleProof`].
+    ///
+    /// Each file is written to a temporary file and renamed into place, and roles are written
+    /// before the roles that refer to them: root, then the delegated and top-level targets, then
+    /// the snapshot, and the timestamp last. A client reading the directory during the write never
+    /// sees a partially written file. With consistent snapshots, it also never sees a timestamp
+    /// that refers to a snapshot that isn't there yet; without them, `snapshot.json` and
+    /// `targets.json` are replaced in place, so a client may briefly see a new snapshot with the
+    /// old timestamp, or new targets with the old snapshot. To also write the targets first, or to
+    /// verify the metadata before publishing it, see
+    /// [`Publisher`](crate::editor::publish::Publisher).
     pub fn write<P>(&self, outdir: P) -> Result<()>
     where
         P: AsRef<Path>,
     {
         let consistent_snapshot = self.root.signed.signed.consistent_snapshot;
         self.root.write(&outdir, consistent_snapshot)?;
+        if let Some(delegated_targets) = &self.delegated_targets {
+            delegated_targets.write(&outdir, consistent_snapshot)?;
+        }
         self.targets.write(&outdir, consistent_snapshot)?;
+        if let Some(snapshot_merkle) = &self.snapshot_merkle {
+            self.write_snapshot_merkle_proofs(
+                snapshot_merkle,
+                outdir.as_ref(),
+                consistent_snapshot,
+            )?;
+        }
         self.snapshot.write(&outdir, consistent_snapshot)?;
         self.timestamp.write(&outdir, consistent_snapshot)?;
+        Ok(())
+    }
+
+    /// Returns the names of the metadata files written by [`SignedRepository::write`], in the
+    /// order they're written.
+    pub(crate) fn metadata_filenames(&self) -> Vec<String> {
+        let consistent_snapshot = self.root.signed.signed.consistent_snapshot;
+        let mut filenames = vec![self.root.signed.signed.filename(consistent_snapshot)];
         if let Some(delegated_targets) = &self.delegated_targets {
-            delegated_targets.write(&outdir, consistent_snapshot)?;
+            filenames.extend(
+                delegated_targets
+                    .roles
+                    .iter()
+                    .map(|role| role.signed.signed.filename(consistent_snapshot)),
+            );
+        }
+        filenames.push(self.targets.signed.signed.filename(consistent_snapshot));
+        if let Some(snapshot_merkle) = &self.snapshot_merkle {
+            filenames.extend(snapshot_merkle.proofs().iter().map(|proof| {
+                SnapshotMerkleProof::filename(
+                    &proof.leaf_contents.name,
+                    self.timestamp.signed.signed.version,
+                    consistent_snapshot,
+                )
+            }));
+        }
+        filenames.push(self.snapshot.signed.signed.filename(consistent_snapshot));
+        filenames.push(self.timestamp.signed.signed.filename(consistent_snapshot));
+        filenames
+    }
+
+    /// Returns the snapshot Merkle tree, if one was requested.
+    pub fn snapshot_merkle(&self) -> Option<&SnapshotMerkleTree> {
+        self.snapshot_merkle.as_ref()
+    }
+
+    fn write_snapshot_merkle_proofs(
+        &self,
+        snapshot_merkle: &SnapshotMerkleTree,
+        outdir: &Path,
+        consistent_snapshot: bool,
+    ) -> Result<()> {
+        let timestamp_version = self.timestamp.signed.signed.version;
+        for proof in snapshot_merkle.proofs() {
+            let mut buffer =
+                serde_json::to_vec_pretty(&proof).context(error::SerializeSnapshotMerkleProof {
+                    name: &proof.leaf_contents.name,
+                })?;
+            buffer.push(b'\n');
+            write_atomic(
+                &outdir.join(SnapshotMerkleProof::filename(
+                    &proof.leaf_contents.name,
+                    timestamp_version,
+                    consistent_snapshot,
+                )),
+                &buffer,
+            )?;
         }
         Ok(())
     }
 
+    /// Removes old versions of metadata from `metadata_dir`, and targets no longer referenced from
+    /// `targets_dir`, keeping the metadata referenced by the `keep` most recent snapshots; see
+    /// [`prune`].
+    ///
+    /// The metadata and targets of this repository are always kept, even if it hasn't been
+    /// written yet, so it's safe to prune before or after calling [`SignedRepository::write`].
+    ///
+    /// [`prune`]: crate::editor::prune::prune
+    pub fn prune<P1, P2>(
+        &self,
+        metadata_dir: P1,
+        targets_dir: P2,
+        keep: NonZeroUsize,
+        unknown_targets: UnknownTargets,
+        dry_run: bool,
+    ) -> Result<PruneReport>
+    where
+        P1: AsRef<Path>,
+        P2: AsRef<Path>,
+    {
+        let mut pruner = Pruner::default();
+        pruner.metadata.extend(self.metadata_filenames());
+        for (name, target) in self.targets() {
+            pruner.reference_target(&name, &target.hashes.sha256);
+        }
+        pruner.prune(
+            metadata_dir.as_ref(),
+            targets_dir.as_ref(),
+            keep,
+            unknown_targets,
+            dry_run,
+        )
+    }
+
     /// Crawls a given directory and symlinks any targets found to the given
     /// "out" directory. If consistent snapshots are used, the target files
     /// are prefixed with their `sha256`.
     ///
-    /// For each file found in the `indir`, the method gets the filename and
-    /// if the filename exists in `Targets`, the file's sha256 is compared
-    /// against the data in `Targets`. If this data does not match, the
-    /// method will fail.
+    /// For each file found in the `indir`, the method gets its path relative
+    /// to `indir`, or failing that its filename, and if that name exists in
+    /// `Targets`, the file's sha256 is compared against the data in `Targets`.
+    /// If this data does not match, the method will fail.
     pub fn link_targets<P1, P2>(
         &self,
         indir: P1,
@@ -251,10 +574,10 @@ impl SignedRepository {
     /// "out" directory. If consistent snapshots are used, the target files
     /// are prefixed with their `sha256`.
     ///
-    /// For each file found in the `indir`, the method gets the filename and
-    /// if the filename exists in `Targets`, the file's sha256 is compared
-    /// against the data in `Targets`. If this data does not match, the
-    /// method will fail.
+    /// For each file found in the `indir`, the method gets its path relative
+    /// to `indir`, or failing that its filename, and if that name exists in
+    /// `Targets`, the file's sha256 is compared against the data in `Targets`.
+    /// If this data does not match, the method will fail.
     pub fn copy_targets<P1, P2>(
         &self,
         indir: P1,
@@ -275,10 +598,12 @@ impl SignedRepository {
 
     /// Symlinks a single target to the desired directory. If `target_filename` is given, it
     /// becomes the filename suffix, otherwise the original filename is used. (A unique filename
-    /// prefix is used if consistent snapshots are enabled.)  Fails if the target already exists in
-    /// the repo with a different hash, or if it has the same hash but is not a symlink.  Using the
-    /// `replace_behavior` parameter, you can decide what happens if it exists with the same hash
-    /// and file type - skip, fail, or replace.
+    /// prefix is used if consistent snapshots are enabled.)  A `target_filename` that is a path,
+    /// like `images/root.img`, is linked into the same subdirectories of `outdir`, with any prefix
+    /// on its final component.  Fails if the target already exists in the repo with a different
+    /// hash, or if it has the same hash but is not a symlink.  Using the `replace_behavior`
+    /// parameter, you can decide what happens if it exists with the same hash and file type -
+    /// skip, fail, or replace.
     pub fn link_target(
         &self,
         input_path: &Path,
@@ -317,10 +642,12 @@ impl SignedRepository {
 
     /// Copies a single target to the desired directory. If `target_filename` is given, it becomes
     /// the filename suffix, otherwise the original filename is used. (A unique filename prefix is
-    /// used if consistent hashing is enabled.)  Fails if the target already exists in the repo
-    /// with a different hash, or if it has the same hash but is not a regular file.  Using the
-    /// `replace_behavior` parameter, you can decide what happens if it exists with the same hash
-    /// and file type - skip, fail, or replace.
+    /// used if consistent hashing is enabled.)  A `target_filename` that is a path, like
+    /// `images/root.img`, is copied into the same subdirectories of `outdir`, with any prefix on
+    /// its final component.  Fails if the target already exists in the repo with a different
+    /// hash, or if it has the same hash but is not a regular file.  Using the `replace_behavior`
+    /// parameter, you can decide what happens if it exists with the same hash and file type -
+    /// skip, fail, or replace.
     pub fn copy_target(
         &self,
         input_path: &Path,
@@ -399,10 +726,10 @@ impl SignedDelegatedTargets {
     /// "out" directory. If consistent snapshots are used, the target files
     /// are prefixed with their `sha256`.
     ///
-    /// For each file found in the `indir`, the method gets the filename and
-    /// if the filename exists in `Targets`, the file's sha256 is compared
-    /// against the data in `Targets`. If this data does not match, the
-    /// method will fail.
+    /// For each file found in the `indir`, the method gets its path relative
+    /// to `indir`, or failing that its filename, and if that name exists in
+    /// `Targets`, the file's sha256 is compared against the data in `Targets`.
+    /// If this data does not match, the method will fail.
     pub fn link_targets<P1, P2>(
         &self,
         indir: P1,
@@ -425,10 +752,10 @@ impl SignedDelegatedTargets {
     /// "out" directory. If consistent snapshots are used, the target files
     /// are prefixed with their `sha256`.
     ///
-    /// For each file found in the `indir`, the method gets the filename and
-    /// if the filename exists in `Targets`, the file's sha256 is compared
-    /// against the data in `Targets`. If this data does not match, the
-    /// method will fail.
+    /// For each file found in the `indir`, the method gets its path relative
+    /// to `indir`, or failing that its filename, and if that name exists in
+    /// `Targets`, the file's sha256 is compared against the data in `Targets`.
+    /// If this data does not match, the method will fail.
     pub fn copy_targets<P1, P2>(
         &self,
         indir: P1,
@@ -449,10 +776,12 @@ impl SignedDelegatedTargets {
 
     /// Symlinks a single target to the desired directory. If `target_filename` is given, it
     /// becomes the filename suffix, otherwise the original filename is used. (A unique filename
-    /// prefix is used if consistent snapshots are enabled.)  Fails if the target already exists in
-    /// the repo with a different hash, or if it has the same hash but is not a symlink.  Using the
-    /// `replace_behavior` parameter, you can decide what happens if it exists with the same hash
-    /// and file type - skip, fail, or replace.
+    /// prefix is used if consistent snapshots are enabled.)  A `target_filename` that is a path,
+    /// like `images/root.img`, is linked into the same subdirectories of `outdir`, with any prefix
+    /// on its final component.  Fails if the target already exists in the repo with a different
+    /// hash, or if it has the same hash but is not a symlink.  Using the `replace_behavior`
+    /// parameter, you can decide what happens if it exists with the same hash and file type -
+    /// skip, fail, or replace.
     pub fn link_target(
         &self,
         input_path: &Path,
@@ -491,10 +820,12 @@ impl SignedDelegatedTargets {
 
     /// Copies a single target to the desired directory. If `target_filename` is given, it becomes
     /// the filename suffix, otherwise the original filename is used. (A unique filename prefix is
-    /// used if consistent hashing is enabled.)  Fails if the target already exists in the repo
-    /// with a different hash, or if it has the same hash but is not a regular file.  Using the
-    /// `replace_behavior` parameter, you can decide what happens if it exists with the same hash
-    /// and file type - skip, fail, or replace.
+    /// used if consistent hashing is enabled.)  A `target_filename` that is a path, like
+    /// `images/root.img`, is copied into the same subdirectories of `outdir`, with any prefix on
+    /// its final component.  Fails if the target already exists in the repo with a different
+    /// hash, or if it has the same hash but is not a regular file.  Using the `replace_behavior`
+    /// parameter, you can decide what happens if it exists with the same hash and file type -
+    /// skip, fail, or replace.
     pub fn copy_target(
         &self,
         input_path: &Path,
@@ -561,6 +892,10 @@ trait TargetsWalker {
     /// Walks a given directory and calls the provided function with every file found.
     /// The function is given the file path, the output directory where the user expects
     /// it to go, and optionally a desired filename.
+    ///
+    /// A file is given its path relative to `indir` as its filename if a target has that name,
+    /// so that the directory structure of targets named by their paths is recreated in `outdir`.
+    /// Otherwise the file's own name is used.
     fn walk_targets<F>(
         &self,
         indir: &Path,
@@ -579,6 +914,7 @@ trait TargetsWalker {
 
         // Walk the absolute path of the indir. Using the absolute path here
         // means that `entry.path()` call will return its absolute path.
+        let targets = self.targets();
         let walker = WalkDir::new(&abs_indir).follow_links(true);
         for entry in walker {
             let entry = entry.context(error::WalkDir {
@@ -590,8 +926,19 @@ trait TargetsWalker {
                 continue;
             };
 
+            // Use the path relative to the indir if a target is named by it
+            let relative_name = target_name::from_path(&abs_indir, entry.path())
+                .ok()
+                .filter(|name| targets.contains_key(name));
+
             // Call the requested function to manipulate the path we found
-            if let Err(e) = f(self, entry.path(), outdir, replace_behavior, None) {
+            if let Err(e) = f(
+                self,
+                entry.path(),
+                outdir,
+                replace_behavior,
+                relative_name.as_deref(),
+            ) {
                 match e {
                     // If we found a path that isn't a known target in the repo, skip it.
                     error::Error::PathIsNotTarget { .. } => continue,
@@ -648,11 +995,12 @@ trait TargetsWalker {
             }
         );
 
+        // Target names may be paths, so make sure the name stays within the outdir.
+        target_name::validate(file_name)?;
         let dest = if self.consistent_snapshot() {
-            outdir.join(format!(
-                "{}.{}",
-                hex::encode(&target_from_path.hashes.sha256),
-                file_name
+            outdir.join(target_name::consistent_filename(
+                file_name,
+                &target_from_path.hashes.sha256,
             ))
         } else {
             outdir.join(&file_name)
@@ -661,6 +1009,10 @@ trait TargetsWalker {
         // Return the target path, using the `TargetPath` enum that represents the type of file
         // that already exists at that path (if any)
         if !dest.exists() {
+            // Recreate the directories of targets named by their paths.
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent).context(error::DirCreate { path: parent })?;
+            }
             return Ok(TargetPath::New { path: dest });
         }
 
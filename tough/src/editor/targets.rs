@@ -4,30 +4,39 @@
 
 //! Provides a `TargetsEditor` object for building and editing targets roles.
 
-use crate::editor::signed::{SignedDelegatedTargets, SignedRole};
+use crate::editor::signed::{PendingRole, SignedDelegatedTargets, SignedRole};
 use crate::error::{self, Result};
 use crate::fetch::fetch_max_size;
 use crate::key_source::KeySource;
 use crate::schema::decoded::{Decoded, Hex};
 use crate::schema::key::Key;
 use crate::schema::{
-    DelegatedRole, DelegatedTargets, Delegations, KeyHolder, PathSet, RoleType, Signed, Target,
-    Targets,
+    spec_version_supported, DelegatedRole, DelegatedTargets, Delegations, KeyHolder, PathSet,
+    RoleType, Signed, Target, Targets, SPEC_VERSION,
 };
 use crate::transport::Transport;
 use crate::Limits;
 use crate::Repository;
 use chrono::{DateTime, Utc};
 use ring::rand::SystemRandom;
+use serde::de::DeserializeOwned;
 use serde_json::Value;
-use snafu::{OptionExt, ResultExt};
+use snafu::{ensure, OptionExt, ResultExt};
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::num::NonZeroU64;
 use std::path::Path;
 use url::Url;
 
-const SPEC_VERSION: &str = "1.0.0";
+/// Checks that a target's custom metadata has the type declared with `validate_custom`.
+pub(crate) type CustomValidator = fn(&Target) -> crate::schema::Result<()>;
+
+/// Checks that a target's custom metadata parses as `C`.
+pub(crate) fn validate_custom_as<C: DeserializeOwned>(
+    target: &Target,
+) -> crate::schema::Result<()> {
+    target.custom_as::<C>().map(|_| ())
+}
 
 /// If you are not working with a repository that utilizes delegated targets, use the `RepositoryEditor`.
 ///
@@ -72,6 +81,12 @@ pub struct TargetsEditor<'a, T: Transport> {
     version: Option<NonZeroU64>,
     /// Expiration of the `Targets`
     expires: Option<DateTime<Utc>>,
+    /// The `spec_version` to write to the `Targets`, or `SPEC_VERSION` if unset
+    pub(crate) spec_version: Option<String>,
+    /// Whether the role is signed as a DSSE envelope
+    pub(crate) dsse: bool,
+    /// Checks the custom metadata of each target before the role is built, if set
+    pub(crate) custom_validator: Option<CustomValidator>,
     /// New roles that were created with the editor
     new_roles: Option<Vec<DelegatedRole>>,
 
@@ -92,6 +107,9 @@ impl<'a, T: Transport> TargetsEditor<'a, T> {
             existing_targets: None,
             version: None,
             expires: None,
+            spec_version: None,
+            dsse: false,
+            custom_validator: None,
             name: name.to_string(),
             new_roles: None,
             _extra: None,
@@ -110,6 +128,9 @@ impl<'a, T: Transport> TargetsEditor<'a, T> {
             existing_targets: Some(targets.targets),
             version: None,
             expires: None,
+            spec_version: None,
+            dsse: false,
+            custom_validator: None,
             name: name.to_string(),
             new_roles: None,
             _extra: Some(targets._extra),
@@ -159,6 +180,9 @@ impl<'a, T: Transport> TargetsEditor<'a, T> {
             existing_targets: Some(targets.targets),
             version: None,
             expires: None,
+            spec_version: None,
+            dsse: false,
+            custom_validator: None,
             name: name.to_string(),
             new_roles: None,
             _extra: Some(targets._extra),
@@ -259,6 +283,34 @@ impl<'a, T: Transport> TargetsEditor<'a, T> {
         self
     }
 
+    /// Set the `spec_version`. Defaults to [`SPEC_VERSION`]; other versions must have the same
+    /// major version.
+    pub fn spec_version(&mut self, spec_version: &str) -> Result<&mut Self> {
+        ensure!(
+            spec_version_supported(spec_version),
+            error::SpecVersion {
+                given: spec_version,
+                supported: SPEC_VERSION
+            }
+        );
+        self.spec_version = Some(spec_version.to_owned());
+        Ok(self)
+    }
+
+    /// Sign the role as a DSSE envelope instead of in the TUF JSON format. Defaults to `false`.
+    pub fn dsse(&mut self, dsse: bool) -> &mut Self {
+        self.dsse = dsse;
+        self
+    }
+
+    /// Declares the type of the custom metadata of every target in the role. When the role is
+    /// built, each target's custom metadata must parse as `C` (see [`Target::custom_as`]), or
+    /// building fails with [`Error::TargetCustom`](crate::error::Error::TargetCustom).
+    pub fn validate_custom<C: DeserializeOwned>(&mut self) -> &mut Self {
+        self.custom_validator = Some(validate_custom_as::<C>);
+        self
+    }
+
     /// Adds a key to delegations keyids, adds the key to `role` if it is provided
     pub fn add_key(
         &mut self,
@@ -336,6 +388,7 @@ impl<'a, T: Transport> TargetsEditor<'a, T> {
                 targets: Some(Signed {
                     signed: targets.signed.targets,
                     signatures: targets.signatures,
+                    dsse: targets.dsse,
                 }),
             });
         Ok(self)
@@ -404,6 +457,7 @@ impl<'a, T: Transport> TargetsEditor<'a, T> {
                 targets: role.signed.clone(),
             },
             signatures: role.signatures.clone(),
+            dsse: role.dsse.clone(),
         };
         let (keyids, key_pairs) = if let Some(keys) = keys {
             (keys.keys().cloned().collect(), keys)
@@ -438,6 +492,11 @@ impl<'a, T: Transport> TargetsEditor<'a, T> {
         if let Some(ref new_targets) = self.new_targets {
             targets.extend(new_targets.clone());
         }
+        if let Some(validate) = self.custom_validator {
+            for (name, target) in &targets {
+                validate(target).context(error::TargetCustom { name })?;
+            }
+        }
 
         let mut delegations = self.delegations.clone();
         if let Some(delegations) = delegations.as_mut() {
@@ -450,7 +509,10 @@ impl<'a, T: Transport> TargetsEditor<'a, T> {
         Ok(DelegatedTargets {
             name: self.name.clone(),
             targets: Targets {
-                spec_version: SPEC_VERSION.to_string(),
+                spec_version: self
+                    .spec_version
+                    .clone()
+                    .unwrap_or_else(|| SPEC_VERSION.to_string()),
                 version,
                 expires,
                 targets,
@@ -515,10 +577,36 @@ impl<'a, T: Transport> TargetsEditor<'a, T> {
         // create a signed role for the targets being edited
         let targets = self
             .build_targets()
-            .and_then(|targets| SignedRole::new(targets, &key_holder, keys, &rng))?;
+            .and_then(|targets| SignedRole::sign(targets, &key_holder, keys, &rng, self.dsse))?;
         Ok(targets.signed)
     }
 
+    /// Builds the Targets role being edited without signing it, so that it can be signed by
+    /// parties who each hold some of its keys. See [`PendingRole`].
+    ///
+    /// The role must already have a `KeyHolder`, which is the case for roles that were loaded
+    /// from a repository or delegated by a role being edited.
+    pub fn create_pending(&self) -> Result<PendingRole<DelegatedTargets>> {
+        let key_holder = self.key_holder.clone().context(error::NoKeyHolder)?;
+        PendingRole::create(self.build_targets()?, key_holder, self.dsse)
+    }
+
+    /// Finalizes a role created by `create_pending()`, checking that it is the role being edited
+    /// and that it is signed by a threshold of its keys.
+    pub fn finalize_pending(
+        &self,
+        pending: PendingRole<DelegatedTargets>,
+    ) -> Result<Signed<DelegatedTargets>> {
+        ensure!(
+            pending.signed().signed.name == self.name,
+            error::PendingRoleName {
+                name: &self.name,
+                found: &pending.signed().signed.name,
+            }
+        );
+        Ok(pending.finalize()?.signed)
+    }
+
     /// Creates a `SignedDelegatedTargets` for the Targets role being edited and all added roles
     /// If `key_holder` was not assigned then this is a newly created role and needs to be signed with a
     /// custom delegations as its `key_holder`
@@ -534,7 +622,7 @@ impl<'a, T: Transport> TargetsEditor<'a, T> {
         // create a signed role for the targets we are editing
         let signed_targets = self
             .build_targets()
-            .and_then(|targets| SignedRole::new(targets, &key_holder, keys, &rng))?;
+            .and_then(|targets| SignedRole::sign(targets, &key_holder, keys, &rng, self.dsse))?;
         roles.push(signed_targets);
         // create signed roles for any role metadata we added to this targets
         if let Some(new_roles) = &self.new_roles {
@@ -7,6 +7,7 @@
 
 use crate::schema;
 use crate::schema::RoleType;
+use crate::sign::KeyType;
 use chrono::{DateTime, Utc};
 use snafu::{Backtrace, Snafu};
 use std::io;
@@ -37,6 +38,22 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    /// The library failed to write to a file in the datastore.
+    #[snafu(display("Failed to write to datastore path {}: {}", path.display(), source))]
+    DatastoreWrite {
+        path: PathBuf,
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+
+    /// The library failed to lock the datastore.
+    #[snafu(display("Failed to lock datastore with {}: {}", path.display(), source))]
+    DatastoreLock {
+        path: PathBuf,
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+
     /// The library failed to open a file in the datastore.
     #[snafu(display("Failed to open file from datastore path {}: {}", path.display(), source))]
     DatastoreOpen {
@@ -45,6 +62,14 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    /// The library failed to move a temporary file into place in the datastore.
+    #[snafu(display("Failed to write datastore path {}: {}", path.display(), source))]
+    DatastorePersist {
+        path: PathBuf,
+        source: tempfile::PersistError,
+        backtrace: Backtrace,
+    },
+
     /// The library failed to remove a file in the datastore.
     #[snafu(display("Failed to remove file at datastore path {}: {}", path.display(), source))]
     DatastoreRemove {
@@ -69,6 +94,13 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    #[snafu(display("Failed to read directory '{}': {}", path.display(), source))]
+    DirRead {
+        path: PathBuf,
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+
     /// A metadata file has expired.
     #[snafu(display("{} metadata is expired", role))]
     ExpiredMetadata {
@@ -76,6 +108,10 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    /// A delegated targets metadata file has expired.
+    #[snafu(display("Metadata for delegated role '{}' is expired", name))]
+    ExpiredDelegatedMetadata { name: String, backtrace: Backtrace },
+
     #[snafu(display("Failed to stat '{}': {}", path.display(), source))]
     FileMetadata {
         path: PathBuf,
@@ -104,6 +140,20 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    #[snafu(display("Failed to move {} into place: {}", path.display(), source))]
+    FilePersist {
+        path: PathBuf,
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Failed to remove {}: {}", path.display(), source))]
+    FileRemove {
+        path: PathBuf,
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+
     #[snafu(display("Can't build URL from relative path '{}'", path.display()))]
     FileUrl { path: PathBuf, backtrace: Backtrace },
 
@@ -129,6 +179,28 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    /// An entry in the metadata history log does not follow from the entry before it.
+    #[snafu(display(
+        "Metadata history entry on line {} does not match the previous entry; the history may have been tampered with",
+        line
+    ))]
+    HistoryChain { line: usize, backtrace: Backtrace },
+
+    /// An entry in the metadata history log could not be parsed.
+    #[snafu(display("Failed to parse metadata history entry on line {}: {}", line, source))]
+    HistoryParse {
+        line: usize,
+        source: serde_json::Error,
+        backtrace: Backtrace,
+    },
+
+    /// The metadata history log could not be read.
+    #[snafu(display("Failed to read metadata history: {}", source))]
+    HistoryRead {
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+
     #[snafu(display("Source path for target must be file or symlink - '{}'", path.display()))]
     InvalidFileType { path: PathBuf, backtrace: Backtrace },
 
@@ -141,6 +213,31 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    #[snafu(display("Failed to encode private key as PKCS#8: {}", source))]
+    KeyEncode {
+        source: rsa::pkcs8::Error,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Duplicate key ID: {}", key_id))]
+    KeyDuplicate {
+        key_id: String,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Failed to generate {} key", key_type))]
+    KeyGenerate {
+        key_type: KeyType,
+        source: ring::error::Unspecified,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Failed to generate RSA key: {}", source))]
+    KeyGenerateRsa {
+        source: rsa::Error,
+        backtrace: Backtrace,
+    },
+
     #[snafu(display("Unable to parse keypair: {}", source))]
     KeyPairFromKeySource {
         source: Box<dyn std::error::Error + Send + Sync + 'static>,
@@ -159,6 +256,25 @@ pub enum Error {
     #[snafu(display("Unrecognized private key format"))]
     KeyUnrecognized { backtrace: Backtrace },
 
+    #[snafu(display(
+        "Invalid RSA key size {}, must be between {} and {} bits",
+        bits,
+        min,
+        max
+    ))]
+    KeySizeInvalid {
+        bits: usize,
+        min: usize,
+        max: usize,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Failed to write key to key source: {}", source))]
+    KeySourceWrite {
+        source: Box<dyn std::error::Error + Send + Sync + 'static>,
+        backtrace: Backtrace,
+    },
+
     #[snafu(display("Failed to create symlink at '{}': {}", path.display(), source))]
     LinkCreate {
         path: PathBuf,
@@ -174,6 +290,42 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    /// A delegated targets metadata file is larger than the `max_delegated_targets_size` limit.
+    #[snafu(display(
+        "Delegated role '{}' exceeds maximum size {} (specified by max_delegated_targets_size parameter)",
+        name,
+        max_size
+    ))]
+    MaxDelegatedTargetsSizeExceeded {
+        name: String,
+        max_size: u64,
+        backtrace: Backtrace,
+    },
+
+    /// More delegated roles were found than the `max_delegated_roles` limit allows.
+    #[snafu(display(
+        "Loading delegated role '{}' exceeds maximum of {} delegated roles",
+        name,
+        max_delegated_roles
+    ))]
+    MaxDelegatedRolesExceeded {
+        name: String,
+        max_delegated_roles: u64,
+        backtrace: Backtrace,
+    },
+
+    /// A delegated role is nested deeper than the `max_delegation_depth` limit allows.
+    #[snafu(display(
+        "Delegated role '{}' exceeds maximum delegation depth {}",
+        name,
+        max_delegation_depth
+    ))]
+    MaxDelegationDepthExceeded {
+        name: String,
+        max_delegation_depth: u64,
+        backtrace: Backtrace,
+    },
+
     /// The maximum root updates setting was exceeded.
     #[snafu(display("Maximum root updates {} exceeded", max_root_updates))]
     MaxUpdatesExceeded {
@@ -213,6 +365,20 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    /// A snapshot Merkle proof lists an older version of a role than the trusted Merkle tree.
+    #[snafu(display(
+        "Snapshot Merkle proof for role '{}' lists version {}, older than trusted version {}",
+        name,
+        new_version,
+        current_version
+    ))]
+    OlderSnapshotMerkleLeaf {
+        name: String,
+        current_version: u64,
+        new_version: u64,
+        backtrace: Backtrace,
+    },
+
     /// The library failed to parse a metadata file, either because it was not valid JSON or it did
     /// not conform to the expected schema.
     //
@@ -229,6 +395,18 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    /// A snapshot Merkle proof could not be parsed.
+    #[snafu(display(
+        "Failed to parse snapshot Merkle proof for role '{}': {}",
+        name,
+        source
+    ))]
+    ParseSnapshotMerkleProof {
+        name: String,
+        source: serde_json::Error,
+        backtrace: Backtrace,
+    },
+
     /// The library failed to parse the trusted root metadata file, either because it was not valid
     /// JSON or it did not conform to the expected schema. The *trusted* root metadata file is the
     /// file is either the `root` argument passed to `Repository::load`, or the most recently
@@ -260,6 +438,16 @@ pub enum Error {
     #[snafu(display("Path {} is not valid UTF-8", path.display()))]
     PathUtf8 { path: PathBuf, backtrace: Backtrace },
 
+    /// A pending role was given to a targets editor that is editing a different role.
+    #[snafu(display("Pending role '{}' is not the role being edited, '{}'", found, name))]
+    PendingRoleName { name: String, found: String },
+
+    /// A repository that publishes a snapshot Merkle tree was given to a `RefreshEditor`.
+    #[snafu(display(
+        "Cannot re-sign the timestamp of a repository that publishes a snapshot Merkle tree"
+    ))]
+    RefreshSnapshotMerkle { backtrace: Backtrace },
+
     #[snafu(display("Failed to remove existing target path '{}': {}", path.display(), source))]
     RemoveTarget {
         path: PathBuf,
@@ -267,6 +455,13 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    #[snafu(display("Failed to create DSSE payload for role '{}': {}", role, source))]
+    SerializeDssePayload {
+        role: String,
+        source: crate::schema::Error,
+        backtrace: Backtrace,
+    },
+
     #[snafu(display("Failed to serialize role '{}' for signing: {}", role, source))]
     SerializeRole {
         role: String,
@@ -281,17 +476,60 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    #[snafu(display(
+        "Failed to serialize snapshot Merkle proof for role '{}': {}",
+        name,
+        source
+    ))]
+    SerializeSnapshotMerkleProof {
+        name: String,
+        source: serde_json::Error,
+        backtrace: Backtrace,
+    },
+
     #[snafu(display("Failed to sign message"))]
     Sign {
         source: ring::error::Unspecified,
         backtrace: Backtrace,
     },
 
+    /// A detached signature does not verify against the role's signing payload.
+    #[snafu(display("Signature by key '{}' is not valid for role '{}'", keyid, role))]
+    SignatureInvalid { role: String, keyid: String },
+
+    /// A detached signature was made by a key that isn't authorized to sign the role.
+    #[snafu(display("Key '{}' is not authorized to sign role '{}'", keyid, role))]
+    SignatureNotAuthorized { role: String, keyid: String },
+
     #[snafu(display("Unable to find signing keys for role '{}'", role))]
     SigningKeysNotFound { role: String },
 
+    /// The payload to sign could not be recreated from signed metadata.
+    #[snafu(display("Failed to create signing payload for role '{}': {}", role, source))]
+    SigningPayload {
+        role: String,
+        source: crate::schema::Error,
+        backtrace: Backtrace,
+    },
+
+    /// A snapshot Merkle proof was fetched for one role but describes another.
+    #[snafu(display("Snapshot Merkle proof for role '{}' describes role '{}'", name, found))]
+    SnapshotMerkleProofRole {
+        name: String,
+        found: String,
+        backtrace: Backtrace,
+    },
+
+    /// The editor could not build a snapshot Merkle tree.
+    #[snafu(display("Failed to build snapshot Merkle tree: {}", source))]
+    SnapshotMerkleTree {
+        source: crate::schema::Error,
+        backtrace: Backtrace,
+    },
+
+    /// Role metadata has a spec version that is not compatible with this library.
     #[snafu(display(
-        "Tried to use role metadata with spec version '{}', version '{}' is supported",
+        "Tried to use role metadata with spec version '{}', versions compatible with '{}' are supported",
         given,
         supported
     ))]
@@ -312,6 +550,14 @@ pub enum Error {
         latest_known_time: DateTime<Utc>,
     },
 
+    /// A target's custom metadata does not match the type declared with `validate_custom`.
+    #[snafu(display("Invalid custom metadata for target '{}': {}", name, source))]
+    TargetCustom {
+        name: String,
+        source: crate::schema::Error,
+        backtrace: Backtrace,
+    },
+
     #[snafu(display("Refusing to replace {} with requested {} for target {}", found, expected, path.display()))]
     TargetFileTypeMismatch {
         expected: String,
@@ -327,6 +573,49 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    /// A local target file's length does not match the length listed in the repository metadata.
+    #[snafu(display(
+        "Length mismatch for target '{}': found {}{} bytes, expected {}",
+        name,
+        if *truncated { "more than " } else { "" },
+        found,
+        expected
+    ))]
+    TargetLengthMismatch {
+        name: String,
+        found: u64,
+        truncated: bool,
+        expected: u64,
+        backtrace: Backtrace,
+    },
+
+    /// A target name is not a relative path that stays within the targets directory.
+    #[snafu(display(
+        "Invalid target name '{}': must be a relative path without '..' components",
+        name
+    ))]
+    TargetNameInvalid { name: String, backtrace: Backtrace },
+
+    /// The requested target is not listed in the repository metadata.
+    #[snafu(display("Target '{}' is not listed in the repository metadata", name))]
+    TargetNotListed { name: String, backtrace: Backtrace },
+
+    /// A target file to be named by its path within a directory is not in that directory.
+    #[snafu(display("Target '{}' is not within '{}'", path.display(), indir.display()))]
+    TargetOutsideDir {
+        path: PathBuf,
+        indir: PathBuf,
+        backtrace: Backtrace,
+    },
+
+    /// Reading a local copy of a target failed.
+    #[snafu(display("Failed to read target '{}': {}", name, source))]
+    TargetRead {
+        name: String,
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+
     /// A transport error occurred while fetching a URL.
     #[snafu(display("Failed to fetch {}: {}", url, source))]
     Transport {
@@ -350,6 +639,18 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    /// A role's snapshot Merkle proof does not lead to the Merkle root in the timestamp metadata.
+    #[snafu(display(
+        "Failed to verify snapshot Merkle proof for role '{}': {}",
+        name,
+        source
+    ))]
+    VerifySnapshotMerkleProof {
+        name: String,
+        source: crate::schema::Error,
+        backtrace: Backtrace,
+    },
+
     /// The trusted root metadata file could not be verified.
     #[snafu(display("Failed to verify trusted root metadata: {}", source))]
     VerifyTrustedMetadata {
@@ -357,6 +658,14 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    /// The user-selected targets metadata (TAP 13) could not be verified with the keys the user
+    /// trusts.
+    #[snafu(display("Failed to verify user-selected targets metadata: {}", source))]
+    VerifyUserTargets {
+        source: crate::schema::Error,
+        backtrace: Backtrace,
+    },
+
     /// A fetched metadata file did not have the version we expected it to have.
     #[snafu(display(
         "{} metadata version mismatch: fetched {}, expected {}",
@@ -371,6 +680,26 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    /// A version given for re-signed metadata isn't greater than its current version.
+    #[snafu(display(
+        "New {} metadata version {} must be greater than the current version {}",
+        role,
+        version,
+        current
+    ))]
+    VersionNotIncreased {
+        role: RoleType,
+        version: u64,
+        current: u64,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("The version of the {} metadata can't be incremented", role))]
+    VersionOverflow {
+        role: RoleType,
+        backtrace: Backtrace,
+    },
+
     #[snafu(display("Error reading data from '{}': {}", url, source))]
     CacheFileRead {
         url: Url,
@@ -385,6 +714,13 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    #[snafu(display("Error moving cached file into place at '{}': {}", path.display(), source))]
+    CacheFilePersist {
+        path: PathBuf,
+        source: tempfile::PersistError,
+        backtrace: Backtrace,
+    },
+
     #[snafu(display("Error creating the directory '{}': {}", path.display(), source))]
     CacheDirectoryCreate {
         path: PathBuf,
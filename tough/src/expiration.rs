@@ -0,0 +1,196 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::clock::Clock;
+use crate::datastore::Datastore;
+use crate::error::{self, Result};
+use crate::schema::{RoleId, RoleType};
+use crate::{system_time, ExpirationEnforcement};
+use chrono::{DateTime, Duration, Utc};
+use log::warn;
+
+/// How a [`Repository`] treats a metadata file that has expired.
+///
+/// [`Repository`]: crate::Repository
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpirationRule {
+    /// Expired metadata is an error.
+    Strict,
+
+    /// Metadata that expired less than the given duration ago is accepted and reported as an
+    /// [`ExpirationWarning`]. Metadata that expired longer ago is an error. A grace period too long
+    /// to add to the expiration time is treated as [`Strict`](ExpirationRule::Strict).
+    Grace(Duration),
+
+    /// Expired metadata is accepted and reported as an [`ExpirationWarning`].
+    Warn,
+}
+
+/// The [`ExpirationRule`] for each metadata role, used with [`ExpirationEnforcement::Policy`].
+///
+/// The [`Default`] implementation is strict for every role, which is the same as
+/// [`ExpirationEnforcement::Safe`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExpirationPolicy {
+    /// The rule for root.json.
+    pub root: ExpirationRule,
+
+    /// The rule for timestamp.json.
+    pub timestamp: ExpirationRule,
+
+    /// The rule for snapshot.json.
+    pub snapshot: ExpirationRule,
+
+    /// The rule for the top-level targets.json.
+    pub targets: ExpirationRule,
+
+    /// The rule for every delegated targets role.
+    pub delegated: ExpirationRule,
+}
+
+impl Default for ExpirationPolicy {
+    fn default() -> Self {
+        Self {
+            root: ExpirationRule::Strict,
+            timestamp: ExpirationRule::Strict,
+            snapshot: ExpirationRule::Strict,
+            targets: ExpirationRule::Strict,
+            delegated: ExpirationRule::Strict,
+        }
+    }
+}
+
+/// Describes an expired metadata file that was accepted because of an [`ExpirationPolicy`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpirationWarning {
+    /// The type of the expired role.
+    pub role: RoleType,
+
+    /// The name of the expired role, if it is a delegated targets role.
+    pub delegated_role: Option<String>,
+
+    /// When the role expired.
+    pub expires: DateTime<Utc>,
+
+    /// The time the role was checked.
+    pub now: DateTime<Utc>,
+}
+
+/// Identifies the metadata file being checked for expiration.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Expiring<'a> {
+    Standard(RoleType),
+    Delegated(&'a str),
+}
+
+impl Expiring<'_> {
+    fn rule(self, policy: &ExpirationPolicy) -> ExpirationRule {
+        match self {
+            Expiring::Standard(RoleType::Root) => policy.root,
+            Expiring::Standard(RoleType::Timestamp) => policy.timestamp,
+            Expiring::Standard(RoleType::Snapshot) => policy.snapshot,
+            Expiring::Standard(RoleType::Targets) => policy.targets,
+            Expiring::Standard(RoleType::DelegatedTargets) | Expiring::Delegated(_) => {
+                policy.delegated
+            }
+        }
+    }
+
+    /// Returns the error for this role having expired.
+    pub(crate) fn expired<T>(self) -> Result<T> {
+        match self {
+            Expiring::Standard(role) => error::ExpiredMetadata { role }.fail(),
+            Expiring::Delegated(name) => error::ExpiredDelegatedMetadata { name }.fail(),
+        }
+    }
+}
+
+impl<'a> From<&'a RoleId> for Expiring<'a> {
+    fn from(role: &'a RoleId) -> Self {
+        match role {
+            RoleId::StandardRole(role_type) => Expiring::Standard(*role_type),
+            RoleId::DelegatedRole(name) => Expiring::Delegated(name),
+        }
+    }
+}
+
+/// Everything needed to check a role for expiration while loading a repository.
+#[derive(Clone, Copy)]
+pub(crate) struct ExpirationCheck<'a> {
+    pub(crate) enforcement: ExpirationEnforcement,
+    pub(crate) clock: &'a dyn Clock,
+    pub(crate) on_warning: Option<&'a dyn Fn(&ExpirationWarning)>,
+}
+
+impl ExpirationCheck<'_> {
+    /// Returns the rule for a role, or `None` if expiration is not enforced at all.
+    fn rule(&self, role: Expiring<'_>) -> Option<ExpirationRule> {
+        match self.enforcement {
+            ExpirationEnforcement::Safe => Some(ExpirationRule::Strict),
+            ExpirationEnforcement::Unsafe => None,
+            ExpirationEnforcement::Policy(policy) => Some(role.rule(&policy)),
+        }
+    }
+
+    /// Returns the time after which a role that expires at `expires` is no longer accepted, or
+    /// `None` if it is always accepted.
+    pub(crate) fn deadline(
+        &self,
+        role: Expiring<'_>,
+        expires: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        match self.rule(role)? {
+            ExpirationRule::Strict => Some(expires),
+            // Overflow must not turn into "never expires", so it falls back to the strict rule.
+            ExpirationRule::Grace(grace) => {
+                Some(expires.checked_add_signed(grace).unwrap_or(expires))
+            }
+            ExpirationRule::Warn => None,
+        }
+    }
+
+    /// Checks a role for expiration, reporting a warning if it has expired but is accepted anyway.
+    pub(crate) fn check(
+        &self,
+        datastore: &Datastore<'_>,
+        role: Expiring<'_>,
+        expires: DateTime<Utc>,
+    ) -> Result<()> {
+        if self.rule(role).is_none() {
+            return Ok(());
+        }
+        let now = system_time(datastore, self.clock)?;
+        if now < expires {
+            return Ok(());
+        }
+        match self.deadline(role, expires) {
+            Some(deadline) if now >= deadline => return role.expired(),
+            _ => {}
+        }
+        let warning = ExpirationWarning {
+            role: match role {
+                Expiring::Standard(role_type) => role_type,
+                Expiring::Delegated(_) => RoleType::Targets,
+            },
+            delegated_role: match role {
+                Expiring::Standard(_) => None,
+                Expiring::Delegated(name) => Some(name.to_owned()),
+            },
+            expires,
+            now,
+        };
+        warn!(
+            "Accepting expired {} metadata{}, which expired at {}",
+            warning.role,
+            warning
+                .delegated_role
+                .as_ref()
+                .map_or_else(String::new, |name| format!(" for role '{}'", name)),
+            expires
+        );
+        if let Some(on_warning) = self.on_warning {
+            on_warning(&warning);
+        }
+        Ok(())
+    }
+}
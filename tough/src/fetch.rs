@@ -5,7 +5,7 @@ use crate::error::{self, Result};
 use crate::io::{DigestAdapter, MaxSizeAdapter};
 use crate::transport::Transport;
 use snafu::ResultExt;
-use std::io::Read;
+use std::io::{BufReader, Read};
 use url::Url;
 
 pub(crate) fn fetch_max_size<T: Transport>(
@@ -14,11 +14,15 @@ pub(crate) fn fetch_max_size<T: Transport>(
     max_size: u64,
     specifier: &'static str,
 ) -> Result<impl Read> {
+    // Metadata is parsed straight from the reader, a few bytes at a time, so buffer the reads
+    // from the transport.
     Ok(MaxSizeAdapter::new(
-        transport
-            .fetch(url.clone())
-            .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)
-            .context(error::Transport { url })?,
+        BufReader::new(
+            transport
+                .fetch(url.clone())
+                .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)
+                .context(error::Transport { url })?,
+        ),
         specifier,
         max_size,
     ))
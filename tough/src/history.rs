@@ -0,0 +1,212 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Provides an append-only log, kept in the datastore, of the metadata a `Repository` has trusted.
+
+use crate::clock::Clock;
+use crate::datastore::Datastore;
+use crate::error::{self, Result};
+use crate::schema::decoded::{Decoded, Hex};
+use crate::schema::RoleType;
+use crate::{Repository, Transport};
+use chrono::{DateTime, Utc};
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use snafu::{ensure, ResultExt};
+use std::io::{BufRead, BufReader};
+use std::num::NonZeroU64;
+use std::path::Path;
+
+/// The name of the history log in the datastore.
+const HISTORY_FILE: &str = "history.jsonl";
+
+/// The name of the file in the datastore that records the end of the history log, so that new
+/// entries can be chained and checked for duplicates without reading the whole log.
+const HISTORY_HEAD_FILE: &str = "history-head.json";
+
+/// A metadata file that was trusted by a [`Repository`], as recorded in the datastore when
+/// [`Settings::history`] is enabled.
+///
+/// Each entry is stored as one line of JSON. Entries are chained together: each entry records the
+/// SHA-256 digest of the line before it, so an entry that is changed or removed after it was
+/// written is detected when the history is read. (Entries removed from the end of the log cannot
+/// be detected this way.)
+///
+/// The chain is not anchored to anything outside the log: the first entry has no previous digest,
+/// and nothing is signed. It detects damage and careless edits, but anyone who can write to the
+/// datastore can rewrite the whole log with a new, valid chain.
+///
+/// [`Settings::history`]: crate::Settings::history
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// The type of the trusted role.
+    pub role: RoleType,
+
+    /// The name of the trusted role, if it is a delegated targets role.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delegated_role: Option<String>,
+
+    /// The version of the trusted metadata.
+    pub version: NonZeroU64,
+
+    /// The SHA-256 digest of the metadata file, as it was fetched.
+    pub sha256: Decoded<Hex>,
+
+    /// When the metadata was trusted, according to the repository's [`Clock`].
+    ///
+    /// [`Clock`]: crate::Clock
+    pub accepted_at: DateTime<Utc>,
+
+    /// The SHA-256 digest of the previous line of the log, or `None` for the first entry.
+    pub previous: Option<Decoded<Hex>>,
+}
+
+/// Reads the metadata history log from a datastore directory, checking that the entries are
+/// correctly chained together.
+///
+/// This does not load the repository, so it can be used even if the metadata in the datastore can
+/// no longer be loaded. Returns an empty list if there is no history log.
+pub fn read_history<P: AsRef<Path>>(datastore: P) -> Result<Vec<HistoryEntry>> {
+    read_entries(&Datastore::new(datastore.as_ref(), false))
+}
+
+impl<T: Transport> Repository<'_, T> {
+    /// Returns the metadata history log from the repository's datastore, checking that the
+    /// entries are correctly chained together.
+    ///
+    /// Metadata is only recorded when [`Settings::history`] is enabled.
+    ///
+    /// [`Settings::history`]: crate::Settings::history
+    pub fn history(&self) -> Result<Vec<HistoryEntry>> {
+        read_entries(&self.datastore)
+    }
+}
+
+fn read_entries(datastore: &Datastore<'_>) -> Result<Vec<HistoryEntry>> {
+    let mut entries = Vec::new();
+    let reader = match datastore.reader(HISTORY_FILE)? {
+        Some(reader) => reader,
+        None => return Ok(entries),
+    };
+    let mut previous: Option<Decoded<Hex>> = None;
+    for (index, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line.context(error::HistoryRead)?;
+        let entry: HistoryEntry =
+            serde_json::from_str(&line).context(error::HistoryParse { line: index + 1 })?;
+        ensure!(
+            entry.previous == previous,
+            error::HistoryChain { line: index + 1 }
+        );
+        previous = Some(line_digest(&line));
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// The end of the history log, kept next to it in the datastore.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HistoryHead {
+    /// The size of the log, in bytes, when the head was written. If the log has a different size,
+    /// something other than `record` changed it, and the head is rebuilt from the log.
+    size: u64,
+
+    /// The SHA-256 digest of the last line of the log.
+    previous: Option<Decoded<Hex>>,
+
+    /// The most recent entry for each role in the log.
+    latest: Vec<HistoryEntry>,
+}
+
+impl HistoryHead {
+    /// Reads the head of the history log, or rebuilds it by reading the whole log if it is
+    /// missing or out of date.
+    fn read(datastore: &Datastore<'_>) -> Result<Self> {
+        let size = datastore.size(HISTORY_FILE)?.unwrap_or(0);
+        if let Some(reader) = datastore.reader(HISTORY_HEAD_FILE)? {
+            if let Ok(head) = serde_json::from_reader::<_, Self>(reader) {
+                if head.size == size {
+                    return Ok(head);
+                }
+            }
+        }
+
+        // The chain is not checked here; a damaged log shouldn't prevent updates, and is reported
+        // when the history is read.
+        let mut head = Self {
+            size,
+            ..Self::default()
+        };
+        if let Some(reader) = datastore.reader(HISTORY_FILE)? {
+            for line in BufReader::new(reader).lines() {
+                let line = line.context(error::HistoryRead)?;
+                if let Ok(entry) = serde_json::from_str::<HistoryEntry>(&line) {
+                    head.push(entry);
+                }
+                head.previous = Some(line_digest(&line));
+            }
+        }
+        Ok(head)
+    }
+
+    /// Returns whether `entry` is already recorded: it is the most recent entry for its role,
+    /// ignoring when it was accepted, or it is older than that entry. An older version is one
+    /// that was trusted before, such as a root earlier in the chain that is verified again on
+    /// each load.
+    fn is_recorded(&self, entry: &HistoryEntry) -> bool {
+        self.latest.iter().any(|latest| {
+            latest.role == entry.role
+                && latest.delegated_role == entry.delegated_role
+                && (latest.version > entry.version
+                    || (latest.version == entry.version && latest.sha256 == entry.sha256))
+        })
+    }
+
+    /// Makes `entry` the most recent entry for its role.
+    fn push(&mut self, entry: HistoryEntry) {
+        self.latest.retain(|latest| {
+            latest.role != entry.role || latest.delegated_role != entry.delegated_role
+        });
+        self.latest.push(entry);
+    }
+}
+
+/// Appends a newly trusted metadata file to the history log, if history is enabled for the
+/// datastore. Metadata that is already recorded, or older than the most recent entry for its
+/// role, is not recorded again. Rollback protection keeps the client from trusting an older
+/// version of a role, except when recovering from a fast-forward attack; the older versions
+/// trusted then are not recorded.
+pub(crate) fn record(
+    datastore: &Datastore<'_>,
+    clock: &dyn Clock,
+    role: RoleType,
+    delegated_role: Option<&str>,
+    version: NonZeroU64,
+    sha256: Vec<u8>,
+) -> Result<()> {
+    if !datastore.history() {
+        return Ok(());
+    }
+
+    let mut head = HistoryHead::read(datastore)?;
+    let entry = HistoryEntry {
+        role,
+        delegated_role: delegated_role.map(str::to_owned),
+        version,
+        sha256: sha256.into(),
+        accepted_at: clock.now(),
+        previous: head.previous.take(),
+    };
+    if head.is_recorded(&entry) {
+        return Ok(());
+    }
+
+    let line = datastore.append(HISTORY_FILE, &entry)?;
+    head.previous = Some(line_digest(&line));
+    head.size = datastore.size(HISTORY_FILE)?.unwrap_or(0);
+    head.push(entry);
+    datastore.create(HISTORY_HEAD_FILE, &head)
+}
+
+fn line_digest(line: &str) -> Decoded<Hex> {
+    digest(&SHA256, line.as_bytes()).as_ref().to_vec().into()
+}
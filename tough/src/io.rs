@@ -52,6 +52,62 @@ impl<T: Read> Read for DigestAdapter<T> {
     }
 }
 
+/// Passes data through from a reader, calculating its SHA-256 digest along the way.
+pub(crate) struct Sha256Reader<T> {
+    reader: T,
+    digest: Context,
+}
+
+impl<T: Read> Sha256Reader<T> {
+    pub(crate) fn new(reader: T) -> Self {
+        Self {
+            reader,
+            digest: Context::new(&SHA256),
+        }
+    }
+
+    /// Returns the digest of the data read so far.
+    pub(crate) fn finish(self) -> Vec<u8> {
+        self.digest.finish().as_ref().to_vec()
+    }
+}
+
+impl<T: Read> Read for Sha256Reader<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let size = self.reader.read(buf)?;
+        self.digest.update(&buf[..size]);
+        Ok(size)
+    }
+}
+
+/// Passes data through from a reader, keeping a copy of everything read.
+pub(crate) struct BufferingReader<T> {
+    reader: T,
+    buffer: Vec<u8>,
+}
+
+impl<T: Read> BufferingReader<T> {
+    pub(crate) fn new(reader: T) -> Self {
+        Self {
+            reader,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Returns the data read so far.
+    pub(crate) fn into_buffer(self) -> Vec<u8> {
+        self.buffer
+    }
+}
+
+impl<T: Read> Read for BufferingReader<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let size = self.reader.read(buf)?;
+        self.buffer.extend_from_slice(&buf[..size]);
+        Ok(size)
+    }
+}
+
 pub(crate) struct MaxSizeAdapter<T> {
     reader: T,
     /// How the `max_size` was specified. For example the max size of `root.json` is specified by
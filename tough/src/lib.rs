@@ -30,32 +30,61 @@
 )]
 
 mod cache;
+mod clock;
 mod datastore;
 pub mod editor;
 pub mod error;
+mod expiration;
 mod fetch;
+mod history;
 #[cfg(feature = "http")]
 pub mod http;
 mod io;
 pub mod key_source;
+mod report;
+mod root_chain;
 pub mod schema;
 pub mod sign;
+mod target_index;
+mod target_name;
 mod transport;
+mod verify_target;
 
+pub use crate::cache::CacheReport;
+pub use crate::clock::{Clock, FixedClock, SystemClock};
 use crate::datastore::Datastore;
 use crate::error::Result;
+use crate::expiration::{ExpirationCheck, Expiring};
+pub use crate::expiration::{ExpirationPolicy, ExpirationRule, ExpirationWarning};
 use crate::fetch::{fetch_max_size, fetch_sha256};
+pub use crate::history::{read_history, HistoryEntry};
 /// An HTTP transport that includes retries.
 #[cfg(feature = "http")]
 pub use crate::http::{ClientSettings, HttpTransport, RetryRead};
-use crate::schema::{DelegatedRole, Delegations};
-use crate::schema::{Role, RoleType, Root, Signed, Snapshot, Timestamp};
+use crate::io::{BufferingReader, Sha256Reader};
+pub use crate::report::{KeyReport, RepositoryReport, RoleReport};
+use crate::root_chain::verify_root_update;
+pub use crate::root_chain::{KeyChange, VerifiedRoot};
+use crate::schema::key::Key;
+use crate::schema::{
+    spec_version_supported, Role, RoleId, RoleType, Root, Signed, Snapshot, SnapshotMerkleProof,
+    SnapshotMeta, Timestamp, SPEC_VERSION,
+};
+use crate::schema::{
+    DelegatedRole, Delegations, PathSet, Target, TargetMatch, TargetQuery, Targets,
+};
+pub use crate::target_index::IndexedTarget;
+use crate::target_index::TargetIndex;
 pub use crate::transport::{FilesystemTransport, Transport};
 use chrono::{DateTime, Utc};
+use ring::digest::{digest, SHA256};
 use snafu::{ensure, OptionExt, ResultExt};
 use std::borrow::Cow;
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fmt;
 use std::io::Read;
+use std::num::NonZeroU64;
 use std::path::Path;
 use url::Url;
 
@@ -69,6 +98,12 @@ pub enum ExpirationEnforcement {
     /// Expirations will not be enforced. This is available for certain offline use cases, does NOT
     /// provide TUF security guarantees, and should only be used if you are sure that you need it.
     Unsafe,
+
+    /// Expirations will be enforced according to the [`ExpirationRule`] for each role. Roles that
+    /// are accepted despite having expired are reported to [`Settings::expiration_warning`].
+    /// Any rule other than [`ExpirationRule::Strict`] weakens TUF security guarantees for that
+    /// role.
+    Policy(ExpirationPolicy),
 }
 
 /// `ExpirationEnforcement` defaults to `Safe` mode.
@@ -90,12 +125,32 @@ impl From<bool> for ExpirationEnforcement {
 
 impl From<ExpirationEnforcement> for bool {
     fn from(ee: ExpirationEnforcement) -> Self {
-        ee == ExpirationEnforcement::Safe
+        ee != ExpirationEnforcement::Unsafe
     }
 }
 
 /// Repository fetch settings, provided to [`Repository::load`].
-#[derive(Debug, Clone)]
+///
+/// Create `Settings` with [`Settings::new`], which uses defaults for the optional settings, and
+/// change those with its builder methods:
+///
+/// ```no_run
+/// # use std::fs::File;
+/// # use std::path::Path;
+/// # use tough::{ExpirationEnforcement, FilesystemTransport, Repository, Settings};
+/// let repo = Repository::load(
+///     &FilesystemTransport,
+///     Settings::new(
+///         File::open("root.json").unwrap(),
+///         Path::new("/var/lib/tough"),
+///         "file:///srv/repo/metadata",
+///         "file:///srv/repo/targets",
+///     )
+///     .history(true),
+/// );
+/// ```
+#[derive(Clone)]
+#[non_exhaustive]
 pub struct Settings<'a, R: Read> {
     /// A [`Read`]er to the trusted root metadata file, which you must ship with your software
     /// using an out-of-band-process.
@@ -108,6 +163,9 @@ pub struct Settings<'a, R: Read> {
     /// A [`Path`] to a directory on a persistent filesystem. Tough stores the most recently
     /// fetched timestamp, snapshot, and targets metadata files here to detect version rollback
     /// attacks. The directory must exist prior to calling [`Repository::load`].
+    ///
+    /// Loading a repository takes a file lock in this directory, so separate processes can safely
+    /// share a datastore; their loads happen one at a time.
     pub datastore: &'a Path,
 
     /// The URL base for TUF metadata (such as timestamp.json).
@@ -130,6 +188,117 @@ pub struct Settings<'a, R: Read> {
     /// limit a replay attack window. By setting `expiration_enforcement` to `Unsafe`, you are
     /// disabling this feature of TUF. Use `Safe` unless you have a good reason to use `Unsafe`.
     pub expiration_enforcement: ExpirationEnforcement,
+
+    /// The source of the current time, used to check metadata expiration.
+    ///
+    /// Use [`SystemClock`] unless the system clock cannot be trusted, such as on devices that have
+    /// no real-time clock at boot.
+    pub clock: &'a dyn Clock,
+
+    /// Called for each metadata file that has expired but is accepted anyway because of an
+    /// [`ExpirationEnforcement::Policy`]. Warnings are also logged.
+    pub expiration_warning: Option<&'a dyn Fn(&ExpirationWarning)>,
+
+    /// Whether to record each newly trusted metadata file in an append-only history log in the
+    /// datastore. The log can be read with [`Repository::history`] or [`read_history`].
+    pub history: bool,
+
+    /// A user-selected top-level targets role (TAP 13), which takes the place of the repository's
+    /// top-level targets role when looking up targets. See [`UserTargets`].
+    pub user_targets: Option<UserTargets>,
+}
+
+impl<R: Read + fmt::Debug> fmt::Debug for Settings<'_, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Settings")
+            .field("root", &self.root)
+            .field("datastore", &self.datastore)
+            .field("metadata_base_url", &self.metadata_base_url)
+            .field("targets_base_url", &self.targets_base_url)
+            .field("limits", &self.limits)
+            .field("expiration_enforcement", &self.expiration_enforcement)
+            .field("clock", &self.clock)
+            .field(
+                "expiration_warning",
+                &self.expiration_warning.map(|_| "<callback>"),
+            )
+            .field("history", &self.history)
+            .field("user_targets", &self.user_targets)
+            .finish()
+    }
+}
+
+impl<'a, R: Read> Settings<'a, R> {
+    /// Creates `Settings` that trust the root metadata read from `root`, store metadata in
+    /// `datastore`, and fetch from `metadata_base_url` and `targets_base_url`.
+    ///
+    /// The other settings start at their defaults: [`Limits::default`], `Safe` expiration
+    /// enforcement against the [`SystemClock`], no expiration warning callback, no history, and no
+    /// user-selected targets role.
+    pub fn new(
+        root: R,
+        datastore: &'a Path,
+        metadata_base_url: &'a str,
+        targets_base_url: &'a str,
+    ) -> Self {
+        Self {
+            root,
+            datastore,
+            metadata_base_url,
+            targets_base_url,
+            limits: Limits::default(),
+            expiration_enforcement: ExpirationEnforcement::default(),
+            clock: &SystemClock,
+            expiration_warning: None,
+            history: false,
+            user_targets: None,
+        }
+    }
+
+    /// Set the limits used when fetching repository metadata.
+    #[must_use]
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Set how metadata expiration is enforced.
+    #[must_use]
+    pub fn expiration_enforcement(mut self, expiration_enforcement: ExpirationEnforcement) -> Self {
+        self.expiration_enforcement = expiration_enforcement;
+        self
+    }
+
+    /// Set the source of the current time used to check metadata expiration.
+    #[must_use]
+    pub fn clock(mut self, clock: &'a dyn Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Set a callback for each metadata file that has expired but is accepted anyway.
+    #[must_use]
+    pub fn expiration_warning(
+        mut self,
+        expiration_warning: &'a dyn Fn(&ExpirationWarning),
+    ) -> Self {
+        self.expiration_warning = Some(expiration_warning);
+        self
+    }
+
+    /// Set whether to record newly trusted metadata in the datastore's history log.
+    #[must_use]
+    pub fn history(mut self, history: bool) -> Self {
+        self.history = history;
+        self
+    }
+
+    /// Set a user-selected top-level targets role (TAP 13).
+    #[must_use]
+    pub fn user_targets(mut self, user_targets: UserTargets) -> Self {
+        self.user_targets = Some(user_targets);
+        self
+    }
 }
 
 /// Limits used when fetching repository metadata.
@@ -143,6 +312,9 @@ pub struct Settings<'a, R: Read> {
 /// * `max_targets_size`: 10 MiB
 /// * `max_timestamp_size`: 1 MiB
 /// * `max_root_updates`: 1024
+/// * `max_delegated_targets_size`: 10 MiB
+/// * `max_delegation_depth`: 32
+/// * `max_delegated_roles`: 1024
 #[derive(Debug, Clone, Copy)]
 pub struct Limits {
     /// The maximum allowable size in bytes for downloaded root.json files.
@@ -158,6 +330,17 @@ pub struct Limits {
 
     /// The maximum number of updates to root.json to download.
     pub max_root_updates: u64,
+
+    /// The maximum allowable size in bytes for each downloaded delegated targets metadata file. If
+    /// snapshot.json lists the size of a delegated role, that size must not exceed this limit.
+    pub max_delegated_targets_size: u64,
+
+    /// The maximum depth of delegated targets roles. Roles delegated by targets.json are at depth
+    /// 1, roles they delegate to are at depth 2, and so on.
+    pub max_delegation_depth: u64,
+
+    /// The maximum number of delegated targets roles to load from the repository.
+    pub max_delegated_roles: u64,
 }
 
 impl Default for Limits {
@@ -167,10 +350,35 @@ impl Default for Limits {
             max_targets_size: 1024 * 1024 * 10, // 10 MiB
             max_timestamp_size: 1024 * 1024,    // 1 MiB
             max_root_updates: 1024,
+            max_delegated_targets_size: 1024 * 1024 * 10, // 10 MiB
+            max_delegation_depth: 32,
+            max_delegated_roles: 1024,
         }
     }
 }
 
+/// A top-level targets role selected by the user, as described by [TAP 13].
+///
+/// The metadata is provided locally and signed by keys that the user trusts, rather than by keys
+/// listed in the repository's root metadata. When it is provided, a [`Repository`] looks up targets
+/// starting from this role instead of the repository's top-level targets role: targets it lists
+/// come first, followed by those of its delegated roles, in order. Delegated roles are fetched from
+/// the repository and checked against its snapshot metadata like any other delegated role. To fall
+/// back to the repository's own top-level targets, delegate to a role named `targets`.
+///
+/// [TAP 13]: https://github.com/theupdateframework/taps/blob/master/tap13.md
+#[derive(Debug, Clone)]
+pub struct UserTargets {
+    /// The user-selected targets metadata.
+    pub metadata: Signed<Targets>,
+
+    /// The keys trusted to sign `metadata`.
+    pub keys: Vec<Key>,
+
+    /// The number of valid signatures from `keys` that `metadata` requires.
+    pub threshold: NonZeroU64,
+}
+
 /// A TUF repository.
 ///
 /// You can create a `Repository` using the `load` method.
@@ -179,16 +387,20 @@ pub struct Repository<'a, T: Transport> {
     transport: &'a T,
     consistent_snapshot: bool,
     datastore: Datastore<'a>,
-    earliest_expiration: DateTime<Utc>,
-    earliest_expiration_role: RoleType,
+    earliest_expiration: Option<(DateTime<Utc>, RoleId)>,
     root: Signed<Root>,
-    snapshot: Signed<Snapshot>,
+    root_chain: Vec<VerifiedRoot>,
+    snapshot: Option<Signed<Snapshot>>,
     timestamp: Signed<Timestamp>,
     targets: Signed<crate::schema::Targets>,
+    user_targets: Option<Signed<crate::schema::Targets>>,
+    /// The keys the user trusts to sign `user_targets`, as a delegation to it.
+    user_targets_keys: Option<Delegations>,
+    target_index: TargetIndex,
     limits: Limits,
     metadata_base_url: Url,
     targets_base_url: Url,
-    expiration_enforcement: ExpirationEnforcement,
+    clock: &'a dyn Clock,
 }
 
 impl<'a, T: Transport> Repository<'a, T> {
@@ -212,22 +424,33 @@ impl<'a, T: Transport> Repository<'a, T> {
     ///
     /// `metadata_base_url` and `targets_base_url` are the HTTP(S) base URLs for where the client
     /// can find metadata (such as root.json) and targets (as listed in targets.json).
+    #[allow(clippy::too_many_lines)]
     pub fn load<R: Read>(transport: &'a T, settings: Settings<'a, R>) -> Result<Self> {
         let metadata_base_url = parse_url(settings.metadata_base_url)?;
         let targets_base_url = parse_url(settings.targets_base_url)?;
 
-        let datastore = Datastore::new(settings.datastore);
+        let datastore = Datastore::new(settings.datastore, settings.history);
+        // Hold the datastore lock until loading is complete, so that another process loading a
+        // repository with the same datastore can't interleave its writes with ours.
+        let _lock = datastore.lock()?;
+        let expiration = ExpirationCheck {
+            enforcement: settings.expiration_enforcement,
+            clock: settings.clock,
+            on_warning: settings.expiration_warning,
+        };
 
         // 0. Load the trusted root metadata file + 1. Update the root metadata file
-        let root = load_root(
+        let root_chain = load_root(
             transport,
             settings.root,
             &datastore,
             settings.limits.max_root_size,
             settings.limits.max_root_updates,
             &metadata_base_url,
-            settings.expiration_enforcement,
+            &expiration,
         )?;
+        // `load_root` always returns at least the trusted root.
+        let root = root_chain[root_chain.len() - 1].signed().clone();
 
         // 2. Download the timestamp metadata file
         let timestamp = load_timestamp(
@@ -236,69 +459,132 @@ impl<'a, T: Transport> Repository<'a, T> {
             &datastore,
             settings.limits.max_timestamp_size,
             &metadata_base_url,
-            settings.expiration_enforcement,
+            &expiration,
         )?;
 
-        // 3. Download the snapshot metadata file
-        let snapshot = load_snapshot(
-            transport,
-            &root,
-            &timestamp,
-            &datastore,
-            &metadata_base_url,
-            settings.expiration_enforcement,
-        )?;
+        // 3. Download the snapshot metadata file, unless the repository publishes a snapshot
+        //    Merkle tree (TAP 16), in which case each targets role is checked against its proof.
+        let snapshot = match timestamp.signed.merkle_root {
+            Some(_) => None,
+            None => Some(load_snapshot(
+                transport,
+                &root,
+                &timestamp,
+                &datastore,
+                &metadata_base_url,
+                &expiration,
+            )?),
+        };
+        let merkle_versions = MerkleVersions::read(&datastore)?;
+        let snapshot_source = match &snapshot {
+            Some(snapshot) => SnapshotSource::Snapshot(snapshot),
+            None => SnapshotSource::Merkle(&timestamp, &merkle_versions),
+        };
 
         // 4. Download the targets metadata file
+        let mut roles_loaded = 0;
         let targets = load_targets(
             transport,
             &root,
-            &snapshot,
+            snapshot_source,
             &datastore,
-            settings.limits.max_targets_size,
+            &settings.limits,
             &metadata_base_url,
-            settings.expiration_enforcement,
+            &expiration,
+            &mut roles_loaded,
         )?;
 
-        let expires_iter = [
-            (root.signed.expires, RoleType::Root),
-            (timestamp.signed.expires, RoleType::Timestamp),
-            (snapshot.signed.expires, RoleType::Snapshot),
-            (targets.signed.expires, RoleType::Targets),
-        ];
-        let (earliest_expiration, earliest_expiration_role) =
-            expires_iter.iter().min_by_key(|tup| tup.0).unwrap();
+        // TAP 13: a user-selected top-level targets role takes the place of the repository's.
+        let (user_targets, user_targets_keys) = match settings.user_targets {
+            Some(user_targets) => {
+                let (user_targets, keys) = load_user_targets(
+                    transport,
+                    user_targets,
+                    snapshot_source,
+                    root.signed.consistent_snapshot,
+                    &datastore,
+                    &settings.limits,
+                    &metadata_base_url,
+                    &expiration,
+                    &mut roles_loaded,
+                )?;
+                (Some(user_targets), Some(keys))
+            }
+            None => (None, None),
+        };
+        if let SnapshotSource::Merkle(..) = snapshot_source {
+            merkle_versions.write(&datastore)?;
+        }
+
+        let earliest_expiration = earliest_expiration(
+            &expiration,
+            &root,
+            &timestamp,
+            snapshot.as_ref(),
+            &targets,
+            user_targets.as_ref(),
+        );
+        let target_index = TargetIndex::new(&user_targets.as_ref().unwrap_or(&targets).signed);
 
         Ok(Self {
             transport,
             consistent_snapshot: root.signed.consistent_snapshot,
             datastore,
-            earliest_expiration: earliest_expiration.to_owned(),
-            earliest_expiration_role: *earliest_expiration_role,
+            earliest_expiration,
             root,
+            root_chain,
             snapshot,
             timestamp,
             targets,
+            user_targets,
+            user_targets_keys,
+            target_index,
             limits: settings.limits,
             metadata_base_url,
             targets_base_url,
-            expiration_enforcement: settings.expiration_enforcement,
+            clock: settings.clock,
         })
     }
 
     /// Returns the list of targets present in the repository.
+    ///
+    /// This is always the repository's top-level targets role, even if a [`UserTargets`] role was
+    /// provided; see [`Repository::user_targets`].
     pub fn targets(&self) -> &Signed<crate::schema::Targets> {
         &self.targets
     }
 
+    /// Returns the user-selected top-level targets role (TAP 13), with its delegated roles loaded,
+    /// if one was provided in [`Settings::user_targets`].
+    pub fn user_targets(&self) -> Option<&Signed<crate::schema::Targets>> {
+        self.user_targets.as_ref()
+    }
+
+    /// Returns the top-level targets role that targets are looked up from: the user-selected
+    /// targets role if there is one, otherwise the repository's.
+    pub(crate) fn selected_targets(&self) -> &Signed<crate::schema::Targets> {
+        self.user_targets.as_ref().unwrap_or(&self.targets)
+    }
+
+    /// Returns the user-selected targets role and the keys the user trusts to sign it, as a
+    /// delegation to a role named [`USER_TARGETS_ROLE`], if one was provided.
+    pub(crate) fn user_targets_with_keys(
+        &self,
+    ) -> Option<(&Signed<crate::schema::Targets>, &Delegations)> {
+        self.user_targets
+            .as_ref()
+            .zip(self.user_targets_keys.as_ref())
+    }
+
     /// Returns a reference to the signed root
     pub fn root(&self) -> &Signed<Root> {
         &self.root
     }
 
-    /// Returns a reference to the signed snapshot
-    pub fn snapshot(&self) -> &Signed<Snapshot> {
-        &self.snapshot
+    /// Returns a reference to the signed snapshot, or `None` if the repository publishes a
+    /// snapshot Merkle tree (TAP 16) and the snapshot metadata was not needed.
+    pub fn snapshot(&self) -> Option<&Signed<Snapshot>> {
+        self.snapshot.as_ref()
     }
 
     /// Returns a reference to the signed timestamp
@@ -306,9 +592,19 @@ impl<'a, T: Transport> Repository<'a, T> {
         &self.timestamp
     }
 
-    ///return a vec of all targets including all target files delegated by targets
-    pub fn all_targets<'b>(&'b self) -> impl Iterator + 'b {
-        self.targets.signed.targets_iter()
+    /// Returns the name and metadata of each target in the repository, including targets provided
+    /// by delegated roles, in no particular order.
+    ///
+    /// Each target name is listed once, from the role that [`Repository::find_target`] would find
+    /// it in.
+    pub fn all_targets(&self) -> impl Iterator<Item = (&str, &Target)> + '_ {
+        self.target_index.iter(&self.selected_targets().signed)
+    }
+
+    /// Returns the targets in the repository that match `query`, sorted by name, along with the
+    /// name of the role that provides each one. See [`Targets::query`](crate::schema::Targets::query).
+    pub fn query_targets(&self, query: &TargetQuery<'_>) -> Vec<TargetMatch<'_>> {
+        self.selected_targets().signed.query(query)
     }
 
     /// Fetches a target from the repository.
@@ -324,14 +620,7 @@ impl<'a, T: Transport> Repository<'a, T> {
     /// data from the reader if it returns an error.**
     pub fn read_target(&self, name: &str) -> Result<Option<impl Read>> {
         // Check for repository metadata expiration.
-        if self.expiration_enforcement == ExpirationEnforcement::Safe {
-            ensure!(
-                system_time(&self.datastore)? < self.earliest_expiration,
-                error::ExpiredMetadata {
-                    role: self.earliest_expiration_role
-                }
-            );
-        }
+        self.check_earliest_expiration()?;
 
         // 5. Verify the desired target against its targets metadata.
         //
@@ -350,25 +639,38 @@ impl<'a, T: Transport> Repository<'a, T> {
         //   HASH is one of the hashes of the targets file listed in the targets metadata file
         //   found earlier in step 4. In either case, the client MUST write the file to
         //   non-volatile storage as FILENAME.EXT.
-        Ok(if let Ok(target) = self.targets.signed.find_target(name) {
-            let (sha256, file) = self.target_digest_and_filename(target, name);
-            Some(self.fetch_target(target, &sha256, file.as_str())?)
-        } else {
-            None
-        })
+        Ok(
+            if let Some(IndexedTarget { target, .. }) = self.find_target(name) {
+                let (sha256, file) = self.target_digest_and_filename(target, name);
+                Some(self.fetch_target(target, &sha256, file.as_str())?)
+            } else {
+                None
+            },
+        )
     }
 
     /// Return the named `DelegatedRole` if found.
     pub fn delegated_role(&self, name: &str) -> Option<&DelegatedRole> {
-        self.targets.signed.delegated_role(name).ok()
+        self.selected_targets().signed.delegated_role(name).ok()
+    }
+
+    /// Returns an error if any of the loaded metadata is no longer accepted, given the time
+    /// reported by the repository's `Clock`.
+    pub(crate) fn check_earliest_expiration(&self) -> Result<()> {
+        if let Some((deadline, role)) = &self.earliest_expiration {
+            if system_time(&self.datastore, self.clock)? >= *deadline {
+                return Expiring::from(role).expired();
+            }
+        }
+        Ok(())
     }
 }
 
 /// Ensures that system time has not stepped backward since it was last sampled
-fn system_time(datastore: &Datastore<'_>) -> Result<DateTime<Utc>> {
+fn system_time(datastore: &Datastore<'_>, clock: &dyn Clock) -> Result<DateTime<Utc>> {
     let file = "latest_known_time.json";
     // Get 'current' system time
-    let sys_time = Utc::now();
+    let sys_time = clock.now();
     // Load the latest known system time, if it exists
     if let Some(Ok(latest_known_time)) = datastore
         .reader(file)?
@@ -389,10 +691,23 @@ fn system_time(datastore: &Datastore<'_>) -> Result<DateTime<Utc>> {
     Ok(sys_time)
 }
 
-fn check_expired<T: Role>(datastore: &Datastore<'_>, role: &T) -> Result<()> {
+fn check_expired<T: Role>(
+    datastore: &Datastore<'_>,
+    expiration: &ExpirationCheck<'_>,
+    role: &T,
+) -> Result<()> {
+    expiration.check(datastore, Expiring::Standard(T::TYPE), role.expires())
+}
+
+/// Ensures that metadata was written for a version of the TUF specification that we support. The
+/// spec requires clients to reject metadata with an unknown major version.
+fn check_spec_version(spec_version: &str) -> Result<()> {
     ensure!(
-        system_time(datastore)? < role.expires(),
-        error::ExpiredMetadata { role: T::TYPE }
+        spec_version_supported(spec_version),
+        error::SpecVersion {
+            given: spec_version,
+            supported: SPEC_VERSION
+        }
     );
     Ok(())
 }
@@ -414,17 +729,31 @@ fn load_root<R: Read, T: Transport>(
     max_root_size: u64,
     max_root_updates: u64,
     metadata_base_url: &Url,
-    expiration_enforcement: ExpirationEnforcement,
-) -> Result<Signed<Root>> {
+    expiration: &ExpirationCheck<'_>,
+) -> Result<Vec<VerifiedRoot>> {
     // 0. Load the trusted root metadata file. We assume that a good, trusted copy of this file was
     //    shipped with the package manager or software updater using an out-of-band process. Note
     //    that the expiration of the trusted root metadata file does not matter, because we will
     //    attempt to update it in the next step.
+    let mut reader = BufferingReader::new(root);
     let mut root: Signed<Root> =
-        serde_json::from_reader(root).context(error::ParseTrustedMetadata)?;
+        serde_json::from_reader(&mut reader).context(error::ParseTrustedMetadata)?;
     root.signed
         .verify_role(&root)
         .context(error::VerifyTrustedMetadata)?;
+    check_spec_version(&root.signed.spec_version)?;
+    let buffer = reader.into_buffer();
+    history::record(
+        datastore,
+        expiration.clock,
+        RoleType::Root,
+        None,
+        root.signed.version,
+        digest(&SHA256, &buffer).as_ref().to_vec(),
+    )?;
+    // Every version of the root metadata that becomes trusted is kept, along with the exact bytes
+    // that were verified.
+    let mut chain = vec![VerifiedRoot::new(root.clone(), buffer, None)];
 
     // Used in step 1.2
     let original_root_version = root.signed.version.get();
@@ -473,8 +802,9 @@ fn load_root<R: Read, T: Transport>(
         ) {
             Err(_) => break, // If this file is not available, then go to step 1.8.
             Ok(reader) => {
+                let mut reader = BufferingReader::new(reader);
                 let new_root: Signed<Root> =
-                    serde_json::from_reader(reader).context(error::ParseMetadata {
+                    serde_json::from_reader(&mut reader).context(error::ParseMetadata {
                         role: RoleType::Root,
                     })?;
 
@@ -484,17 +814,8 @@ fn load_root<R: Read, T: Transport>(
                 //   file being validated (version N+1). If version N+1 is not signed as required,
                 //   discard it, abort the update cycle, and report the signature failure. On the
                 //   next update cycle, begin at step 0 and version N of the root metadata file.
-                root.signed
-                    .verify_role(&new_root)
-                    .context(error::VerifyMetadata {
-                        role: RoleType::Root,
-                    })?;
-                new_root
-                    .signed
-                    .verify_role(&new_root)
-                    .context(error::VerifyMetadata {
-                        role: RoleType::Root,
-                    })?;
+                verify_root_update(&root, &new_root)?;
+                check_spec_version(&new_root.signed.spec_version)?;
 
                 // 1.4. Check for a rollback attack. The version number of the trusted root
                 //   metadata file (version N) must be less than or equal to the version number of
@@ -529,6 +850,16 @@ fn load_root<R: Read, T: Transport>(
                 // 1.6. Set the trusted root metadata file to the new root metadata file.
                 //
                 // (This is where version N+1 becomes version N.)
+                let buffer = reader.into_buffer();
+                history::record(
+                    datastore,
+                    expiration.clock,
+                    RoleType::Root,
+                    None,
+                    new_root.signed.version,
+                    digest(&SHA256, &buffer).as_ref().to_vec(),
+                )?;
+                chain.push(VerifiedRoot::new(new_root.clone(), buffer, chain.last()));
                 root = new_root;
 
                 // 1.7. Repeat steps 1.1 to 1.7.
@@ -541,9 +872,7 @@ fn load_root<R: Read, T: Transport>(
     //   timestamp in the trusted root metadata file (version N). If the trusted root metadata file
     //   has expired, abort the update cycle, report the potential freeze attack. On the next
     //   update cycle, begin at step 0 and version N of the root metadata file.
-    if expiration_enforcement == ExpirationEnforcement::Safe {
-        check_expired(datastore, &root.signed)?;
-    }
+    check_expired(datastore, expiration, &root.signed)?;
 
     // 1.9. If the timestamp and / or snapshot keys have been rotated, then delete the trusted
     //   timestamp and snapshot metadata files. This is done in order to recover from fast-forward
@@ -560,7 +889,8 @@ fn load_root<R: Read, T: Transport>(
     {
         let r1 = datastore.remove("timestamp.json");
         let r2 = datastore.remove("snapshot.json");
-        r1.and(r2)?;
+        let r3 = datastore.remove(SNAPSHOT_MERKLE_VERSIONS_FILE);
+        r1.and(r2).and(r3)?;
     }
 
     // 1.10. Set whether consistent snapshots are used as per the trusted root metadata file (see
@@ -569,7 +899,7 @@ fn load_root<R: Read, T: Transport>(
     // (This is done by checking the value of root.signed.consistent_snapshot throughout this
     // library.)
 
-    Ok(root)
+    Ok(chain)
 }
 
 /// Step 2 of the client application, which loads the timestamp metadata file.
@@ -579,7 +909,7 @@ fn load_timestamp<T: Transport>(
     datastore: &Datastore<'_>,
     max_timestamp_size: u64,
     metadata_base_url: &Url,
-    expiration_enforcement: ExpirationEnforcement,
+    expiration: &ExpirationCheck<'_>,
 ) -> Result<Signed<Timestamp>> {
     // 2. Download the timestamp metadata file, up to Y number of bytes (because the size is
     //    unknown.) The value for Y is set by the authors of the application using TUF. For
@@ -595,8 +925,9 @@ fn load_timestamp<T: Transport>(
         max_timestamp_size,
         "max_timestamp_size argument",
     )?;
+    let mut reader = Sha256Reader::new(reader);
     let timestamp: Signed<Timestamp> =
-        serde_json::from_reader(reader).context(error::ParseMetadata {
+        serde_json::from_reader(&mut reader).context(error::ParseMetadata {
             role: RoleType::Timestamp,
         })?;
 
@@ -608,6 +939,7 @@ fn load_timestamp<T: Transport>(
         .context(error::VerifyMetadata {
             role: RoleType::Timestamp,
         })?;
+    check_spec_version(&timestamp.signed.spec_version)?;
 
     // 2.2. Check for a rollback attack. The version number of the trusted timestamp metadata file,
     //   if any, must be less than or equal to the version number of the new timestamp metadata
@@ -633,25 +965,31 @@ fn load_timestamp<T: Transport>(
     //   timestamp in the new timestamp metadata file. If so, the new timestamp metadata file
     //   becomes the trusted timestamp metadata file. If the new timestamp metadata file has
     //   expired, discard it, abort the update cycle, and report the potential freeze attack.
-    if expiration_enforcement == ExpirationEnforcement::Safe {
-        check_expired(datastore, &timestamp.signed)?;
-    }
+    check_expired(datastore, expiration, &timestamp.signed)?;
 
     // Now that everything seems okay, write the timestamp file to the datastore.
     datastore.create("timestamp.json", &timestamp)?;
+    history::record(
+        datastore,
+        expiration.clock,
+        RoleType::Timestamp,
+        None,
+        timestamp.signed.version,
+        reader.finish(),
+    )?;
 
     Ok(timestamp)
 }
 
-/// Step 3 of the client application, which loads the snapshot metadata file.
-fn load_snapshot<T: Transport>(
+/// Fetches the snapshot metadata file listed in the timestamp metadata, and checks it against the
+/// timestamp metadata and the keys in the trusted root, without checking for a rollback attack.
+/// Returns the snapshot metadata along with the SHA-256 digest of the file.
+pub(crate) fn fetch_snapshot<T: Transport>(
     transport: &T,
     root: &Signed<Root>,
     timestamp: &Signed<Timestamp>,
-    datastore: &Datastore<'_>,
     metadata_base_url: &Url,
-    expiration_enforcement: ExpirationEnforcement,
-) -> Result<Signed<Snapshot>> {
+) -> Result<(Signed<Snapshot>, Vec<u8>)> {
     // 3. Download snapshot metadata file, up to the number of bytes specified in the timestamp
     //    metadata file. If consistent snapshots are not used (see Section 7), then the filename
     //    used to download the snapshot metadata file is of the fixed form FILENAME.EXT (e.g.,
@@ -682,8 +1020,9 @@ fn load_snapshot<T: Transport>(
         "timestamp.json",
         &snapshot_meta.hashes.sha256,
     )?;
+    let mut reader = Sha256Reader::new(reader);
     let snapshot: Signed<Snapshot> =
-        serde_json::from_reader(reader).context(error::ParseMetadata {
+        serde_json::from_reader(&mut reader).context(error::ParseMetadata {
             role: RoleType::Snapshot,
         })?;
 
@@ -711,6 +1050,20 @@ fn load_snapshot<T: Transport>(
         .context(error::VerifyMetadata {
             role: RoleType::Snapshot,
         })?;
+    check_spec_version(&snapshot.signed.spec_version)?;
+    Ok((snapshot, reader.finish()))
+}
+
+/// Step 3 of the client application, which loads the snapshot metadata file.
+fn load_snapshot<T: Transport>(
+    transport: &T,
+    root: &Signed<Root>,
+    timestamp: &Signed<Timestamp>,
+    datastore: &Datastore<'_>,
+    metadata_base_url: &Url,
+    expiration: &ExpirationCheck<'_>,
+) -> Result<Signed<Snapshot>> {
+    let (snapshot, sha256) = fetch_snapshot(transport, root, timestamp, metadata_base_url)?;
 
     // 3.3. Check for a rollback attack.
     //
@@ -767,25 +1120,205 @@ fn load_snapshot<T: Transport>(
     //   timestamp in the new snapshot metadata file. If so, the new snapshot metadata file becomes
     //   the trusted snapshot metadata file. If the new snapshot metadata file is expired, discard
     //   it, abort the update cycle, and report the potential freeze attack.
-    if expiration_enforcement == ExpirationEnforcement::Safe {
-        check_expired(datastore, &snapshot.signed)?;
-    }
+    check_expired(datastore, expiration, &snapshot.signed)?;
 
     // Now that everything seems okay, write the snapshot file to the datastore.
     datastore.create("snapshot.json", &snapshot)?;
+    history::record(
+        datastore,
+        expiration.clock,
+        RoleType::Snapshot,
+        None,
+        snapshot.signed.version,
+        sha256,
+    )?;
 
     Ok(snapshot)
 }
 
-/// Step 4 of the client application, which loads the targets metadata file.
+/// Finds the earliest time at which `read_target` must stop trusting the loaded metadata, taking
+/// the grace period for each role into account.
+fn earliest_expiration(
+    expiration: &ExpirationCheck<'_>,
+    root: &Signed<Root>,
+    timestamp: &Signed<Timestamp>,
+    snapshot: Option<&Signed<Snapshot>>,
+    targets: &Signed<crate::schema::Targets>,
+    user_targets: Option<&Signed<crate::schema::Targets>>,
+) -> Option<(DateTime<Utc>, RoleId)> {
+    let mut expires_iter = vec![
+        (root.signed.expires, RoleId::StandardRole(RoleType::Root)),
+        (
+            timestamp.signed.expires,
+            RoleId::StandardRole(RoleType::Timestamp),
+        ),
+    ];
+    if let Some(snapshot) = snapshot {
+        expires_iter.push((
+            snapshot.signed.expires,
+            RoleId::StandardRole(RoleType::Snapshot),
+        ));
+    }
+    for targets in std::iter::once(targets).chain(user_targets) {
+        expires_iter.push((
+            targets.signed.expires,
+            RoleId::StandardRole(RoleType::Targets),
+        ));
+        for name in targets.signed.role_names() {
+            if let Ok(delegated) = targets.signed.delegated_targets(name) {
+                expires_iter.push((
+                    delegated.signed.expires,
+                    RoleId::DelegatedRole(name.clone()),
+                ));
+            }
+        }
+    }
+    expires_iter
+        .into_iter()
+        .filter_map(|(expires, role)| {
+            expiration
+                .deadline(Expiring::from(&role), expires)
+                .map(|deadline| (deadline, role))
+        })
+        .min_by_key(|(deadline, _)| *deadline)
+}
+
+/// Where the snapshot metadata for each targets role comes from.
+#[derive(Debug, Clone, Copy)]
+enum SnapshotSource<'a> {
+    /// The snapshot metadata file.
+    Snapshot(&'a Signed<Snapshot>),
+    /// A proof for each role, verified against the snapshot Merkle root in the timestamp metadata
+    /// (TAP 16), and checked against the versions of the roles in the trusted Merkle tree.
+    Merkle(&'a Signed<Timestamp>, &'a MerkleVersions),
+}
+
+/// The name of the file in the datastore that records the version of each role in the trusted
+/// snapshot Merkle tree.
+const SNAPSHOT_MERKLE_VERSIONS_FILE: &str = "snapshot-merkle-versions.json";
+
+/// The versions of the roles in snapshot Merkle trees. Without a snapshot metadata file to compare
+/// against, the versions from the last trusted tree are kept in the datastore, and a new tree may
+/// not list an older version of any role (TAP 16).
+#[derive(Debug, Default)]
+struct MerkleVersions {
+    /// The version of each role in the trusted Merkle tree.
+    trusted: HashMap<String, NonZeroU64>,
+    /// The version of each role in the new Merkle tree whose proof has been verified.
+    new: RefCell<HashMap<String, NonZeroU64>>,
+}
+
+impl MerkleVersions {
+    /// Reads the versions of the roles in the trusted Merkle tree from the datastore. If there are
+    /// none, or they can't be parsed, any version is accepted.
+    fn read(datastore: &Datastore<'_>) -> Result<Self> {
+        let trusted = datastore
+            .reader(SNAPSHOT_MERKLE_VERSIONS_FILE)?
+            .and_then(|reader| serde_json::from_reader(reader).ok())
+            .unwrap_or_default();
+        Ok(Self {
+            trusted,
+            new: RefCell::default(),
+        })
+    }
+
+    /// Checks that the version of the named role in the new Merkle tree is not older than its
+    /// version in the trusted tree, and records it.
+    fn check(&self, name: &str, version: NonZeroU64) -> Result<()> {
+        if let Some(current_version) = self.trusted.get(name) {
+            ensure!(
+                *current_version <= version,
+                error::OlderSnapshotMerkleLeaf {
+                    name,
+                    current_version: current_version.get(),
+                    new_version: version.get(),
+                }
+            );
+        }
+        self.new.borrow_mut().insert(name.to_owned(), version);
+        Ok(())
+    }
+
+    /// Makes the versions of the roles in the new Merkle tree the trusted versions, by writing
+    /// them to the datastore. Roles whose proofs were not fetched in this load, such as roles no
+    /// longer delegated, keep their trusted versions, so that a later tree can't roll them back.
+    fn write(&self, datastore: &Datastore<'_>) -> Result<()> {
+        let mut versions = self.trusted.clone();
+        versions.extend(
+            self.new
+                .borrow()
+                .iter()
+                .map(|(name, version)| (name.clone(), *version)),
+        );
+        datastore.create(SNAPSHOT_MERKLE_VERSIONS_FILE, &versions)
+    }
+}
+
+/// Returns the snapshot metadata for the named targets role, or `None` if the snapshot metadata
+/// file does not list it. With a snapshot Merkle tree, the role's proof is fetched and verified
+/// against the Merkle root instead, and the role's version checked for a rollback attack; proofs
+/// are limited to `max_timestamp_size`.
+fn snapshot_role_meta<T: Transport>(
+    transport: &T,
+    snapshot: SnapshotSource<'_>,
+    consistent_snapshot: bool,
+    metadata_base_url: &Url,
+    limits: &Limits,
+    name: &str,
+) -> Result<Option<SnapshotMeta>> {
+    let (timestamp, versions) = match snapshot {
+        SnapshotSource::Snapshot(snapshot) => {
+            return Ok(snapshot.signed.meta.get(&format!("{}.json", name)).cloned())
+        }
+        SnapshotSource::Merkle(timestamp, versions) => (timestamp, versions),
+    };
+    let merkle_root = timestamp
+        .signed
+        .merkle_root
+        .as_ref()
+        .context(error::MetaMissing {
+            file: "snapshot.json",
+            role: RoleType::Timestamp,
+        })?;
+    let path = SnapshotMerkleProof::filename(name, timestamp.signed.version, consistent_snapshot);
+    let url = metadata_base_url.join(&path).context(error::JoinUrl {
+        path,
+        url: metadata_base_url.to_owned(),
+    })?;
+    let reader = fetch_max_size(
+        transport,
+        url,
+        limits.max_timestamp_size,
+        "max_timestamp_size parameter",
+    )?;
+    let proof: SnapshotMerkleProof =
+        serde_json::from_reader(reader).context(error::ParseSnapshotMerkleProof { name })?;
+    ensure!(
+        proof.leaf_contents.name == name,
+        error::SnapshotMerkleProofRole {
+            name,
+            found: proof.leaf_contents.name,
+        }
+    );
+    proof
+        .verify(merkle_root)
+        .context(error::VerifySnapshotMerkleProof { name })?;
+    versions.check(name, proof.leaf_contents.version)?;
+    Ok(Some(proof.leaf_contents.to_snapshot_meta()))
+}
+
+/// Step 4 of the client application, which loads the targets metadata file. `roles_loaded` counts
+/// the delegated roles loaded so far.
+#[allow(clippy::too_many_arguments)]
 fn load_targets<T: Transport>(
     transport: &T,
     root: &Signed<Root>,
-    snapshot: &Signed<Snapshot>,
+    snapshot: SnapshotSource<'_>,
     datastore: &Datastore<'_>,
-    max_targets_size: u64,
+    limits: &Limits,
     metadata_base_url: &Url,
-    expiration_enforcement: ExpirationEnforcement,
+    expiration: &ExpirationCheck<'_>,
+    roles_loaded: &mut u64,
 ) -> Result<Signed<crate::schema::Targets>> {
     // 4. Download the top-level targets metadata file, up to either the number of bytes specified
     //    in the snapshot metadata file, or some Z number of bytes. The value for Z is set by the
@@ -796,14 +1329,18 @@ fn load_targets<T: Transport>(
     //    VERSION_NUMBER is the version number of the targets metadata file listed in the snapshot
     //    metadata file. In either case, the client MUST write the file to non-volatile storage as
     //    FILENAME.EXT.
-    let targets_meta = snapshot
-        .signed
-        .meta
-        .get("targets.json")
-        .context(error::MetaMissing {
-            file: "targets.json",
-            role: RoleType::Timestamp,
-        })?;
+    let targets_meta = snapshot_role_meta(
+        transport,
+        snapshot,
+        root.signed.consistent_snapshot,
+        metadata_base_url,
+        limits,
+        "targets",
+    )?
+    .context(error::MetaMissing {
+        file: "targets.json",
+        role: RoleType::Timestamp,
+    })?;
     let path = if root.signed.consistent_snapshot {
         format!("{}.targets.json", targets_meta.version)
     } else {
@@ -815,7 +1352,7 @@ fn load_targets<T: Transport>(
     })?;
     let (max_targets_size, specifier) = match targets_meta.length {
         Some(length) => (length, "snapshot.json"),
-        None => (max_targets_size, "max_targets_size parameter"),
+        None => (limits.max_targets_size, "max_targets_size parameter"),
     };
     let reader = if let Some(hashes) = &targets_meta.hashes {
         Box::new(fetch_sha256(
@@ -833,8 +1370,9 @@ fn load_targets<T: Transport>(
             specifier,
         )?)
     };
-    let mut targets: Signed<crate::schema::Targets> =
-        serde_json::from_reader(reader).context(error::ParseMetadata {
+    let mut reader = Sha256Reader::new(reader);
+    let mut targets: Signed<crate::schema::Targets> = serde_json::from_reader(&mut reader)
+        .context(error::ParseMetadata {
             role: RoleType::Targets,
         })?;
 
@@ -862,6 +1400,7 @@ fn load_targets<T: Transport>(
         .context(error::VerifyMetadata {
             role: RoleType::Targets,
         })?;
+    check_spec_version(&targets.signed.spec_version)?;
 
     // 4.3. Check for a rollback attack. The version number of the trusted targets metadata file,
     //   if any, MUST be less than or equal to the version number of the new targets metadata file.
@@ -887,12 +1426,18 @@ fn load_targets<T: Transport>(
     //   timestamp in the new targets metadata file. If so, the new targets metadata file becomes
     //   the trusted targets metadata file. If the new targets metadata file is expired, discard
     //   it, abort the update cycle, and report the potential freeze attack.
-    if expiration_enforcement == ExpirationEnforcement::Safe {
-        check_expired(datastore, &targets.signed)?;
-    }
+    check_expired(datastore, expiration, &targets.signed)?;
 
     // Now that everything seems okay, write the targets file to the datastore.
     datastore.create("targets.json", &targets)?;
+    history::record(
+        datastore,
+        expiration.clock,
+        RoleType::Targets,
+        None,
+        targets.signed.version,
+        reader.finish(),
+    )?;
 
     // 4.5. Perform a preorder depth-first search for metadata about the desired target, beginning
     //   with the top-level targets role.
@@ -902,73 +1447,132 @@ fn load_targets<T: Transport>(
             snapshot,
             root.signed.consistent_snapshot,
             metadata_base_url,
-            max_targets_size,
+            limits,
             delegations,
             &datastore,
+            expiration,
+            1,
+            roles_loaded,
         )?;
     }
 
     Ok(targets)
 }
 
-// Follow the paths of delegations starting with the top level targets.json delegation
+/// The name given to the user-selected top-level targets role (TAP 13) in errors and reports.
+pub(crate) const USER_TARGETS_ROLE: &str = "user-selected targets";
+
+/// Verifies a user-selected top-level targets role (TAP 13) with the keys the user trusts, then
+/// loads the roles it delegates to from the repository. Returns the role, and the user's keys as a
+/// delegation to it. `roles_loaded` counts the delegated roles loaded so far.
+#[allow(clippy::too_many_arguments)]
+fn load_user_targets<T: Transport>(
+    transport: &T,
+    user_targets: UserTargets,
+    snapshot: SnapshotSource<'_>,
+    consistent_snapshot: bool,
+    datastore: &Datastore<'_>,
+    limits: &Limits,
+    metadata_base_url: &Url,
+    expiration: &ExpirationCheck<'_>,
+    roles_loaded: &mut u64,
+) -> Result<(Signed<crate::schema::Targets>, Delegations)> {
+    // The user's keys are checked the same way as the keys of a delegated role.
+    let keys = user_targets
+        .keys
+        .into_iter()
+        .map(|key| Ok((key.key_id().context(error::VerifyUserTargets)?, key)))
+        .collect::<Result<HashMap<_, _>>>()?;
+    let trusted = Delegations {
+        roles: vec![DelegatedRole {
+            name: USER_TARGETS_ROLE.to_owned(),
+            keyids: keys.keys().cloned().collect(),
+            threshold: user_targets.threshold,
+            paths: PathSet::Paths(vec!["*".to_owned()]),
+            terminating: true,
+            targets: None,
+        }],
+        keys,
+        ..Delegations::default()
+    };
+    let mut targets = user_targets.metadata;
+    trusted
+        .verify_role(&targets, USER_TARGETS_ROLE)
+        .context(error::VerifyUserTargets)?;
+    check_spec_version(&targets.signed.spec_version)?;
+    check_expired(datastore, expiration, &targets.signed)?;
+
+    if let Some(delegations) = &mut targets.signed.delegations {
+        delegations.verify_paths().context(error::InvalidPath)?;
+        load_delegations(
+            transport,
+            snapshot,
+            consistent_snapshot,
+            metadata_base_url,
+            limits,
+            delegations,
+            datastore,
+            expiration,
+            1,
+            roles_loaded,
+        )?;
+    }
+    Ok((targets, trusted))
+}
+
+// Follow the paths of delegations starting with the top level targets.json delegation. `depth` is
+// the depth of the roles in `delegation`, and `roles_loaded` counts the delegated roles loaded so
+// far.
+#[allow(clippy::too_many_arguments)]
 fn load_delegations<T: Transport>(
     transport: &T,
-    snapshot: &Signed<Snapshot>,
+    snapshot: SnapshotSource<'_>,
     consistent_snapshot: bool,
     metadata_base_url: &Url,
-    max_targets_size: u64,
+    limits: &Limits,
     delegation: &mut Delegations,
     datastore: &Datastore<'_>,
+    expiration: &ExpirationCheck<'_>,
+    depth: u64,
+    roles_loaded: &mut u64,
 ) -> Result<()> {
+    let fetched = fetch_delegated_roles(
+        transport,
+        snapshot,
+        consistent_snapshot,
+        metadata_base_url,
+        limits,
+        delegation,
+        depth,
+        roles_loaded,
+    )?;
+    // verify each role with the delegation
+    verify_delegated_roles(delegation, &fetched)?;
+
     let mut delegated_roles: HashMap<String, Option<Signed<crate::schema::Targets>>> =
         HashMap::new();
-    for delegated_role in &delegation.roles {
-        // find the role file metadata
-        let role_meta = snapshot
-            .signed
-            .meta
-            .get(&format!("{}.json", &delegated_role.name))
-            .context(error::RoleNotInMeta {
-                name: delegated_role.name.clone(),
-            })?;
-
-        let path = if consistent_snapshot {
-            format!("{}.{}.json", &role_meta.version, &delegated_role.name)
-        } else {
-            format!("{}.json", &delegated_role.name)
-        };
-        let role_url = metadata_base_url.join(&path).context(error::JoinUrl {
-            path: path.clone(),
-            url: metadata_base_url.to_owned(),
-        })?;
-        let specifier = "max_targets_size parameter";
-        // load the role json file
-        let reader = Box::new(fetch_max_size(
-            transport,
-            role_url,
-            max_targets_size,
-            specifier,
-        )?);
-        // since each role is a targets, we load them as such
-        let role: Signed<crate::schema::Targets> =
-            serde_json::from_reader(reader).context(error::ParseMetadata {
-                role: RoleType::Targets,
-            })?;
-        // verify each role with the delegation
-        delegation
-            .verify_role(&role, &delegated_role.name)
-            .context(error::VerifyMetadata {
-                role: RoleType::Targets,
-            })?;
+    for FetchedRole {
+        delegated_role,
+        version,
+        path,
+        role,
+        sha256,
+    } in fetched
+    {
+        check_spec_version(&role.signed.spec_version)?;
         ensure!(
-            role.signed.version == role_meta.version,
+            role.signed.version == version,
             error::VersionMismatch {
                 role: RoleType::Targets,
                 fetched: role.signed.version,
-                expected: role_meta.version
+                expected: version
             }
         );
+        expiration.check(
+            datastore,
+            Expiring::Delegated(&delegated_role.name),
+            role.signed.expires,
+        )?;
         {
             if let Some(delegations) = role.signed.delegations.as_ref() {
                 delegations.verify_paths().context(error::InvalidPath {})?
@@ -976,6 +1580,14 @@ fn load_delegations<T: Transport>(
         }
 
         datastore.create(&path, &role)?;
+        history::record(
+            datastore,
+            expiration.clock,
+            RoleType::Targets,
+            Some(&delegated_role.name),
+            role.signed.version,
+            sha256,
+        )?;
         delegated_roles.insert(delegated_role.name.clone(), Some(role));
     }
     // load all roles delegated by this role
@@ -992,9 +1604,12 @@ fn load_delegations<T: Transport>(
                     snapshot,
                     consistent_snapshot,
                     metadata_base_url,
-                    max_targets_size,
+                    limits,
                     delegations,
                     datastore,
+                    expiration,
+                    depth + 1,
+                    roles_loaded,
                 )?;
             }
         }
@@ -1002,6 +1617,167 @@ fn load_delegations<T: Transport>(
     Ok(())
 }
 
+/// Fetches each of the roles delegated by `delegation`, checking that loading them stays within
+/// `limits`.
+#[allow(clippy::too_many_arguments)]
+fn fetch_delegated_roles<'d, T: Transport>(
+    transport: &T,
+    snapshot: SnapshotSource<'_>,
+    consistent_snapshot: bool,
+    metadata_base_url: &Url,
+    limits: &Limits,
+    delegation: &'d Delegations,
+    depth: u64,
+    roles_loaded: &mut u64,
+) -> Result<Vec<FetchedRole<'d>>> {
+    let mut fetched = Vec::with_capacity(delegation.roles.len());
+    for delegated_role in &delegation.roles {
+        *roles_loaded += 1;
+        check_delegation_limits(limits, &delegated_role.name, depth, *roles_loaded)?;
+
+        // find the role file metadata
+        let role_meta = snapshot_role_meta(
+            transport,
+            snapshot,
+            consistent_snapshot,
+            metadata_base_url,
+            limits,
+            &delegated_role.name,
+        )?
+        .context(error::RoleNotInMeta {
+            name: delegated_role.name.clone(),
+        })?;
+
+        let path = if consistent_snapshot {
+            format!("{}.{}.json", &role_meta.version, &delegated_role.name)
+        } else {
+            format!("{}.json", &delegated_role.name)
+        };
+        let role_url = metadata_base_url.join(&path).context(error::JoinUrl {
+            path: path.clone(),
+            url: metadata_base_url.to_owned(),
+        })?;
+        // load the role json file
+        let (role, sha256) = fetch_delegated_role(
+            transport,
+            role_url,
+            role_meta.length,
+            &delegated_role.name,
+            limits,
+        )?;
+        fetched.push(FetchedRole {
+            delegated_role,
+            version: role_meta.version,
+            path,
+            role,
+            sha256,
+        });
+    }
+    Ok(fetched)
+}
+
+/// A delegated role that has been fetched, but not yet verified.
+struct FetchedRole<'a> {
+    delegated_role: &'a DelegatedRole,
+    /// The version of the role listed in snapshot.json.
+    version: NonZeroU64,
+    /// The path of the role's metadata file, relative to the metadata base URL.
+    path: String,
+    role: Signed<crate::schema::Targets>,
+    sha256: Vec<u8>,
+}
+
+/// Verifies that each of the `fetched` roles, delegated by `delegation`, is signed by a threshold
+/// of its keys.
+///
+/// The keys of `delegation` are parsed once for all of the roles, and with the `parallel`
+/// feature the roles are verified in parallel; see `Delegations::verify_roles`.
+fn verify_delegated_roles(delegation: &Delegations, fetched: &[FetchedRole<'_>]) -> Result<()> {
+    let roles = fetched
+        .iter()
+        .map(|fetched| (fetched.delegated_role.name.as_str(), &fetched.role))
+        .collect::<Vec<_>>();
+    delegation
+        .verify_roles(&roles)
+        .context(error::VerifyMetadata {
+            role: RoleType::Targets,
+        })
+}
+
+/// Checks that loading the named delegated role, at `depth` and as the `roles_loaded`th delegated
+/// role, stays within `limits`.
+fn check_delegation_limits(
+    limits: &Limits,
+    name: &str,
+    depth: u64,
+    roles_loaded: u64,
+) -> Result<()> {
+    ensure!(
+        depth <= limits.max_delegation_depth,
+        error::MaxDelegationDepthExceeded {
+            name,
+            max_delegation_depth: limits.max_delegation_depth,
+        }
+    );
+    ensure!(
+        roles_loaded <= limits.max_delegated_roles,
+        error::MaxDelegatedRolesExceeded {
+            name,
+            max_delegated_roles: limits.max_delegated_roles,
+        }
+    );
+    Ok(())
+}
+
+/// Fetches and parses a delegated targets role, returning it along with the SHA-256 digest of the
+/// fetched file. `length` is the length of the role listed in snapshot.json, if any.
+fn fetch_delegated_role<T: Transport>(
+    transport: &T,
+    url: Url,
+    length: Option<u64>,
+    name: &str,
+    limits: &Limits,
+) -> Result<(Signed<crate::schema::Targets>, Vec<u8>)> {
+    let max_size = limits.max_delegated_targets_size;
+    let (size, specifier) = match length {
+        Some(length) => {
+            ensure!(
+                length <= max_size,
+                error::MaxDelegatedTargetsSizeExceeded { name, max_size }
+            );
+            (length, "snapshot.json")
+        }
+        None => (max_size, "max_delegated_targets_size parameter"),
+    };
+    let mut reader = Sha256Reader::new(fetch_max_size(transport, url, size, specifier)?);
+    // since each role is a targets, we load them as such
+    match serde_json::from_reader(&mut reader) {
+        Ok(role) => Ok((role, reader.finish())),
+        Err(err) if length.is_none() && max_size_exceeded(&err) => {
+            error::MaxDelegatedTargetsSizeExceeded { name, max_size }.fail()
+        }
+        Err(err) => Err(err).context(error::ParseMetadata {
+            role: RoleType::Targets,
+        }),
+    }
+}
+
+/// Returns `true` if `err` was caused by a `MaxSizeAdapter` reaching its limit.
+fn max_size_exceeded(err: &serde_json::Error) -> bool {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(err);
+    while let Some(err) = source {
+        let err = err
+            .downcast_ref::<std::io::Error>()
+            .and_then(std::io::Error::get_ref)
+            .map_or(err, |inner| inner as &(dyn std::error::Error + 'static));
+        if let Some(error::Error::MaxSizeExceeded { .. }) = err.downcast_ref::<error::Error>() {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1017,6 +1793,38 @@ mod tests {
         )
     }
 
+    // Check that metadata is accepted for any spec version with a supported major version
+    #[test]
+    fn spec_version_compatibility() {
+        for version in &["1.0.0", "1.0.31", "1.1.0"] {
+            assert!(check_spec_version(version).is_ok(), "{}", version);
+        }
+        // Two-part versions are the one exception to MAJOR.MINOR.PATCH, since existing metadata
+        // uses them.
+        for version in &["1.0", "1.1"] {
+            assert!(check_spec_version(version).is_ok(), "{}", version);
+        }
+        assert!(check_spec_version("2.0").is_err());
+        for version in &["2.0.0", "0.9.0", "", "one.zero", "v1.0.0"] {
+            assert!(check_spec_version(version).is_err(), "{}", version);
+        }
+        // Malformed versions are rejected even if they start with a supported major version
+        for version in &[
+            "1",
+            "1.",
+            "1..",
+            "1.garbage",
+            "1.x.y.z",
+            "1.0.0.0",
+            "1.0.x",
+            "1.0.0-rc1",
+            "1.-1.0",
+            " 1.0.0",
+        ] {
+            assert!(check_spec_version(version).is_err(), "{}", version);
+        }
+    }
+
     // Ensure that the `ExpirationEnforcement` traits are not changed by mistake.
     #[test]
     fn expiration_enforcement_traits() {
@@ -0,0 +1,216 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::error::{self, Result};
+use crate::schema::decoded::{Decoded, Hex};
+use crate::schema::key::Key;
+use crate::schema::{DelegatedRole, Delegations, Role, RoleKeys, RoleType, Root, Signed, Targets};
+use crate::{Repository, Transport, USER_TARGETS_ROLE};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use snafu::ResultExt;
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroU64;
+
+/// A summary of the trust state of a loaded [`Repository`], returned by [`Repository::report`].
+///
+/// The report serializes to JSON, so it can be handed to dashboards or printed by tools.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RepositoryReport {
+    /// The time used to calculate how long each role has left before it expires.
+    pub generated_at: DateTime<Utc>,
+
+    /// Whether the repository uses consistent snapshots.
+    pub consistent_snapshot: bool,
+
+    /// Every role in the repository: root, timestamp, snapshot, and targets, followed by the
+    /// delegated targets roles in the order they are searched. If the repository was loaded with
+    /// [`UserTargets`](crate::UserTargets), the user-selected targets role and the roles it
+    /// delegates to follow; targets are looked up from those roles instead.
+    pub roles: Vec<RoleReport>,
+}
+
+/// The state of a single role in a [`RepositoryReport`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RoleReport {
+    /// The name of the role. This is the role type for top-level roles, and the delegated name for
+    /// delegated targets roles.
+    pub name: String,
+
+    /// The type of the role; delegated roles are `targets` roles.
+    pub role_type: RoleType,
+
+    /// The name of the role that delegated to this role, if it is a delegated targets role. This
+    /// is `None` for the user-selected targets role, whose keys are the ones the user trusts.
+    pub delegated_by: Option<String>,
+
+    /// The version of the role's metadata.
+    pub version: NonZeroU64,
+
+    /// When the role's metadata expires.
+    pub expires: DateTime<Utc>,
+
+    /// The number of seconds until the role expires. This is negative if it has already expired.
+    pub seconds_remaining: i64,
+
+    /// The number of valid signatures required for the role.
+    pub threshold: NonZeroU64,
+
+    /// The keys authorized to sign the role.
+    pub keys: Vec<KeyReport>,
+}
+
+/// A key authorized to sign a role, in a [`RoleReport`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KeyReport {
+    /// The key ID.
+    pub keyid: Decoded<Hex>,
+
+    /// The type of the key (such as "rsa" or "ed25519"), or `None` if the key ID is not listed in
+    /// the delegating role's keys.
+    pub keytype: Option<String>,
+
+    /// Whether this key produced a valid signature of the role's current metadata.
+    pub signed: bool,
+}
+
+impl<T: Transport> Repository<'_, T> {
+    /// Returns a report listing every role in the repository, with its version, expiration,
+    /// threshold, and which of its keys produced valid signatures.
+    ///
+    /// The time remaining for each role is calculated using the repository's [`Clock`].
+    ///
+    /// [`Clock`]: crate::Clock
+    pub fn report(&self) -> Result<RepositoryReport> {
+        let now = self.clock.now();
+        let root = &self.root.signed;
+        let mut roles = vec![
+            top_level_report(root, &self.root, now)?,
+            top_level_report(root, &self.timestamp, now)?,
+        ];
+        // The snapshot is not loaded if the repository publishes a snapshot Merkle tree.
+        if let Some(snapshot) = &self.snapshot {
+            roles.push(top_level_report(root, snapshot, now)?);
+        }
+        roles.push(top_level_report(root, &self.targets, now)?);
+        if let Some(delegations) = &self.targets.signed.delegations {
+            delegated_reports("targets", delegations, now, &mut roles)?;
+        }
+        if let Some((user_targets, user_keys)) = self.user_targets_with_keys() {
+            // The user's keys are a delegation to the user-selected targets role.
+            roles.push(delegated_report(
+                None,
+                user_keys,
+                &user_keys.roles[0],
+                user_targets,
+                now,
+            )?);
+            if let Some(delegations) = &user_targets.signed.delegations {
+                delegated_reports(USER_TARGETS_ROLE, delegations, now, &mut roles)?;
+            }
+        }
+
+        Ok(RepositoryReport {
+            generated_at: now,
+            consistent_snapshot: self.consistent_snapshot,
+            roles,
+        })
+    }
+}
+
+/// Builds the report for a top-level role, whose keys are listed in root.json.
+fn top_level_report<R: Role>(
+    root: &Root,
+    role: &Signed<R>,
+    now: DateTime<Utc>,
+) -> Result<RoleReport> {
+    let signing_keyids = root
+        .signing_keyids(role)
+        .context(error::VerifyMetadata { role: R::TYPE })?;
+    // `signing_keyids` fails if the role is missing from root.json.
+    let role_keys = &root.roles[&R::TYPE];
+    Ok(RoleReport {
+        name: R::TYPE.to_string(),
+        role_type: R::TYPE,
+        delegated_by: None,
+        version: role.signed.version(),
+        expires: role.signed.expires(),
+        seconds_remaining: (role.signed.expires() - now).num_seconds(),
+        threshold: role_keys.threshold,
+        keys: key_reports(role_keys, &root.keys, &signing_keyids),
+    })
+}
+
+/// Adds reports for the roles delegated by `parent`, and their delegated roles, in pre-order.
+fn delegated_reports(
+    parent: &str,
+    delegations: &Delegations,
+    now: DateTime<Utc>,
+    reports: &mut Vec<RoleReport>,
+) -> Result<()> {
+    for delegated_role in &delegations.roles {
+        let targets: &Signed<Targets> = match &delegated_role.targets {
+            Some(targets) => targets,
+            None => continue,
+        };
+        reports.push(delegated_report(
+            Some(parent),
+            delegations,
+            delegated_role,
+            targets,
+            now,
+        )?);
+        if let Some(child_delegations) = &targets.signed.delegations {
+            delegated_reports(&delegated_role.name, child_delegations, now, reports)?;
+        }
+    }
+    Ok(())
+}
+
+/// Builds the report for a targets role delegated to by `delegations`.
+fn delegated_report(
+    parent: Option<&str>,
+    delegations: &Delegations,
+    delegated_role: &DelegatedRole,
+    targets: &Signed<Targets>,
+    now: DateTime<Utc>,
+) -> Result<RoleReport> {
+    let signing_keyids = delegations
+        .signing_keyids(targets, &delegated_role.name)
+        .context(error::VerifyMetadata {
+            role: RoleType::Targets,
+        })?;
+    Ok(RoleReport {
+        name: delegated_role.name.clone(),
+        role_type: RoleType::Targets,
+        delegated_by: parent.map(str::to_owned),
+        version: targets.signed.version,
+        expires: targets.signed.expires,
+        seconds_remaining: (targets.signed.expires - now).num_seconds(),
+        threshold: delegated_role.threshold,
+        keys: key_reports(&delegated_role.keys(), &delegations.keys, &signing_keyids),
+    })
+}
+
+fn key_reports(
+    role_keys: &RoleKeys,
+    keys: &HashMap<Decoded<Hex>, Key>,
+    signing_keyids: &HashSet<Decoded<Hex>>,
+) -> Vec<KeyReport> {
+    role_keys
+        .keyids
+        .iter()
+        .map(|keyid| KeyReport {
+            keyid: keyid.clone(),
+            keytype: keys.get(keyid).map(|key| {
+                match key {
+                    Key::Rsa { .. } => "rsa",
+                    Key::Ed25519 { .. } => "ed25519",
+                    Key::Ecdsa { .. } => "ecdsa",
+                }
+                .to_owned()
+            }),
+            signed: signing_keyids.contains(keyid),
+        })
+        .collect()
+}
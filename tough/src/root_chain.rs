@@ -0,0 +1,160 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Provides the chain of root metadata versions that a `Repository` verified while loading.
+
+use crate::error::{self, Result};
+use crate::schema::decoded::{Decoded, Hex};
+use crate::schema::{RoleType, Root, Signed};
+use crate::{Repository, Transport};
+use snafu::ResultExt;
+use std::num::NonZeroU64;
+
+/// The top-level roles whose keys are listed in root metadata, in the order their key changes are
+/// reported.
+const ROLES: [RoleType; 4] = [
+    RoleType::Root,
+    RoleType::Snapshot,
+    RoleType::Targets,
+    RoleType::Timestamp,
+];
+
+/// A version of the root metadata that was verified while loading a repository, as returned by
+/// [`Repository::root_chain`].
+#[derive(Debug, Clone)]
+pub struct VerifiedRoot {
+    signed: Signed<Root>,
+    buffer: Vec<u8>,
+    key_changes: Vec<KeyChange>,
+}
+
+impl VerifiedRoot {
+    /// Creates an entry for `signed`, which was verified from `buffer`, recording its key changes
+    /// from the `previous` entry in the chain.
+    pub(crate) fn new(
+        signed: Signed<Root>,
+        buffer: Vec<u8>,
+        previous: Option<&VerifiedRoot>,
+    ) -> Self {
+        let key_changes = previous.map_or_else(Vec::new, |previous| {
+            KeyChange::between(&previous.signed.signed, &signed.signed)
+        });
+        Self {
+            signed,
+            buffer,
+            key_changes,
+        }
+    }
+
+    /// Provides access to the verified root metadata.
+    pub fn signed(&self) -> &Signed<Root> {
+        &self.signed
+    }
+
+    /// Provides access to the exact bytes of the root metadata file that was verified.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// Returns how the keys of each top-level role changed from the previous version in the chain.
+    /// This is empty for the first version, which is the trusted root provided to
+    /// [`Repository::load`].
+    pub fn key_changes(&self) -> &[KeyChange] {
+        &self.key_changes
+    }
+}
+
+/// A change to the keys of a top-level role between consecutive versions of the root metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyChange {
+    /// A key was authorized to sign for the role.
+    Added {
+        /// The role the key signs for.
+        role: RoleType,
+        /// The ID of the key.
+        keyid: Decoded<Hex>,
+    },
+
+    /// A key is no longer authorized to sign for the role.
+    Removed {
+        /// The role the key signed for.
+        role: RoleType,
+        /// The ID of the key.
+        keyid: Decoded<Hex>,
+    },
+
+    /// The number of signatures required for the role changed.
+    Threshold {
+        /// The role whose threshold changed.
+        role: RoleType,
+        /// The threshold in the previous version.
+        old: NonZeroU64,
+        /// The threshold in the new version.
+        new: NonZeroU64,
+    },
+}
+
+impl KeyChange {
+    /// Lists the key changes from `old` to `new`, role by role. Within a role, removed keys are
+    /// listed before added keys, each in the order of the root metadata that lists them.
+    pub fn between(old: &Root, new: &Root) -> Vec<KeyChange> {
+        let mut changes = Vec::new();
+        for role in &ROLES {
+            let old_keys = old.roles.get(role);
+            let new_keys = new.roles.get(role);
+            let old_keyids = old_keys.map_or(&[][..], |keys| &keys.keyids);
+            let new_keyids = new_keys.map_or(&[][..], |keys| &keys.keyids);
+            changes.extend(
+                old_keyids
+                    .iter()
+                    .filter(|keyid| !new_keyids.contains(keyid))
+                    .map(|keyid| KeyChange::Removed {
+                        role: *role,
+                        keyid: keyid.clone(),
+                    }),
+            );
+            changes.extend(
+                new_keyids
+                    .iter()
+                    .filter(|keyid| !old_keyids.contains(keyid))
+                    .map(|keyid| KeyChange::Added {
+                        role: *role,
+                        keyid: keyid.clone(),
+                    }),
+            );
+            if let (Some(old_keys), Some(new_keys)) = (old_keys, new_keys) {
+                if old_keys.threshold != new_keys.threshold {
+                    changes.push(KeyChange::Threshold {
+                        role: *role,
+                        old: old_keys.threshold,
+                        new: new_keys.threshold,
+                    });
+                }
+            }
+        }
+        changes
+    }
+}
+
+impl<T: Transport> Repository<'_, T> {
+    /// Returns each version of the root metadata that was verified while loading the repository,
+    /// in order. The first is the trusted root provided to [`Repository::load`], and the last is
+    /// the root returned by [`Repository::root`].
+    pub fn root_chain(&self) -> &[VerifiedRoot] {
+        &self.root_chain
+    }
+}
+
+/// Verifies that `new` may replace `trusted` as the trusted root metadata: it must be signed by a
+/// threshold of the keys listed in both.
+pub(crate) fn verify_root_update(trusted: &Signed<Root>, new: &Signed<Root>) -> Result<()> {
+    trusted
+        .signed
+        .verify_role(new)
+        .context(error::VerifyMetadata {
+            role: RoleType::Root,
+        })?;
+    new.signed.verify_role(new).context(error::VerifyMetadata {
+        role: RoleType::Root,
+    })
+}
@@ -84,6 +84,23 @@ impl Encode for Hex {
     }
 }
 
+/// [`Decode`]/[`Encode`] implementation for base64-encoded strings, using the standard alphabet
+/// with padding.
+#[derive(Debug, Clone, Copy)]
+pub struct Base64;
+
+impl Decode for Base64 {
+    fn decode(s: &str) -> Result<Vec<u8>, Error> {
+        base64::decode(s).context(error::Base64Decode)
+    }
+}
+
+impl Encode for Base64 {
+    fn encode(b: &[u8]) -> String {
+        base64::encode(b)
+    }
+}
+
 // =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=
 
 /// [`Decode`]/[`Encode`] implementation for PEM-encoded RSA public keys.
@@ -0,0 +1,243 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Provides support for metadata in Dead Simple Signing Envelopes (DSSE).
+//!
+//! A DSSE envelope holds the serialized metadata as an opaque, base64-encoded payload. Its
+//! signatures are made over a pre-authentication encoding (PAE) of the payload and its type,
+//! rather than over the canonical JSON form of the metadata, so verifiers never need to
+//! re-serialize what they received. See <https://github.com/secure-systems-lab/dsse>.
+//!
+//! [`Signed`] metadata is read from either format, and written as a DSSE envelope if it has a
+//! [`DssePayload`].
+
+use crate::schema::decoded::{Base64, Decode, Decoded, Hex};
+use crate::schema::error::{self, Result};
+use crate::schema::{Signature, Signed};
+use olpc_cjson::CanonicalFormatter;
+use serde::de::{DeserializeOwned, Error as _, IgnoredAny, MapAccess, Visitor};
+use serde::ser::{Error as _, SerializeStruct};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use snafu::{ensure, ResultExt};
+use std::fmt;
+use std::marker::PhantomData;
+
+/// The DSSE payload type of TUF metadata.
+pub const DSSE_PAYLOAD_TYPE: &str = "application/vnd.tuf+json";
+
+/// The payload of a DSSE envelope: serialized metadata, exactly as it was signed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DssePayload {
+    /// The type of the payload, which is [`DSSE_PAYLOAD_TYPE`] for TUF metadata.
+    pub payload_type: String,
+
+    /// The serialized metadata.
+    pub payload: Vec<u8>,
+}
+
+impl DssePayload {
+    /// Creates the payload for a role, serialized as canonical JSON.
+    pub fn new<T: Serialize>(role: &T) -> Result<Self> {
+        let mut payload = Vec::new();
+        let mut ser =
+            serde_json::Serializer::with_formatter(&mut payload, CanonicalFormatter::new());
+        role.serialize(&mut ser).context(error::JsonSerialization {
+            what: "DSSE payload",
+        })?;
+        Ok(Self {
+            payload_type: DSSE_PAYLOAD_TYPE.to_owned(),
+            payload,
+        })
+    }
+
+    /// Returns the pre-authentication encoding of the payload, which is the data that the
+    /// envelope's signatures are made over.
+    pub fn pae(&self) -> Vec<u8> {
+        let mut data = format!(
+            "DSSEv1 {} {} {} ",
+            self.payload_type.len(),
+            self.payload_type,
+            self.payload.len()
+        )
+        .into_bytes();
+        data.extend_from_slice(&self.payload);
+        data
+    }
+
+    /// Checks that the payload holds `signed`, so that it is what was signed.
+    fn check_holds<T: Serialize>(&self, signed: &T, what: &str) -> Result<()> {
+        let payload: Value =
+            serde_json::from_slice(&self.payload).context(error::DssePayloadParse)?;
+        let signed = serde_json::to_value(signed).context(error::JsonSerialization { what })?;
+        ensure!(payload == signed, error::DssePayloadMismatch { what });
+        Ok(())
+    }
+}
+
+impl<T: Serialize> Signed<T> {
+    /// Returns the data that the signatures of this metadata are made over: the canonical JSON
+    /// form of `signed`, or for a DSSE envelope, the PAE of its payload. A DSSE payload must be
+    /// TUF metadata and must hold `signed`.
+    pub(crate) fn signed_data(&self, what: &str) -> Result<Vec<u8>> {
+        let dsse = if let Some(dsse) = &self.dsse {
+            dsse
+        } else {
+            let mut data = Vec::new();
+            let mut ser =
+                serde_json::Serializer::with_formatter(&mut data, CanonicalFormatter::new());
+            self.signed
+                .serialize(&mut ser)
+                .context(error::JsonSerialization { what })?;
+            return Ok(data);
+        };
+        ensure!(
+            dsse.payload_type == DSSE_PAYLOAD_TYPE,
+            error::DssePayloadType {
+                found: &dsse.payload_type,
+                expected: DSSE_PAYLOAD_TYPE,
+            }
+        );
+        // The payload is what was signed, so make sure it is what we are verifying.
+        dsse.check_holds(&self.signed, what)?;
+        Ok(dsse.pae())
+    }
+}
+
+// =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=
+
+/// The serialized form of a DSSE envelope.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Envelope<'a> {
+    payload_type: &'a str,
+    payload: Decoded<Base64>,
+    signatures: Vec<EnvelopeSignature<'a>>,
+}
+
+#[derive(Serialize)]
+struct EnvelopeSignature<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keyid: Option<&'a Decoded<Hex>>,
+    sig: Decoded<Base64>,
+}
+
+/// A signature whose encoding depends on the format of the metadata it was read from. The key ID
+/// is required in the TUF JSON format, but optional in a DSSE envelope.
+#[derive(Deserialize)]
+struct RawSignature {
+    keyid: Option<Decoded<Hex>>,
+    sig: String,
+}
+
+impl<T: Serialize> Serialize for Signed<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match &self.dsse {
+            None => {
+                let mut state = serializer.serialize_struct("Signed", 2)?;
+                state.serialize_field("signed", &self.signed)?;
+                state.serialize_field("signatures", &self.signatures)?;
+                state.end()
+            }
+            // The payload is written as it was signed; `signed` is not serialized again. If
+            // `signed` has been changed since, the payload would not hold it, so refuse to write
+            // the envelope rather than silently dropping the change.
+            Some(dsse) => {
+                dsse.check_holds(&self.signed, "signed")
+                    .map_err(S::Error::custom)?;
+                Envelope {
+                    payload_type: &dsse.payload_type,
+                    payload: dsse.payload.clone().into(),
+                    signatures: self
+                        .signatures
+                        .iter()
+                        .map(|signature| EnvelopeSignature {
+                            keyid: Some(&signature.keyid).filter(|keyid| !keyid.is_empty()),
+                            sig: signature.sig.to_vec().into(),
+                        })
+                        .collect(),
+                }
+                .serialize(serializer)
+            }
+        }
+    }
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for Signed<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        deserializer.deserialize_map(SignedVisitor(PhantomData))
+    }
+}
+
+/// Deserializes either signed metadata in the TUF JSON format or a DSSE envelope, depending on
+/// whether a `payloadType` is present.
+struct SignedVisitor<T>(PhantomData<T>);
+
+impl<'de, T: DeserializeOwned> Visitor<'de> for SignedVisitor<T> {
+    type Value = Signed<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("signed metadata or a DSSE envelope")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<Signed<T>, A::Error> {
+        let mut signed: Option<T> = None;
+        let mut signatures: Option<Vec<RawSignature>> = None;
+        let mut payload_type: Option<String> = None;
+        let mut payload: Option<Decoded<Base64>> = None;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "signed" => signed = Some(map.next_value()?),
+                "signatures" => signatures = Some(map.next_value()?),
+                "payloadType" => payload_type = Some(map.next_value()?),
+                "payload" => payload = Some(map.next_value()?),
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        let signatures = signatures.ok_or_else(|| A::Error::missing_field("signatures"))?;
+
+        let payload_type = match payload_type {
+            Some(payload_type) => payload_type,
+            None => {
+                return Ok(Signed {
+                    signed: signed.ok_or_else(|| A::Error::missing_field("signed"))?,
+                    signatures: signatures
+                        .into_iter()
+                        .map(|signature| {
+                            Ok(Signature {
+                                keyid: signature
+                                    .keyid
+                                    .ok_or_else(|| A::Error::missing_field("keyid"))?,
+                                sig: signature.sig.parse().map_err(A::Error::custom)?,
+                            })
+                        })
+                        .collect::<std::result::Result<_, A::Error>>()?,
+                    dsse: None,
+                })
+            }
+        };
+        let payload = payload
+            .ok_or_else(|| A::Error::missing_field("payload"))?
+            .into_vec();
+        Ok(Signed {
+            signed: serde_json::from_slice(&payload).map_err(A::Error::custom)?,
+            signatures: signatures
+                .into_iter()
+                .map(|signature| {
+                    Ok(Signature {
+                        keyid: signature.keyid.unwrap_or_else(|| Vec::new().into()),
+                        sig: Base64::decode(&signature.sig)
+                            .map_err(A::Error::custom)?
+                            .into(),
+                    })
+                })
+                .collect::<std::result::Result<_, A::Error>>()?,
+            dsse: Some(DssePayload {
+                payload_type,
+                payload,
+            }),
+        })
+    }
+}
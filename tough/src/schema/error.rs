@@ -16,6 +16,46 @@ pub type Result<T> = std::result::Result<T, Error>;
 #[non_exhaustive]
 #[allow(missing_docs)]
 pub enum Error {
+    /// Failed to decode a base64-encoded string.
+    #[snafu(display("Invalid base64 string: {}", source))]
+    Base64Decode {
+        source: base64::DecodeError,
+        backtrace: Backtrace,
+    },
+
+    /// Custom metadata for a target did not serialize to a JSON object.
+    #[snafu(display(
+        "Target custom metadata must serialize to a JSON object, found: {}",
+        found
+    ))]
+    CustomMetadataNotObject { found: String, backtrace: Backtrace },
+
+    /// A target's custom metadata could not be parsed as the requested type.
+    #[snafu(display("Failed to parse target custom metadata: {}", source))]
+    CustomMetadataParse {
+        source: serde_json::Error,
+        backtrace: Backtrace,
+    },
+
+    /// The payload of a DSSE envelope is not the metadata it was read as.
+    #[snafu(display("DSSE envelope payload does not match the {} metadata", what))]
+    DssePayloadMismatch { what: String, backtrace: Backtrace },
+
+    /// The payload of a DSSE envelope could not be parsed as JSON.
+    #[snafu(display("Failed to parse DSSE envelope payload: {}", source))]
+    DssePayloadParse {
+        source: serde_json::Error,
+        backtrace: Backtrace,
+    },
+
+    /// A DSSE envelope has a payload type other than TUF metadata.
+    #[snafu(display("Unsupported DSSE payload type '{}', expected '{}'", found, expected))]
+    DssePayloadType {
+        found: String,
+        expected: &'static str,
+        backtrace: Backtrace,
+    },
+
     /// A duplicate key ID was present in the root metadata.
     #[snafu(display("Duplicate key ID: {}", keyid))]
     DuplicateKeyId { keyid: String },
@@ -64,6 +104,49 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    /// A target name pattern could not be parsed as a glob.
+    #[snafu(display("Invalid glob pattern '{}': {}", pattern, source))]
+    Glob {
+        pattern: String,
+        source: globset::Error,
+        backtrace: Backtrace,
+    },
+
+    /// A snapshot Merkle proof has a different number of hashes and directions.
+    #[snafu(display(
+        "Malformed snapshot Merkle proof: {} hashes but {} directions",
+        path,
+        directions
+    ))]
+    MerkleProofMalformed {
+        path: usize,
+        directions: usize,
+        backtrace: Backtrace,
+    },
+
+    /// A snapshot Merkle proof does not lead to the expected Merkle root.
+    #[snafu(display(
+        "Snapshot Merkle proof leads to root {}, expected {}",
+        calculated,
+        expected
+    ))]
+    MerkleProofMismatch {
+        calculated: String,
+        expected: String,
+        backtrace: Backtrace,
+    },
+
+    /// A snapshot Merkle tree was built with more than one leaf for a role.
+    #[snafu(display(
+        "Cannot build a snapshot Merkle tree with more than one leaf for role {}",
+        name
+    ))]
+    MerkleTreeDuplicateLeaf { name: String, backtrace: Backtrace },
+
+    /// A snapshot Merkle tree was built without any leaves.
+    #[snafu(display("Cannot build a snapshot Merkle tree without any roles"))]
+    MerkleTreeEmpty { backtrace: Backtrace },
+
     /// A required role is missing from the root metadata file.
     #[snafu(display("Role {} missing from root metadata", role))]
     MissingRole {
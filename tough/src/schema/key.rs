@@ -6,11 +6,15 @@ use crate::schema::decoded::{Decoded, EcdsaPem, Hex, RsaPem};
 use crate::schema::error::{self, Result};
 use olpc_cjson::CanonicalFormatter;
 use ring::digest::{digest, SHA256};
-use ring::signature::VerificationAlgorithm;
+use rsa::pkcs1::der::Decode;
+use rsa::sha2::Sha256;
+use rsa::signature::Verifier;
+use rsa::traits::PublicKeyParts;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use snafu::ResultExt;
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::fmt;
 use std::str::FromStr;
 
@@ -139,40 +143,84 @@ impl Key {
     }
 
     /// Verify a signature of an object made with this key.
-    pub(super) fn verify(&self, msg: &[u8], signature: &[u8]) -> bool {
-        let (alg, public_key): (&dyn VerificationAlgorithm, untrusted::Input<'_>) = match self {
+    pub(crate) fn verify(&self, msg: &[u8], signature: &[u8]) -> bool {
+        match self.verifying_key() {
+            Some(key) => key.verify(msg, signature),
+            None => false,
+        }
+    }
+
+    /// Parses the public key into the form used to verify signatures made with it, or returns
+    /// `None` if it is not a valid key for its signature scheme. The result can verify any number
+    /// of signatures without parsing the key again.
+    pub(crate) fn verifying_key(&self) -> Option<VerifyingKey> {
+        match self {
             Key::Ecdsa {
-                scheme: EcdsaScheme::EcdsaSha2Nistp256,
                 keyval,
+                scheme: EcdsaScheme::EcdsaSha2Nistp256,
                 ..
-            } => (
-                &ring::signature::ECDSA_P256_SHA256_ASN1,
-                untrusted::Input::from(&keyval.public),
-            ),
+            } => p256::ecdsa::VerifyingKey::from_sec1_bytes(&keyval.public)
+                .ok()
+                .map(VerifyingKey::Ecdsa),
             Key::Ed25519 {
-                scheme: Ed25519Scheme::Ed25519,
                 keyval,
+                scheme: Ed25519Scheme::Ed25519,
                 ..
-            } => (
-                &ring::signature::ED25519,
-                untrusted::Input::from(&keyval.public),
-            ),
+            } => {
+                let public = <[u8; 32]>::try_from(keyval.public.as_ref()).ok()?;
+                ed25519_dalek::VerifyingKey::from_bytes(&public)
+                    .ok()
+                    .map(VerifyingKey::Ed25519)
+            }
             Key::Rsa {
-                scheme: RsaScheme::RsassaPssSha256,
                 keyval,
+                scheme: RsaScheme::RsassaPssSha256,
                 ..
-            } => (
-                &ring::signature::RSA_PSS_2048_8192_SHA256,
-                untrusted::Input::from(&keyval.public),
-            ),
-        };
-
-        alg.verify(
-            public_key,
-            untrusted::Input::from(msg),
-            untrusted::Input::from(signature),
-        )
-        .is_ok()
+            } => {
+                // The same key sizes as ring's `RSA_PSS_2048_8192_SHA256`, which verified these
+                // signatures before.
+                let public = rsa::pkcs1::RsaPublicKey::from_der(&keyval.public).ok()?;
+                let key = rsa::RsaPublicKey::new_with_max_size(
+                    rsa::BigUint::from_bytes_be(public.modulus.as_bytes()),
+                    rsa::BigUint::from_bytes_be(public.public_exponent.as_bytes()),
+                    8192,
+                )
+                .ok()?;
+                if key.n().bits() < 2048 {
+                    return None;
+                }
+                Some(VerifyingKey::Rsa(rsa::pss::VerifyingKey::new(key)))
+            }
+        }
+    }
+}
+
+/// A public key parsed, along with the algorithm for its signature scheme, into the form used to
+/// verify signatures. See [`Key::verifying_key`].
+#[derive(Debug, Clone)]
+pub(crate) enum VerifyingKey {
+    /// `ecdsa-sha2-nistp256`, with ASN.1 DER encoded signatures.
+    Ecdsa(p256::ecdsa::VerifyingKey),
+    /// `ed25519`.
+    Ed25519(ed25519_dalek::VerifyingKey),
+    /// `rsassa-pss-sha256`, with a salt the length of the digest.
+    Rsa(rsa::pss::VerifyingKey<Sha256>),
+}
+
+impl VerifyingKey {
+    /// Verify a signature of an object made with this key.
+    pub(crate) fn verify(&self, msg: &[u8], signature: &[u8]) -> bool {
+        match self {
+            VerifyingKey::Ecdsa(key) => p256::ecdsa::Signature::from_der(signature)
+                .and_then(|signature| key.verify(msg, &signature))
+                .is_ok(),
+            VerifyingKey::Ed25519(key) => ed25519_dalek::Signature::from_slice(signature)
+                .and_then(|signature| key.verify(msg, &signature))
+                .is_ok(),
+            VerifyingKey::Rsa(key) => rsa::pss::Signature::try_from(signature)
+                .and_then(|signature| key.verify(msg, &signature))
+                .is_ok(),
+        }
     }
 }
 
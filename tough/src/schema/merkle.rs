@@ -0,0 +1,266 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Provides the snapshot Merkle tree and proof types described by TAP 16.
+//!
+//! With snapshot Merkle trees, the snapshot metadata is replaced by a Merkle tree whose leaves
+//! describe each targets role. The timestamp role lists the root of the tree, and each role is
+//! accompanied by a proof that its leaf is part of the tree, so a client only needs to download the
+//! proofs for the roles it uses.
+
+use crate::schema::decoded::{Decoded, Hex};
+use crate::schema::error::{self, Result};
+use crate::schema::{Hashes, SnapshotMeta};
+use olpc_cjson::CanonicalFormatter;
+use ring::digest::{Context, SHA256};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use snafu::{ensure, OptionExt, ResultExt};
+use std::collections::HashMap;
+use std::num::NonZeroU64;
+
+/// The prefix hashed with the contents of a leaf, so a leaf can never be confused with an
+/// internal node.
+const LEAF_PREFIX: u8 = 0;
+
+/// The prefix hashed with the two children of an internal node.
+const NODE_PREFIX: u8 = 1;
+
+/// A leaf of a snapshot Merkle tree, describing one targets role in place of its entry in the
+/// snapshot metadata.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct MerkleLeaf {
+    /// The name of the role, such as `targets` or the name of a delegated role.
+    pub name: String,
+
+    /// The version of the role's metadata.
+    pub version: NonZeroU64,
+
+    /// The length in bytes of the role's metadata file, if it is listed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub length: Option<u64>,
+
+    /// The hashes of the role's metadata file, if they are listed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hashes: Option<Hashes>,
+}
+
+impl MerkleLeaf {
+    /// Creates a leaf for the named role from its snapshot metadata.
+    pub fn from_snapshot_meta(name: String, meta: &SnapshotMeta) -> Self {
+        Self {
+            name,
+            version: meta.version,
+            length: meta.length,
+            hashes: meta.hashes.clone(),
+        }
+    }
+
+    /// Returns the snapshot metadata described by this leaf.
+    pub fn to_snapshot_meta(&self) -> SnapshotMeta {
+        SnapshotMeta {
+            length: self.length,
+            hashes: self.hashes.clone(),
+            version: self.version,
+            _extra: HashMap::new(),
+        }
+    }
+
+    /// Returns the hash of this leaf in a Merkle tree.
+    fn hash(&self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        let mut ser = serde_json::Serializer::with_formatter(&mut data, CanonicalFormatter::new());
+        self.serialize(&mut ser).context(error::JsonSerialization {
+            what: "Merkle leaf",
+        })?;
+        let mut context = Context::new(&SHA256);
+        context.update(&[LEAF_PREFIX]);
+        context.update(&data);
+        Ok(context.finish().as_ref().to_vec())
+    }
+}
+
+/// The side of the path that a sibling hash in a [`SnapshotMerkleProof`] is on.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MerkleDirection {
+    /// The sibling is the left child; the path so far is the right child.
+    Left,
+    /// The sibling is the right child; the path so far is the left child.
+    Right,
+}
+
+/// A proof that a role's [`MerkleLeaf`] is part of a snapshot Merkle tree.
+///
+/// A proof is published for each targets role, next to the role's metadata, as
+/// `<role>-snapshot.json` (prefixed with the timestamp version when consistent snapshots are used).
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct SnapshotMerkleProof {
+    /// The leaf being proven.
+    pub leaf_contents: MerkleLeaf,
+
+    /// The sibling hashes on the path from the leaf to the root, starting next to the leaf.
+    pub merkle_path: Vec<Decoded<Hex>>,
+
+    /// The side each hash in `merkle_path` is on.
+    pub path_directions: Vec<MerkleDirection>,
+
+    /// Extra arguments found during deserialization.
+    ///
+    /// If you're instantiating this struct, you should make this `HashMap::empty()`.
+    #[serde(flatten)]
+    pub _extra: HashMap<String, Value>,
+}
+
+impl SnapshotMerkleProof {
+    /// Returns the file name of the proof for the named role. When consistent snapshots are used,
+    /// the name is prefixed with the version of the timestamp metadata that lists the Merkle root.
+    pub fn filename(
+        name: &str,
+        timestamp_version: NonZeroU64,
+        consistent_snapshot: bool,
+    ) -> String {
+        if consistent_snapshot {
+            format!("{}.{}-snapshot.json", timestamp_version, name)
+        } else {
+            format!("{}-snapshot.json", name)
+        }
+    }
+
+    /// Calculates the root of the tree that this proof describes.
+    pub fn merkle_root(&self) -> Result<Vec<u8>> {
+        ensure!(
+            self.merkle_path.len() == self.path_directions.len(),
+            error::MerkleProofMalformed {
+                path: self.merkle_path.len(),
+                directions: self.path_directions.len(),
+            }
+        );
+        let mut hash = self.leaf_contents.hash()?;
+        for (sibling, direction) in self.merkle_path.iter().zip(&self.path_directions) {
+            hash = match direction {
+                MerkleDirection::Left => node_hash(sibling, &hash),
+                MerkleDirection::Right => node_hash(&hash, sibling),
+            };
+        }
+        Ok(hash)
+    }
+
+    /// Verifies that this proof leads to the given Merkle root.
+    pub fn verify(&self, root: &[u8]) -> Result<()> {
+        let calculated = self.merkle_root()?;
+        ensure!(
+            calculated == root,
+            error::MerkleProofMismatch {
+                calculated: hex::encode(calculated),
+                expected: hex::encode(root),
+            }
+        );
+        Ok(())
+    }
+}
+
+/// A snapshot Merkle tree built from the leaves for each targets role.
+#[derive(Debug, Clone)]
+pub struct SnapshotMerkleTree {
+    leaves: Vec<MerkleLeaf>,
+    /// The hashes of each level of the tree, starting with the leaves and ending with the root.
+    levels: Vec<Vec<Vec<u8>>>,
+}
+
+impl SnapshotMerkleTree {
+    /// Builds a tree from the given leaves. The leaves are ordered by role name, so the same set of
+    /// leaves always produces the same tree. Each role must have only one leaf.
+    pub fn new(mut leaves: Vec<MerkleLeaf>) -> Result<Self> {
+        ensure!(!leaves.is_empty(), error::MerkleTreeEmpty);
+        leaves.sort_by(|a, b| a.name.cmp(&b.name));
+        // Sorted leaves with the same name are adjacent.
+        for pair in leaves.windows(2) {
+            ensure!(
+                pair[0].name != pair[1].name,
+                error::MerkleTreeDuplicateLeaf {
+                    name: pair[0].name.as_str()
+                }
+            );
+        }
+
+        let mut level = leaves
+            .iter()
+            .map(MerkleLeaf::hash)
+            .collect::<Result<Vec<_>>>()?;
+        let mut levels = Vec::new();
+        while level.len() > 1 {
+            // A node without a sibling is promoted to the next level unchanged.
+            let next = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(left, right),
+                    _ => pair[0].clone(),
+                })
+                .collect();
+            levels.push(level);
+            level = next;
+        }
+        levels.push(level);
+        Ok(Self { leaves, levels })
+    }
+
+    /// Returns the root hash of the tree.
+    pub fn root(&self) -> &[u8] {
+        // `new` ensures there is at least one level, and the last level holds only the root.
+        &self.levels[self.levels.len() - 1][0]
+    }
+
+    /// Returns the leaves of the tree, ordered by role name.
+    pub fn leaves(&self) -> &[MerkleLeaf] {
+        &self.leaves
+    }
+
+    /// Returns the proof for the named role.
+    pub fn proof(&self, name: &str) -> Result<SnapshotMerkleProof> {
+        let position = self
+            .leaves
+            .binary_search_by(|leaf| leaf.name.as_str().cmp(name))
+            .ok()
+            .context(error::RoleNotFound { name })?;
+        Ok(self.proof_at(position))
+    }
+
+    /// Returns the proofs for every role in the tree, ordered by role name.
+    pub fn proofs(&self) -> Vec<SnapshotMerkleProof> {
+        (0..self.leaves.len()).map(|i| self.proof_at(i)).collect()
+    }
+
+    fn proof_at(&self, leaf: usize) -> SnapshotMerkleProof {
+        let mut position = leaf;
+        let mut merkle_path = Vec::new();
+        let mut path_directions = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = position ^ 1;
+            // A node without a sibling is promoted, so it adds nothing to the path.
+            if let Some(hash) = level.get(sibling) {
+                merkle_path.push(hash.clone().into());
+                path_directions.push(if sibling < position {
+                    MerkleDirection::Left
+                } else {
+                    MerkleDirection::Right
+                });
+            }
+            position /= 2;
+        }
+        SnapshotMerkleProof {
+            leaf_contents: self.leaves[leaf].clone(),
+            merkle_path,
+            path_directions,
+            _extra: HashMap::new(),
+        }
+    }
+}
+
+fn node_hash(left: &[u8], right: &[u8]) -> Vec<u8> {
+    let mut context = Context::new(&SHA256);
+    context.update(&[NODE_PREFIX]);
+    context.update(left);
+    context.update(right);
+    context.finish().as_ref().to_vec()
+}
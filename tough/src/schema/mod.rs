@@ -4,22 +4,32 @@
 
 mod de;
 pub mod decoded;
+mod dsse;
 mod error;
 mod iter;
 pub mod key;
+mod merkle;
+mod query;
 mod spki;
 mod verify;
 
 use crate::schema::decoded::{Decoded, Hex};
+pub use crate::schema::dsse::{DssePayload, DSSE_PAYLOAD_TYPE};
 pub use crate::schema::error::{Error, Result};
 use crate::schema::iter::KeysIter;
 use crate::schema::key::Key;
+pub use crate::schema::merkle::{
+    MerkleDirection, MerkleLeaf, SnapshotMerkleProof, SnapshotMerkleTree,
+};
+pub use crate::schema::query::{TargetMatch, TargetQuery};
+pub(crate) use crate::schema::verify::{KeyCache, VerifyingKeys};
 use crate::sign::Sign;
 pub use crate::transport::{FilesystemTransport, Transport};
 use chrono::{DateTime, Utc};
-use globset::Glob;
+use globset::{Glob, GlobMatcher};
 use olpc_cjson::CanonicalFormatter;
 use ring::digest::{digest, Context, SHA256};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use serde_plain::{forward_display_to_serde, forward_from_str_to_serde};
@@ -31,6 +41,45 @@ use std::num::NonZeroU64;
 use std::ops::{Deref, DerefMut};
 use std::path::Path;
 
+/// The version of the TUF specification that this library implements. This is the `spec_version`
+/// written to new metadata unless an editor is told otherwise.
+pub const SPEC_VERSION: &str = "1.0.0";
+
+/// Returns `true` if metadata with the given `spec_version` can be used by this library.
+///
+/// The TUF specification follows semantic versioning, so metadata is compatible if it has the same
+/// major version as [`SPEC_VERSION`]. The version must be of the form `MAJOR.MINOR.PATCH`, where
+/// each part is a number, with one exception: a two-part `MAJOR.MINOR` version such as "1.0" is
+/// also accepted. That is not a semantic version, but it is what earlier TUF implementations
+/// (and this library's own test repositories) wrote, so rejecting it would make existing
+/// repositories unusable. Any other number of parts is rejected.
+pub fn spec_version_supported(spec_version: &str) -> bool {
+    match (major_version(spec_version), major_version(SPEC_VERSION)) {
+        (Some(given), Some(supported)) => given == supported,
+        _ => false,
+    }
+}
+
+/// Parses a `MAJOR.MINOR.PATCH` version, or a two-part `MAJOR.MINOR` version as described in
+/// [`spec_version_supported`], returning its major version, or `None` if the version is malformed.
+fn major_version(version: &str) -> Option<u64> {
+    let parts = version
+        .split('.')
+        .map(|part| {
+            if !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()) {
+                part.parse::<u64>().ok()
+            } else {
+                None
+            }
+        })
+        .collect::<Option<Vec<_>>>()?;
+    if parts.len() == 2 || parts.len() == 3 {
+        Some(parts[0])
+    } else {
+        None
+    }
+}
+
 /// The type of metadata role.
 #[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "kebab-case")]
@@ -95,20 +144,50 @@ pub trait Role: Serialize {
 }
 
 /// A signed metadata object.
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+///
+/// Signed metadata is usually a JSON object with `signed` and `signatures` fields, where the
+/// signatures are made over the canonical JSON form of `signed`. It can also be read from and
+/// written as a DSSE envelope; see [`DssePayload`].
+#[derive(Debug, Clone, PartialEq)]
 pub struct Signed<T> {
     /// The role that is signed.
+    ///
+    /// The signatures of metadata in a DSSE envelope are made over its payload rather than over
+    /// `signed`, so if `signed` is changed, serializing the envelope fails; sign it again instead.
     pub signed: T,
     /// A list of signatures and their key IDs.
     pub signatures: Vec<Signature>,
+    /// The DSSE envelope payload that the signatures were made over, if this metadata was read
+    /// from or is written as a DSSE envelope, or `None` for the TUF JSON format.
+    pub(crate) dsse: Option<DssePayload>,
+}
+
+impl<T> Signed<T> {
+    /// Creates signed metadata in the TUF JSON format. Metadata written as a DSSE envelope is
+    /// created by signing it with the editor, or by reading it.
+    pub fn new(signed: T, signatures: Vec<Signature>) -> Self {
+        Self {
+            signed,
+            signatures,
+            dsse: None,
+        }
+    }
+
+    /// Returns the DSSE envelope payload that the signatures were made over, if this metadata was
+    /// read from or is written as a DSSE envelope, or `None` for the TUF JSON format.
+    pub fn dsse(&self) -> Option<&DssePayload> {
+        self.dsse.as_ref()
+    }
 }
 
 /// A signature and the key ID that made it.
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct Signature {
-    /// The key ID (listed in root.json) that made this signature.
+    /// The key ID (listed in root.json) that made this signature. DSSE signatures may leave it
+    /// out, in which case it is empty and each key authorized to sign the role is tried.
     pub keyid: Decoded<Hex>,
-    /// A hex-encoded signature of the canonical JSON form of a role.
+    /// A hex-encoded signature of the canonical JSON form of a role, or of the DSSE
+    /// pre-authentication encoding for metadata in a DSSE envelope (where it is base64-encoded).
     pub sig: Decoded<Hex>,
 }
 
@@ -166,6 +245,10 @@ pub struct Root {
     #[serde(flatten)]
     #[serde(deserialize_with = "de::extra_skip_type")]
     pub _extra: HashMap<String, Value>,
+
+    /// `keys`, parsed for verifying signatures the first time a role is verified.
+    #[serde(skip)]
+    pub(crate) verifying_keys: KeyCache,
 }
 
 /// Represents the key IDs used for a role and the threshold of signatures required to validate it.
@@ -486,6 +569,36 @@ impl Target {
             _extra: HashMap::new(),
         })
     }
+
+    /// Parses the target's custom metadata as `T`, treating it as a JSON object.
+    ///
+    /// Fields of `custom` that `T` doesn't declare are ignored unless `T` is marked
+    /// `#[serde(deny_unknown_fields)]`, which is a good way to catch misspelled fields.
+    pub fn custom_as<T: DeserializeOwned>(&self) -> Result<T> {
+        let custom = self
+            .custom
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        serde_json::from_value(Value::Object(custom)).context(error::CustomMetadataParse)
+    }
+
+    /// Replaces the target's custom metadata with `custom`, which must serialize to a JSON object.
+    pub fn set_custom<T: Serialize>(&mut self, custom: &T) -> Result<()> {
+        let value = serde_json::to_value(custom).context(error::JsonSerialization {
+            what: "target custom metadata",
+        })?;
+        match value {
+            Value::Object(map) => {
+                self.custom = map.into_iter().collect();
+                Ok(())
+            }
+            found => error::CustomMetadataNotObject {
+                found: found.to_string(),
+            }
+            .fail(),
+        }
+    }
 }
 
 impl Targets {
@@ -781,6 +894,7 @@ impl Signed<DelegatedTargets> {
             Signed {
                 signed: self.signed.targets,
                 signatures: self.signatures,
+                dsse: self.dsse,
             },
         )
     }
@@ -795,6 +909,7 @@ impl Signed<Targets> {
                 targets: self.signed,
             },
             signatures: self.signatures,
+            dsse: self.dsse,
         }
     }
 }
@@ -825,6 +940,10 @@ pub struct Delegations {
 
     /// The list of delegated roles.
     pub roles: Vec<DelegatedRole>,
+
+    /// `keys`, parsed for verifying signatures the first time a role is verified.
+    #[serde(skip)]
+    pub(crate) verifying_keys: KeyCache,
 }
 
 /// Each role delegated in a targets file is considered a delegated role
@@ -882,24 +1001,22 @@ pub enum PathSet {
 impl PathSet {
     /// Given a target string determines if paths match
     fn matched_target(&self, target: &str) -> bool {
-        match self {
-            Self::Paths(paths) => {
-                for path in paths {
-                    if Self::matched_path(path, target) {
-                        return true;
-                    }
-                }
-            }
+        self.matcher().matched_target(target)
+    }
 
-            Self::PathHashPrefixes(path_prefixes) => {
-                for path in path_prefixes {
-                    if Self::matched_prefix(path, target) {
-                        return true;
-                    }
-                }
-            }
+    /// Compiles the paths, so that many targets can be matched against them without compiling
+    /// each wildcard path again.
+    fn matcher(&self) -> PathMatcher<'_> {
+        match self {
+            Self::Paths(paths) => PathMatcher::Paths(
+                paths
+                    .iter()
+                    .filter_map(|path| Glob::new(path).ok())
+                    .map(|glob| glob.compile_matcher())
+                    .collect(),
+            ),
+            Self::PathHashPrefixes(path_prefixes) => PathMatcher::PathHashPrefixes(path_prefixes),
         }
-        false
     }
 
     /// Given a path hash prefix and a target path determines if target is delegated by prefix
@@ -909,16 +1026,6 @@ impl PathSet {
         hash.as_ref().starts_with(prefix.as_bytes())
     }
 
-    /// Given a shell style wildcard path determines if target matches the path
-    fn matched_path(wildcardpath: &str, target: &str) -> bool {
-        let glob = if let Ok(glob) = Glob::new(wildcardpath) {
-            glob.compile_matcher()
-        } else {
-            return false;
-        };
-        glob.is_match(target)
-    }
-
     /// Returns a Vec representation of the `PathSet`
     pub fn vec(&self) -> &Vec<String> {
         match self {
@@ -927,13 +1034,29 @@ impl PathSet {
     }
 }
 
+/// A `PathSet` with its wildcard paths compiled. Paths that aren't valid wildcards never match.
+#[derive(Debug, Clone)]
+pub(crate) enum PathMatcher<'a> {
+    Paths(Vec<GlobMatcher>),
+    PathHashPrefixes(&'a [String]),
+}
+
+impl PathMatcher<'_> {
+    /// Given a target string determines if paths match
+    pub(crate) fn matched_target(&self, target: &str) -> bool {
+        match self {
+            Self::Paths(globs) => globs.iter().any(|glob| glob.is_match(target)),
+            Self::PathHashPrefixes(path_prefixes) => path_prefixes
+                .iter()
+                .any(|prefix| PathSet::matched_prefix(prefix, target)),
+        }
+    }
+}
+
 impl Delegations {
     /// Creates a new Delegations with no keys or roles
     pub fn new() -> Self {
-        Delegations {
-            keys: HashMap::new(),
-            roles: Vec::new(),
-        }
+        Delegations::default()
     }
 
     /// Determines if target passes pathset specific matching
@@ -1026,6 +1149,12 @@ pub struct Timestamp {
     /// timestamp.json file, this MUST only include a description of the snapshot.json file.
     pub meta: HashMap<String, TimestampMeta>,
 
+    /// The root of the snapshot Merkle tree (TAP 16), if the repository publishes one. When this
+    /// is present, clients verify each targets role using its [`SnapshotMerkleProof`] instead of
+    /// the snapshot metadata.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merkle_root: Option<Decoded<Hex>>,
+
     /// Extra arguments found during deserialization.
     ///
     /// We must store these to correctly verify signatures for this object.
@@ -1067,6 +1196,7 @@ impl Timestamp {
             version,
             expires,
             meta: HashMap::new(),
+            merkle_root: None,
             _extra: HashMap::new(),
         }
     }
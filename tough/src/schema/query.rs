@@ -0,0 +1,198 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Provides `TargetQuery`, for searching the targets of a repository by name and custom metadata.
+
+use crate::schema::error::{self, Result};
+use crate::schema::{PathMatcher, Target, Targets};
+use globset::{Glob, GlobMatcher};
+use serde_json::Value;
+use snafu::ResultExt;
+use std::collections::HashSet;
+use std::fmt;
+use std::rc::Rc;
+
+/// A set of conditions used to select targets with [`Targets::query`].
+///
+/// Each condition added to the query must hold for a target to match; a query with no conditions
+/// matches every target.
+///
+/// ```
+/// # use tough::schema::TargetQuery;
+/// # use serde_json::json;
+/// let query = TargetQuery::new()
+///     .glob("images/x86_64/*")
+///     .unwrap()
+///     .custom("variant", json!("aws-k8s"));
+/// ```
+#[derive(Default)]
+pub struct TargetQuery<'a> {
+    globs: Vec<GlobMatcher>,
+    prefixes: Vec<String>,
+    custom: Vec<(String, Value)>,
+    filters: Vec<Predicate<'a>>,
+}
+
+/// A caller-supplied condition on a target's metadata.
+type Predicate<'a> = Box<dyn Fn(&Target) -> bool + 'a>;
+
+impl<'a> TargetQuery<'a> {
+    /// Creates a query that matches every target.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only match targets whose name matches the shell-style wildcard `pattern`.
+    ///
+    /// Patterns follow the same rules as the `paths` of a delegated role.
+    pub fn glob(mut self, pattern: &str) -> Result<Self> {
+        let glob = Glob::new(pattern).context(error::Glob { pattern })?;
+        self.globs.push(glob.compile_matcher());
+        Ok(self)
+    }
+
+    /// Only match targets whose name starts with `prefix`.
+    #[must_use]
+    pub fn prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.prefixes.push(prefix.into());
+        self
+    }
+
+    /// Only match targets whose `custom` metadata has `key` set to `value`.
+    #[must_use]
+    pub fn custom<S: Into<String>>(mut self, key: S, value: Value) -> Self {
+        self.custom.push((key.into(), value));
+        self
+    }
+
+    /// Only match targets for which `predicate` returns `true`.
+    #[must_use]
+    pub fn filter<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&Target) -> bool + 'a,
+    {
+        self.filters.push(Box::new(predicate));
+        self
+    }
+
+    /// Returns `true` if the named target meets every condition of this query.
+    pub fn matches(&self, name: &str, target: &Target) -> bool {
+        self.globs.iter().all(|glob| glob.is_match(name))
+            && self.prefixes.iter().all(|prefix| name.starts_with(prefix))
+            && self
+                .custom
+                .iter()
+                .all(|(key, value)| target.custom.get(key) == Some(value))
+            && self.filters.iter().all(|filter| filter(target))
+    }
+}
+
+impl fmt::Debug for TargetQuery<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TargetQuery")
+            .field("globs", &self.globs)
+            .field("prefixes", &self.prefixes)
+            .field("custom", &self.custom)
+            .field("filters", &self.filters.len())
+            .finish()
+    }
+}
+
+/// A target selected by a [`TargetQuery`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TargetMatch<'a> {
+    /// The name of the target.
+    pub name: &'a str,
+    /// The target's metadata.
+    pub target: &'a Target,
+    /// The name of the role that lists the target; `"targets"` for the top-level targets role.
+    pub role: &'a str,
+}
+
+/// State shared across the walk of the delegation tree.
+#[derive(Default)]
+struct Walk<'a> {
+    /// Target names already provided by a role earlier in the search order.
+    claimed: HashSet<&'a str>,
+    /// The path chains of terminating roles already visited. Later roles may not provide a target
+    /// that one of these roles was trusted to provide.
+    terminated: Vec<Vec<Rc<PathMatcher<'a>>>>,
+    /// The index of each role in its delegator's list of delegated roles, from the top-level role
+    /// down to the role being visited.
+    position: Vec<usize>,
+}
+
+impl Targets {
+    /// Returns the targets of this role and its delegated roles that match `query`, sorted by
+    /// name.
+    ///
+    /// Delegated roles are searched in the order the TUF spec describes (pre-order depth-first),
+    /// so each target name is reported once, from the first role trusted to provide it. A target
+    /// listed by a delegated role is skipped unless it matches the `paths` of every role in the
+    /// delegation chain, or if a terminating role searched earlier was trusted to provide it.
+    ///
+    /// Targets listed by this role itself are reported as coming from the `"targets"` role.
+    pub fn query<'a>(&'a self, query: &TargetQuery<'_>) -> Vec<TargetMatch<'a>> {
+        let mut matches = Vec::new();
+        self.visit_targets(&mut |name, target, role, _| {
+            if query.matches(name, target) {
+                matches.push(TargetMatch { name, target, role });
+            }
+        });
+        matches.sort_by(|a, b| a.name.cmp(b.name));
+        matches
+    }
+
+    /// Calls `visit` with each target this role and its delegated roles provide, in the search
+    /// order and with the precedence described for [`Targets::query`]. Along with the target's
+    /// name and metadata, `visit` is given the name of the role that lists it and the role's
+    /// position in the delegation tree, as the index of each delegated role on the way to it.
+    pub(crate) fn visit_targets<'a, F>(&'a self, visit: &mut F)
+    where
+        F: FnMut(&'a str, &'a Target, &'a str, &[usize]),
+    {
+        self.visit_walk("targets", &mut Vec::new(), &mut Walk::default(), visit);
+    }
+
+    fn visit_walk<'a, F>(
+        &'a self,
+        role: &'a str,
+        chain: &mut Vec<Rc<PathMatcher<'a>>>,
+        walk: &mut Walk<'a>,
+        visit: &mut F,
+    ) where
+        F: FnMut(&'a str, &'a Target, &'a str, &[usize]),
+    {
+        for (name, target) in &self.targets {
+            let name = name.as_str();
+            if walk.claimed.contains(name)
+                || !chain.iter().all(|paths| paths.matched_target(name))
+                || walk
+                    .terminated
+                    .iter()
+                    .any(|terminated| terminated.iter().all(|paths| paths.matched_target(name)))
+            {
+                continue;
+            }
+            walk.claimed.insert(name);
+            visit(name, target, role, &walk.position);
+        }
+
+        if let Some(delegations) = &self.delegations {
+            for (index, delegated_role) in delegations.roles.iter().enumerate() {
+                chain.push(Rc::new(delegated_role.paths.matcher()));
+                walk.position.push(index);
+                if let Some(targets) = &delegated_role.targets {
+                    targets
+                        .signed
+                        .visit_walk(&delegated_role.name, chain, walk, visit);
+                }
+                if delegated_role.terminating {
+                    walk.terminated.push(chain.clone());
+                }
+                walk.position.pop();
+                chain.pop();
+            }
+        }
+    }
+}
@@ -1,99 +1,252 @@
+use super::decoded::{Decoded, Hex};
 use super::error::{self, Result};
-use super::{Delegations, Role, RoleType, Root, Signed, Targets};
-use olpc_cjson::CanonicalFormatter;
+use super::key::{Key, VerifyingKey};
+use super::{DelegatedRole, Delegations, Role, RoleType, Root, Signature, Signed, Targets};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use serde::Serialize;
-use snafu::{ensure, OptionExt, ResultExt};
-use std::collections::HashSet;
+use snafu::{ensure, OptionExt};
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroU64;
+use std::sync::{Arc, RwLock};
 
-impl Root {
-    /// Checks that the given metadata role is valid based on a threshold of key signatures.
-    pub fn verify_role<T: Role + Serialize>(&self, role: &Signed<T>) -> Result<()> {
-        let role_keys = self
-            .roles
-            .get(&T::TYPE)
-            .context(error::MissingRole { role: T::TYPE })?;
-        let mut valid = 0;
-
-        let mut data = Vec::new();
-        let mut ser = serde_json::Serializer::with_formatter(&mut data, CanonicalFormatter::new());
-        role.signed
-            .serialize(&mut ser)
-            .context(error::JsonSerialization {
-                what: format!("{} role", T::TYPE),
-            })?;
-
-        let mut valid_keyids = HashSet::new();
-
-        for signature in &role.signatures {
-            if role_keys.keyids.contains(&signature.keyid) {
-                if let Some(key) = self.keys.get(&signature.keyid) {
-                    if key.verify(&data, &signature.sig) {
-                        // Ignore duplicate keyids.
-                        if valid_keyids.insert(&signature.keyid) {
-                            valid += 1;
-                        }
-                    }
-                }
-            }
+/// The public keys listed in a `Root` or `Delegations`, by key ID, each parsed into the form used
+/// to verify signatures made with it. A key that cannot be parsed is left out, so no signature made
+/// with it is valid.
+#[derive(Debug)]
+pub(crate) struct VerifyingKeys {
+    /// The keys as listed in the metadata, to tell whether they have changed since being parsed.
+    source: HashMap<Decoded<Hex>, Key>,
+    keys: HashMap<Decoded<Hex>, VerifyingKey>,
+}
+
+impl VerifyingKeys {
+    /// Parses each of `keys` for verifying signatures.
+    pub(crate) fn new(keys: &HashMap<Decoded<Hex>, Key>) -> Self {
+        Self {
+            source: keys.clone(),
+            keys: keys
+                .iter()
+                .filter_map(|(keyid, key)| Some((keyid.clone(), key.verifying_key()?)))
+                .collect(),
         }
+    }
+
+    /// Checks that at least `threshold` of the keys in `keyids` made a valid signature of `data`.
+    /// Signatures from the same key are only counted once.
+    pub(crate) fn verify_threshold(
+        &self,
+        role: RoleType,
+        keyids: &[Decoded<Hex>],
+        threshold: NonZeroU64,
+        data: &[u8],
+        signatures: &[Signature],
+    ) -> Result<()> {
+        let valid = self.valid_keyids(keyids, data, signatures).len() as u64;
 
         ensure!(
-            valid >= u64::from(role_keys.threshold),
+            valid >= u64::from(threshold),
             error::SignatureThreshold {
-                role: T::TYPE,
-                threshold: role_keys.threshold,
+                role,
+                threshold,
                 valid,
             }
         );
         Ok(())
     }
+
+    /// Returns the key IDs in `keyids` that made a valid signature of `data`. A signature without
+    /// a key ID, which DSSE allows, is checked against each of `keyids` in turn.
+    fn valid_keyids<'k>(
+        &self,
+        keyids: &'k [Decoded<Hex>],
+        data: &[u8],
+        signatures: &[Signature],
+    ) -> HashSet<&'k Decoded<Hex>> {
+        let verifies = |keyid: &Decoded<Hex>, signature: &Signature| match self.keys.get(keyid) {
+            Some(key) => key.verify(data, &signature.sig),
+            None => false,
+        };
+        signatures
+            .iter()
+            .filter_map(|signature| {
+                keyids.iter().find(|keyid| {
+                    (signature.keyid.is_empty() || **keyid == signature.keyid)
+                        && verifies(keyid, signature)
+                })
+            })
+            .collect()
+    }
+}
+
+/// The keys of a `Root` or `Delegations`, parsed the first time a role is verified with them and
+/// kept for verifying later roles.
+///
+/// This is not part of the metadata: serde skips it, and it compares equal to any other
+/// `KeyCache` so that it does not affect comparing metadata. If the keys are changed after being
+/// parsed, they are parsed again the next time they are used.
+#[derive(Debug, Default)]
+pub(crate) struct KeyCache(RwLock<Option<Arc<VerifyingKeys>>>);
+
+impl KeyCache {
+    /// Returns `keys` parsed for verifying signatures, parsing them only if they are not cached.
+    pub(crate) fn get(&self, keys: &HashMap<Decoded<Hex>, Key>) -> Arc<VerifyingKeys> {
+        let cached = self.0.read().ok().and_then(|cached| cached.clone());
+        if let Some(cached) = cached.filter(|cached| cached.source == *keys) {
+            return cached;
+        }
+        let parsed = Arc::new(VerifyingKeys::new(keys));
+        if let Ok(mut cached) = self.0.write() {
+            *cached = Some(Arc::clone(&parsed));
+        }
+        parsed
+    }
+}
+
+impl Clone for KeyCache {
+    fn clone(&self) -> Self {
+        Self(RwLock::new(
+            self.0.read().ok().and_then(|cached| cached.clone()),
+        ))
+    }
+}
+
+impl PartialEq for KeyCache {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Root {
+    /// Checks that the given metadata role is valid based on a threshold of key signatures.
+    pub fn verify_role<T: Role + Serialize>(&self, role: &Signed<T>) -> Result<()> {
+        let role_keys = self
+            .roles
+            .get(&T::TYPE)
+            .context(error::MissingRole { role: T::TYPE })?;
+        let data = role.signed_data(&format!("{} role", T::TYPE))?;
+        self.verifying_keys.get(&self.keys).verify_threshold(
+            T::TYPE,
+            &role_keys.keyids,
+            role_keys.threshold,
+            &data,
+            &role.signatures,
+        )
+    }
 }
 
 impl Delegations {
     /// Verifies that roles matches contain valid keys
     pub fn verify_role(&self, role: &Signed<Targets>, name: &str) -> Result<()> {
-        let role_keys =
+        let delegated_role =
             self.roles
                 .iter()
                 .find(|role| role.name == name)
                 .ok_or(error::Error::RoleNotFound {
                     name: name.to_string(),
                 })?;
-        let mut valid = 0;
+        Self::verify_delegated_role(&self.verifying_keys.get(&self.keys), delegated_role, role)
+    }
+
+    /// Verifies each of `roles`, given as pairs of delegated role name and metadata.
+    ///
+    /// The roles are verified independently of each other, so with the `parallel` feature they
+    /// are verified on rayon's global thread pool. Either way, the error returned is that of the
+    /// first role in `roles` that fails verification.
+    pub fn verify_roles(&self, roles: &[(&str, &Signed<Targets>)]) -> Result<()> {
+        let keys = self.verifying_keys.get(&self.keys);
+        let delegated_roles = self
+            .roles
+            .iter()
+            .map(|role| (role.name.as_str(), role))
+            .collect::<HashMap<_, _>>();
+        let verify = |(name, role): &(&str, &Signed<Targets>)| {
+            let delegated_role =
+                delegated_roles
+                    .get(name)
+                    .ok_or_else(|| error::Error::RoleNotFound {
+                        name: (*name).to_string(),
+                    })?;
+            Self::verify_delegated_role(&keys, delegated_role, role)
+        };
+        #[cfg(feature = "parallel")]
+        let results = roles.par_iter().map(verify).collect::<Vec<_>>();
+        #[cfg(not(feature = "parallel"))]
+        let results = roles.iter().map(verify).collect::<Vec<_>>();
+        results.into_iter().collect()
+    }
 
+    /// Checks that `role` is signed by a threshold of the keys `delegated_role` lists, where `keys`
+    /// are the keys of the `Delegations` that `delegated_role` belongs to.
+    pub(crate) fn verify_delegated_role(
+        keys: &VerifyingKeys,
+        delegated_role: &DelegatedRole,
+        role: &Signed<Targets>,
+    ) -> Result<()> {
         // serialize the role to verify the key ID by using the JSON representation
-        let mut data = Vec::new();
-        let mut ser = serde_json::Serializer::with_formatter(&mut data, CanonicalFormatter::new());
-        role.signed
-            .serialize(&mut ser)
-            .context(error::JsonSerialization {
-                what: format!("{} role", name.to_string()),
-            })?;
-        for signature in &role.signatures {
-            if role_keys.keyids.contains(&signature.keyid) {
-                if let Some(key) = self.keys.get(&signature.keyid) {
-                    if key.verify(&data, &signature.sig) {
-                        valid += 1;
-                    }
-                }
-            }
-        }
+        let data = role.signed_data(&format!("{} role", delegated_role.name))?;
+        keys.verify_threshold(
+            RoleType::Targets,
+            &delegated_role.keyids,
+            delegated_role.threshold,
+            &data,
+            &role.signatures,
+        )
+    }
+}
 
-        ensure!(
-            valid >= u64::from(role_keys.threshold),
-            error::SignatureThreshold {
-                role: RoleType::Targets,
-                threshold: role_keys.threshold,
-                valid,
-            }
-        );
-        Ok(())
+impl Root {
+    /// Returns the key IDs, out of those authorized for the role, that made a valid signature of
+    /// the given metadata.
+    pub fn signing_keyids<T: Role + Serialize>(
+        &self,
+        role: &Signed<T>,
+    ) -> Result<HashSet<Decoded<Hex>>> {
+        let role_keys = self
+            .roles
+            .get(&T::TYPE)
+            .context(error::MissingRole { role: T::TYPE })?;
+        let data = role.signed_data(&format!("{} role", T::TYPE))?;
+        Ok(self
+            .verifying_keys
+            .get(&self.keys)
+            .valid_keyids(&role_keys.keyids, &data, &role.signatures)
+            .into_iter()
+            .cloned()
+            .collect())
+    }
+}
+
+impl Delegations {
+    /// Returns the key IDs, out of those authorized for the delegated role `name`, that made a
+    /// valid signature of the given metadata.
+    pub fn signing_keyids(
+        &self,
+        role: &Signed<Targets>,
+        name: &str,
+    ) -> Result<HashSet<Decoded<Hex>>> {
+        let role_keys =
+            self.roles
+                .iter()
+                .find(|role| role.name == name)
+                .ok_or(error::Error::RoleNotFound {
+                    name: name.to_string(),
+                })?;
+        let data = role.signed_data(&format!("{} role", name))?;
+        Ok(self
+            .verifying_keys
+            .get(&self.keys)
+            .valid_keyids(&role_keys.keyids, &data, &role.signatures)
+            .into_iter()
+            .cloned()
+            .collect())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Root, Signed};
+    use super::{Root, Signed, Targets};
+    use std::num::NonZeroU64;
 
     #[test]
     fn simple_rsa() {
@@ -171,4 +324,25 @@ mod tests {
             .verify_role(&root)
             .expect_err("expired root signature should not verify");
     }
+
+    #[test]
+    fn duplicate_delegated_sigs_is_err() {
+        let mut targets: Signed<Targets> = serde_json::from_str(include_str!(
+            "../../tests/data/tuf-reference-impl/metadata/targets.json"
+        ))
+        .expect("should be parsable targets.json");
+        let mut role1: Signed<Targets> = serde_json::from_str(include_str!(
+            "../../tests/data/tuf-reference-impl/metadata/role1.json"
+        ))
+        .expect("should be parsable role1.json");
+        let delegations = targets.signed.delegations.as_mut().unwrap();
+        delegations.verify_role(&role1, "role1").unwrap();
+
+        // A second copy of the same signature must not count towards a threshold of 2.
+        delegations.roles[0].threshold = NonZeroU64::new(2).unwrap();
+        role1.signatures.push(role1.signatures[0].clone());
+        delegations
+            .verify_role(&role1, "role1")
+            .expect_err("duplicate signatures should not meet the threshold");
+    }
 }
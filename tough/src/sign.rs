@@ -1,16 +1,24 @@
 // Copyright 2019 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-//! Provides the `Sign` trait which abstracts over the method of signing with different key types.
+//! Provides the `Sign` trait which abstracts over the method of signing with different key types,
+//! and functions for generating new keys of those types.
 
 use crate::error::{self, Result};
+use crate::key_source::KeySource;
 use crate::schema::key::Key;
 use crate::sign::SignKeyPair::ECDSA;
 use crate::sign::SignKeyPair::ED25519;
 use crate::sign::SignKeyPair::RSA;
+use rand_chacha::rand_core::SeedableRng;
+use rand_chacha::ChaCha20Rng;
 use ring::rand::SecureRandom;
 use ring::signature::{EcdsaKeyPair, Ed25519KeyPair, KeyPair, RsaKeyPair};
-use snafu::ResultExt;
+use rsa::pkcs8::EncodePrivateKey;
+use rsa::{BigUint, RsaPrivateKey};
+use serde::{Deserialize, Serialize};
+use serde_plain::{forward_display_to_serde, forward_from_str_to_serde};
+use snafu::{ensure, ResultExt};
 use std::collections::HashMap;
 
 /// This trait must be implemented for each type of key with which you will
@@ -119,7 +127,7 @@ impl Sign for SignKeyPair {
 
 /// Parses a supplied keypair and if it is recognized, returns an object that
 /// implements the Sign trait
-/// Accepted Keys: ED25519 pkcs8, Ecdsa pkcs8, RSA
+/// Accepted Keys: ED25519 pkcs8, Ecdsa pkcs8, RSA, each either DER or PEM encoded
 pub fn parse_keypair(key: &[u8]) -> Result<impl Sign> {
     if let Ok(ed25519_key_pair) = Ed25519KeyPair::from_pkcs8(key) {
         Ok(SignKeyPair::ED25519(ed25519_key_pair))
@@ -132,6 +140,13 @@ pub fn parse_keypair(key: &[u8]) -> Result<impl Sign> {
             "PRIVATE KEY" => {
                 if let Ok(rsa_key_pair) = RsaKeyPair::from_pkcs8(&pem.contents) {
                     Ok(SignKeyPair::RSA(rsa_key_pair))
+                } else if let Ok(ed25519_key_pair) = Ed25519KeyPair::from_pkcs8(&pem.contents) {
+                    Ok(SignKeyPair::ED25519(ed25519_key_pair))
+                } else if let Ok(ecdsa_key_pair) = EcdsaKeyPair::from_pkcs8(
+                    &ring::signature::ECDSA_P256_SHA256_ASN1_SIGNING,
+                    &pem.contents,
+                ) {
+                    Ok(SignKeyPair::ECDSA(ecdsa_key_pair))
                 } else {
                     error::KeyUnrecognized.fail()
                 }
@@ -145,3 +160,105 @@ pub fn parse_keypair(key: &[u8]) -> Result<impl Sign> {
         error::KeyUnrecognized.fail()
     }
 }
+
+/// The types of keys that [`generate_key`] can create.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyType {
+    /// An Ed25519 key.
+    Ed25519,
+    /// An ECDSA key on the NIST P-256 curve, which signs SHA-256 digests.
+    Ecdsa,
+    /// A 2048-bit RSA key with a public exponent of 65537; use [`generate_rsa_key`] for others.
+    Rsa,
+}
+forward_display_to_serde!(KeyType);
+forward_from_str_to_serde!(KeyType);
+
+/// A newly generated private key, and the public key that goes in root.json.
+#[derive(Debug, Clone)]
+pub struct GeneratedKey {
+    /// The private key, as PEM-encoded PKCS#8, which [`parse_keypair`] accepts.
+    pub pem: String,
+    /// The public key.
+    pub key: Key,
+}
+
+impl GeneratedKey {
+    /// Writes the private key to `key_source`, passing along the hex-encoded key ID of the public
+    /// key, which is also returned.
+    pub fn write(&self, key_source: &dyn KeySource) -> Result<String> {
+        let key_id = hex::encode(self.key.key_id().context(error::JsonSerialization)?);
+        key_source
+            .write(&self.pem, &key_id)
+            .context(error::KeySourceWrite)?;
+        Ok(key_id)
+    }
+}
+
+/// Generates a new key of the given type, using `rng` for randomness.
+pub fn generate_key(key_type: KeyType, rng: &dyn SecureRandom) -> Result<GeneratedKey> {
+    let pkcs8 = match key_type {
+        KeyType::Ed25519 => Ed25519KeyPair::generate_pkcs8(rng)
+            .context(error::KeyGenerate { key_type })?
+            .as_ref()
+            .to_vec(),
+        KeyType::Ecdsa => {
+            EcdsaKeyPair::generate_pkcs8(&ring::signature::ECDSA_P256_SHA256_ASN1_SIGNING, rng)
+                .context(error::KeyGenerate { key_type })?
+                .as_ref()
+                .to_vec()
+        }
+        KeyType::Rsa => return generate_rsa_key(2048, 65537, rng),
+    };
+    generated_key(pkcs8)
+}
+
+/// The smallest RSA key, in bits, that can be generated; ring won't sign with smaller keys.
+const RSA_MIN_BITS: usize = 2048;
+/// The largest RSA key, in bits, that can be generated; ring won't sign with larger keys.
+const RSA_MAX_BITS: usize = 8192;
+
+/// Generates a new RSA key with a modulus of `bits` bits and the given public exponent, using
+/// `rng` for randomness. Keys must be between 2048 and 8192 bits to be usable for signing.
+pub fn generate_rsa_key(
+    bits: usize,
+    exponent: u64,
+    rng: &dyn SecureRandom,
+) -> Result<GeneratedKey> {
+    ensure!(
+        (RSA_MIN_BITS..=RSA_MAX_BITS).contains(&bits),
+        error::KeySizeInvalid {
+            bits,
+            min: RSA_MIN_BITS,
+            max: RSA_MAX_BITS,
+        }
+    );
+
+    // The `rsa` crate needs an infallible `RngCore`, so rather than adapting `rng` (which can
+    // fail), seed a ChaCha20 CSPRNG from it and let any failure surface here.
+    let mut seed = <ChaCha20Rng as SeedableRng>::Seed::default();
+    rng.fill(&mut seed).context(error::KeyGenerate {
+        key_type: KeyType::Rsa,
+    })?;
+    let mut csprng = ChaCha20Rng::from_seed(seed);
+    let private_key = RsaPrivateKey::new_with_exp(&mut csprng, bits, &BigUint::from(exponent))
+        .context(error::KeyGenerateRsa)?;
+    let pkcs8 = private_key.to_pkcs8_der().context(error::KeyEncode)?;
+    generated_key(pkcs8.as_bytes().to_vec())
+}
+
+/// PEM-encodes a generated PKCS#8 key, and parses it back to find its public key.
+fn generated_key(pkcs8: Vec<u8>) -> Result<GeneratedKey> {
+    let pem = pem::encode_config(
+        &pem::Pem {
+            tag: "PRIVATE KEY".to_owned(),
+            contents: pkcs8,
+        },
+        pem::EncodeConfig {
+            line_ending: pem::LineEnding::LF,
+        },
+    );
+    let key = parse_keypair(pem.as_bytes())?.tuf_key();
+    Ok(GeneratedKey { pem, key })
+}
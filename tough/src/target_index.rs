@@ -0,0 +1,123 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Provides the index a `Repository` builds of its targets when it is loaded, so that looking up
+//! or listing targets doesn't walk the delegation tree each time.
+
+use crate::schema::{Target, Targets};
+use crate::{Repository, Transport};
+use std::collections::HashMap;
+
+/// Maps each target name to the role that provides it, following the search order and precedence
+/// described for [`Targets::query`].
+///
+/// Roles are recorded by their position in the delegation tree rather than by reference, so the
+/// index can be stored alongside the metadata it was built from.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TargetIndex {
+    /// The position of each role that provides at least one target, as the index of each
+    /// delegated role on the way to it from the top-level role.
+    roles: Vec<Vec<usize>>,
+    /// For each target name, the index in `roles` of the role that provides it.
+    names: HashMap<String, usize>,
+}
+
+impl TargetIndex {
+    /// Builds the index of the targets provided by `targets` and its delegated roles.
+    pub(crate) fn new(targets: &Targets) -> Self {
+        let mut index = Self::default();
+        targets.visit_targets(&mut |name, _, _, position| {
+            // Each role's targets are visited together, so a role is only recorded once.
+            if index.roles.last().map(Vec::as_slice) != Some(position) {
+                index.roles.push(position.to_vec());
+            }
+            index.names.insert(name.to_owned(), index.roles.len() - 1);
+        });
+        index
+    }
+
+    /// Returns the named target from `targets`, which must be the role the index was built from.
+    pub(crate) fn get<'a>(
+        &self,
+        targets: &'a Targets,
+        name: &str,
+    ) -> Option<(&'a str, &'a Target)> {
+        let role = self.role(targets, *self.names.get(name)?)?;
+        role.targets
+            .get_key_value(name)
+            .map(|(name, target)| (name.as_str(), target))
+    }
+
+    /// Returns each target from `targets`, which must be the role the index was built from, in no
+    /// particular order.
+    pub(crate) fn iter<'a>(
+        &'a self,
+        targets: &'a Targets,
+    ) -> impl Iterator<Item = (&'a str, &'a Target)> + 'a {
+        self.names.iter().filter_map(move |(name, role)| {
+            let target = self.role(targets, *role)?.targets.get(name)?;
+            Some((name.as_str(), target))
+        })
+    }
+
+    /// Returns the names of the roles on the way to the role that provides the named target,
+    /// starting with `"targets"` for the top-level role.
+    fn role_path<'a>(&self, targets: &'a Targets, name: &str) -> Option<Vec<&'a str>> {
+        let position = self.roles.get(*self.names.get(name)?)?;
+        let mut role_path = vec!["targets"];
+        let mut role = targets;
+        for &index in position {
+            let delegated_role = role.delegations.as_ref()?.roles.get(index)?;
+            role_path.push(delegated_role.name.as_str());
+            role = &delegated_role.targets.as_ref()?.signed;
+        }
+        Some(role_path)
+    }
+
+    /// Returns the role at the given index in `roles`.
+    fn role<'a>(&self, targets: &'a Targets, role: usize) -> Option<&'a Targets> {
+        let mut role_targets = targets;
+        for &index in self.roles.get(role)? {
+            role_targets = &role_targets
+                .delegations
+                .as_ref()?
+                .roles
+                .get(index)?
+                .targets
+                .as_ref()?
+                .signed;
+        }
+        Some(role_targets)
+    }
+}
+
+/// A target found with [`Repository::find_target`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedTarget<'a> {
+    /// The name of the target.
+    pub name: &'a str,
+    /// The target's metadata.
+    pub target: &'a Target,
+    /// The names of the roles the target was delegated through, from `"targets"` for the
+    /// top-level targets role to the role that lists the target.
+    pub role_path: Vec<&'a str>,
+}
+
+impl<T: Transport> Repository<'_, T> {
+    /// Returns the named target and the roles it was delegated through, or `None` if no role
+    /// trusted to provide the target lists it.
+    ///
+    /// Targets are looked up in an index built when the repository was loaded. Each target name is
+    /// provided by the first role trusted to provide it, in the search order described for
+    /// [`Targets::query`].
+    pub fn find_target(&self, name: &str) -> Option<IndexedTarget<'_>> {
+        let targets = &self.selected_targets().signed;
+        let (name, target) = self.target_index.get(targets, name)?;
+        let role_path = self.target_index.role_path(targets, name)?;
+        Some(IndexedTarget {
+            name,
+            target,
+            role_path,
+        })
+    }
+}
@@ -0,0 +1,57 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Provides checks for target names that are paths, such as `images/x86_64/root.img`, and the
+//! filenames they are stored under.
+
+use crate::error::{self, Result};
+use snafu::{ensure, OptionExt};
+use std::path::{Component, Path};
+
+/// Checks that `name`, as a path, stays within the directory it is joined to: it must be relative
+/// and must not have `..` components. Other names, such as ones with empty or `.` components,
+/// are left alone, since existing repositories may list them.
+pub(crate) fn validate(name: &str) -> Result<()> {
+    ensure!(
+        Path::new(name)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir)),
+        error::TargetNameInvalid { name }
+    );
+    Ok(())
+}
+
+/// Returns the name of the target at `path`: its path relative to `indir`, with `/` separators.
+pub(crate) fn from_path(indir: &Path, path: &Path) -> Result<String> {
+    let relative = path
+        .strip_prefix(indir)
+        .ok()
+        .context(error::TargetOutsideDir { path, indir })?;
+    let mut components = Vec::new();
+    for component in relative.components() {
+        match component {
+            Component::Normal(component) => {
+                components.push(component.to_str().context(error::PathUtf8 { path })?);
+            }
+            _ => error::TargetOutsideDir { path, indir }.fail()?,
+        }
+    }
+    let name = components.join("/");
+    validate(&name)?;
+    Ok(name)
+}
+
+/// Returns the filename that the target `name` is stored under when consistent snapshots are
+/// used: the hex-encoded `sha256` is prefixed to the final component of the name, as in
+/// `images/<sha256>.root.img`.
+pub(crate) fn consistent_filename(name: &str, sha256: &[u8]) -> String {
+    match name.rfind('/') {
+        Some(index) => format!(
+            "{}/{}.{}",
+            &name[..index],
+            hex::encode(sha256),
+            &name[index + 1..]
+        ),
+        None => format!("{}.{}", hex::encode(sha256), name),
+    }
+}
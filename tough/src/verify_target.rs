@@ -0,0 +1,142 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Verifies local copies of targets against the repository metadata.
+
+use crate::error::{self, Result};
+use crate::schema::Target;
+use crate::{Repository, Transport};
+use ring::digest::{Algorithm, Context, SHA256, SHA384, SHA512};
+use snafu::{ensure, OptionExt, ResultExt};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+impl<T: Transport> Repository<'_, T> {
+    /// Verifies that the file at `path` is the named target, without fetching the target from
+    /// the repository.
+    ///
+    /// The target is looked up in the same way as [`Repository::read_target`], including
+    /// delegated roles. The file's length and every hash listed for the target that this library
+    /// supports (`sha256`, `sha384`, and `sha512`) must match the metadata.
+    ///
+    /// Returns an error if the repository metadata is expired, if the target is not listed in the
+    /// metadata, or if the file does not match.
+    pub fn verify_target_file<P: AsRef<Path>>(&self, name: &str, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let target = self.find_local_target(name)?;
+        let file = File::open(path).context(error::FileOpen { path })?;
+        // Check the length before reading the whole file, so an obviously wrong file fails fast.
+        let found = file.metadata().context(error::FileMetadata { path })?.len();
+        ensure!(
+            found == target.length,
+            error::TargetLengthMismatch {
+                name,
+                found,
+                truncated: false,
+                expected: target.length,
+            }
+        );
+        verify_target(name, target, file)
+    }
+
+    /// Verifies that the contents of `reader` are the named target, without fetching the target
+    /// from the repository.
+    ///
+    /// This behaves like [`Repository::verify_target_file`]. At most one byte more than the
+    /// target's listed length is read from `reader`.
+    pub fn verify_target_reader<R: Read>(&self, name: &str, reader: R) -> Result<()> {
+        let target = self.find_local_target(name)?;
+        verify_target(name, target, reader)
+    }
+
+    /// Checks metadata expiration and finds the named target, as `read_target` does.
+    fn find_local_target(&self, name: &str) -> Result<&Target> {
+        self.check_earliest_expiration()?;
+        self.find_target(name)
+            .map(|found| found.target)
+            .context(error::TargetNotListed { name })
+    }
+}
+
+/// A hash listed for a target, and the digest being calculated to check it.
+struct ExpectedHash {
+    algorithm: &'static str,
+    /// The hash as listed, for reporting a mismatch.
+    listed: String,
+    /// The listed hash decoded from hex, or `None` if it isn't valid hex and can never match.
+    expected: Option<Vec<u8>>,
+    context: Context,
+}
+
+impl ExpectedHash {
+    fn new(algorithm: &'static str, digest: &'static Algorithm, listed: String) -> Self {
+        Self {
+            algorithm,
+            expected: hex::decode(&listed).ok(),
+            listed,
+            context: Context::new(digest),
+        }
+    }
+}
+
+/// Returns the hashes listed for `target` that can be checked.
+fn expected_hashes(target: &Target) -> Vec<ExpectedHash> {
+    let mut hashes = vec![ExpectedHash::new(
+        "sha256",
+        &SHA256,
+        hex::encode(&target.hashes.sha256),
+    )];
+    for (algorithm, digest) in &[("sha384", &SHA384), ("sha512", &SHA512)] {
+        if let Some(listed) = target.hashes._extra.get(*algorithm) {
+            // A hash that isn't a string can never match, but we still want to report it.
+            let listed = listed
+                .as_str()
+                .map_or_else(|| listed.to_string(), str::to_owned);
+            hashes.push(ExpectedHash::new(algorithm, digest, listed));
+        }
+    }
+    hashes
+}
+
+fn verify_target<R: Read>(name: &str, target: &Target, reader: R) -> Result<()> {
+    let mut hashes = expected_hashes(target);
+    // Read one byte past the expected length so that a longer file is detected without reading
+    // all of it.
+    let mut reader = reader.take(target.length.saturating_add(1));
+    let mut found = 0;
+    let mut buf = [0; 8192];
+    loop {
+        let size = reader.read(&mut buf).context(error::TargetRead { name })?;
+        if size == 0 {
+            break;
+        }
+        found += size as u64;
+        for hash in &mut hashes {
+            hash.context.update(&buf[..size]);
+        }
+    }
+    ensure!(
+        found == target.length,
+        error::TargetLengthMismatch {
+            name,
+            found,
+            truncated: found > target.length,
+            expected: target.length,
+        }
+    );
+
+    for hash in hashes {
+        // Compare the decoded bytes, so the case of the listed hex digits doesn't matter.
+        let calculated = hash.context.finish();
+        ensure!(
+            hash.expected.as_deref() == Some(calculated.as_ref()),
+            error::HashMismatch {
+                context: format!("target '{}' ({})", name, hash.algorithm),
+                calculated: hex::encode(calculated),
+                expected: hash.listed,
+            }
+        );
+    }
+    Ok(())
+}
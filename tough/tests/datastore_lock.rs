@@ -0,0 +1,101 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use serde_json::Value;
+use std::fs::File;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use tempfile::TempDir;
+use test_utils::{dir_url, test_data};
+use tough::{FilesystemTransport, Repository, Settings};
+
+mod test_utils;
+
+/// When set, `child_loads` loads the repository into the datastore at this path; see
+/// `test_concurrent_loads`.
+const CHILD_DATASTORE: &str = "TOUGH_TEST_CHILD_DATASTORE";
+
+const DATASTORE_FILES: &[&str] = &[
+    "timestamp.json",
+    "snapshot.json",
+    "targets.json",
+    "role1.json",
+    "role2.json",
+    "latest_known_time.json",
+    "history-head.json",
+];
+
+fn load(datastore: &Path) {
+    let base = test_data().join("tuf-reference-impl");
+    Repository::load(
+        &FilesystemTransport,
+        Settings::new(
+            File::open(base.join("metadata").join("1.root.json")).unwrap(),
+            datastore,
+            &dir_url(base.join("metadata")),
+            &dir_url(base.join("targets")),
+        )
+        .history(true),
+    )
+    .unwrap();
+}
+
+/// Returns `false` if any datastore file exists but doesn't contain complete JSON.
+fn datastore_files_complete(datastore: &Path) -> bool {
+    DATASTORE_FILES
+        .iter()
+        .all(|name| match File::open(datastore.join(name)) {
+            Ok(file) => serde_json::from_reader::<_, Value>(file).is_ok(),
+            Err(_) => true,
+        })
+}
+
+/// Loads the repository repeatedly when run as a child process of `test_concurrent_loads`, and
+/// does nothing otherwise.
+#[test]
+fn child_loads() {
+    if let Some(datastore) = std::env::var_os(CHILD_DATASTORE) {
+        for _ in 0..10 {
+            load(Path::new(&datastore));
+        }
+    }
+}
+
+/// Test that concurrent loads by separate processes sharing a datastore all succeed, and that a
+/// reader never sees a truncated or partially written datastore file.
+///
+/// Each process is this test binary, running only `child_loads`.
+#[test]
+fn test_concurrent_loads() {
+    let datastore = TempDir::new().unwrap();
+    let mut children = (0..8)
+        .map(|_| {
+            Command::new(std::env::current_exe().unwrap())
+                .args(["--exact", "child_loads", "--test-threads", "1"])
+                .env(CHILD_DATASTORE, datastore.path())
+                .stdout(Stdio::null())
+                .spawn()
+                .unwrap()
+        })
+        .collect::<Vec<Child>>();
+
+    let mut checks = 0;
+    while children
+        .iter_mut()
+        .any(|child| child.try_wait().unwrap().is_none())
+    {
+        assert!(datastore_files_complete(datastore.path()));
+        checks += 1;
+    }
+    assert!(checks > 0);
+    for child in &mut children {
+        assert!(child.wait().unwrap().success());
+    }
+
+    assert!(DATASTORE_FILES
+        .iter()
+        .all(|name| datastore.path().join(name).exists()));
+    assert!(datastore_files_complete(datastore.path()));
+    // Each metadata file was only recorded once, even though the loads raced.
+    assert_eq!(tough::read_history(datastore.path()).unwrap().len(), 6);
+}
@@ -0,0 +1,77 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use std::fs::File;
+use tempfile::TempDir;
+use test_utils::{dir_url, test_data};
+use tough::error::{Error, Result};
+use tough::{FilesystemTransport, Limits, Repository, Settings};
+
+mod test_utils;
+
+/// Loads the reference repository, in which "targets" delegates to "role1", which delegates to
+/// "role2".
+fn load(limits: Limits) -> Result<()> {
+    let base = test_data().join("tuf-reference-impl");
+    let datastore = TempDir::new().unwrap();
+    Repository::load(
+        &FilesystemTransport,
+        Settings::new(
+            File::open(base.join("metadata").join("1.root.json")).unwrap(),
+            datastore.path(),
+            &dir_url(base.join("metadata")),
+            &dir_url(base.join("targets")),
+        )
+        .limits(limits),
+    )
+    .map(|_| ())
+}
+
+/// Test that delegated roles nested too deeply are rejected.
+#[test]
+fn test_max_delegation_depth() {
+    load(Limits {
+        max_delegation_depth: 2,
+        ..Limits::default()
+    })
+    .unwrap();
+    match load(Limits {
+        max_delegation_depth: 1,
+        ..Limits::default()
+    }) {
+        Err(Error::MaxDelegationDepthExceeded { name, .. }) => assert_eq!(name, "role2"),
+        other => panic!("expected delegation depth error, got {:?}", other),
+    }
+}
+
+/// Test that loading too many delegated roles is rejected.
+#[test]
+fn test_max_delegated_roles() {
+    load(Limits {
+        max_delegated_roles: 2,
+        ..Limits::default()
+    })
+    .unwrap();
+    match load(Limits {
+        max_delegated_roles: 1,
+        ..Limits::default()
+    }) {
+        Err(Error::MaxDelegatedRolesExceeded { name, .. }) => assert_eq!(name, "role2"),
+        other => panic!("expected delegated role count error, got {:?}", other),
+    }
+}
+
+/// Test that delegated metadata larger than `max_delegated_targets_size` is rejected, even though
+/// it is within `max_targets_size`.
+#[test]
+fn test_max_delegated_targets_size() {
+    match load(Limits {
+        max_delegated_targets_size: 100,
+        ..Limits::default()
+    }) {
+        Err(Error::MaxDelegatedTargetsSizeExceeded { name, max_size, .. }) => {
+            assert_eq!((name.as_str(), max_size), ("role1", 100))
+        }
+        other => panic!("expected delegated size error, got {:?}", other),
+    }
+}
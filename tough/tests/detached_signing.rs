@@ -0,0 +1,120 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use chrono::{Duration, Utc};
+use std::num::NonZeroU64;
+use tempfile::TempDir;
+use test_utils::{dir_url, key, load, root_path, targets_path};
+use tough::editor::signed::sign_payload;
+use tough::editor::RepositoryEditor;
+use tough::error::Error;
+use tough::schema::PathSet;
+use tough::FilesystemTransport;
+
+mod test_utils;
+
+/// Creates an editor for a repository whose top-level targets role delegates file1.txt to "role1",
+/// which requires signatures from both `targetskey` and `targetskey-1`, and starts editing
+/// "role1".
+fn editor() -> RepositoryEditor<'static, FilesystemTransport> {
+    let expiration = Utc::now().checked_add_signed(Duration::days(21)).unwrap();
+    let version = NonZeroU64::new(1).unwrap();
+    let mut role_keys = key("targetskey");
+    role_keys.extend(key("targetskey-1"));
+
+    let mut editor = RepositoryEditor::<FilesystemTransport>::new(root_path()).unwrap();
+    editor
+        .targets_expires(expiration)
+        .unwrap()
+        .targets_version(version)
+        .unwrap()
+        .snapshot_expires(expiration)
+        .snapshot_version(version)
+        .timestamp_expires(expiration)
+        .timestamp_version(version)
+        .delegate_role(
+            "role1",
+            &role_keys,
+            PathSet::Paths(vec!["file1.txt".to_string()]),
+            NonZeroU64::new(2).unwrap(),
+            expiration,
+            version,
+        )
+        .unwrap();
+    editor
+        .sign_targets_editor(&key("snakeoil.pem"))
+        .unwrap()
+        .change_delegated_targets("role1")
+        .unwrap()
+        .add_target_paths(vec![targets_path().join("file1.txt")])
+        .unwrap()
+        .targets_version(version)
+        .unwrap()
+        .targets_expires(expiration)
+        .unwrap();
+    editor
+}
+
+/// Test that a role is signed by parties who each hold one of its keys, and can only be finalized
+/// once both have signed.
+#[test]
+fn detached_signing() {
+    let mut editor = editor();
+    let mut pending = editor.pending_targets_editor().unwrap();
+
+    // The first party signs; one signature doesn't meet the threshold.
+    for signature in sign_payload(pending.payload(), &key("targetskey")).unwrap() {
+        pending.add_signature(signature).unwrap();
+    }
+    assert!(matches!(
+        editor.finalize_targets_editor(pending.clone()),
+        Err(Error::VerifyRoleMetadata { .. })
+    ));
+
+    // The second party signs, and the role can be finalized and written.
+    for signature in sign_payload(pending.payload(), &key("targetskey-1")).unwrap() {
+        pending.add_signature(signature).unwrap();
+    }
+    assert_eq!(pending.signed().signatures.len(), 2);
+    editor.finalize_targets_editor(pending).unwrap();
+
+    let repo_dir = TempDir::new().unwrap();
+    let datastore = TempDir::new().unwrap();
+    let metadata_dir = repo_dir.path().join("metadata");
+    editor
+        .sign(&key("snakeoil.pem"))
+        .unwrap()
+        .write(&metadata_dir)
+        .unwrap();
+    let metadata_base_url = dir_url(&metadata_dir);
+    let targets_base_url = dir_url(targets_path());
+    let repo = load(&metadata_base_url, &targets_base_url, datastore.path());
+    assert_eq!(
+        repo.find_target("file1.txt").unwrap().role_path,
+        vec!["targets", "role1"]
+    );
+}
+
+/// Test that signatures by keys the role doesn't trust, or over other data, are rejected.
+#[test]
+fn detached_signing_rejects_signatures() {
+    let editor = editor();
+    let mut pending = editor.pending_targets_editor().unwrap();
+
+    let signature = sign_payload(pending.payload(), &key("snakeoil.pem"))
+        .unwrap()
+        .remove(0);
+    assert!(matches!(
+        pending.add_signature(signature),
+        Err(Error::SignatureNotAuthorized { .. })
+    ));
+
+    let signature = sign_payload(b"not the payload", &key("targetskey"))
+        .unwrap()
+        .remove(0);
+    assert!(matches!(
+        pending.add_signature(signature),
+        Err(Error::SignatureInvalid { .. })
+    ));
+    assert!(pending.signed().signatures.is_empty());
+}
@@ -0,0 +1,191 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use chrono::{Duration, Utc};
+use serde_json::Value;
+use std::num::NonZeroU64;
+use std::path::Path;
+use tempfile::TempDir;
+use test_utils::{dir_url, load, read_to_end, root_path, targets_path, test_data, try_load};
+use tough::editor::signed::PathExists;
+use tough::editor::RepositoryEditor;
+use tough::error::Error;
+use tough::key_source::{KeySource, LocalKeySource};
+use tough::schema::{DssePayload, PathSet, DSSE_PAYLOAD_TYPE};
+use tough::FilesystemTransport;
+
+mod test_utils;
+
+/// Creates a repository whose metadata is signed as DSSE envelopes, in which "targets" delegates
+/// file1.txt to "role1".
+fn create_repo(outdir: &Path) {
+    let expiration = Utc::now().checked_add_signed(Duration::days(21)).unwrap();
+    let version = NonZeroU64::new(1).unwrap();
+    let targets_key: &[Box<dyn KeySource>] = &[Box::new(LocalKeySource {
+        path: test_data().join("snakeoil.pem"),
+    })];
+    let role1_key: &[Box<dyn KeySource>] = &[Box::new(LocalKeySource {
+        path: test_data().join("targetskey"),
+    })];
+
+    let mut editor = RepositoryEditor::<FilesystemTransport>::new(root_path()).unwrap();
+    editor
+        .dsse(true)
+        .targets_expires(expiration)
+        .unwrap()
+        .targets_version(version)
+        .unwrap()
+        .snapshot_expires(expiration)
+        .snapshot_version(version)
+        .timestamp_expires(expiration)
+        .timestamp_version(version)
+        .add_target_paths(vec![targets_path().join("file3.txt")])
+        .unwrap()
+        .delegate_role(
+            "role1",
+            role1_key,
+            PathSet::Paths(vec!["file1.txt".to_string()]),
+            NonZeroU64::new(1).unwrap(),
+            expiration,
+            version,
+        )
+        .unwrap();
+    editor
+        .sign_targets_editor(targets_key)
+        .unwrap()
+        .change_delegated_targets("role1")
+        .unwrap()
+        .add_target_paths(vec![targets_path().join("file1.txt")])
+        .unwrap()
+        .targets_version(version)
+        .unwrap()
+        .targets_expires(expiration)
+        .unwrap()
+        .sign_targets_editor(role1_key)
+        .unwrap()
+        .change_delegated_targets("targets")
+        .unwrap()
+        .targets_version(version)
+        .unwrap()
+        .targets_expires(expiration)
+        .unwrap();
+
+    let signed_repo = editor.sign(targets_key).unwrap();
+    signed_repo.write(outdir.join("metadata")).unwrap();
+    signed_repo
+        .link_targets(&targets_path(), &outdir.join("targets"), PathExists::Skip)
+        .unwrap();
+}
+
+fn read_json(path: &Path) -> Value {
+    serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap()
+}
+
+/// Test that a repository signed with DSSE envelopes is written as envelopes and can be loaded,
+/// including from metadata already in the datastore.
+#[test]
+fn load_dsse_repo() {
+    let repo_dir = TempDir::new().unwrap();
+    let datastore = TempDir::new().unwrap();
+    create_repo(repo_dir.path());
+    let metadata = repo_dir.path().join("metadata");
+    let metadata_base_url = dir_url(&metadata);
+    let targets_base_url = dir_url(repo_dir.path().join("targets"));
+
+    for file in &[
+        "timestamp.json",
+        "1.snapshot.json",
+        "1.targets.json",
+        "1.role1.json",
+    ] {
+        let envelope = read_json(&metadata.join(file));
+        assert_eq!(envelope["payloadType"], DSSE_PAYLOAD_TYPE, "{}", file);
+        assert!(envelope.get("signed").is_none(), "{}", file);
+    }
+
+    for _ in 0..2 {
+        let repo = load(&metadata_base_url, &targets_base_url, datastore.path());
+        assert!(repo.timestamp().dsse().is_some());
+        assert!(repo.targets().signed.delegated_targets("role1").is_ok());
+        assert_eq!(
+            read_to_end(repo.read_target("file1.txt").unwrap().unwrap()),
+            std::fs::read(targets_path().join("file1.txt")).unwrap()
+        );
+    }
+}
+
+/// Test that an envelope whose payload was changed after signing is rejected.
+#[test]
+fn tampered_dsse_payload() {
+    let repo_dir = TempDir::new().unwrap();
+    let datastore = TempDir::new().unwrap();
+    create_repo(repo_dir.path());
+    let metadata = repo_dir.path().join("metadata");
+    let metadata_base_url = dir_url(&metadata);
+    let targets_base_url = dir_url(repo_dir.path().join("targets"));
+
+    let path = metadata.join("timestamp.json");
+    let mut envelope = read_json(&path);
+    let payload = base64::decode(envelope["payload"].as_str().unwrap()).unwrap();
+    let mut timestamp: Value = serde_json::from_slice(&payload).unwrap();
+    timestamp["version"] = 2.into();
+    envelope["payload"] = base64::encode(serde_json::to_vec(&timestamp).unwrap()).into();
+    std::fs::write(&path, serde_json::to_vec(&envelope).unwrap()).unwrap();
+
+    match try_load(&metadata_base_url, &targets_base_url, datastore.path()) {
+        Err(Error::VerifyMetadata { .. }) => {}
+        other => panic!("expected verification error, got {:?}", other),
+    }
+}
+
+/// Test that DSSE signatures without a key ID are checked against each authorized key, and that
+/// they are written back without one.
+#[test]
+fn dsse_signature_without_keyid() {
+    let repo_dir = TempDir::new().unwrap();
+    let datastore = TempDir::new().unwrap();
+    create_repo(repo_dir.path());
+    let metadata = repo_dir.path().join("metadata");
+    let metadata_base_url = dir_url(&metadata);
+    let targets_base_url = dir_url(repo_dir.path().join("targets"));
+
+    let path = metadata.join("timestamp.json");
+    let mut envelope = read_json(&path);
+    for signature in envelope["signatures"].as_array_mut().unwrap() {
+        signature.as_object_mut().unwrap().remove("keyid");
+    }
+    std::fs::write(&path, serde_json::to_vec(&envelope).unwrap()).unwrap();
+
+    let repo = load(&metadata_base_url, &targets_base_url, datastore.path());
+    assert!(repo.timestamp().signatures[0].keyid.is_empty());
+    assert_eq!(serde_json::to_value(repo.timestamp()).unwrap(), envelope);
+}
+
+/// Test the pre-authentication encoding against the example in the DSSE specification.
+#[test]
+fn changed_dsse_metadata_is_not_serialized() {
+    let repo_dir = TempDir::new().unwrap();
+    let datastore = TempDir::new().unwrap();
+    create_repo(repo_dir.path());
+    let metadata_base_url = dir_url(repo_dir.path().join("metadata"));
+    let targets_base_url = dir_url(repo_dir.path().join("targets"));
+
+    let repo = load(&metadata_base_url, &targets_base_url, datastore.path());
+    let mut timestamp = repo.timestamp().clone();
+    serde_json::to_vec(&timestamp).unwrap();
+    timestamp.signed.version = NonZeroU64::new(2).unwrap();
+    serde_json::to_vec(&timestamp)
+        .expect_err("a payload that no longer holds the metadata should not be written");
+}
+
+#[test]
+fn dsse_pae() {
+    let payload = DssePayload {
+        payload_type: "http://example.com/HelloWorld".to_owned(),
+        payload: b"hello world".to_vec(),
+    };
+    assert_eq!(
+        payload.pae(),
+        b"DSSEv1 29 http://example.com/HelloWorld 11 hello world".to_vec()
+    );
+}
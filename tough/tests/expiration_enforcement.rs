@@ -1,12 +1,17 @@
 // Copyright 2019 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use std::cell::{Cell, RefCell};
 use std::fs::File;
 use tempfile::TempDir;
 use test_utils::{dir_url, test_data};
-use tough::error::Error::ExpiredMetadata;
+use tough::error::Error::{ExpiredMetadata, SystemTimeSteppedBackward};
 use tough::schema::RoleType;
-use tough::{ExpirationEnforcement, FilesystemTransport, Limits, Repository, Settings};
+use tough::{
+    Clock, ExpirationEnforcement, ExpirationPolicy, ExpirationRule, ExpirationWarning,
+    FilesystemTransport, FixedClock, Repository, Settings, SystemClock,
+};
 
 mod test_utils;
 
@@ -22,14 +27,12 @@ fn test_expiration_enforcement_safe() {
 
     let result = Repository::load(
         &FilesystemTransport,
-        Settings {
-            root: File::open(base.join("metadata").join("1.root.json")).unwrap(),
-            datastore: datastore.as_ref(),
+        Settings::new(
+            File::open(base.join("metadata").join("1.root.json")).unwrap(),
+            datastore.as_ref(),
             metadata_base_url,
             targets_base_url,
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        ),
     );
 
     if let Err(err) = result {
@@ -60,14 +63,177 @@ fn test_expiration_enforcement_unsafe() {
 
     let result = Repository::load(
         &FilesystemTransport,
-        Settings {
-            root: File::open(base.join("metadata").join("1.root.json")).unwrap(),
-            datastore: datastore.as_ref(),
+        Settings::new(
+            File::open(base.join("metadata").join("1.root.json")).unwrap(),
+            datastore.as_ref(),
             metadata_base_url,
             targets_base_url,
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Unsafe,
-        },
+        )
+        .expiration_enforcement(ExpirationEnforcement::Unsafe),
     );
     assert!(result.is_ok())
 }
+
+/// Test that expiration is checked against the time given by `Settings::clock`, and that the clock
+/// is still not allowed to step backward.
+#[test]
+fn test_expiration_enforcement_clock() {
+    let base = test_data().join("expired-repository");
+    let datastore = TempDir::new().unwrap();
+
+    let metadata_base_url = &dir_url(base.join("metadata"));
+    let targets_base_url = &dir_url(base.join("targets"));
+
+    let load = |clock: &dyn Clock| {
+        Repository::load(
+            &FilesystemTransport,
+            Settings::new(
+                File::open(base.join("metadata").join("1.root.json")).unwrap(),
+                datastore.as_ref(),
+                metadata_base_url,
+                targets_base_url,
+            )
+            .clock(clock),
+        )
+        .map(|_| ())
+    };
+
+    // The timestamp expired in 1999, so the repository is valid in 1998.
+    assert!(load(&FixedClock(Utc.ymd(1998, 1, 1).and_hms(0, 0, 0))).is_ok());
+
+    match load(&FixedClock(Utc.ymd(1997, 1, 1).and_hms(0, 0, 0))) {
+        Err(SystemTimeSteppedBackward { .. }) => {}
+        other => panic!("Expected SystemTimeSteppedBackward, got {:?}", other),
+    }
+
+    match load(&SystemClock) {
+        Err(ExpiredMetadata { role, .. }) => assert_eq!(role, RoleType::Timestamp),
+        other => panic!("Expected ExpiredMetadata, got {:?}", other),
+    }
+}
+
+/// A `Clock` whose time can be changed after a `Repository` is loaded.
+#[derive(Debug)]
+struct SettableClock(Cell<DateTime<Utc>>);
+
+impl Clock for SettableClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0.get()
+    }
+}
+
+/// Test that an `ExpirationPolicy` can accept recently expired metadata, reporting a warning, and
+/// that `read_target` stops trusting the metadata once the grace period is over.
+#[test]
+fn test_expiration_enforcement_policy() {
+    let base = test_data().join("expired-repository");
+    let metadata_base_url = &dir_url(base.join("metadata"));
+    let targets_base_url = &dir_url(base.join("targets"));
+    let expired = Utc.ymd(1999, 1, 1).and_hms(0, 0, 0);
+
+    let load = |datastore: &TempDir,
+                clock: &dyn Clock,
+                timestamp: ExpirationRule,
+                warnings: &RefCell<Vec<ExpirationWarning>>| {
+        let on_warning = |warning: &ExpirationWarning| warnings.borrow_mut().push(warning.clone());
+        let policy = ExpirationPolicy {
+            timestamp,
+            ..ExpirationPolicy::default()
+        };
+        Repository::load(
+            &FilesystemTransport,
+            Settings::new(
+                File::open(base.join("metadata").join("1.root.json")).unwrap(),
+                datastore.as_ref(),
+                metadata_base_url,
+                targets_base_url,
+            )
+            .expiration_enforcement(ExpirationEnforcement::Policy(policy))
+            .clock(clock)
+            .expiration_warning(&on_warning),
+        )
+        .map(|repo| repo.read_target("file.txt").map(|target| target.is_none()))
+    };
+
+    // Within the grace period, the repository loads and a warning is reported.
+    let datastore = TempDir::new().unwrap();
+    let warnings = RefCell::new(Vec::new());
+    let clock = FixedClock(expired + Duration::hours(12));
+    let grace = ExpirationRule::Grace(Duration::days(1));
+    assert!(load(&datastore, &clock, grace, &warnings).unwrap().unwrap());
+    assert_eq!(
+        warnings.into_inner(),
+        vec![ExpirationWarning {
+            role: RoleType::Timestamp,
+            delegated_role: None,
+            expires: expired,
+            now: expired + Duration::hours(12),
+        }]
+    );
+
+    // After the grace period, the repository fails to load.
+    let datastore = TempDir::new().unwrap();
+    let warnings = RefCell::new(Vec::new());
+    let clock = FixedClock(expired + Duration::days(2));
+    match load(&datastore, &clock, grace, &warnings) {
+        Err(ExpiredMetadata { role, .. }) => assert_eq!(role, RoleType::Timestamp),
+        other => panic!("Expected ExpiredMetadata, got {:?}", other.map(|_| ())),
+    }
+    assert!(warnings.into_inner().is_empty());
+
+    // A grace period too long to add to the expiration time is strict, rather than unlimited.
+    let datastore = TempDir::new().unwrap();
+    let warnings = RefCell::new(Vec::new());
+    let clock = FixedClock(expired + Duration::hours(12));
+    let grace = ExpirationRule::Grace(Duration::max_value());
+    match load(&datastore, &clock, grace, &warnings) {
+        Err(ExpiredMetadata { role, .. }) => assert_eq!(role, RoleType::Timestamp),
+        other => panic!("Expected ExpiredMetadata, got {:?}", other.map(|_| ())),
+    }
+    assert!(warnings.into_inner().is_empty());
+
+    // Warn-only accepts the expired timestamp no matter how old it is.
+    let datastore = TempDir::new().unwrap();
+    let warnings = RefCell::new(Vec::new());
+    assert!(
+        load(&datastore, &SystemClock, ExpirationRule::Warn, &warnings)
+            .unwrap()
+            .unwrap()
+    );
+    assert_eq!(warnings.into_inner().len(), 1);
+}
+
+/// Test that `read_target` enforces the grace period of an `ExpirationPolicy` after loading.
+#[test]
+fn test_expiration_enforcement_policy_read_target() {
+    let base = test_data().join("expired-repository");
+    let datastore = TempDir::new().unwrap();
+    let metadata_base_url = &dir_url(base.join("metadata"));
+    let targets_base_url = &dir_url(base.join("targets"));
+    let expired = Utc.ymd(1999, 1, 1).and_hms(0, 0, 0);
+    let clock = SettableClock(Cell::new(expired + Duration::hours(12)));
+
+    let repo = Repository::load(
+        &FilesystemTransport,
+        Settings::new(
+            File::open(base.join("metadata").join("1.root.json")).unwrap(),
+            datastore.as_ref(),
+            metadata_base_url,
+            targets_base_url,
+        )
+        .expiration_enforcement(ExpirationEnforcement::Policy(ExpirationPolicy {
+            timestamp: ExpirationRule::Grace(Duration::days(1)),
+            ..ExpirationPolicy::default()
+        }))
+        .clock(&clock),
+    )
+    .unwrap();
+    assert!(repo.read_target("file.txt").unwrap().is_none());
+
+    clock.0.set(expired + Duration::days(2));
+    match repo.read_target("file.txt") {
+        Err(ExpiredMetadata { role, .. }) => assert_eq!(role, RoleType::Timestamp),
+        Err(err) => panic!("Expected ExpiredMetadata, got {:?}", err),
+        Ok(_) => panic!("Expected ExpiredMetadata, got a target"),
+    }
+}
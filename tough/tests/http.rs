@@ -8,7 +8,7 @@ mod http_happy {
     use std::fs::File;
     use std::str::FromStr;
     use tempfile::TempDir;
-    use tough::{ExpirationEnforcement, HttpTransport, Limits, Repository, Settings};
+    use tough::{HttpTransport, Repository, Settings};
     use url::Url;
 
     /// Create a path in a mock HTTP server which serves a file from `tuf-reference-impl`.
@@ -41,14 +41,12 @@ mod http_happy {
         let transport = HttpTransport::default();
         let repo = Repository::load(
             &transport,
-            Settings {
-                root: File::open(repo_dir.join("metadata").join("1.root.json")).unwrap(),
-                datastore: datastore.as_ref(),
-                metadata_base_url: metadata_base_url.as_str(),
-                targets_base_url: targets_base_url.as_str(),
-                limits: Limits::default(),
-                expiration_enforcement: ExpirationEnforcement::Safe,
-            },
+            Settings::new(
+                File::open(repo_dir.join("metadata").join("1.root.json")).unwrap(),
+                datastore.as_ref(),
+                metadata_base_url.as_str(),
+                targets_base_url.as_str(),
+            ),
         )
         .unwrap();
 
@@ -90,9 +88,7 @@ mod http_integ {
     use std::path::PathBuf;
     use std::process::{Command, Stdio};
     use tempfile::TempDir;
-    use tough::{
-        ClientSettings, ExpirationEnforcement, HttpTransport, Limits, Repository, Settings,
-    };
+    use tough::{ClientSettings, HttpTransport, Repository, Settings};
 
     pub fn integ_dir() -> PathBuf {
         let mut p = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -154,14 +150,12 @@ mod http_integ {
             let tempdir = TempDir::new().unwrap();
             Repository::load(
                 &transport,
-                Settings {
-                    root: File::open(&root_path).unwrap(),
-                    datastore: tempdir.path(),
-                    metadata_base_url: "http://localhost:10103/metadata",
-                    targets_base_url: "http://localhost:10103/targets",
-                    limits: Limits::default(),
-                    expiration_enforcement: ExpirationEnforcement::Safe,
-                },
+                Settings::new(
+                    File::open(&root_path).unwrap(),
+                    tempdir.path(),
+                    "http://localhost:10103/metadata",
+                    "http://localhost:10103/targets",
+                ),
             )
             .unwrap();
             println!("{}:{} SUCCESSFULLY LOADED THE REPO {}", file!(), line!(), i,);
@@ -4,7 +4,7 @@
 use std::fs::File;
 use tempfile::TempDir;
 use test_utils::{dir_url, read_to_end, test_data};
-use tough::{ExpirationEnforcement, Limits, Repository, Settings};
+use tough::{Repository, Settings};
 
 mod test_utils;
 
@@ -22,14 +22,12 @@ fn test_tuf_reference_impl() {
 
     let repo = Repository::load(
         &tough::FilesystemTransport,
-        Settings {
-            root: File::open(base.join("metadata").join("1.root.json")).unwrap(),
-            datastore: datastore.as_ref(),
+        Settings::new(
+            File::open(base.join("metadata").join("1.root.json")).unwrap(),
+            datastore.as_ref(),
             metadata_base_url,
             targets_base_url,
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        ),
     )
     .unwrap();
 
@@ -0,0 +1,310 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use chrono::{Duration, Utc};
+use std::num::{NonZeroU64, NonZeroUsize};
+use std::path::Path;
+use tempfile::TempDir;
+use test_utils::{dir_url, key, load, read_to_end, root_path, targets_path};
+use tough::editor::prune::{prune, UnknownTargets};
+use tough::editor::signed::{PathExists, SignedRepository};
+use tough::editor::RepositoryEditor;
+use tough::FilesystemTransport;
+
+mod test_utils;
+
+/// Signs a repository at `version` listing the named targets, publishing a snapshot Merkle tree if
+/// `snapshot_merkle` is set, and copies the targets to `outdir/targets`. The metadata is left for
+/// the caller to write.
+fn publish(
+    outdir: &Path,
+    version: u64,
+    snapshot_merkle: bool,
+    targets: &[&str],
+) -> SignedRepository {
+    let expiration = Utc::now().checked_add_signed(Duration::days(21)).unwrap();
+    let version = NonZeroU64::new(version).unwrap();
+    let mut editor = RepositoryEditor::<FilesystemTransport>::new(root_path()).unwrap();
+    editor
+        .targets_expires(expiration)
+        .unwrap()
+        .targets_version(version)
+        .unwrap()
+        .snapshot_expires(expiration)
+        .snapshot_version(version)
+        .timestamp_expires(expiration)
+        .timestamp_version(version)
+        .snapshot_merkle(snapshot_merkle)
+        .add_target_paths(
+            targets
+                .iter()
+                .map(|name| targets_path().join(name))
+                .collect(),
+        )
+        .unwrap();
+    let signed_repo = editor.sign(&key("snakeoil.pem")).unwrap();
+    signed_repo
+        .copy_targets(targets_path(), outdir.join("targets"), PathExists::Skip)
+        .unwrap();
+    signed_repo
+}
+
+/// Returns the names of the files in `dir`, sorted.
+fn filenames(dir: &Path) -> Vec<String> {
+    let mut names = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+/// Returns the number of target files in `targets_dir` whose names end with `name`.
+fn count_targets(targets_dir: &Path, name: &str) -> usize {
+    filenames(targets_dir)
+        .iter()
+        .filter(|filename| filename.ends_with(name))
+        .count()
+}
+
+/// Test that pruning keeps the most recent snapshots, the targets roles they refer to, and the
+/// targets those list, and that a dry run removes nothing.
+#[test]
+fn prune_keeps_recent_versions() {
+    let outdir = TempDir::new().unwrap();
+    let metadata_dir = outdir.path().join("metadata");
+    let targets_dir = outdir.path().join("targets");
+    publish(outdir.path(), 1, false, &["file1.txt", "file2.txt"])
+        .write(&metadata_dir)
+        .unwrap();
+    publish(outdir.path(), 2, false, &["file1.txt", "file3.txt"])
+        .write(&metadata_dir)
+        .unwrap();
+    publish(outdir.path(), 3, false, &["file3.txt"])
+        .write(&metadata_dir)
+        .unwrap();
+    let keep = NonZeroUsize::new(2).unwrap();
+
+    let before = filenames(&metadata_dir);
+    let report = prune(
+        &metadata_dir,
+        &targets_dir,
+        keep,
+        UnknownTargets::Keep,
+        true,
+    )
+    .unwrap();
+    assert_eq!(
+        report.metadata,
+        vec![
+            metadata_dir.join("1.snapshot.json"),
+            metadata_dir.join("1.targets.json")
+        ]
+    );
+    assert_eq!(report.targets.len(), 1);
+    assert!(report.targets[0].to_str().unwrap().ends_with("file2.txt"));
+    assert_eq!(filenames(&metadata_dir), before);
+    assert_eq!(count_targets(&targets_dir, "file2.txt"), 1);
+
+    assert_eq!(
+        prune(
+            &metadata_dir,
+            &targets_dir,
+            keep,
+            UnknownTargets::Keep,
+            false
+        )
+        .unwrap(),
+        report
+    );
+    assert_eq!(
+        filenames(&metadata_dir),
+        vec![
+            "1.root.json",
+            "2.snapshot.json",
+            "2.targets.json",
+            "3.snapshot.json",
+            "3.targets.json",
+            "timestamp.json",
+        ]
+    );
+    assert_eq!(count_targets(&targets_dir, "file1.txt"), 1);
+    assert_eq!(count_targets(&targets_dir, "file2.txt"), 0);
+    assert_eq!(count_targets(&targets_dir, "file3.txt"), 1);
+    assert!(prune(
+        &metadata_dir,
+        &targets_dir,
+        keep,
+        UnknownTargets::Keep,
+        false
+    )
+    .unwrap()
+    .is_empty());
+
+    let metadata_base_url = dir_url(&metadata_dir);
+    let targets_base_url = dir_url(&targets_dir);
+    let datastore = TempDir::new().unwrap();
+    let repo = load(&metadata_base_url, &targets_base_url, datastore.path());
+    assert_eq!(
+        read_to_end(repo.read_target("file3.txt").unwrap().unwrap()),
+        std::fs::read(targets_path().join("file3.txt")).unwrap()
+    );
+}
+
+/// Test that a `SignedRepository` keeps its own metadata and targets when pruning, even before
+/// it's written.
+#[test]
+fn signed_repository_prune() {
+    let outdir = TempDir::new().unwrap();
+    let metadata_dir = outdir.path().join("metadata");
+    let targets_dir = outdir.path().join("targets");
+    publish(outdir.path(), 1, false, &["file1.txt"])
+        .write(&metadata_dir)
+        .unwrap();
+    publish(outdir.path(), 2, false, &["file2.txt"])
+        .write(&metadata_dir)
+        .unwrap();
+    let signed_repo = publish(outdir.path(), 3, false, &["file1.txt", "file3.txt"]);
+
+    let report = signed_repo
+        .prune(
+            &metadata_dir,
+            &targets_dir,
+            NonZeroUsize::new(1).unwrap(),
+            UnknownTargets::Keep,
+            false,
+        )
+        .unwrap();
+    assert_eq!(
+        report.metadata,
+        vec![
+            metadata_dir.join("1.snapshot.json"),
+            metadata_dir.join("1.targets.json")
+        ]
+    );
+    assert_eq!(count_targets(&targets_dir, "file1.txt"), 1);
+    assert_eq!(count_targets(&targets_dir, "file2.txt"), 1);
+    assert_eq!(count_targets(&targets_dir, "file3.txt"), 1);
+
+    signed_repo.write(&metadata_dir).unwrap();
+    let metadata_base_url = dir_url(&metadata_dir);
+    let targets_base_url = dir_url(&targets_dir);
+    let datastore = TempDir::new().unwrap();
+    let repo = load(&metadata_base_url, &targets_base_url, datastore.path());
+    assert_eq!(repo.targets().signed.version.get(), 3);
+    assert!(repo.read_target("file1.txt").unwrap().is_some());
+}
+
+/// Test that the snapshot the timestamp refers to is kept even if it isn't one of the most recent,
+/// along with the targets role it refers to.
+#[test]
+fn prune_keeps_current_snapshot() {
+    let outdir = TempDir::new().unwrap();
+    let metadata_dir = outdir.path().join("metadata");
+    let targets_dir = outdir.path().join("targets");
+    publish(outdir.path(), 1, false, &["file1.txt"])
+        .write(&metadata_dir)
+        .unwrap();
+    let timestamp = std::fs::read(metadata_dir.join("timestamp.json")).unwrap();
+    // Version 2 is written, but its timestamp isn't published yet.
+    publish(outdir.path(), 2, false, &["file2.txt"])
+        .write(&metadata_dir)
+        .unwrap();
+    std::fs::write(metadata_dir.join("timestamp.json"), timestamp).unwrap();
+
+    let report = prune(
+        &metadata_dir,
+        &targets_dir,
+        NonZeroUsize::new(1).unwrap(),
+        UnknownTargets::Keep,
+        false,
+    )
+    .unwrap();
+    assert!(report.is_empty());
+
+    let metadata_base_url = dir_url(&metadata_dir);
+    let targets_base_url = dir_url(&targets_dir);
+    let datastore = TempDir::new().unwrap();
+    let repo = load(&metadata_base_url, &targets_base_url, datastore.path());
+    assert_eq!(repo.targets().signed.version.get(), 1);
+    assert!(repo.read_target("file1.txt").unwrap().is_some());
+}
+
+/// Test that target files that no metadata lists are only removed when asked.
+#[test]
+fn prune_unknown_targets() {
+    let outdir = TempDir::new().unwrap();
+    let metadata_dir = outdir.path().join("metadata");
+    let targets_dir = outdir.path().join("targets");
+    publish(outdir.path(), 1, false, &["file1.txt"])
+        .write(&metadata_dir)
+        .unwrap();
+    let unknown = targets_dir.join("unknown.txt");
+    std::fs::write(&unknown, b"from another repository").unwrap();
+    let keep = NonZeroUsize::new(1).unwrap();
+
+    let report = prune(
+        &metadata_dir,
+        &targets_dir,
+        keep,
+        UnknownTargets::Keep,
+        false,
+    )
+    .unwrap();
+    assert!(report.is_empty());
+    assert_eq!(report.unknown_targets, vec![unknown.clone()]);
+    assert!(unknown.exists());
+
+    let report = prune(
+        &metadata_dir,
+        &targets_dir,
+        keep,
+        UnknownTargets::Remove,
+        false,
+    )
+    .unwrap();
+    assert_eq!(report.targets, vec![unknown.clone()]);
+    assert!(report.unknown_targets.is_empty());
+    assert!(!unknown.exists());
+    assert_eq!(count_targets(&targets_dir, "file1.txt"), 1);
+}
+
+/// Test that for a repository with a snapshot Merkle tree, the proofs of the most recent
+/// timestamps are kept, along with the targets roles they prove.
+#[test]
+fn prune_snapshot_merkle() {
+    let outdir = TempDir::new().unwrap();
+    let metadata_dir = outdir.path().join("metadata");
+    let targets_dir = outdir.path().join("targets");
+    for version in 1..=3 {
+        publish(outdir.path(), version, true, &["file1.txt"])
+            .write(&metadata_dir)
+            .unwrap();
+    }
+
+    let report = prune(
+        &metadata_dir,
+        &targets_dir,
+        NonZeroUsize::new(2).unwrap(),
+        UnknownTargets::Keep,
+        false,
+    )
+    .unwrap();
+    assert_eq!(
+        report.metadata,
+        vec![
+            metadata_dir.join("1.snapshot.json"),
+            metadata_dir.join("1.targets-snapshot.json"),
+            metadata_dir.join("1.targets.json"),
+        ]
+    );
+    assert!(metadata_dir.join("2.targets-snapshot.json").exists());
+    assert!(metadata_dir.join("2.targets.json").exists());
+
+    let metadata_base_url = dir_url(&metadata_dir);
+    let targets_base_url = dir_url(&targets_dir);
+    let datastore = TempDir::new().unwrap();
+    let repo = load(&metadata_base_url, &targets_base_url, datastore.path());
+    assert_eq!(repo.targets().signed.version.get(), 3);
+    assert!(repo.read_target("file1.txt").unwrap().is_some());
+}
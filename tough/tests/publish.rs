@@ -0,0 +1,193 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use chrono::{DateTime, Duration, Utc};
+use std::fs::File;
+use std::num::NonZeroU64;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use tempfile::TempDir;
+use test_utils::{dir_url, key, read_to_end, root_path, targets_path, test_data};
+use tough::editor::publish::Publisher;
+use tough::editor::signed::{PathExists, SignedRepository};
+use tough::editor::{RepositoryEditor, RootEditor};
+use tough::key_source::{KeySource, LocalKeySource};
+use tough::schema::RoleType;
+use tough::{FilesystemTransport, Repository, Settings};
+
+mod test_utils;
+
+/// Signs a repository of the reference targets whose timestamp expires at `timestamp_expires`.
+fn sign_repo(timestamp_expires: DateTime<Utc>) -> SignedRepository {
+    let expiration = Utc::now().checked_add_signed(Duration::days(21)).unwrap();
+    let version = NonZeroU64::new(1).unwrap();
+    let mut editor = RepositoryEditor::<FilesystemTransport>::new(root_path()).unwrap();
+    editor
+        .targets_expires(expiration)
+        .unwrap()
+        .targets_version(version)
+        .unwrap()
+        .snapshot_expires(expiration)
+        .snapshot_version(version)
+        .timestamp_expires(timestamp_expires)
+        .timestamp_version(version)
+        .add_target_paths(vec![
+            targets_path().join("file1.txt"),
+            targets_path().join("file2.txt"),
+        ])
+        .unwrap();
+    let keys: Vec<Box<dyn KeySource>> = vec![Box::new(LocalKeySource {
+        path: test_data().join("snakeoil.pem"),
+    })];
+    editor.sign(&keys).unwrap()
+}
+
+/// Returns the permission bits of the file at `path`.
+fn mode(path: &Path) -> u32 {
+    std::fs::metadata(path).unwrap().permissions().mode() & 0o777
+}
+
+/// Test that staged metadata is verified and promoted, along with its targets, and that the
+/// published files are readable by a client.
+#[test]
+fn publish_staged() {
+    let repo_dir = TempDir::new().unwrap();
+    let staging_dir = TempDir::new().unwrap();
+    let metadata_dir = repo_dir.path().join("metadata");
+    let targets_dir = repo_dir.path().join("targets");
+    let signed_repo = sign_repo(Utc::now().checked_add_signed(Duration::days(1)).unwrap());
+
+    Publisher::new(&signed_repo, &metadata_dir)
+        .copy_targets(targets_path(), &targets_dir, PathExists::Fail)
+        .stage(staging_dir.path())
+        .publish()
+        .unwrap();
+
+    // Published files get the mode a plain write would give them.
+    let plain_path = repo_dir.path().join("plain");
+    std::fs::write(&plain_path, b"").unwrap();
+    let plain_mode = mode(&plain_path);
+    for filename in &[
+        "1.root.json",
+        "1.targets.json",
+        "1.snapshot.json",
+        "timestamp.json",
+    ] {
+        assert!(staging_dir.path().join(filename).is_file());
+        assert_eq!(mode(&metadata_dir.join(filename)), plain_mode);
+    }
+
+    let metadata_base_url = dir_url(&metadata_dir);
+    let targets_base_url = dir_url(&targets_dir);
+    let datastore = TempDir::new().unwrap();
+    let repo = Repository::load(
+        &FilesystemTransport,
+        Settings::new(
+            File::open(root_path()).unwrap(),
+            datastore.path(),
+            &metadata_base_url,
+            &targets_base_url,
+        ),
+    )
+    .unwrap();
+    assert_eq!(
+        read_to_end(repo.read_target("file1.txt").unwrap().unwrap()),
+        &b"This is an example target file."[..]
+    );
+}
+
+/// Test that files left in the staging directory by an earlier attempt are not read in place of
+/// the published metadata.
+#[test]
+fn publish_staged_clears_staging_dir() {
+    let repo_dir = TempDir::new().unwrap();
+    let staging_dir = TempDir::new().unwrap();
+    let metadata_dir = repo_dir.path().join("metadata");
+    let expiration = Utc::now().checked_add_signed(Duration::days(21)).unwrap();
+    std::fs::write(staging_dir.path().join("2.root.json"), b"leftover").unwrap();
+
+    Publisher::new(&sign_repo(expiration), &metadata_dir)
+        .stage(staging_dir.path())
+        .publish()
+        .unwrap();
+    assert!(!staging_dir.path().join("2.root.json").exists());
+    assert!(metadata_dir.join("timestamp.json").is_file());
+}
+
+/// Test that metadata a client rejects is left in the staging directory, and not published.
+#[test]
+fn publish_staged_rejects_expired() {
+    let repo_dir = TempDir::new().unwrap();
+    let staging_dir = TempDir::new().unwrap();
+    let metadata_dir = repo_dir.path().join("metadata");
+    let signed_repo = sign_repo(Utc::now().checked_sub_signed(Duration::days(1)).unwrap());
+
+    assert!(Publisher::new(&signed_repo, &metadata_dir)
+        .stage(staging_dir.path())
+        .publish()
+        .is_err());
+    assert!(staging_dir.path().join("timestamp.json").is_file());
+    assert!(!metadata_dir.exists());
+}
+
+/// Test that staged metadata is verified starting from the published root, so a new root that
+/// isn't signed by the published root's keys isn't published.
+#[test]
+fn publish_staged_verifies_from_published_root() {
+    let repo_dir = TempDir::new().unwrap();
+    let metadata_dir = repo_dir.path().join("metadata");
+    let expiration = Utc::now().checked_add_signed(Duration::days(21)).unwrap();
+    Publisher::new(&sign_repo(expiration), &metadata_dir)
+        .stage(TempDir::new().unwrap().path())
+        .publish()
+        .unwrap();
+
+    // Replace the root with one that only trusts targetskey, and sign it only with targetskey.
+    let one = NonZeroU64::new(1).unwrap();
+    let two = NonZeroU64::new(2).unwrap();
+    let mut root_editor = RootEditor::new();
+    root_editor
+        .version(two)
+        .expires(expiration)
+        .add_key(
+            key("targetskey")[0].as_sign().unwrap().tuf_key(),
+            &[
+                RoleType::Root,
+                RoleType::Snapshot,
+                RoleType::Targets,
+                RoleType::Timestamp,
+            ],
+        )
+        .unwrap()
+        .threshold(RoleType::Root, one)
+        .threshold(RoleType::Snapshot, one)
+        .threshold(RoleType::Targets, one)
+        .threshold(RoleType::Timestamp, one);
+    let root_dir = TempDir::new().unwrap();
+    let new_root_path = root_dir.path().join("2.root.json");
+    std::fs::write(
+        &new_root_path,
+        root_editor.sign(&key("targetskey")).unwrap().buffer(),
+    )
+    .unwrap();
+
+    let mut editor = RepositoryEditor::<FilesystemTransport>::new(&new_root_path).unwrap();
+    editor
+        .targets_expires(expiration)
+        .unwrap()
+        .targets_version(two)
+        .unwrap()
+        .snapshot_expires(expiration)
+        .snapshot_version(two)
+        .timestamp_expires(expiration)
+        .timestamp_version(two);
+    let signed_repo = editor.sign(&key("targetskey")).unwrap();
+
+    let staging_dir = TempDir::new().unwrap();
+    assert!(Publisher::new(&signed_repo, &metadata_dir)
+        .stage(staging_dir.path())
+        .publish()
+        .is_err());
+    assert!(staging_dir.path().join("2.root.json").is_file());
+    assert!(!metadata_dir.join("2.root.json").exists());
+}
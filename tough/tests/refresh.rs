@@ -0,0 +1,142 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use chrono::{Duration, Utc};
+use std::num::NonZeroU64;
+use std::path::Path;
+use tempfile::TempDir;
+use test_utils::{dir_url, key, load, root_path, targets_path};
+use tough::editor::refresh::RefreshEditor;
+use tough::editor::RepositoryEditor;
+use tough::error::Error;
+use tough::FilesystemTransport;
+
+mod test_utils;
+
+/// Writes the metadata of a repository whose roles are all at version 1.
+fn create_repo(metadata_dir: &Path) {
+    let expiration = Utc::now().checked_add_signed(Duration::days(21)).unwrap();
+    let version = NonZeroU64::new(1).unwrap();
+    let mut editor = RepositoryEditor::<FilesystemTransport>::new(root_path()).unwrap();
+    editor
+        .targets_expires(expiration)
+        .unwrap()
+        .targets_version(version)
+        .unwrap()
+        .snapshot_expires(expiration)
+        .snapshot_version(version)
+        .timestamp_expires(expiration)
+        .timestamp_version(version)
+        .add_target_paths(vec![targets_path().join("file1.txt")])
+        .unwrap();
+    editor
+        .sign(&key("snakeoil.pem"))
+        .unwrap()
+        .write(metadata_dir)
+        .unwrap();
+}
+
+/// Test that the timestamp, and then the snapshot and timestamp, are re-signed without changing
+/// the other roles.
+#[test]
+fn refresh() {
+    let repo_dir = TempDir::new().unwrap();
+    let metadata_dir = repo_dir.path().join("metadata");
+    create_repo(&metadata_dir);
+    let metadata_base_url = dir_url(&metadata_dir);
+    let targets_base_url = dir_url(targets_path());
+    let later = Utc::now().checked_add_signed(Duration::days(42)).unwrap();
+
+    // Re-sign the timestamp only.
+    let datastore = TempDir::new().unwrap();
+    let repo = load(&metadata_base_url, &targets_base_url, datastore.path());
+    let mut editor = RefreshEditor::from_repo(&repo).unwrap();
+    editor.timestamp_expires(later);
+    editor
+        .sign_timestamp(&key("snakeoil.pem"))
+        .unwrap()
+        .write(&metadata_dir)
+        .unwrap();
+
+    let datastore = TempDir::new().unwrap();
+    let repo = load(&metadata_base_url, &targets_base_url, datastore.path());
+    assert_eq!(repo.timestamp().signed.version.get(), 2);
+    assert_eq!(repo.timestamp().signed.expires, later);
+    assert_eq!(repo.snapshot().unwrap().signed.version.get(), 1);
+    assert_eq!(repo.targets().signed.version.get(), 1);
+
+    // Re-sign the snapshot and timestamp.
+    let mut editor = RefreshEditor::from_repo(&repo).unwrap();
+    editor
+        .snapshot_version(NonZeroU64::new(5).unwrap())
+        .snapshot_expires(later)
+        .timestamp_expires(later);
+    editor
+        .sign_snapshot_and_timestamp(&key("snakeoil.pem"))
+        .unwrap()
+        .write(&metadata_dir)
+        .unwrap();
+
+    let datastore = TempDir::new().unwrap();
+    let repo = load(&metadata_base_url, &targets_base_url, datastore.path());
+    assert_eq!(repo.timestamp().signed.version.get(), 3);
+    assert_eq!(repo.snapshot().unwrap().signed.version.get(), 5);
+    assert_eq!(repo.snapshot().unwrap().signed.expires, later);
+    assert_eq!(repo.targets().signed.version.get(), 1);
+    assert!(repo.find_target("file1.txt").is_some());
+}
+
+/// Test that re-signing requires an expiration, the timestamp key, and a version greater than the
+/// current version.
+#[test]
+fn refresh_errors() {
+    let repo_dir = TempDir::new().unwrap();
+    let metadata_dir = repo_dir.path().join("metadata");
+    create_repo(&metadata_dir);
+    let metadata_base_url = dir_url(&metadata_dir);
+    let targets_base_url = dir_url(targets_path());
+    let datastore = TempDir::new().unwrap();
+    let repo = load(&metadata_base_url, &targets_base_url, datastore.path());
+
+    let mut editor = RefreshEditor::from_repo(&repo).unwrap();
+    assert!(matches!(
+        editor.sign_timestamp(&key("snakeoil.pem")),
+        Err(Error::Missing { .. })
+    ));
+    editor.timestamp_expires(Utc::now().checked_add_signed(Duration::days(42)).unwrap());
+    assert!(editor.sign_timestamp(&key("targetskey")).is_err());
+
+    editor.timestamp_version(NonZeroU64::new(1).unwrap());
+    assert!(matches!(
+        editor.sign_timestamp(&key("snakeoil.pem")),
+        Err(Error::VersionNotIncreased {
+            version: 1,
+            current: 1,
+            ..
+        })
+    ));
+    editor
+        .timestamp_version(NonZeroU64::new(2).unwrap())
+        .snapshot_version(NonZeroU64::new(1).unwrap())
+        .snapshot_expires(Utc::now().checked_add_signed(Duration::days(42)).unwrap());
+    assert!(matches!(
+        editor.sign_snapshot_and_timestamp(&key("snakeoil.pem")),
+        Err(Error::VersionNotIncreased { .. })
+    ));
+
+    // Once the timestamp is at the largest version, it can't be refreshed again.
+    editor.timestamp_version(NonZeroU64::new(u64::MAX).unwrap());
+    editor
+        .sign_timestamp(&key("snakeoil.pem"))
+        .unwrap()
+        .write(&metadata_dir)
+        .unwrap();
+    let datastore = TempDir::new().unwrap();
+    let repo = load(&metadata_base_url, &targets_base_url, datastore.path());
+    let mut editor = RefreshEditor::from_repo(&repo).unwrap();
+    editor.timestamp_expires(Utc::now().checked_add_signed(Duration::days(42)).unwrap());
+    assert!(matches!(
+        editor.sign_timestamp(&key("snakeoil.pem")),
+        Err(Error::VersionOverflow { .. })
+    ));
+}
@@ -4,9 +4,10 @@
 use std::fs::File;
 use std::io::Read;
 use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
 use tempfile::TempDir;
 use test_utils::{dir_url, test_data};
-use tough::{ExpirationEnforcement, FilesystemTransport, Limits, Repository, Settings};
+use tough::{CacheReport, FilesystemTransport, Repository, Settings};
 
 mod test_utils;
 
@@ -36,14 +37,12 @@ impl RepoPaths {
 fn load_tuf_reference_impl<'a>(paths: &'a mut RepoPaths) -> Repository<'a, FilesystemTransport> {
     Repository::load(
         &tough::FilesystemTransport,
-        Settings {
-            root: &mut paths.root(),
-            datastore: paths.datastore.as_ref(),
-            metadata_base_url: paths.metadata_base_url.as_str(),
-            targets_base_url: paths.targets_base_url.as_str(),
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            &mut paths.root(),
+            paths.datastore.as_ref(),
+            paths.metadata_base_url.as_str(),
+            paths.targets_base_url.as_str(),
+        ),
     )
     .unwrap()
 }
@@ -73,14 +72,12 @@ fn test_repo_cache_all_targets() {
     let targets_base_url = dir_url(&targets_destination);
     let copied_repo = Repository::load(
         &tough::FilesystemTransport,
-        Settings {
-            root: repo_paths.root(),
-            datastore: datastore.as_ref(),
-            metadata_base_url: metadata_base_url.as_str(),
-            targets_base_url: targets_base_url.as_str(),
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            repo_paths.root(),
+            datastore.as_ref(),
+            metadata_base_url.as_str(),
+            targets_base_url.as_str(),
+        ),
     )
     .unwrap();
 
@@ -130,14 +127,12 @@ fn test_repo_cache_list_of_two_targets() {
     let targets_base_url = dir_url(&targets_destination);
     let copied_repo = Repository::load(
         &tough::FilesystemTransport,
-        Settings {
-            root: repo_paths.root(),
-            datastore: datastore.as_ref(),
-            metadata_base_url: metadata_base_url.as_str(),
-            targets_base_url: targets_base_url.as_str(),
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            repo_paths.root(),
+            datastore.as_ref(),
+            metadata_base_url.as_str(),
+            targets_base_url.as_str(),
+        ),
     )
     .unwrap();
 
@@ -187,14 +182,12 @@ fn test_repo_cache_some() {
     let targets_base_url = dir_url(&targets_destination);
     let copied_repo = Repository::load(
         &tough::FilesystemTransport,
-        Settings {
-            root: repo_paths.root(),
-            datastore: datastore.as_ref(),
-            metadata_base_url: metadata_base_url.as_str(),
-            targets_base_url: targets_base_url.as_str(),
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            repo_paths.root(),
+            datastore.as_ref(),
+            metadata_base_url.as_str(),
+            targets_base_url.as_str(),
+        ),
     )
     .unwrap();
 
@@ -211,3 +204,124 @@ fn test_repo_cache_some() {
         .unwrap();
     assert_eq!(39, file_size);
 }
+
+/// Test that `repo.cache()` replaces a longer, stale target rather than leaving trailing bytes.
+#[test]
+fn test_repo_cache_replaces_stale_target() {
+    let mut repo_paths = RepoPaths::new();
+    let repo = load_tuf_reference_impl(&mut repo_paths);
+
+    let destination = TempDir::new().unwrap();
+    let metadata_destination = destination.as_ref().join("metadata");
+    let targets_destination = destination.as_ref().join("targets");
+    std::fs::create_dir_all(&targets_destination).unwrap();
+    std::fs::write(
+        targets_destination.join("file1.txt"),
+        "This is a much longer stale version of the example target file.",
+    )
+    .unwrap();
+
+    repo.cache(
+        &metadata_destination,
+        &targets_destination,
+        None::<&[&str]>,
+        true,
+    )
+    .unwrap();
+
+    assert_eq!(
+        std::fs::read(targets_destination.join("file1.txt")).unwrap(),
+        &b"This is an example target file."[..]
+    );
+}
+
+/// Test that `repo.cache_incremental()` keeps targets that are already correct and replaces
+/// targets that are not.
+#[test]
+fn test_repo_cache_incremental() {
+    let mut repo_paths = RepoPaths::new();
+    let repo = load_tuf_reference_impl(&mut repo_paths);
+
+    let destination = TempDir::new().unwrap();
+    let metadata_destination = destination.as_ref().join("metadata");
+    let targets_destination = destination.as_ref().join("targets");
+    repo.cache(
+        &metadata_destination,
+        &targets_destination,
+        None::<&[&str]>,
+        true,
+    )
+    .unwrap();
+
+    // Corrupt one target, and backdate a good one so we can tell whether it's rewritten.
+    std::fs::write(targets_destination.join("file1.txt"), "corrupt").unwrap();
+    let file2_path = targets_destination.join("file2.txt");
+    let backdated = SystemTime::UNIX_EPOCH + Duration::from_secs(86400);
+    std::fs::OpenOptions::new()
+        .write(true)
+        .open(&file2_path)
+        .unwrap()
+        .set_modified(backdated)
+        .unwrap();
+
+    repo.cache_incremental(
+        &metadata_destination,
+        &targets_destination,
+        None::<&[&str]>,
+        true,
+    )
+    .unwrap();
+
+    // The good target was left alone; the corrupt one was downloaded again.
+    assert_eq!(
+        std::fs::metadata(&file2_path).unwrap().modified().unwrap(),
+        backdated
+    );
+    assert_eq!(
+        std::fs::read(targets_destination.join("file1.txt")).unwrap(),
+        &b"This is an example target file."[..]
+    );
+    assert!(repo
+        .verify_cache(&metadata_destination, &targets_destination)
+        .unwrap()
+        .is_clean());
+}
+
+/// Test that `repo.verify_cache()` reports missing, corrupt and extraneous files.
+#[test]
+fn test_repo_verify_cache() {
+    let mut repo_paths = RepoPaths::new();
+    let repo = load_tuf_reference_impl(&mut repo_paths);
+
+    let destination = TempDir::new().unwrap();
+    let metadata_destination = destination.as_ref().join("metadata");
+    let targets_destination = destination.as_ref().join("targets");
+    repo.cache(
+        &metadata_destination,
+        &targets_destination,
+        None::<&[&str]>,
+        true,
+    )
+    .unwrap();
+    assert_eq!(
+        repo.verify_cache(&metadata_destination, &targets_destination)
+            .unwrap(),
+        CacheReport::default()
+    );
+
+    std::fs::remove_file(targets_destination.join("file1.txt")).unwrap();
+    std::fs::write(targets_destination.join("file2.txt"), "corrupt").unwrap();
+    std::fs::write(targets_destination.join("stray.txt"), "stray").unwrap();
+    std::fs::write(metadata_destination.join("snapshot.json"), "{}").unwrap();
+
+    let report = repo
+        .verify_cache(&metadata_destination, &targets_destination)
+        .unwrap();
+    assert!(!report.is_clean());
+    assert_eq!(report.missing, vec![PathBuf::from("file1.txt")]);
+    assert_eq!(
+        report.corrupt,
+        vec![PathBuf::from("snapshot.json"), PathBuf::from("file2.txt")]
+    );
+    assert_eq!(report.extraneous, vec![PathBuf::from("stray.txt")]);
+}
@@ -17,7 +17,7 @@ use tough::schema::decoded::Decoded;
 use tough::schema::decoded::Hex;
 use tough::schema::key::Key;
 use tough::schema::PathSet;
-use tough::{ExpirationEnforcement, FilesystemTransport, Limits, Repository, Settings};
+use tough::{FilesystemTransport, Repository, Settings};
 
 mod test_utils;
 
@@ -69,14 +69,12 @@ fn targets_path() -> PathBuf {
 fn load_tuf_reference_impl<'a>(paths: &'a mut RepoPaths) -> Repository<'a, FilesystemTransport> {
     Repository::load(
         &tough::FilesystemTransport,
-        Settings {
-            root: &mut paths.root(),
-            datastore: paths.datastore.as_ref(),
-            metadata_base_url: paths.metadata_base_url.as_str(),
-            targets_base_url: paths.targets_base_url.as_str(),
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            &mut paths.root(),
+            paths.datastore.as_ref(),
+            paths.metadata_base_url.as_str(),
+            paths.targets_base_url.as_str(),
+        ),
     )
     .unwrap()
 }
@@ -155,13 +153,13 @@ fn create_sign_write_reload_repo() {
         .add_target_paths(target_list)
         .unwrap();
 
-    let targets_key: &[std::boxed::Box<(dyn tough::key_source::KeySource + 'static)>] =
+    let targets_key: &[std::boxed::Box<dyn tough::key_source::KeySource + 'static>] =
         &[Box::new(LocalKeySource { path: key_path() })];
-    let role1_key: &[std::boxed::Box<(dyn tough::key_source::KeySource + 'static)>] =
+    let role1_key: &[std::boxed::Box<dyn tough::key_source::KeySource + 'static>] =
         &[Box::new(LocalKeySource {
             path: targets_key_path(),
         })];
-    let role2_key: &[std::boxed::Box<(dyn tough::key_source::KeySource + 'static)>] =
+    let role2_key: &[std::boxed::Box<dyn tough::key_source::KeySource + 'static>] =
         &[Box::new(LocalKeySource {
             path: targets_key_path1(),
         })];
@@ -240,30 +238,62 @@ fn create_sign_write_reload_repo() {
     let targets_base_url = dir_url(&targets_destination);
     let _new_repo = Repository::load(
         &FilesystemTransport,
-        Settings {
-            root: File::open(&root).unwrap(),
-            datastore: &create_dir.path(),
-            metadata_base_url: metadata_base_url.as_str(),
-            targets_base_url: targets_base_url.as_str(),
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            File::open(&root).unwrap(),
+            &create_dir.path(),
+            metadata_base_url.as_str(),
+            targets_base_url.as_str(),
+        ),
     )
     .unwrap();
 }
 
+// Create a repo that uses a newer 1.x spec version, then load and edit it
+#[test]
+fn create_reload_edit_repo_with_spec_version() {
+    let root = root_path();
+    let create_dir = TempDir::new().unwrap();
+    let keys: &[Box<dyn KeySource>] = &[Box::new(LocalKeySource { path: key_path() })];
+
+    let mut editor = test_repo_editor();
+    assert!(editor.spec_version("2.0.0").is_err());
+    editor.spec_version("1.0.31").unwrap();
+    let signed_repo = editor.sign(keys).unwrap();
+    let metadata_destination = create_dir.path().join("metadata");
+    let targets_destination = create_dir.path().join("targets");
+    signed_repo.write(&metadata_destination).unwrap();
+
+    let metadata_base_url = dir_url(&metadata_destination);
+    let targets_base_url = dir_url(&targets_destination);
+    let repo = Repository::load(
+        &FilesystemTransport,
+        Settings::new(
+            File::open(&root).unwrap(),
+            create_dir.path(),
+            metadata_base_url.as_str(),
+            targets_base_url.as_str(),
+        ),
+    )
+    .unwrap();
+    assert_eq!(repo.targets().signed.spec_version, "1.0.31");
+    assert_eq!(repo.snapshot().unwrap().signed.spec_version, "1.0.31");
+    assert_eq!(repo.timestamp().signed.spec_version, "1.0.31");
+
+    assert!(RepositoryEditor::from_repo(&root, repo).is_ok());
+}
+
 #[test]
 /// Delegates role from Targets to A and then A to B
 fn create_role_flow() {
     let editor = test_repo_editor();
 
-    let targets_key: &[std::boxed::Box<(dyn tough::key_source::KeySource + 'static)>] =
+    let targets_key: &[std::boxed::Box<dyn tough::key_source::KeySource + 'static>] =
         &[Box::new(LocalKeySource { path: key_path() })];
-    let role1_key: &[std::boxed::Box<(dyn tough::key_source::KeySource + 'static)>] =
+    let role1_key: &[std::boxed::Box<dyn tough::key_source::KeySource + 'static>] =
         &[Box::new(LocalKeySource {
             path: targets_key_path(),
         })];
-    let role2_key: &[std::boxed::Box<(dyn tough::key_source::KeySource + 'static)>] =
+    let role2_key: &[std::boxed::Box<dyn tough::key_source::KeySource + 'static>] =
         &[Box::new(LocalKeySource {
             path: targets_key_path1(),
         })];
@@ -294,14 +324,12 @@ fn create_role_flow() {
     let targets_base_url = dir_url(&targets_destination);
     let new_repo = Repository::load(
         &FilesystemTransport,
-        Settings {
-            root: File::open(&root).unwrap(),
-            datastore: &datastore.path(),
-            metadata_base_url: metadata_base_url.as_str(),
-            targets_base_url: targets_base_url.as_str(),
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            File::open(&root).unwrap(),
+            &datastore.path(),
+            metadata_base_url.as_str(),
+            targets_base_url.as_str(),
+        ),
     )
     .unwrap();
 
@@ -353,14 +381,12 @@ fn create_role_flow() {
     let targets_base_url = dir_url(&targets_destination);
     let new_repo = Repository::load(
         &FilesystemTransport,
-        Settings {
-            root: File::open(&root).unwrap(),
-            datastore: &datastore.path(),
-            metadata_base_url: metadata_base_url.as_str(),
-            targets_base_url: targets_base_url.as_str(),
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            File::open(&root).unwrap(),
+            &datastore.path(),
+            metadata_base_url.as_str(),
+            targets_base_url.as_str(),
+        ),
     )
     .unwrap();
     new_repo.delegated_role("A").unwrap();
@@ -385,14 +411,12 @@ fn create_role_flow() {
     let targets_base_url = dir_url(&targets_destination);
     let new_repo = Repository::load(
         &FilesystemTransport,
-        Settings {
-            root: File::open(&root).unwrap(),
-            datastore: &datastore.path(),
-            metadata_base_url: metadata_base_url.as_str(),
-            targets_base_url: targets_base_url.as_str(),
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            File::open(&root).unwrap(),
+            &datastore.path(),
+            metadata_base_url.as_str(),
+            targets_base_url.as_str(),
+        ),
     )
     .unwrap();
 
@@ -432,14 +456,12 @@ fn create_role_flow() {
     let targets_base_url = dir_url(&targets_destination);
     let new_repo = Repository::load(
         &FilesystemTransport,
-        Settings {
-            root: File::open(&root).unwrap(),
-            datastore: &datastore.path(),
-            metadata_base_url: metadata_base_url.as_str(),
-            targets_base_url: targets_base_url.as_str(),
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            File::open(&root).unwrap(),
+            &datastore.path(),
+            metadata_base_url.as_str(),
+            targets_base_url.as_str(),
+        ),
     )
     .unwrap();
 
@@ -475,14 +497,12 @@ fn create_role_flow() {
     let targets_base_url = dir_url(&targets_destination);
     let new_repo = Repository::load(
         &FilesystemTransport,
-        Settings {
-            root: File::open(&root).unwrap(),
-            datastore: &datastore.path(),
-            metadata_base_url: metadata_base_url.as_str(),
-            targets_base_url: targets_base_url.as_str(),
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            File::open(&root).unwrap(),
+            &datastore.path(),
+            metadata_base_url.as_str(),
+            targets_base_url.as_str(),
+        ),
     )
     .unwrap();
 
@@ -497,13 +517,13 @@ fn update_targets_flow() {
     // The beginning of this creates a repo with Target -> A ('*.txt') -> B ('file?.txt')
     let editor = test_repo_editor();
 
-    let targets_key: &[std::boxed::Box<(dyn tough::key_source::KeySource + 'static)>] =
+    let targets_key: &[std::boxed::Box<dyn tough::key_source::KeySource + 'static>] =
         &[Box::new(LocalKeySource { path: key_path() })];
-    let role1_key: &[std::boxed::Box<(dyn tough::key_source::KeySource + 'static)>] =
+    let role1_key: &[std::boxed::Box<dyn tough::key_source::KeySource + 'static>] =
         &[Box::new(LocalKeySource {
             path: targets_key_path(),
         })];
-    let role2_key: &[std::boxed::Box<(dyn tough::key_source::KeySource + 'static)>] =
+    let role2_key: &[std::boxed::Box<dyn tough::key_source::KeySource + 'static>] =
         &[Box::new(LocalKeySource {
             path: targets_key_path1(),
         })];
@@ -534,14 +554,12 @@ fn update_targets_flow() {
     let targets_base_url = dir_url(&targets_destination);
     let new_repo = Repository::load(
         &FilesystemTransport,
-        Settings {
-            root: File::open(&root).unwrap(),
-            datastore: &datastore.path(),
-            metadata_base_url: metadata_base_url.as_str(),
-            targets_base_url: targets_base_url.as_str(),
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            File::open(&root).unwrap(),
+            &datastore.path(),
+            metadata_base_url.as_str(),
+            targets_base_url.as_str(),
+        ),
     )
     .unwrap();
 
@@ -593,14 +611,12 @@ fn update_targets_flow() {
     let targets_base_url = dir_url(&targets_destination);
     let new_repo = Repository::load(
         &FilesystemTransport,
-        Settings {
-            root: File::open(&root).unwrap(),
-            datastore: &datastore.path(),
-            metadata_base_url: metadata_base_url.as_str(),
-            targets_base_url: targets_base_url.as_str(),
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            File::open(&root).unwrap(),
+            &datastore.path(),
+            metadata_base_url.as_str(),
+            targets_base_url.as_str(),
+        ),
     )
     .unwrap();
     new_repo.delegated_role("A").unwrap();
@@ -625,14 +641,12 @@ fn update_targets_flow() {
     let targets_base_url = dir_url(&targets_destination);
     let new_repo = Repository::load(
         &FilesystemTransport,
-        Settings {
-            root: File::open(&root).unwrap(),
-            datastore: &datastore.path(),
-            metadata_base_url: metadata_base_url.as_str(),
-            targets_base_url: targets_base_url.as_str(),
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            File::open(&root).unwrap(),
+            &datastore.path(),
+            metadata_base_url.as_str(),
+            targets_base_url.as_str(),
+        ),
     )
     .unwrap();
 
@@ -672,14 +686,12 @@ fn update_targets_flow() {
     let targets_base_url = dir_url(&targets_destination);
     let new_repo = Repository::load(
         &FilesystemTransport,
-        Settings {
-            root: File::open(&root).unwrap(),
-            datastore: &datastore.path(),
-            metadata_base_url: metadata_base_url.as_str(),
-            targets_base_url: targets_base_url.as_str(),
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            File::open(&root).unwrap(),
+            &datastore.path(),
+            metadata_base_url.as_str(),
+            targets_base_url.as_str(),
+        ),
     )
     .unwrap();
 
@@ -715,14 +727,12 @@ fn update_targets_flow() {
     let targets_base_url = dir_url(&targets_destination);
     let new_repo = Repository::load(
         &FilesystemTransport,
-        Settings {
-            root: File::open(&root).unwrap(),
-            datastore: &datastore.path(),
-            metadata_base_url: metadata_base_url.as_str(),
-            targets_base_url: targets_base_url.as_str(),
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            File::open(&root).unwrap(),
+            &datastore.path(),
+            metadata_base_url.as_str(),
+            targets_base_url.as_str(),
+        ),
     )
     .unwrap();
 
@@ -766,14 +776,12 @@ fn update_targets_flow() {
     let targets_base_url = dir_url(&targets_destination);
     let new_repo = Repository::load(
         &FilesystemTransport,
-        Settings {
-            root: File::open(&root).unwrap(),
-            datastore: &datastore.path(),
-            metadata_base_url: metadata_base_url.as_str(),
-            targets_base_url: targets_base_url.as_str(),
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            File::open(&root).unwrap(),
+            &datastore.path(),
+            metadata_base_url.as_str(),
+            targets_base_url.as_str(),
+        ),
     )
     .unwrap();
 
@@ -811,14 +819,12 @@ fn update_targets_flow() {
     let targets_base_url = dir_url(&targets_destination);
     let new_repo = Repository::load(
         &FilesystemTransport,
-        Settings {
-            root: File::open(&root).unwrap(),
-            datastore: &datastore.path(),
-            metadata_base_url: metadata_base_url.as_str(),
-            targets_base_url: targets_base_url.as_str(),
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            File::open(&root).unwrap(),
+            &datastore.path(),
+            metadata_base_url.as_str(),
+            targets_base_url.as_str(),
+        ),
     )
     .unwrap();
 
@@ -867,14 +873,12 @@ fn update_targets_flow() {
     let targets_base_url = dir_url(&targets_destination);
     let new_repo = Repository::load(
         &FilesystemTransport,
-        Settings {
-            root: File::open(&root).unwrap(),
-            datastore: &datastore.path(),
-            metadata_base_url: metadata_base_url.as_str(),
-            targets_base_url: targets_base_url.as_str(),
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            File::open(&root).unwrap(),
+            &datastore.path(),
+            metadata_base_url.as_str(),
+            targets_base_url.as_str(),
+        ),
     )
     .unwrap();
 
@@ -913,14 +917,12 @@ fn update_targets_flow() {
     let targets_base_url = dir_url(&targets_destination);
     let new_repo = Repository::load(
         &FilesystemTransport,
-        Settings {
-            root: File::open(&root).unwrap(),
-            datastore: &datastore.path(),
-            metadata_base_url: metadata_base_url.as_str(),
-            targets_base_url: targets_base_url.as_str(),
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            File::open(&root).unwrap(),
+            &datastore.path(),
+            metadata_base_url.as_str(),
+            targets_base_url.as_str(),
+        ),
     )
     .unwrap();
 
@@ -0,0 +1,124 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use chrono::{TimeZone, Utc};
+use ring::digest::{digest, SHA256};
+use std::fs::File;
+use std::path::Path;
+use tempfile::TempDir;
+use test_utils::{dir_url, test_data};
+use tough::error::Error;
+use tough::schema::RoleType;
+use tough::{read_history, FilesystemTransport, FixedClock, HistoryEntry, Repository, Settings};
+
+mod test_utils;
+
+/// Loads the reference repository with history enabled, and returns the history log.
+fn load_history(datastore: &Path, clock: &FixedClock) -> Vec<HistoryEntry> {
+    let base = test_data().join("tuf-reference-impl");
+    Repository::load(
+        &FilesystemTransport,
+        Settings::new(
+            File::open(base.join("metadata").join("1.root.json")).unwrap(),
+            datastore,
+            &dir_url(base.join("metadata")),
+            &dir_url(base.join("targets")),
+        )
+        .clock(clock)
+        .history(true),
+    )
+    .unwrap()
+    .history()
+    .unwrap()
+}
+
+/// Test that newly trusted metadata is recorded once in the history log, and that changes to the
+/// log are detected.
+#[test]
+fn test_repo_history() {
+    let datastore = TempDir::new().unwrap();
+    let first = FixedClock(Utc.ymd(2020, 1, 1).and_hms(0, 0, 0));
+    let history = load_history(datastore.path(), &first);
+    assert_eq!(
+        history
+            .iter()
+            .map(|entry| (entry.role, entry.delegated_role.as_deref()))
+            .collect::<Vec<_>>(),
+        vec![
+            (RoleType::Root, None),
+            (RoleType::Timestamp, None),
+            (RoleType::Snapshot, None),
+            (RoleType::Targets, None),
+            (RoleType::Targets, Some("role1")),
+            (RoleType::Targets, Some("role2")),
+        ]
+    );
+    assert!(history.iter().all(|entry| entry.accepted_at == first.0));
+    assert!(history[0].previous.is_none());
+    let timestamp = std::fs::read(
+        test_data()
+            .join("tuf-reference-impl")
+            .join("metadata")
+            .join("timestamp.json"),
+    )
+    .unwrap();
+    assert_eq!(
+        history[1].sha256,
+        digest(&SHA256, &timestamp).as_ref().to_vec()
+    );
+
+    // Loading the same metadata again records nothing new.
+    let second = FixedClock(Utc.ymd(2020, 1, 2).and_hms(0, 0, 0));
+    assert_eq!(load_history(datastore.path(), &second), history);
+    assert_eq!(read_history(datastore.path()).unwrap(), history);
+
+    // Without the head of the log, it is rebuilt from the log, and still nothing new is recorded.
+    std::fs::remove_file(datastore.path().join("history-head.json")).unwrap();
+    assert_eq!(load_history(datastore.path(), &second), history);
+
+    // Rewrite the timestamp entry; the entry after it no longer follows from it.
+    let path = datastore.path().join("history.jsonl");
+    let log = std::fs::read_to_string(&path).unwrap();
+    let mut lines: Vec<String> = log.lines().map(str::to_owned).collect();
+    lines[1] = lines[1].replace("\"version\":1", "\"version\":2");
+    std::fs::write(&path, lines.join("\n") + "\n").unwrap();
+    match read_history(datastore.path()) {
+        Err(Error::HistoryChain { line, .. }) => assert_eq!(line, 3),
+        other => panic!("expected a broken history chain, got {:?}", other),
+    }
+}
+
+/// Test that loading a repository whose root was rotated records the root chain once, even
+/// though every root in the chain is verified on each load.
+#[test]
+fn test_rotated_root_history() {
+    let base = test_data().join("rotated-root");
+    let datastore = TempDir::new().unwrap();
+    let load = || {
+        Repository::load(
+            &FilesystemTransport,
+            Settings::new(
+                File::open(base.join("1.root.json")).unwrap(),
+                datastore.path(),
+                &dir_url(&base),
+                &dir_url(base.join("targets")),
+            )
+            .history(true),
+        )
+        .unwrap()
+        .history()
+        .unwrap()
+    };
+
+    let history = load();
+    assert_eq!(
+        history
+            .iter()
+            .filter(|entry| entry.role == RoleType::Root)
+            .map(|entry| entry.version.get())
+            .collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+    assert_eq!(load(), history);
+    assert_eq!(load(), history);
+}
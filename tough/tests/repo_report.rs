@@ -0,0 +1,73 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use chrono::{TimeZone, Utc};
+use std::fs::File;
+use tempfile::TempDir;
+use test_utils::{dir_url, test_data};
+use tough::schema::RoleType;
+use tough::{FilesystemTransport, FixedClock, Repository, Settings};
+
+mod test_utils;
+
+/// Test that a report lists every role, including delegated roles, with the keys that signed it.
+#[test]
+fn test_repo_report() {
+    let base = test_data().join("tuf-reference-impl");
+    let datastore = TempDir::new().unwrap();
+    let metadata_base_url = &dir_url(base.join("metadata"));
+    let targets_base_url = &dir_url(base.join("targets"));
+    let now = Utc.ymd(2020, 1, 1).and_hms(0, 0, 0);
+    let clock = FixedClock(now);
+    let repo = Repository::load(
+        &FilesystemTransport,
+        Settings::new(
+            File::open(base.join("metadata").join("1.root.json")).unwrap(),
+            datastore.as_ref(),
+            metadata_base_url,
+            targets_base_url,
+        )
+        .clock(&clock),
+    )
+    .unwrap();
+
+    let report = repo.report().unwrap();
+    assert_eq!(report.generated_at, now);
+    assert!(!report.consistent_snapshot);
+    assert_eq!(
+        report
+            .roles
+            .iter()
+            .map(|role| (role.name.as_str(), role.delegated_by.as_deref()))
+            .collect::<Vec<_>>(),
+        vec![
+            ("root", None),
+            ("timestamp", None),
+            ("snapshot", None),
+            ("targets", None),
+            ("role1", Some("targets")),
+            ("role2", Some("role1")),
+        ]
+    );
+
+    for role in &report.roles {
+        let signed = role.keys.iter().filter(|key| key.signed).count() as u64;
+        assert!(signed >= role.threshold.get(), "{}", role.name);
+        assert!(role.keys.iter().all(|key| key.keytype.is_some()));
+    }
+
+    let targets = &report.roles[3];
+    assert_eq!(targets.role_type, RoleType::Targets);
+    assert_eq!(targets.version.get(), 1);
+    assert_eq!(targets.expires, Utc.ymd(2030, 1, 1).and_hms(0, 0, 0));
+    assert_eq!(
+        targets.seconds_remaining,
+        (targets.expires - now).num_seconds()
+    );
+    assert_eq!(targets.keys[0].keytype.as_deref(), Some("ed25519"));
+
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["roles"][4]["name"], "role1");
+    assert_eq!(json["roles"][4]["delegated_by"], "targets");
+    assert_eq!(json["roles"][0]["role_type"], "root");
+}
@@ -0,0 +1,145 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use chrono::{Duration, Utc};
+use serde_json::json;
+use std::fs::File;
+use std::num::NonZeroU64;
+use tempfile::TempDir;
+use test_utils::{dir_url, key};
+use tough::editor::{RepositoryEditor, RootEditor};
+use tough::error::Error;
+use tough::schema::RoleType;
+use tough::{FilesystemTransport, Repository, Settings};
+
+mod test_utils;
+
+/// Returns a `RootEditor` for a new root where snakeoil.pem signs every role.
+fn root_editor() -> RootEditor {
+    let one = NonZeroU64::new(1).unwrap();
+    let mut editor = RootEditor::new();
+    editor
+        .expires(Utc::now().checked_add_signed(Duration::days(21)).unwrap())
+        .add_key(
+            key("snakeoil.pem")[0].as_sign().unwrap().tuf_key(),
+            &[
+                RoleType::Root,
+                RoleType::Snapshot,
+                RoleType::Targets,
+                RoleType::Timestamp,
+            ],
+        )
+        .unwrap()
+        .threshold(RoleType::Root, one)
+        .threshold(RoleType::Snapshot, one)
+        .threshold(RoleType::Targets, one)
+        .threshold(RoleType::Timestamp, one);
+    editor
+}
+
+/// Test that a root built with `RootEditor` can be used to sign and load a repository, with its
+/// extra fields intact.
+#[test]
+fn root_editor_creates_usable_root() {
+    let mut editor = root_editor();
+    editor
+        .extra("owner", json!("example"))
+        .bump_version()
+        .unwrap();
+    let signed_root = editor.sign(&key("snakeoil.pem")).unwrap();
+    assert_eq!(signed_root.signed().signed.version.get(), 2);
+    assert_eq!(signed_root.signed().signatures.len(), 1);
+
+    let root_dir = TempDir::new().unwrap();
+    let root_path = root_dir.path().join("root.json");
+    std::fs::write(&root_path, signed_root.buffer()).unwrap();
+
+    let expiration = Utc::now().checked_add_signed(Duration::days(21)).unwrap();
+    let version = NonZeroU64::new(1).unwrap();
+    let mut repo_editor = RepositoryEditor::<FilesystemTransport>::new(&root_path).unwrap();
+    repo_editor
+        .targets_expires(expiration)
+        .unwrap()
+        .targets_version(version)
+        .unwrap()
+        .snapshot_expires(expiration)
+        .snapshot_version(version)
+        .timestamp_expires(expiration)
+        .timestamp_version(version);
+    let repo_dir = TempDir::new().unwrap();
+    let metadata_dir = repo_dir.path().join("metadata");
+    repo_editor
+        .sign(&key("snakeoil.pem"))
+        .unwrap()
+        .write(&metadata_dir)
+        .unwrap();
+
+    let metadata_base_url = dir_url(&metadata_dir);
+    let datastore = TempDir::new().unwrap();
+    let repo = Repository::load(
+        &FilesystemTransport,
+        Settings::new(
+            File::open(&root_path).unwrap(),
+            datastore.path(),
+            &metadata_base_url,
+            &metadata_base_url,
+        ),
+    )
+    .unwrap();
+    assert_eq!(repo.root().signed.version.get(), 2);
+    assert_eq!(repo.root().signed._extra["owner"], json!("example"));
+}
+
+/// Test that a root whose roles don't list enough keys for their thresholds can't be signed, and
+/// that a root can't be signed without a threshold of its keys.
+#[test]
+fn root_editor_rejects_unusable_root() {
+    let mut editor = root_editor();
+    editor.threshold(RoleType::Snapshot, NonZeroU64::new(2).unwrap());
+    assert!(matches!(
+        editor.sign(&key("snakeoil.pem")),
+        Err(Error::UnstableRoot {
+            role: RoleType::Snapshot,
+            threshold: 2,
+            actual: 1,
+        })
+    ));
+
+    let mut editor = root_editor();
+    let key_id = editor.root().roles[&RoleType::Timestamp].keyids[0].clone();
+    editor.remove_key(&key_id, Some(RoleType::Timestamp));
+    assert!(editor.root().keys.contains_key(&key_id));
+    assert!(matches!(
+        editor.sign(&key("snakeoil.pem")),
+        Err(Error::UnstableRoot {
+            role: RoleType::Timestamp,
+            ..
+        })
+    ));
+
+    let editor = root_editor();
+    assert!(editor.sign(&key("targetskey")).is_err());
+}
+
+/// Test that a key can't be added if its key ID is already listed for a different key.
+#[test]
+fn root_editor_rejects_duplicate_key_id() {
+    let snakeoil = key("snakeoil.pem")[0].as_sign().unwrap().tuf_key();
+    let mut root = root_editor().root().clone();
+    let key_id = snakeoil.key_id().unwrap();
+    root.keys.insert(
+        key_id.clone(),
+        key("targetskey")[0].as_sign().unwrap().tuf_key(),
+    );
+
+    let mut editor = RootEditor::from_root(root);
+    match editor.add_key(snakeoil, &[RoleType::Targets]) {
+        Err(Error::KeyDuplicate {
+            key_id: duplicate, ..
+        }) => {
+            assert_eq!(duplicate, hex::encode(&key_id))
+        }
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(_) => panic!("added a key whose key ID is already in use"),
+    }
+}
@@ -3,10 +3,29 @@
 
 mod test_utils;
 
-use std::fs::File;
+use std::path::Path;
 use tempfile::TempDir;
-use test_utils::{dir_url, test_data};
-use tough::{ExpirationEnforcement, Limits, Repository, Settings};
+use test_utils::{dir_url, settings, test_data};
+use tough::{FilesystemTransport, KeyChange, Repository};
+
+fn load<'a>(
+    base: &Path,
+    trusted_root: &str,
+    datastore: &'a Path,
+    metadata_base_url: &'a str,
+    targets_base_url: &'a str,
+) -> Repository<'a, FilesystemTransport> {
+    Repository::load(
+        &FilesystemTransport,
+        settings(
+            &base.join(trusted_root),
+            metadata_base_url,
+            targets_base_url,
+            datastore,
+        ),
+    )
+    .unwrap()
+}
 
 #[test]
 fn rotated_root() {
@@ -16,18 +35,87 @@ fn rotated_root() {
     let metadata_base_url = &dir_url(&base);
     let targets_base_url = &dir_url(base.join("targets"));
 
-    let repo = Repository::load(
-        &tough::FilesystemTransport,
-        Settings {
-            root: File::open(base.join("1.root.json")).unwrap(),
-            datastore: datastore.as_ref(),
-            metadata_base_url,
-            targets_base_url,
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
-    )
-    .unwrap();
+    let repo = load(
+        &base,
+        "1.root.json",
+        datastore.path(),
+        metadata_base_url,
+        targets_base_url,
+    );
 
     assert_eq!(u64::from(repo.root().signed.version), 2);
 }
+
+/// Test that every verified root is returned, with the exact bytes that were verified and the key
+/// changes between versions.
+#[test]
+fn rotated_root_chain() {
+    let base = test_data().join("rotated-root");
+    let datastore = TempDir::new().unwrap();
+    let metadata_base_url = &dir_url(&base);
+    let targets_base_url = &dir_url(base.join("targets"));
+
+    let repo = load(
+        &base,
+        "1.root.json",
+        datastore.path(),
+        metadata_base_url,
+        targets_base_url,
+    );
+    let chain = repo.root_chain();
+    assert_eq!(chain.len(), 2);
+    for (verified, version) in chain.iter().zip(1..) {
+        assert_eq!(verified.signed().signed.version.get(), version);
+        assert_eq!(
+            verified.buffer(),
+            &std::fs::read(base.join(format!("{}.root.json", version))).unwrap()[..]
+        );
+    }
+    assert!(chain[0].key_changes().is_empty());
+    assert_eq!(
+        chain[1].key_changes(),
+        &KeyChange::between(&chain[0].signed().signed, &chain[1].signed().signed)[..]
+    );
+    assert!(!chain[1].key_changes().is_empty());
+    assert_eq!(chain[1].signed(), repo.root());
+}
+
+/// Test that caching the root chain writes the verified roots, and fetches and checks the roots
+/// older than the trusted root.
+#[test]
+fn cache_rotated_root_chain() {
+    let base = test_data().join("rotated-root");
+    let metadata_base_url = &dir_url(&base);
+    let targets_base_url = &dir_url(base.join("targets"));
+
+    for trusted_root in &["1.root.json", "2.root.json"] {
+        let datastore = TempDir::new().unwrap();
+        let repo = load(
+            &base,
+            trusted_root,
+            datastore.path(),
+            metadata_base_url,
+            targets_base_url,
+        );
+        let cache = TempDir::new().unwrap();
+        let metadata_dir = cache.path().join("metadata");
+        repo.cache(
+            &metadata_dir,
+            cache.path().join("targets"),
+            None::<&[&str]>,
+            true,
+        )
+        .unwrap();
+        for version in 1..=2 {
+            let filename = format!("{}.root.json", version);
+            assert_eq!(
+                std::fs::read(metadata_dir.join(&filename)).unwrap(),
+                std::fs::read(base.join(&filename)).unwrap()
+            );
+        }
+        assert!(repo
+            .verify_cache(&metadata_dir, cache.path().join("targets"))
+            .unwrap()
+            .is_clean());
+    }
+}
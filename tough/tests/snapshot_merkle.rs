@@ -0,0 +1,241 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use chrono::{Duration, Utc};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::num::NonZeroU64;
+use std::path::Path;
+use tempfile::TempDir;
+use test_utils::{dir_url, key, load, read_to_end, root_path, targets_path, test_data, try_load};
+use tough::editor::signed::PathExists;
+use tough::editor::RepositoryEditor;
+use tough::error::Error;
+use tough::key_source::{KeySource, LocalKeySource};
+use tough::schema::{
+    MerkleLeaf, PathSet, Signed, Snapshot, SnapshotMerkleProof, SnapshotMerkleTree,
+};
+use tough::FilesystemTransport;
+
+mod test_utils;
+
+/// Creates a repository that publishes a snapshot Merkle tree, in which "targets" delegates
+/// file1.txt to "role1" if `role1_version` is given. The snapshot metadata has a "custom" field.
+fn create_repo(outdir: &Path, timestamp_version: u64, role1_version: Option<u64>) {
+    let expiration = Utc::now().checked_add_signed(Duration::days(21)).unwrap();
+    let version = NonZeroU64::new(1).unwrap();
+    let targets_key: &[Box<dyn KeySource>] = &[Box::new(LocalKeySource {
+        path: test_data().join("snakeoil.pem"),
+    })];
+    let role1_key: &[Box<dyn KeySource>] = &[Box::new(LocalKeySource {
+        path: test_data().join("targetskey"),
+    })];
+
+    let mut editor = RepositoryEditor::<FilesystemTransport>::new(root_path()).unwrap();
+    editor
+        .targets_expires(expiration)
+        .unwrap()
+        .targets_version(version)
+        .unwrap()
+        .snapshot_expires(expiration)
+        .snapshot_version(version)
+        .timestamp_expires(expiration)
+        .timestamp_version(NonZeroU64::new(timestamp_version).unwrap())
+        .snapshot_merkle(true)
+        .add_target_paths(vec![targets_path().join("file3.txt")])
+        .unwrap();
+    let mut snapshot = Snapshot::new("1.0.0".to_owned(), version, expiration);
+    snapshot
+        ._extra
+        .insert("custom".to_owned(), Value::from("snapshot data"));
+    editor.snapshot(snapshot).unwrap();
+    if let Some(role1_version) = role1_version {
+        editor
+            .delegate_role(
+                "role1",
+                role1_key,
+                PathSet::Paths(vec!["file1.txt".to_string()]),
+                NonZeroU64::new(1).unwrap(),
+                expiration,
+                NonZeroU64::new(role1_version).unwrap(),
+            )
+            .unwrap()
+            .sign_targets_editor(targets_key)
+            .unwrap()
+            .change_delegated_targets("role1")
+            .unwrap()
+            .add_target_paths(vec![targets_path().join("file1.txt")])
+            .unwrap()
+            .targets_version(NonZeroU64::new(role1_version).unwrap())
+            .unwrap()
+            .targets_expires(expiration)
+            .unwrap()
+            .sign_targets_editor(role1_key)
+            .unwrap()
+            .change_delegated_targets("targets")
+            .unwrap()
+            .targets_version(version)
+            .unwrap()
+            .targets_expires(expiration)
+            .unwrap();
+    }
+
+    let signed_repo = editor.sign(targets_key).unwrap();
+    signed_repo.write(outdir.join("metadata")).unwrap();
+    signed_repo
+        .link_targets(&targets_path(), &outdir.join("targets"), PathExists::Skip)
+        .unwrap();
+}
+
+/// Test that a repository with a snapshot Merkle tree is loaded using the proofs, without the
+/// snapshot metadata.
+#[test]
+fn load_with_snapshot_merkle_proofs() {
+    let repo_dir = TempDir::new().unwrap();
+    let datastore = TempDir::new().unwrap();
+    create_repo(repo_dir.path(), 7, Some(1));
+    let metadata = repo_dir.path().join("metadata");
+    let metadata_base_url = dir_url(&metadata);
+    let targets_base_url = dir_url(repo_dir.path().join("targets"));
+    assert!(metadata.join("7.targets-snapshot.json").is_file());
+    assert!(metadata.join("7.role1-snapshot.json").is_file());
+
+    // Loading must not depend on the snapshot metadata.
+    std::fs::remove_file(metadata.join("1.snapshot.json")).unwrap();
+    let repo = load(&metadata_base_url, &targets_base_url, datastore.path());
+    assert!(repo.snapshot().is_none());
+    assert!(repo.timestamp().signed.merkle_root.is_some());
+    assert!(repo.targets().signed.delegated_targets("role1").is_ok());
+    assert_eq!(
+        read_to_end(repo.read_target("file1.txt").unwrap().unwrap()),
+        std::fs::read(targets_path().join("file1.txt")).unwrap()
+    );
+}
+
+/// Test that editing a repository with a snapshot Merkle tree keeps the data of its snapshot
+/// metadata, which the client doesn't load.
+#[test]
+fn edit_snapshot_merkle_repo() {
+    let repo_dir = TempDir::new().unwrap();
+    let datastore = TempDir::new().unwrap();
+    create_repo(repo_dir.path(), 7, Some(1));
+    let metadata_base_url = dir_url(repo_dir.path().join("metadata"));
+    let targets_base_url = dir_url(repo_dir.path().join("targets"));
+    let repo = load(&metadata_base_url, &targets_base_url, datastore.path());
+    assert!(repo.snapshot().is_none());
+
+    let expiration = Utc::now().checked_add_signed(Duration::days(21)).unwrap();
+    let mut editor = RepositoryEditor::from_repo(root_path(), repo).unwrap();
+    editor
+        .targets_expires(expiration)
+        .unwrap()
+        .targets_version(NonZeroU64::new(2).unwrap())
+        .unwrap()
+        .snapshot_expires(expiration)
+        .snapshot_version(NonZeroU64::new(2).unwrap())
+        .timestamp_expires(expiration)
+        .timestamp_version(NonZeroU64::new(8).unwrap());
+    let outdir = TempDir::new().unwrap();
+    editor
+        .sign(&key("snakeoil.pem"))
+        .unwrap()
+        .write(outdir.path())
+        .unwrap();
+
+    let snapshot: Signed<Snapshot> =
+        serde_json::from_slice(&std::fs::read(outdir.path().join("2.snapshot.json")).unwrap())
+            .unwrap();
+    assert_eq!(
+        snapshot.signed._extra.get("custom"),
+        Some(&Value::from("snapshot data"))
+    );
+}
+
+/// Test that a proof that doesn't lead to the Merkle root in the timestamp is rejected.
+#[test]
+fn tampered_snapshot_merkle_proof() {
+    let repo_dir = TempDir::new().unwrap();
+    let datastore = TempDir::new().unwrap();
+    create_repo(repo_dir.path(), 7, Some(1));
+
+    let metadata = repo_dir.path().join("metadata");
+    let metadata_base_url = dir_url(&metadata);
+    let targets_base_url = dir_url(repo_dir.path().join("targets"));
+
+    let path = metadata.join("7.role1-snapshot.json");
+    let mut proof: SnapshotMerkleProof =
+        serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    proof.leaf_contents.version = NonZeroU64::new(2).unwrap();
+    std::fs::write(&path, serde_json::to_vec(&proof).unwrap()).unwrap();
+
+    match try_load(&metadata_base_url, &targets_base_url, datastore.path()) {
+        Err(Error::VerifySnapshotMerkleProof { name, .. }) => assert_eq!(name, "role1"),
+        other => panic!("expected proof verification error, got {:?}", other),
+    }
+}
+
+/// Test that a newer snapshot Merkle tree listing an older version of a role than the trusted tree
+/// is rejected as a rollback attack, even if a tree in between did not list the role.
+#[test]
+fn snapshot_merkle_rollback() {
+    let datastore = TempDir::new().unwrap();
+    let urls = |timestamp_version, role1_version| {
+        let repo_dir = TempDir::new().unwrap();
+        create_repo(repo_dir.path(), timestamp_version, role1_version);
+        let metadata_base_url = dir_url(repo_dir.path().join("metadata"));
+        let targets_base_url = dir_url(repo_dir.path().join("targets"));
+        (repo_dir, metadata_base_url, targets_base_url)
+    };
+
+    let (_repo_dir, metadata_base_url, targets_base_url) = urls(7, Some(2));
+    load(&metadata_base_url, &targets_base_url, datastore.path());
+    // A tree that doesn't list the role must not make the client forget its trusted version.
+    let (_repo_dir, metadata_base_url, targets_base_url) = urls(8, None);
+    load(&metadata_base_url, &targets_base_url, datastore.path());
+    let (_repo_dir, metadata_base_url, targets_base_url) = urls(9, Some(1));
+    match try_load(&metadata_base_url, &targets_base_url, datastore.path()) {
+        Err(Error::OlderSnapshotMerkleLeaf {
+            name,
+            current_version,
+            new_version,
+            ..
+        }) => assert_eq!(
+            (name.as_str(), current_version, new_version),
+            ("role1", 2, 1)
+        ),
+        other => panic!("expected rollback error, got {:?}", other),
+    }
+    let (_repo_dir, metadata_base_url, targets_base_url) = urls(10, Some(2));
+    load(&metadata_base_url, &targets_base_url, datastore.path());
+}
+
+/// Test that every proof in trees of various sizes leads to the tree's root, and only that root.
+#[test]
+fn snapshot_merkle_tree_proofs() {
+    let leaf = |i: u64| MerkleLeaf {
+        name: format!("role{}", i),
+        version: NonZeroU64::new(i).unwrap(),
+        length: None,
+        hashes: None,
+    };
+    let mut previous_root: Option<Vec<u8>> = None;
+    for size in 1..=9 {
+        let tree = SnapshotMerkleTree::new((1..=size).map(leaf).collect()).unwrap();
+        let proofs: HashMap<_, _> = tree
+            .proofs()
+            .into_iter()
+            .map(|proof| (proof.leaf_contents.name.clone(), proof))
+            .collect();
+        assert_eq!(proofs.len() as u64, size);
+        for proof in proofs.values() {
+            proof.verify(tree.root()).unwrap();
+            if let Some(previous_root) = &previous_root {
+                assert!(proof.verify(previous_root).is_err());
+            }
+        }
+        assert_eq!(tree.proof("role1").unwrap(), proofs["role1"]);
+        previous_root = Some(tree.root().to_vec());
+    }
+    assert!(SnapshotMerkleTree::new(Vec::new()).is_err());
+    assert!(SnapshotMerkleTree::new(vec![leaf(1), leaf(2), leaf(1)]).is_err());
+}
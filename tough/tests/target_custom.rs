@@ -0,0 +1,87 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::num::NonZeroU64;
+use test_utils::test_data;
+use tough::editor::targets::TargetsEditor;
+use tough::error::Error;
+use tough::schema::Target;
+use tough::FilesystemTransport;
+
+mod test_utils;
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct Custom {
+    version: String,
+    variant: String,
+    #[serde(default)]
+    signature_urls: Vec<String>,
+}
+
+fn target() -> Target {
+    Target::from_path(
+        test_data()
+            .join("tuf-reference-impl")
+            .join("targets")
+            .join("file1.txt"),
+    )
+    .unwrap()
+}
+
+fn custom() -> Custom {
+    Custom {
+        version: "1.2.3".to_owned(),
+        variant: "aws-k8s".to_owned(),
+        signature_urls: vec!["https://example.com/file1.txt.sig".to_owned()],
+    }
+}
+
+/// Test that custom metadata set from a type is read back as that type.
+#[test]
+fn custom_round_trip() {
+    let mut target = target();
+    target.set_custom(&custom()).unwrap();
+    assert_eq!(target.custom["variant"], "aws-k8s");
+    assert_eq!(target.custom_as::<Custom>().unwrap(), custom());
+
+    // The custom metadata survives serialization of the target.
+    let target: Target = serde_json::from_slice(&serde_json::to_vec(&target).unwrap()).unwrap();
+    assert_eq!(target.custom_as::<Custom>().unwrap(), custom());
+}
+
+/// Test that misspelled or non-object custom metadata is rejected.
+#[test]
+fn custom_invalid() {
+    let mut target = target();
+    target.custom.insert("version".to_owned(), "1.2.3".into());
+    target.custom.insert("varient".to_owned(), "aws-k8s".into());
+    assert!(target.custom_as::<Custom>().is_err());
+    assert!(target.set_custom(&"aws-k8s").is_err());
+}
+
+/// Test that `TargetsEditor` only builds a role whose targets have custom metadata of the declared
+/// type.
+#[test]
+fn editor_validates_custom() {
+    let mut valid = target();
+    valid.set_custom(&custom()).unwrap();
+    let mut invalid = target();
+    invalid.custom.insert("version".to_owned(), "1.2.3".into());
+
+    let mut editor = TargetsEditor::<FilesystemTransport>::new("role");
+    editor
+        .version(NonZeroU64::new(1).unwrap())
+        .expires(Utc::now().checked_add_signed(Duration::days(21)).unwrap())
+        .validate_custom::<Custom>()
+        .add_target("valid.txt", valid);
+    assert!(editor.build_targets().is_ok());
+
+    editor.add_target("invalid.txt", invalid);
+    match editor.build_targets() {
+        Err(Error::TargetCustom { name, .. }) => assert_eq!(name, "invalid.txt"),
+        other => panic!("expected custom metadata error, got {:?}", other),
+    }
+}
@@ -0,0 +1,200 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use chrono::{Duration, Utc};
+use std::fs::{self};
+use std::num::NonZeroU64;
+use std::path::Path;
+use tempfile::TempDir;
+use test_utils::{dir_url, key, load, read_to_end, root_path};
+use tough::editor::signed::PathExists;
+use tough::editor::{target_name_from_path, RepositoryEditor};
+use tough::error::Error;
+use tough::FilesystemTransport;
+
+mod test_utils;
+
+/// The targets written to the input directory, by name, and their contents.
+const TARGETS: [(&str, &str); 3] = [
+    ("a/foo.txt", "foo from a\n"),
+    ("b/foo.txt", "foo from b\n"),
+    ("images/x86_64/root.img", "root image\n"),
+];
+
+/// Writes each of `TARGETS` to its path within `indir`.
+fn write_targets(indir: &Path) {
+    for (name, contents) in &TARGETS {
+        let path = indir.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+}
+
+/// Creates a repository whose targets are named by their paths within `indir`, and copies the
+/// targets to `outdir`.
+fn create_repo(indir: &Path, outdir: &Path) {
+    let expiration = Utc::now().checked_add_signed(Duration::days(21)).unwrap();
+    let version = NonZeroU64::new(1).unwrap();
+    let mut editor = RepositoryEditor::<FilesystemTransport>::new(root_path()).unwrap();
+    editor
+        .targets_expires(expiration)
+        .unwrap()
+        .targets_version(version)
+        .unwrap()
+        .snapshot_expires(expiration)
+        .snapshot_version(version)
+        .timestamp_expires(expiration)
+        .timestamp_version(version);
+    for (name, _) in &TARGETS {
+        let (target_name, target) =
+            RepositoryEditor::<FilesystemTransport>::build_target_relative(indir, indir.join(name))
+                .unwrap();
+        assert_eq!(&target_name, name);
+        editor.add_target(&target_name, target).unwrap();
+    }
+    let signed_repo = editor.sign(&key("snakeoil.pem")).unwrap();
+    signed_repo.write(outdir.join("metadata")).unwrap();
+    signed_repo
+        .copy_targets(indir, outdir.join("targets"), PathExists::Fail)
+        .unwrap();
+}
+
+/// Test that targets named by their paths are copied into subdirectories, with the hash prefixed
+/// to the final path component, and can be read and cached.
+#[test]
+fn target_paths() {
+    let indir = TempDir::new().unwrap();
+    let repo_dir = TempDir::new().unwrap();
+    write_targets(indir.path());
+    create_repo(indir.path(), repo_dir.path());
+
+    let metadata_base_url = dir_url(repo_dir.path().join("metadata"));
+    let targets_base_url = dir_url(repo_dir.path().join("targets"));
+    let datastore = TempDir::new().unwrap();
+    let repo = load(&metadata_base_url, &targets_base_url, datastore.path());
+
+    for (name, contents) in &TARGETS {
+        // The simple-rsa root uses consistent snapshots.
+        let sha256 = hex::encode(&repo.find_target(name).unwrap().target.hashes.sha256);
+        let (dir, file) = name.rsplit_once('/').unwrap();
+        assert!(repo_dir
+            .path()
+            .join("targets")
+            .join(dir)
+            .join(format!("{}.{}", sha256, file))
+            .is_file());
+
+        let target = read_to_end(repo.read_target(name).unwrap().unwrap());
+        assert_eq!(target, contents.as_bytes());
+    }
+
+    let cache_dir = TempDir::new().unwrap();
+    repo.cache(
+        cache_dir.path().join("metadata"),
+        cache_dir.path().join("targets"),
+        None::<&[&str]>,
+        false,
+    )
+    .unwrap();
+    assert!(repo
+        .verify_cache(
+            cache_dir.path().join("metadata"),
+            cache_dir.path().join("targets")
+        )
+        .unwrap()
+        .is_clean());
+}
+
+/// Creates a repository listing a target under `name`, which is not how `build_target_relative`
+/// would name it, and writes the target where the consistent snapshot filename leads.
+fn create_repo_with_name(name: &str, outdir: &Path) {
+    let expiration = Utc::now().checked_add_signed(Duration::days(21)).unwrap();
+    let version = NonZeroU64::new(1).unwrap();
+    let indir = TempDir::new().unwrap();
+    let path = indir.path().join("foo.txt");
+    fs::write(&path, "foo\n").unwrap();
+    let (_, target) = RepositoryEditor::<FilesystemTransport>::build_target(&path).unwrap();
+    let (dir, file) = name.rsplit_once('/').unwrap();
+    let filename = format!("{}/{}.{}", dir, hex::encode(&target.hashes.sha256), file);
+    let target_path = outdir.join("targets").join(filename);
+    fs::create_dir_all(target_path.parent().unwrap()).unwrap();
+    fs::copy(&path, target_path).unwrap();
+
+    let mut editor = RepositoryEditor::<FilesystemTransport>::new(root_path()).unwrap();
+    editor
+        .targets_expires(expiration)
+        .unwrap()
+        .targets_version(version)
+        .unwrap()
+        .snapshot_expires(expiration)
+        .snapshot_version(version)
+        .timestamp_expires(expiration)
+        .timestamp_version(version)
+        .add_target(name, target)
+        .unwrap();
+    let signed_repo = editor.sign(&key("snakeoil.pem")).unwrap();
+    signed_repo.write(outdir.join("metadata")).unwrap();
+}
+
+/// Test that a repository whose target names are not plain relative paths, as repositories
+/// created before targets could be named by their paths may have, can still be cached, but that
+/// a target is never cached outside the cache directory.
+#[test]
+fn cache_unusual_target_names() {
+    for name in &["./foo.txt", "a//foo.txt"] {
+        let repo_dir = TempDir::new().unwrap();
+        create_repo_with_name(name, repo_dir.path());
+        let metadata_base_url = dir_url(repo_dir.path().join("metadata"));
+        let targets_base_url = dir_url(repo_dir.path().join("targets"));
+        let datastore = TempDir::new().unwrap();
+        let repo = load(&metadata_base_url, &targets_base_url, datastore.path());
+
+        let cache_dir = TempDir::new().unwrap();
+        repo.cache(
+            cache_dir.path().join("metadata"),
+            cache_dir.path().join("targets"),
+            None::<&[&str]>,
+            false,
+        )
+        .unwrap();
+    }
+
+    let repo_dir = TempDir::new().unwrap();
+    create_repo_with_name("../foo.txt", repo_dir.path());
+    let metadata_base_url = dir_url(repo_dir.path().join("metadata"));
+    let targets_base_url = dir_url(repo_dir.path().join("targets"));
+    let datastore = TempDir::new().unwrap();
+    let repo = load(&metadata_base_url, &targets_base_url, datastore.path());
+    let cache_dir = TempDir::new().unwrap();
+    assert!(matches!(
+        repo.cache(
+            cache_dir.path().join("metadata"),
+            cache_dir.path().join("targets"),
+            None::<&[&str]>,
+            false,
+        ),
+        Err(Error::TargetNameInvalid { .. })
+    ));
+}
+
+/// Test that a target can only be named by its path within the input directory.
+#[test]
+fn target_name_outside_dir() {
+    let indir = TempDir::new().unwrap();
+    write_targets(indir.path());
+    assert_eq!(
+        target_name_from_path(indir.path(), indir.path().join("a").join("foo.txt")).unwrap(),
+        "a/foo.txt"
+    );
+    assert!(matches!(
+        target_name_from_path(
+            indir.path().join("a"),
+            indir.path().join("b").join("foo.txt")
+        ),
+        Err(Error::TargetOutsideDir { .. })
+    ));
+    assert!(matches!(
+        target_name_from_path(indir.path(), indir.path().join("..").join("foo.txt")),
+        Err(Error::TargetOutsideDir { .. })
+    ));
+}
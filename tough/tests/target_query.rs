@@ -0,0 +1,186 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use chrono::{TimeZone, Utc};
+use serde_json::json;
+use std::collections::HashMap;
+use std::num::NonZeroU64;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+use test_utils::{dir_url, settings, test_data};
+use tough::schema::decoded::Decoded;
+use tough::schema::{
+    DelegatedRole, Hashes, PathSet, Signed, Target, TargetMatch, TargetQuery, Targets,
+};
+use tough::{FilesystemTransport, Repository};
+
+mod test_utils;
+
+fn names_and_roles<'a>(matches: &[TargetMatch<'a>]) -> Vec<(&'a str, &'a str)> {
+    matches.iter().map(|m| (m.name, m.role)).collect()
+}
+
+fn target(length: u64) -> Target {
+    Target {
+        length,
+        hashes: Hashes {
+            sha256: Decoded::from(vec![0; 32]),
+            _extra: HashMap::new(),
+        },
+        custom: HashMap::new(),
+        _extra: HashMap::new(),
+    }
+}
+
+fn targets_role(targets: &[&str]) -> Targets {
+    let mut role = Targets::new(
+        "1.0.0".to_string(),
+        NonZeroU64::new(1).unwrap(),
+        Utc.ymd(2030, 1, 1).and_hms(0, 0, 0),
+    );
+    for name in targets {
+        role.add_target(name, target(1));
+    }
+    role
+}
+
+fn delegate(parent: &mut Targets, name: &str, paths: &[&str], terminating: bool, child: Targets) {
+    parent
+        .delegations
+        .as_mut()
+        .unwrap()
+        .roles
+        .push(DelegatedRole {
+            name: name.to_string(),
+            keyids: Vec::new(),
+            threshold: NonZeroU64::new(1).unwrap(),
+            paths: PathSet::Paths(paths.iter().map(|p| p.to_string()).collect()),
+            terminating,
+            targets: Some(Signed::new(child, Vec::new())),
+        });
+}
+
+fn reference_impl() -> PathBuf {
+    test_data().join("tuf-reference-impl")
+}
+
+fn load<'a>(
+    metadata_base_url: &'a str,
+    targets_base_url: &'a str,
+    datastore: &'a Path,
+) -> Repository<'a, FilesystemTransport> {
+    Repository::load(
+        &FilesystemTransport,
+        settings(
+            &reference_impl().join("metadata").join("1.root.json"),
+            metadata_base_url,
+            targets_base_url,
+            datastore,
+        ),
+    )
+    .unwrap()
+}
+
+/// Test querying the targets of a repository by name and custom metadata.
+#[test]
+fn test_repo_query_targets() {
+    let datastore = TempDir::new().unwrap();
+    let metadata_base_url = dir_url(reference_impl().join("metadata"));
+    let targets_base_url = dir_url(reference_impl().join("targets"));
+    let repo = load(&metadata_base_url, &targets_base_url, datastore.path());
+
+    assert_eq!(
+        names_and_roles(&repo.query_targets(&TargetQuery::new())),
+        vec![
+            ("file1.txt", "targets"),
+            ("file2.txt", "targets"),
+            ("file3.txt", "role1")
+        ]
+    );
+    assert_eq!(
+        names_and_roles(&repo.query_targets(&TargetQuery::new().glob("file[23]*").unwrap())),
+        vec![("file2.txt", "targets"), ("file3.txt", "role1")]
+    );
+    assert_eq!(
+        names_and_roles(
+            &repo.query_targets(&TargetQuery::new().custom("file_permissions", json!("0644")))
+        ),
+        vec![("file1.txt", "targets")]
+    );
+    assert_eq!(
+        names_and_roles(
+            &repo.query_targets(
+                &TargetQuery::new()
+                    .prefix("file")
+                    .filter(|target| target.length < 35)
+            )
+        ),
+        vec![("file1.txt", "targets"), ("file3.txt", "role1")]
+    );
+    assert!(TargetQuery::new().glob("file[").is_err());
+}
+
+/// Test looking up and listing the targets of a repository through its target index.
+#[test]
+fn test_repo_find_target() {
+    let datastore = TempDir::new().unwrap();
+    let metadata_base_url = dir_url(reference_impl().join("metadata"));
+    let targets_base_url = dir_url(reference_impl().join("targets"));
+    let repo = load(&metadata_base_url, &targets_base_url, datastore.path());
+
+    let found = repo.find_target("file3.txt").unwrap();
+    assert_eq!(found.name, "file3.txt");
+    assert_eq!(found.role_path, vec!["targets", "role1"]);
+    assert_eq!(
+        found.target,
+        repo.targets()
+            .signed
+            .delegated_targets("role1")
+            .unwrap()
+            .signed
+            .targets
+            .get("file3.txt")
+            .unwrap()
+    );
+    assert_eq!(
+        repo.find_target("file1.txt").unwrap().role_path,
+        vec!["targets"]
+    );
+    assert!(repo.find_target("file4.txt").is_none());
+
+    let mut names = repo.all_targets().map(|(name, _)| name).collect::<Vec<_>>();
+    names.sort_unstable();
+    assert_eq!(names, vec!["file1.txt", "file2.txt", "file3.txt"]);
+}
+
+/// Test that queries only report targets that a role is trusted to provide.
+#[test]
+fn test_query_respects_delegations() {
+    let mut a = targets_role(&["images/a.img", "other/a.img"]);
+    let nested = targets_role(&["images/x86_64/a.img", "images/aarch64/a.img"]);
+    delegate(&mut a, "nested", &["images/x86_64/*"], false, nested);
+
+    let b = targets_role(&["images/a.img", "images/b.img"]);
+    let c = targets_role(&["images/c.img"]);
+
+    let mut top = targets_role(&["top.txt"]);
+    delegate(&mut top, "a", &["images/*"], true, a);
+    delegate(&mut top, "b", &["images/*"], false, b);
+    delegate(&mut top, "c", &["misc/*"], false, c);
+
+    // "other/a.img" is outside the paths of role "a", "images/aarch64/a.img" is outside the paths
+    // of role "nested", "b" comes after a terminating role for "images/*", and "c" lists a target
+    // outside its paths.
+    assert_eq!(
+        names_and_roles(&top.query(&TargetQuery::new())),
+        vec![
+            ("images/a.img", "a"),
+            ("images/x86_64/a.img", "nested"),
+            ("top.txt", "targets")
+        ]
+    );
+    assert_eq!(
+        names_and_roles(&top.query(&TargetQuery::new().prefix("images/x86_64/"))),
+        vec![("images/x86_64/a.img", "nested")]
+    );
+}
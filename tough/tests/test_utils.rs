@@ -1,8 +1,12 @@
 // Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+use std::fs::File;
 use std::io::Read;
 use std::path::{Path, PathBuf};
+use tough::error::Result;
+use tough::key_source::{KeySource, LocalKeySource};
+use tough::{FilesystemTransport, Repository, Settings};
 use url::Url;
 
 /// Utilities for tests. Not every test module uses every function, so we suppress unused warnings.
@@ -28,3 +32,64 @@ pub fn read_to_end<R: Read>(mut reader: R) -> Vec<u8> {
     reader.read_to_end(&mut v).unwrap();
     v
 }
+
+/// Returns the path to the root.json that corresponds with snakeoil.pem
+#[allow(unused)]
+pub fn root_path() -> PathBuf {
+    test_data().join("simple-rsa").join("root.json")
+}
+
+/// Returns the path to the targets of the TUF reference implementation repository
+#[allow(unused)]
+pub fn targets_path() -> PathBuf {
+    test_data().join("tuf-reference-impl").join("targets")
+}
+
+/// Returns the key file `name` from our test data directory, as a list of key sources for signing
+#[allow(unused)]
+pub fn key(name: &str) -> Vec<Box<dyn KeySource>> {
+    vec![Box::new(LocalKeySource {
+        path: test_data().join(name),
+    })]
+}
+
+/// Returns `Settings` for loading the repository at the given URLs, trusting the root.json at
+/// `root`
+#[allow(unused)]
+pub fn settings<'a>(
+    root: &Path,
+    metadata_base_url: &'a str,
+    targets_base_url: &'a str,
+    datastore: &'a Path,
+) -> Settings<'a, File> {
+    Settings::new(
+        File::open(root).unwrap(),
+        datastore,
+        metadata_base_url,
+        targets_base_url,
+    )
+}
+
+/// Loads the repository at the given URLs, trusting the root.json from `root_path`
+#[allow(unused)]
+pub fn try_load<'a>(
+    metadata_base_url: &'a str,
+    targets_base_url: &'a str,
+    datastore: &'a Path,
+) -> Result<Repository<'a, FilesystemTransport>> {
+    Repository::load(
+        &FilesystemTransport,
+        settings(&root_path(), metadata_base_url, targets_base_url, datastore),
+    )
+}
+
+/// Loads the repository at the given URLs, trusting the root.json from `root_path`, and panics if
+/// it can't be loaded
+#[allow(unused)]
+pub fn load<'a>(
+    metadata_base_url: &'a str,
+    targets_base_url: &'a str,
+    datastore: &'a Path,
+) -> Repository<'a, FilesystemTransport> {
+    try_load(metadata_base_url, targets_base_url, datastore).unwrap()
+}
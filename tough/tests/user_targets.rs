@@ -0,0 +1,304 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use chrono::{Duration, Utc};
+use std::num::NonZeroU64;
+use std::path::Path;
+use tempfile::TempDir;
+use test_utils::{dir_url, key, read_to_end, root_path, settings, targets_path};
+use tough::editor::signed::PathExists;
+use tough::editor::targets::TargetsEditor;
+use tough::editor::RepositoryEditor;
+use tough::error::{Error, Result};
+use tough::schema::{PathSet, RoleType, Signed, Targets};
+use tough::{FilesystemTransport, Repository, UserTargets};
+
+mod test_utils;
+
+/// Creates a repository whose top-level targets role lists file2.txt and file3.txt, and delegates
+/// file1.txt to "role1".
+fn create_repo(outdir: &Path) {
+    let expiration = Utc::now().checked_add_signed(Duration::days(21)).unwrap();
+    let version = NonZeroU64::new(1).unwrap();
+
+    let mut editor = RepositoryEditor::<FilesystemTransport>::new(root_path()).unwrap();
+    editor
+        .targets_expires(expiration)
+        .unwrap()
+        .targets_version(version)
+        .unwrap()
+        .snapshot_expires(expiration)
+        .snapshot_version(version)
+        .timestamp_expires(expiration)
+        .timestamp_version(version)
+        .add_target_paths(vec![
+            targets_path().join("file2.txt"),
+            targets_path().join("file3.txt"),
+        ])
+        .unwrap()
+        .delegate_role(
+            "role1",
+            &key("targetskey"),
+            PathSet::Paths(vec!["file1.txt".to_string()]),
+            NonZeroU64::new(1).unwrap(),
+            expiration,
+            version,
+        )
+        .unwrap();
+    editor
+        .sign_targets_editor(&key("snakeoil.pem"))
+        .unwrap()
+        .change_delegated_targets("role1")
+        .unwrap()
+        .add_target_paths(vec![targets_path().join("file1.txt")])
+        .unwrap()
+        .targets_version(version)
+        .unwrap()
+        .targets_expires(expiration)
+        .unwrap()
+        .sign_targets_editor(&key("targetskey"))
+        .unwrap()
+        .change_delegated_targets("targets")
+        .unwrap()
+        .targets_version(version)
+        .unwrap()
+        .targets_expires(expiration)
+        .unwrap();
+
+    let signed_repo = editor.sign(&key("snakeoil.pem")).unwrap();
+    signed_repo.write(outdir.join("metadata")).unwrap();
+    signed_repo
+        .link_targets(&targets_path(), &outdir.join("targets"), PathExists::Skip)
+        .unwrap();
+}
+
+fn load<'a>(
+    metadata_base_url: &'a str,
+    targets_base_url: &'a str,
+    datastore: &'a Path,
+    user_targets: Option<UserTargets>,
+) -> Result<Repository<'a, FilesystemTransport>> {
+    let mut settings = settings(&root_path(), metadata_base_url, targets_base_url, datastore);
+    if let Some(user_targets) = user_targets {
+        settings = settings.user_targets(user_targets);
+    }
+    Repository::load(&FilesystemTransport, settings)
+}
+
+/// Creates user-selected targets metadata, signed with targetskey-1, that lists file3.txt and
+/// delegates to each of `roles` from `repo` with the keys the repository uses for them.
+fn user_targets_metadata(
+    repo: &Repository<'_, FilesystemTransport>,
+    roles: &[&str],
+) -> Signed<Targets> {
+    let mut editor = TargetsEditor::<FilesystemTransport>::new("user");
+    editor
+        .version(NonZeroU64::new(1).unwrap())
+        .expires(Utc::now().checked_add_signed(Duration::days(21)).unwrap())
+        .add_target_path(targets_path().join("file3.txt"))
+        .unwrap();
+    for &name in roles {
+        let (metadata, keys, threshold) = if name == "targets" {
+            let role_keys = &repo.root().signed.roles[&RoleType::Targets];
+            (
+                repo.targets().clone(),
+                repo.root().signed.keys.clone(),
+                role_keys.threshold,
+            )
+        } else {
+            let delegations = repo.targets().signed.delegations.as_ref().unwrap();
+            let role = repo.delegated_role(name).unwrap();
+            (
+                role.targets.clone().unwrap(),
+                delegations.keys.clone(),
+                role.threshold,
+            )
+        };
+        let keyids = keys.keys().cloned().collect();
+        editor
+            .delegate_role(
+                metadata.delegated_targets(name),
+                PathSet::Paths(vec!["*".to_string()]),
+                keys,
+                keyids,
+                threshold,
+            )
+            .unwrap();
+    }
+    editor
+        .create_signed(&key("targetskey-1"))
+        .unwrap()
+        .targets()
+        .1
+}
+
+/// Returns `metadata` as a user-selected targets role trusted with the named key.
+fn user_targets(metadata: Signed<Targets>, key_name: &str) -> UserTargets {
+    UserTargets {
+        metadata,
+        keys: vec![key(key_name)[0].as_sign().unwrap().tuf_key()],
+        threshold: NonZeroU64::new(1).unwrap(),
+    }
+}
+
+/// Test that targets are looked up from the user-selected targets role and its delegations, and
+/// not from the repository's top-level targets role.
+#[test]
+fn user_targets_take_precedence() {
+    let repo_dir = TempDir::new().unwrap();
+    let datastore = TempDir::new().unwrap();
+    create_repo(repo_dir.path());
+    let metadata_base_url = dir_url(repo_dir.path().join("metadata"));
+    let targets_base_url = dir_url(repo_dir.path().join("targets"));
+
+    let repo = load(
+        &metadata_base_url,
+        &targets_base_url,
+        datastore.path(),
+        None,
+    )
+    .unwrap();
+    let user_targets = user_targets(user_targets_metadata(&repo, &["role1"]), "targetskey-1");
+    let repo = load(
+        &metadata_base_url,
+        &targets_base_url,
+        datastore.path(),
+        Some(user_targets),
+    )
+    .unwrap();
+
+    assert!(repo.user_targets().is_some());
+    for name in &["file1.txt", "file3.txt"] {
+        assert_eq!(
+            read_to_end(repo.read_target(name).unwrap().unwrap()),
+            std::fs::read(targets_path().join(name)).unwrap()
+        );
+    }
+    // file2.txt is only listed by the repository's top-level targets role.
+    assert!(repo.targets().signed.find_target("file2.txt").is_ok());
+    assert!(repo.read_target("file2.txt").unwrap().is_none());
+}
+
+/// Test that the user-selected targets role can fall back to the repository's top-level targets
+/// role by delegating to it.
+#[test]
+fn user_targets_delegate_to_repository_targets() {
+    let repo_dir = TempDir::new().unwrap();
+    let datastore = TempDir::new().unwrap();
+    create_repo(repo_dir.path());
+    let metadata_base_url = dir_url(repo_dir.path().join("metadata"));
+    let targets_base_url = dir_url(repo_dir.path().join("targets"));
+
+    let repo = load(
+        &metadata_base_url,
+        &targets_base_url,
+        datastore.path(),
+        None,
+    )
+    .unwrap();
+    let user_targets = user_targets(user_targets_metadata(&repo, &["targets"]), "targetskey-1");
+    let repo = load(
+        &metadata_base_url,
+        &targets_base_url,
+        datastore.path(),
+        Some(user_targets),
+    )
+    .unwrap();
+
+    for name in &["file1.txt", "file2.txt", "file3.txt"] {
+        assert_eq!(
+            read_to_end(repo.read_target(name).unwrap().unwrap()),
+            std::fs::read(targets_path().join(name)).unwrap()
+        );
+    }
+}
+
+/// Test that user-selected targets metadata that isn't signed by the user's keys is rejected.
+#[test]
+fn user_targets_wrong_key() {
+    let repo_dir = TempDir::new().unwrap();
+    let datastore = TempDir::new().unwrap();
+    create_repo(repo_dir.path());
+    let metadata_base_url = dir_url(repo_dir.path().join("metadata"));
+    let targets_base_url = dir_url(repo_dir.path().join("targets"));
+
+    let repo = load(
+        &metadata_base_url,
+        &targets_base_url,
+        datastore.path(),
+        None,
+    )
+    .unwrap();
+    // Signed with targetskey-1, but the user trusts targetskey.
+    let user_targets = user_targets(user_targets_metadata(&repo, &["role1"]), "targetskey");
+    match load(
+        &metadata_base_url,
+        &targets_base_url,
+        datastore.path(),
+        Some(user_targets),
+    ) {
+        Err(Error::VerifyUserTargets { .. }) => {}
+        other => panic!("expected verification error, got {:?}", other.map(|_| ())),
+    }
+}
+
+/// Test that caching a repository loaded with user-selected targets caches the targets they
+/// select and the metadata needed to load them, and that the report lists the user-selected
+/// targets role.
+#[test]
+fn user_targets_cache_and_report() {
+    let repo_dir = TempDir::new().unwrap();
+    let datastore = TempDir::new().unwrap();
+    create_repo(repo_dir.path());
+    let metadata_base_url = dir_url(repo_dir.path().join("metadata"));
+    let targets_base_url = dir_url(repo_dir.path().join("targets"));
+
+    let repo = load(
+        &metadata_base_url,
+        &targets_base_url,
+        datastore.path(),
+        None,
+    )
+    .unwrap();
+    let metadata = user_targets_metadata(&repo, &[]);
+    let repo = load(
+        &metadata_base_url,
+        &targets_base_url,
+        datastore.path(),
+        Some(user_targets(metadata.clone(), "targetskey-1")),
+    )
+    .unwrap();
+
+    let report = repo.report().unwrap();
+    let user_role = report.roles.last().unwrap();
+    assert_eq!(user_role.name, "user-selected targets");
+    assert_eq!(user_role.delegated_by, None);
+    assert!(user_role.keys.iter().all(|key| key.signed));
+    assert!(report.roles.iter().any(|role| role.name == "role1"));
+
+    let cache_dir = TempDir::new().unwrap();
+    let metadata_dir = cache_dir.path().join("metadata");
+    let targets_dir = cache_dir.path().join("targets");
+    repo.cache(&metadata_dir, &targets_dir, None::<&[&str]>, true)
+        .unwrap();
+    assert!(repo
+        .verify_cache(&metadata_dir, &targets_dir)
+        .unwrap()
+        .is_clean());
+    // Only file3.txt is selected, but role1 is still needed to load the repository's targets.
+    assert_eq!(std::fs::read_dir(&targets_dir).unwrap().count(), 1);
+    assert!(metadata_dir.join("1.role1.json").exists());
+
+    let datastore = TempDir::new().unwrap();
+    let cached_metadata_url = dir_url(&metadata_dir);
+    let cached_targets_url = dir_url(&targets_dir);
+    let cached = load(
+        &cached_metadata_url,
+        &cached_targets_url,
+        datastore.path(),
+        Some(user_targets(metadata, "targetskey-1")),
+    )
+    .unwrap();
+    assert!(cached.read_target("file3.txt").unwrap().is_some());
+    assert!(cached.read_target("file1.txt").unwrap().is_none());
+}
@@ -0,0 +1,141 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use chrono::{Duration, Utc};
+use ring::digest::{digest, SHA384, SHA512};
+use serde_json::Value;
+use std::fs::File;
+use std::io::Write;
+use std::num::NonZeroU64;
+use tempfile::TempDir;
+use test_utils::{dir_url, key, load, root_path, targets_path, test_data};
+use tough::editor::signed::PathExists;
+use tough::editor::RepositoryEditor;
+use tough::error::Error;
+use tough::{FilesystemTransport, Repository, Settings};
+
+mod test_utils;
+
+/// Test verifying local copies of targets, including a delegated target, against the metadata.
+#[test]
+fn test_verify_target_file() {
+    let base = test_data().join("tuf-reference-impl");
+    let datastore = TempDir::new().unwrap();
+    let metadata_base_url = &dir_url(base.join("metadata"));
+    let targets_base_url = &dir_url(base.join("targets"));
+    let repo = Repository::load(
+        &FilesystemTransport,
+        Settings::new(
+            File::open(base.join("metadata").join("1.root.json")).unwrap(),
+            datastore.as_ref(),
+            metadata_base_url,
+            targets_base_url,
+        ),
+    )
+    .unwrap();
+
+    let targets = base.join("targets");
+    repo.verify_target_file("file1.txt", targets.join("file1.txt"))
+        .unwrap();
+    repo.verify_target_file("file3.txt", targets.join("file3.txt"))
+        .unwrap();
+    repo.verify_target_reader("file2.txt", File::open(targets.join("file2.txt")).unwrap())
+        .unwrap();
+
+    // Same length as file1.txt, but different contents.
+    let wrong = b"This is not the right file1.txt";
+    assert_eq!(wrong.len(), 31);
+    match repo.verify_target_reader("file1.txt", &wrong[..]) {
+        Err(Error::HashMismatch { context, .. }) => {
+            assert_eq!(context, "target 'file1.txt' (sha256)")
+        }
+        other => panic!("expected a hash mismatch, got {:?}", other),
+    }
+
+    let dir = TempDir::new().unwrap();
+    let longer = dir.path().join("file1.txt");
+    let mut file = File::create(&longer).unwrap();
+    file.write_all(&std::fs::read(targets.join("file1.txt")).unwrap())
+        .unwrap();
+    file.write_all(b"extra").unwrap();
+    match repo.verify_target_file("file1.txt", &longer) {
+        Err(Error::TargetLengthMismatch {
+            found, expected, ..
+        }) => assert_eq!((found, expected), (36, 31)),
+        other => panic!("expected a length mismatch, got {:?}", other),
+    }
+    match repo.verify_target_reader("file1.txt", File::open(&longer).unwrap()) {
+        Err(Error::TargetLengthMismatch {
+            found, truncated, ..
+        }) => assert_eq!((found, truncated), (32, true)),
+        other => panic!("expected a length mismatch, got {:?}", other),
+    }
+
+    match repo.verify_target_file("file4.txt", targets.join("file1.txt")) {
+        Err(Error::TargetNotListed { name, .. }) => assert_eq!(name, "file4.txt"),
+        other => panic!("expected an unlisted target, got {:?}", other),
+    }
+}
+
+/// Test that every supported hash listed for a target is checked, whatever the case of its hex
+/// digits.
+#[test]
+fn test_verify_target_hashes() {
+    let path = targets_path().join("file1.txt");
+    let contents = std::fs::read(&path).unwrap();
+    let sha384 = hex::encode(digest(&SHA384, &contents));
+    let sha512 = hex::encode(digest(&SHA512, &contents)).to_uppercase();
+
+    let expiration = Utc::now().checked_add_signed(Duration::days(21)).unwrap();
+    let version = NonZeroU64::new(1).unwrap();
+    let repo_dir = TempDir::new().unwrap();
+    let (_, mut target) = RepositoryEditor::<FilesystemTransport>::build_target(&path).unwrap();
+    target
+        .hashes
+        ._extra
+        .insert("sha512".to_owned(), Value::from(sha512));
+    let mut wrong = target.clone();
+    wrong.hashes._extra.insert(
+        "sha384".to_owned(),
+        Value::from(hex::encode(digest(&SHA384, b"other contents"))),
+    );
+    target
+        .hashes
+        ._extra
+        .insert("sha384".to_owned(), Value::from(sha384));
+    let mut editor = RepositoryEditor::<FilesystemTransport>::new(root_path()).unwrap();
+    editor
+        .targets_expires(expiration)
+        .unwrap()
+        .targets_version(version)
+        .unwrap()
+        .snapshot_expires(expiration)
+        .snapshot_version(version)
+        .timestamp_expires(expiration)
+        .timestamp_version(version)
+        .add_target("file1.txt", target)
+        .unwrap()
+        .add_target("wrong.txt", wrong)
+        .unwrap();
+    let signed_repo = editor.sign(&key("snakeoil.pem")).unwrap();
+    signed_repo.write(repo_dir.path().join("metadata")).unwrap();
+    signed_repo
+        .link_targets(
+            targets_path(),
+            repo_dir.path().join("targets"),
+            PathExists::Skip,
+        )
+        .unwrap();
+
+    let datastore = TempDir::new().unwrap();
+    let metadata_base_url = dir_url(repo_dir.path().join("metadata"));
+    let targets_base_url = dir_url(repo_dir.path().join("targets"));
+    let repo = load(&metadata_base_url, &targets_base_url, datastore.path());
+    repo.verify_target_file("file1.txt", &path).unwrap();
+    match repo.verify_target_file("wrong.txt", &path) {
+        Err(Error::HashMismatch { context, .. }) => {
+            assert_eq!(context, "target 'wrong.txt' (sha384)")
+        }
+        other => panic!("expected a hash mismatch, got {:?}", other),
+    }
+}
@@ -1,11 +1,13 @@
 // Copyright 2019 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use crate::build_targets;
+use crate::build_new_targets;
 use crate::datetime::parse_datetime;
 use crate::error::{self, Result};
+use crate::manifest::{parse_custom, Manifest};
 use crate::source::parse_key_source;
 use chrono::{DateTime, Utc};
+use serde_json::Value;
 use snafu::ResultExt;
 use std::num::{NonZeroU64, NonZeroUsize};
 use std::path::PathBuf;
@@ -50,8 +52,22 @@ pub(crate) struct CreateArgs {
     root: PathBuf,
 
     /// Directory of targets
-    #[structopt(short = "t", long = "add-targets")]
-    targets_indir: PathBuf,
+    #[structopt(
+        short = "t",
+        long = "add-targets",
+        required_unless = "targets-manifest"
+    )]
+    targets_indir: Option<PathBuf>,
+
+    /// Custom metadata for each target added, as key=json, like 'version="1.2.3"'; strings must be
+    /// quoted. May be given more than once
+    #[structopt(long = "custom", parse(try_from_str = parse_custom))]
+    custom: Vec<(String, Value)>,
+
+    /// Manifest file (JSON, or TOML if named *.toml) listing targets to add by name, with their
+    /// local paths and custom metadata
+    #[structopt(name = "targets-manifest", long = "targets-manifest")]
+    targets_manifest: Option<PathBuf>,
 
     /// Behavior when a target exists with the same name and hash in the targets directory,
     /// for example from another repository when they share a targets directory.
@@ -63,6 +79,11 @@ pub(crate) struct CreateArgs {
     #[structopt(short = "f", long = "follow")]
     follow: bool,
 
+    /// Name targets by their paths relative to the given directory, like "images/root.img",
+    /// rather than by their file names
+    #[structopt(long = "preserve-paths")]
+    preserve_paths: bool,
+
     /// Number of target hashing threads to run when adding targets
     /// (default: number of cores)
     // No default is specified in structopt here. This is because rayon
@@ -87,7 +108,17 @@ impl CreateArgs {
                 .context(error::InitializeThreadPool)?;
         }
 
-        let targets = build_targets(&self.targets_indir, self.follow)?;
+        let manifest = match &self.targets_manifest {
+            Some(path) => Some(Manifest::load(path)?),
+            None => None,
+        };
+        let targets = build_new_targets(
+            self.targets_indir.as_deref(),
+            self.follow,
+            self.preserve_paths,
+            manifest.as_ref(),
+            &self.custom,
+        )?;
         let mut editor = RepositoryEditor::<FilesystemTransport>::new(&self.root)
             .context(error::EditorCreate { path: &self.root })?;
 
@@ -111,12 +142,19 @@ impl CreateArgs {
 
         let metadata_dir = &self.outdir.join("metadata");
         let targets_outdir = &self.outdir.join("targets");
-        signed_repo
-            .link_targets(&self.targets_indir, targets_outdir, self.target_path_exists)
-            .context(error::LinkTargets {
-                indir: &self.targets_indir,
-                outdir: targets_outdir,
+        if let Some(targets_indir) = &self.targets_indir {
+            signed_repo
+                .link_targets(targets_indir, targets_outdir, self.target_path_exists)
+                .context(error::LinkTargets {
+                    indir: targets_indir,
+                    outdir: targets_outdir,
+                })?;
+        }
+        if let Some(manifest) = &manifest {
+            manifest.publish_targets(targets_outdir, |path, name| {
+                signed_repo.link_target(path, targets_outdir, self.target_path_exists, Some(name))
             })?;
+        }
         signed_repo.write(metadata_dir).context(error::WriteRepo {
             directory: metadata_dir,
         })?;
@@ -10,7 +10,7 @@ use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 use tempfile::tempdir;
 use tough::http::HttpTransport;
-use tough::{ExpirationEnforcement, Limits, Repository, Settings};
+use tough::{Repository, Settings};
 use url::Url;
 
 #[derive(Debug, StructOpt)]
@@ -94,16 +94,12 @@ impl DownloadArgs {
         let repo_dir = tempdir().context(error::TempDir)?;
         let repository = Repository::load(
             &transport,
-            Settings {
-                root: File::open(&root_path).context(error::OpenRoot { path: &root_path })?,
-                datastore: repo_dir.path(),
-                metadata_base_url: &self.metadata_base_url,
-                targets_base_url: &self.targets_base_url,
-                limits: Limits {
-                    ..tough::Limits::default()
-                },
-                expiration_enforcement: ExpirationEnforcement::Safe,
-            },
+            Settings::new(
+                File::open(&root_path).context(error::OpenRoot { path: &root_path })?,
+                repo_dir.path(),
+                &self.metadata_base_url,
+                &self.targets_base_url,
+            ),
         )
         .context(error::Metadata)?;
 
@@ -13,32 +13,29 @@ pub(crate) type Result<T> = std::result::Result<T, Error>;
 #[derive(Debug, Snafu)]
 #[snafu(visibility = "pub(crate)")]
 pub(crate) enum Error {
-    #[snafu(display("Failed to run {}: {}", command_str, source))]
-    CommandExec {
-        command_str: String,
+    #[snafu(display("Cannot determine current directory: {}", source))]
+    CurrentDir {
         source: std::io::Error,
         backtrace: Backtrace,
     },
 
-    #[snafu(display("Command {} failed with {}", command_str, status))]
-    CommandStatus {
-        command_str: String,
-        status: std::process::ExitStatus,
-        backtrace: Backtrace,
-    },
+    #[snafu(display("Custom metadata argument '{}' is invalid: expected key=json", input))]
+    CustomArgInvalid { input: String },
 
-    #[snafu(display("Command {} output is not valid UTF-8: {}", command_str, source))]
-    CommandUtf8 {
-        command_str: String,
-        source: std::string::FromUtf8Error,
-        backtrace: Backtrace,
+    #[snafu(display(
+        "Custom metadata argument '{}' is invalid: value must be JSON, so quote strings: {}",
+        input,
+        source
+    ))]
+    CustomArgJson {
+        input: String,
+        source: serde_json::Error,
     },
 
-    #[snafu(display("Cannot determine current directory: {}", source))]
-    CurrentDir {
-        source: std::io::Error,
-        backtrace: Backtrace,
-    },
+    #[snafu(display(
+        "Custom metadata was given, but no targets to add with --add-targets or --targets-manifest"
+    ))]
+    CustomWithoutTargets,
 
     #[snafu(display("Date argument '{}' is invalid: {}", input, msg))]
     DateArgInvalid { input: String, msg: &'static str },
@@ -144,9 +141,10 @@ pub(crate) enum Error {
         backtrace: Backtrace,
     },
 
-    #[snafu(display("Duplicate key ID: {}", key_id))]
-    KeyDuplicate {
-        key_id: String,
+    #[snafu(display("Failed to generate {} key: {}", key_type, source))]
+    KeyGenerate {
+        key_type: tough::sign::KeyType,
+        source: tough::error::Error,
         backtrace: Backtrace,
     },
 
@@ -156,9 +154,9 @@ pub(crate) enum Error {
         source: tough::schema::Error,
     },
 
-    #[snafu(display("Unable to parse keypair: {}", source))]
-    KeyPairParse {
-        source: tough::error::Error,
+    #[snafu(display("--bits and --exp only apply to RSA keys, not {} keys", key_type))]
+    KeyOptionNotRsa {
+        key_type: tough::sign::KeyType,
         backtrace: Backtrace,
     },
 
@@ -193,6 +191,19 @@ pub(crate) enum Error {
         backtrace: Backtrace,
     },
 
+    #[snafu(display("Failed to parse targets manifest {}: {}", path.display(), source))]
+    ManifestParseToml {
+        path: PathBuf,
+        source: toml::de::Error,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Target '{}' is in both the targets directory and the targets manifest",
+        name
+    ))]
+    ManifestTargetConflict { name: String, backtrace: Backtrace },
+
     #[snafu(display("Metadata error: {}", source))]
     Metadata {
         source: tough::error::Error,
@@ -225,12 +236,37 @@ pub(crate) enum Error {
     #[snafu(display("Path {} is not valid UTF-8", path.display()))]
     PathUtf8 { path: PathBuf, backtrace: Backtrace },
 
+    #[snafu(display("Failed to prune repository in '{}': {}", directory.display(), source))]
+    Prune {
+        directory: PathBuf,
+        source: tough::error::Error,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Failed to load repository for re-signing: {}", source))]
+    RefreshEditor {
+        source: tough::error::Error,
+        backtrace: Backtrace,
+    },
+
     #[snafu(display("Failed to load repository: {}", source))]
     RepoLoad {
         source: tough::error::Error,
         backtrace: Backtrace,
     },
 
+    #[snafu(display("Failed to build repository report: {}", source))]
+    Report {
+        source: tough::error::Error,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Failed to serialize repository report: {}", source))]
+    ReportSerialize {
+        source: serde_json::Error,
+        backtrace: Backtrace,
+    },
+
     #[snafu(display("Failed to copy from response: {}", source))]
     ReqwestCopy {
         source: reqwest::Error,
@@ -243,20 +279,17 @@ pub(crate) enum Error {
         backtrace: Backtrace,
     },
 
-    #[snafu(display("Failed to sign repository: {}", source))]
-    SignRepo {
+    #[snafu(display("Failed to edit root '{}': {}", path.display(), source))]
+    RootEditor {
+        path: PathBuf,
         source: tough::error::Error,
         backtrace: Backtrace,
     },
 
-    #[snafu(display(
-        "Root was signed with {} signatures; it must be signed with at least {}",
-        signature_count,
-        threshold,
-    ))]
-    SignatureRoot {
-        threshold: u64,
-        signature_count: usize,
+    #[snafu(display("Failed to sign repository: {}", source))]
+    SignRepo {
+        source: tough::error::Error,
+        backtrace: Backtrace,
     },
 
     #[snafu(display("Failed to sign '{}': {}", path.display(), source))]
@@ -273,6 +306,13 @@ pub(crate) enum Error {
         backtrace: Backtrace,
     },
 
+    #[snafu(display("Unable to name target '{}' by its path: {}", path.display(), source))]
+    TargetName {
+        path: PathBuf,
+        source: tough::error::Error,
+        backtrace: Backtrace,
+    },
+
     #[snafu(display("Failed to add targets from directory '{}': {}", dir.display(), source))]
     TargetsFromDir {
         dir: PathBuf,
@@ -298,19 +338,6 @@ pub(crate) enum Error {
         backtrace: Backtrace,
     },
 
-    /// Root creates an unloadable repo
-    #[snafu(display(
-        "Unstable root: '{}' role contains {} keys, threshold is {}",
-        role,
-        actual,
-        threshold
-    ))]
-    UnstableRoot {
-        role: tough::schema::RoleType,
-        threshold: u64,
-        actual: usize,
-    },
-
     #[snafu(display("Failed to parse URL \"{}\": {}", url, source))]
     UrlParse {
         url: String,
@@ -318,12 +345,6 @@ pub(crate) enum Error {
         backtrace: Backtrace,
     },
 
-    #[snafu(display("Version number overflow"))]
-    VersionOverflow { backtrace: Backtrace },
-
-    #[snafu(display("Version number is zero"))]
-    VersionZero { backtrace: Backtrace },
-
     #[snafu(display("Failed to walk directory tree '{}': {}", directory.display(), source))]
     WalkDir {
         directory: PathBuf,
@@ -333,7 +354,7 @@ pub(crate) enum Error {
 
     #[snafu(display("Failed write: {}", source))]
     WriteKeySource {
-        source: Box<dyn std::error::Error + Send + Sync + 'static>,
+        source: tough::error::Error,
         backtrace: Backtrace,
     },
 
@@ -0,0 +1,69 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::error::{self, Result};
+use snafu::ResultExt;
+use std::fs::File;
+use std::path::PathBuf;
+use structopt::StructOpt;
+use tempfile::tempdir;
+use tough::http::HttpTransport;
+use tough::{
+    ExpirationEnforcement, FilesystemTransport, Repository, RepositoryReport, Settings, Transport,
+};
+use url::Url;
+
+#[derive(Debug, StructOpt)]
+pub(crate) struct InspectArgs {
+    /// Path to root.json file for the repository
+    #[structopt(short = "r", long = "root")]
+    root: PathBuf,
+
+    /// TUF repository metadata base URL
+    #[structopt(short = "m", long = "metadata-url")]
+    metadata_base_url: Url,
+
+    /// Report on expired metadata instead of failing to load it
+    #[structopt(long)]
+    allow_expired: bool,
+}
+
+impl InspectArgs {
+    pub(crate) fn run(&self) -> Result<()> {
+        // Create a temporary directory where the TUF client can store metadata
+        let workdir = tempdir().context(error::TempDir)?;
+        let settings = Settings::new(
+            File::open(&self.root).context(error::OpenRoot { path: &self.root })?,
+            workdir.path(),
+            self.metadata_base_url.as_str(),
+            // We never load any targets here so the real
+            // `targets_base_url` isn't needed. `tough::Settings` requires
+            // a value so we use `metadata_base_url` as a placeholder
+            self.metadata_base_url.as_str(),
+        )
+        .expiration_enforcement(if self.allow_expired {
+            ExpirationEnforcement::Unsafe
+        } else {
+            ExpirationEnforcement::Safe
+        });
+
+        // Loading a `Repository` with different `Transport`s results in
+        // different types, so each branch builds its own report.
+        let report = if self.metadata_base_url.scheme() == "file" {
+            report(&Repository::load(&FilesystemTransport, settings).context(error::RepoLoad)?)?
+        } else {
+            let transport = HttpTransport::new();
+            report(&Repository::load(&transport, settings).context(error::RepoLoad)?)?
+        };
+
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report).context(error::ReportSerialize)?
+        );
+        Ok(())
+    }
+}
+
+fn report<T: Transport>(repository: &Repository<'_, T>) -> Result<RepositoryReport> {
+    repository.report().context(error::Report)
+}
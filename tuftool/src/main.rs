@@ -18,6 +18,10 @@ mod create_role;
 mod datetime;
 mod download;
 mod error;
+mod inspect;
+mod manifest;
+mod prune;
+mod refresh;
 mod remove_key_role;
 mod remove_role;
 mod root;
@@ -26,20 +30,21 @@ mod update;
 mod update_targets;
 
 use crate::error::Result;
+use crate::manifest::Manifest;
 use rayon::prelude::*;
+use serde_json::Value;
 use simplelog::{ConfigBuilder, LevelFilter, TermLogger, TerminalMode};
-use snafu::{ErrorCompat, OptionExt, ResultExt};
+use snafu::{ensure, ErrorCompat, OptionExt, ResultExt};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
 use structopt::StructOpt;
 use tempfile::NamedTempFile;
+use tough::editor::target_name_from_path;
 use tough::schema::Target;
 use walkdir::WalkDir;
 
-static SPEC_VERSION: &str = "1.0.0";
-
 /// This wrapper enables global options and initializes the logger before running any subcommands.
 #[derive(StructOpt)]
 struct Program {
@@ -76,8 +81,14 @@ enum Command {
     Create(create::CreateArgs),
     /// Download a TUF repository's resources
     Download(download::DownloadArgs),
+    /// Print a JSON report of a TUF repository's roles, expirations, and signing keys
+    Inspect(inspect::InspectArgs),
     /// Update a TUF repository's metadata and optionally add targets
     Update(Box<update::UpdateArgs>),
+    /// Re-sign a TUF repository's timestamp, and optionally its snapshot, without changing targets
+    Refresh(refresh::RefreshArgs),
+    /// Remove old metadata versions and unreferenced targets from a repository's output directory
+    Prune(prune::PruneArgs),
     /// Manipulate a root.json metadata file
     Root(root::Command),
     /// Delegation Commands
@@ -90,7 +101,10 @@ impl Command {
             Command::Create(args) => args.run(),
             Command::Root(root_subcommand) => root_subcommand.run(),
             Command::Download(args) => args.run(),
+            Command::Inspect(args) => args.run(),
             Command::Update(args) => args.run(),
+            Command::Refresh(args) => args.run(),
+            Command::Prune(args) => args.run(),
             Command::Delegation(cmd) => cmd.run(),
         }
     }
@@ -119,8 +133,13 @@ where
 }
 
 // Walk the directory specified, building a map of filename to Target structs.
-// Hashing of the targets is done in parallel
-fn build_targets<P>(indir: P, follow_links: bool) -> Result<HashMap<String, Target>>
+// If `preserve_paths` is set, targets are named by their path relative to the
+// directory instead. Hashing of the targets is done in parallel
+fn build_targets<P>(
+    indir: P,
+    follow_links: bool,
+    preserve_paths: bool,
+) -> Result<HashMap<String, Target>>
 where
     P: AsRef<Path>,
 {
@@ -132,7 +151,7 @@ where
         .filter_map(|entry| match entry {
             Ok(entry) => {
                 if entry.file_type().is_file() {
-                    Some(process_target(entry.path()))
+                    Some(process_target(indir, entry.path(), preserve_paths))
                 } else {
                     None
                 }
@@ -142,10 +161,42 @@ where
         .collect()
 }
 
-fn process_target(path: &Path) -> Result<(String, Target)> {
+// Build the targets to add from the directory and manifest given, if any,
+// and add the custom metadata given to each of them. A target may not be
+// both in the directory and in the manifest.
+fn build_new_targets(
+    indir: Option<&Path>,
+    follow_links: bool,
+    preserve_paths: bool,
+    manifest: Option<&Manifest>,
+    custom: &[(String, Value)],
+) -> Result<HashMap<String, Target>> {
+    let mut targets = match indir {
+        Some(indir) => build_targets(indir, follow_links, preserve_paths)?,
+        None => HashMap::new(),
+    };
+    if let Some(manifest) = manifest {
+        for (name, target) in manifest.build_targets()? {
+            ensure!(
+                !targets.contains_key(&name),
+                error::ManifestTargetConflict { name }
+            );
+            targets.insert(name, target);
+        }
+    }
+    crate::manifest::add_custom(&mut targets, custom);
+    Ok(targets)
+}
+
+fn process_target(indir: &Path, path: &Path, preserve_paths: bool) -> Result<(String, Target)> {
     // Build a Target from the path given. If it is not a file, this will fail
     let target = Target::from_path(path).context(error::TargetFromPath { path })?;
 
+    if preserve_paths {
+        let target_name = target_name_from_path(indir, path).context(error::TargetName { path })?;
+        return Ok((target_name, target));
+    }
+
     // Get the file name as a string
     let target_name = path
         .file_name()
@@ -0,0 +1,118 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Custom target metadata given on the command line, and manifest files that list the targets to
+//! add along with their custom metadata.
+
+use crate::error::{self, Result};
+use crate::load_file;
+use rayon::prelude::*;
+use serde::Deserialize;
+use serde_json::Value;
+use snafu::{OptionExt, ResultExt};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use tough::schema::Target;
+
+/// Parses a custom metadata argument of the form `key=json`, like `version="1.2.3"`. The value
+/// must be valid JSON, so strings must be quoted.
+pub(crate) fn parse_custom(input: &str) -> Result<(String, Value)> {
+    let mut parts = input.splitn(2, '=');
+    let key = parts
+        .next()
+        .filter(|key| !key.is_empty())
+        .context(error::CustomArgInvalid { input })?;
+    let value = parts.next().context(error::CustomArgInvalid { input })?;
+    let value = serde_json::from_str(value).context(error::CustomArgJson { input })?;
+    Ok((key.to_owned(), value))
+}
+
+/// Adds the given custom metadata to each target. Custom metadata a target already has, such as
+/// from a manifest, takes precedence.
+pub(crate) fn add_custom(targets: &mut HashMap<String, Target>, custom: &[(String, Value)]) {
+    for target in targets.values_mut() {
+        for (key, value) in custom {
+            target
+                .custom
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+    }
+}
+
+/// A manifest of targets to add, read from a JSON file, or a TOML file if its name ends in
+/// `.toml`. Each target is named by its key in `targets`:
+///
+/// ```toml
+/// [targets."images/root.img"]
+/// path = "build/root.img"
+///
+/// [targets."images/root.img".custom]
+/// version = "1.2.3"
+/// ```
+///
+/// Relative paths are relative to the directory containing the manifest.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Manifest {
+    #[serde(default)]
+    targets: HashMap<String, ManifestTarget>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ManifestTarget {
+    /// The local path of the target file.
+    path: PathBuf,
+    /// The target's custom metadata.
+    #[serde(default)]
+    custom: HashMap<String, Value>,
+}
+
+impl Manifest {
+    /// Reads the manifest at `path`, resolving the targets' paths relative to it.
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        let mut manifest: Manifest = if path.extension() == Some(OsStr::new("toml")) {
+            let data = std::fs::read_to_string(path).context(error::FileOpen { path })?;
+            toml::from_str(&data).context(error::ManifestParseToml { path })?
+        } else {
+            load_file(path)?
+        };
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        for target in manifest.targets.values_mut() {
+            target.path = dir.join(&target.path);
+        }
+        Ok(manifest)
+    }
+
+    /// Builds the listed targets, with their custom metadata. Hashing of the targets is done in
+    /// parallel.
+    pub(crate) fn build_targets(&self) -> Result<HashMap<String, Target>> {
+        self.targets
+            .par_iter()
+            .map(|(name, manifest_target)| {
+                let path = &manifest_target.path;
+                let mut target = Target::from_path(path).context(error::TargetFromPath { path })?;
+                target.custom = manifest_target.custom.clone();
+                Ok((name.clone(), target))
+            })
+            .collect()
+    }
+
+    /// Links or copies each listed target into `outdir` with `f`, which is given the target's
+    /// local path and name, like `SignedRepository::link_target`.
+    pub(crate) fn publish_targets<F>(&self, outdir: &Path, f: F) -> Result<()>
+    where
+        F: Fn(&Path, &str) -> tough::error::Result<()>,
+    {
+        std::fs::create_dir_all(outdir).context(error::DirCreate { path: outdir })?;
+        for (name, target) in &self.targets {
+            f(&target.path, name).context(error::LinkTargets {
+                indir: &target.path,
+                outdir,
+            })?;
+        }
+        Ok(())
+    }
+}
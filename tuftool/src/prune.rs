@@ -0,0 +1,65 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::error::{self, Result};
+use snafu::ResultExt;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use structopt::StructOpt;
+use tough::editor::prune::UnknownTargets;
+
+#[derive(Debug, StructOpt)]
+pub(crate) struct PruneArgs {
+    /// The repository's output directory, containing `metadata` and `targets` directories
+    #[structopt(short = "o", long = "outdir")]
+    outdir: PathBuf,
+
+    /// Number of recent snapshots to keep, along with the metadata they refer to; clients that
+    /// are partway through an update may still need the older versions
+    #[structopt(long = "keep", default_value = "3")]
+    keep: NonZeroUsize,
+
+    /// Also remove files in the targets directory that aren't listed by any of the repository's
+    /// metadata; don't use this if the targets directory is shared with other repositories
+    #[structopt(long = "remove-unknown-targets")]
+    remove_unknown_targets: bool,
+
+    /// List the files that would be removed without removing them
+    #[structopt(long = "dry-run")]
+    dry_run: bool,
+}
+
+impl PruneArgs {
+    pub(crate) fn run(&self) -> Result<()> {
+        let report = tough::editor::prune::prune(
+            self.outdir.join("metadata"),
+            self.outdir.join("targets"),
+            self.keep,
+            if self.remove_unknown_targets {
+                UnknownTargets::Remove
+            } else {
+                UnknownTargets::Keep
+            },
+            self.dry_run,
+        )
+        .context(error::Prune {
+            directory: &self.outdir,
+        })?;
+
+        let action = if self.dry_run {
+            "Would remove"
+        } else {
+            "Removed"
+        };
+        for path in report.metadata.iter().chain(&report.targets) {
+            println!("{} {}", action, path.display());
+        }
+        for path in &report.unknown_targets {
+            eprintln!(
+                "Kept {}, which no metadata lists; remove it with --remove-unknown-targets",
+                path.display()
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::datetime::parse_datetime;
+use crate::error::{self, Result};
+use crate::source::parse_key_source;
+use chrono::{DateTime, Utc};
+use snafu::{OptionExt, ResultExt};
+use std::fs::File;
+use std::num::NonZeroU64;
+use std::path::PathBuf;
+use structopt::StructOpt;
+use tempfile::tempdir;
+use tough::editor::refresh::RefreshEditor;
+use tough::http::HttpTransport;
+use tough::key_source::KeySource;
+use tough::{ExpirationEnforcement, FilesystemTransport, Repository};
+use url::Url;
+
+#[derive(Debug, StructOpt)]
+pub(crate) struct RefreshArgs {
+    /// Key files to sign with; only the timestamp key (and the snapshot key, with --snapshot) is
+    /// needed
+    #[structopt(short = "k", long = "key", required = true, parse(try_from_str = parse_key_source))]
+    keys: Vec<Box<dyn KeySource>>,
+
+    /// Re-sign snapshot.json as well as timestamp.json
+    #[structopt(long = "snapshot")]
+    snapshot: bool,
+
+    /// Version of snapshot.json file; defaults to one more than the current version
+    #[structopt(long = "snapshot-version")]
+    snapshot_version: Option<NonZeroU64>,
+    /// Expiration of snapshot.json file; can be in full RFC 3339 format, or something like 'in
+    /// 7 days'. Required with --snapshot
+    #[structopt(long = "snapshot-expires", parse(try_from_str = parse_datetime))]
+    snapshot_expires: Option<DateTime<Utc>>,
+
+    /// Version of timestamp.json file; defaults to one more than the current version
+    #[structopt(long = "timestamp-version")]
+    timestamp_version: Option<NonZeroU64>,
+    /// Expiration of timestamp.json file; can be in full RFC 3339 format, or something like 'in
+    /// 7 days'
+    #[structopt(long = "timestamp-expires", parse(try_from_str = parse_datetime))]
+    timestamp_expires: DateTime<Utc>,
+
+    /// Path to root.json file for the repository
+    #[structopt(short = "r", long = "root")]
+    root: PathBuf,
+
+    /// TUF repository metadata base URL
+    #[structopt(short = "m", long = "metadata-url")]
+    metadata_base_url: Url,
+
+    /// The directory where the re-signed metadata will be written
+    #[structopt(short = "o", long = "outdir")]
+    outdir: PathBuf,
+}
+
+impl RefreshArgs {
+    pub(crate) fn run(&self) -> Result<()> {
+        // Create a temporary directory where the TUF client can store metadata
+        let workdir = tempdir().context(error::TempDir)?;
+        let settings = tough::Settings::new(
+            File::open(&self.root).context(error::FileOpen { path: &self.root })?,
+            workdir.path(),
+            self.metadata_base_url.as_str(),
+            // We never load any targets here so the real
+            // `targets_base_url` isn't needed. `tough::Settings` requires
+            // a value so we use `metadata_base_url` as a placeholder
+            self.metadata_base_url.as_str(),
+        )
+        // Refreshing is how an expired timestamp or snapshot gets replaced, so expired metadata
+        // must load. Signatures are still verified.
+        .expiration_enforcement(ExpirationEnforcement::Unsafe);
+
+        let mut editor = if self.metadata_base_url.scheme() == "file" {
+            let repository =
+                Repository::load(&FilesystemTransport, settings).context(error::RepoLoad)?;
+            RefreshEditor::from_repo(&repository).context(error::RefreshEditor)?
+        } else {
+            let transport = HttpTransport::new();
+            let repository = Repository::load(&transport, settings).context(error::RepoLoad)?;
+            RefreshEditor::from_repo(&repository).context(error::RefreshEditor)?
+        };
+
+        editor.timestamp_expires(self.timestamp_expires);
+        if let Some(timestamp_version) = self.timestamp_version {
+            editor.timestamp_version(timestamp_version);
+        }
+        let signed = if self.snapshot {
+            editor.snapshot_expires(self.snapshot_expires.context(error::Missing {
+                what: "snapshot expiration",
+            })?);
+            if let Some(snapshot_version) = self.snapshot_version {
+                editor.snapshot_version(snapshot_version);
+            }
+            editor
+                .sign_snapshot_and_timestamp(&self.keys)
+                .context(error::SignRepo)?
+        } else {
+            editor.sign_timestamp(&self.keys).context(error::SignRepo)?
+        };
+
+        let metadata_dir = &self.outdir.join("metadata");
+        signed.write(metadata_dir).context(error::WriteRepo {
+            directory: metadata_dir,
+        })?;
+
+        Ok(())
+    }
+}
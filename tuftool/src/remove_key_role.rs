@@ -16,7 +16,7 @@ use tough::http::HttpTransport;
 use tough::key_source::KeySource;
 use tough::schema::decoded::{Decoded, Hex};
 use tough::Transport;
-use tough::{ExpirationEnforcement, FilesystemTransport, Limits, Repository};
+use tough::{FilesystemTransport, Repository};
 use url::Url;
 
 #[derive(Debug, StructOpt)]
@@ -60,14 +60,12 @@ impl RemoveKeyArgs {
         // load the repo
         let datastore = tempdir().context(error::TempDir)?;
         // We don't do anything with targets so we will use metadata url
-        let settings = tough::Settings {
-            root: File::open(&self.root).unwrap(),
-            datastore: &datastore.path(),
-            metadata_base_url: self.metadata_base_url.as_str(),
-            targets_base_url: self.metadata_base_url.as_str(),
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        };
+        let settings = tough::Settings::new(
+            File::open(&self.root).unwrap(),
+            &datastore.path(),
+            self.metadata_base_url.as_str(),
+            self.metadata_base_url.as_str(),
+        );
 
         // Load the `Repository` into the `TargetsEditor`
         // Loading a `Repository` with different `Transport`s results in
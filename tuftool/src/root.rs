@@ -5,21 +5,19 @@ use crate::datetime::parse_datetime;
 use crate::error::{self, Result};
 use crate::source::parse_key_source;
 use crate::{load_file, write_file};
-use chrono::{DateTime, Timelike, Utc};
-use maplit::hashmap;
+use chrono::{DateTime, Utc};
 use ring::rand::SystemRandom;
 use snafu::{ensure, OptionExt, ResultExt};
-use std::collections::HashMap;
 use std::io::Write;
 use std::num::NonZeroU64;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 use tempfile::NamedTempFile;
-use tough::editor::signed::SignedRole;
+use tough::editor::RootEditor;
 use tough::key_source::KeySource;
 use tough::schema::decoded::{Decoded, Hex};
-use tough::schema::{key::Key, KeyHolder, RoleKeys, RoleType, Root, Signed};
-use tough::sign::{parse_keypair, Sign};
+use tough::schema::{RoleType, Root, Signed};
+use tough::sign::{generate_key, generate_rsa_key, KeyType};
 
 #[derive(Debug, StructOpt)]
 pub(crate) enum Command {
@@ -72,6 +70,26 @@ pub(crate) enum Command {
         /// file)
         role: Option<RoleType>,
     },
+    /// Generate a new key pair, saving it to a file, and add it to a role
+    GenKey {
+        /// Path to root.json
+        path: PathBuf,
+        /// Where to write the new key
+        #[structopt(parse(try_from_str = parse_key_source))]
+        key_source: Box<dyn KeySource>,
+        /// Type of new key: ed25519, ecdsa, or rsa
+        #[structopt(short = "t", long = "type")]
+        key_type: KeyType,
+        /// Bit length of new key, only for RSA (2048 to 8192) [default: 2048]
+        #[structopt(short = "b", long = "bits")]
+        bits: Option<u16>,
+        /// Public exponent of new key, only for RSA [default: 65537]
+        #[structopt(short = "e", long = "exp")]
+        exponent: Option<u32>,
+        /// The role to add the key to
+        #[structopt(short = "r", long = "role")]
+        roles: Vec<RoleType>,
+    },
     /// Generate a new RSA key pair, saving it to a file, and add it to a role
     GenRsaKey {
         /// Path to root.json
@@ -79,7 +97,7 @@ pub(crate) enum Command {
         /// Where to write the new key
         #[structopt(parse(try_from_str = parse_key_source))]
         key_source: Box<dyn KeySource>,
-        /// Bit length of new key
+        /// Bit length of new key (2048 to 8192)
         #[structopt(short = "b", long = "bits", default_value = "2048")]
         bits: u16,
         /// Public exponent of new key
@@ -98,21 +116,6 @@ pub(crate) enum Command {
     },
 }
 
-macro_rules! role_keys {
-    ($threshold:expr) => {
-        RoleKeys {
-            keyids: Vec::new(),
-            threshold: $threshold,
-            _extra: HashMap::new(),
-        }
-    };
-
-    () => {
-        // absurdly high threshold value so that someone realizes they need to change this
-        role_keys!(NonZeroU64::new(1507).unwrap())
-    };
-}
-
 impl Command {
     pub(crate) fn run(self) -> Result<()> {
         match self {
@@ -130,194 +133,116 @@ impl Command {
                 key_source,
             } => Command::add_key(&path, &roles, &key_source),
             Command::RemoveKey { path, key_id, role } => Command::remove_key(&path, &key_id, role),
+            Command::GenKey {
+                path,
+                roles,
+                key_source,
+                key_type,
+                bits,
+                exponent,
+            } => Command::gen_key(&path, &roles, &key_source, key_type, bits, exponent),
             Command::GenRsaKey {
                 path,
                 roles,
                 key_source,
                 bits,
                 exponent,
-            } => Command::gen_rsa_key(&path, &roles, &key_source, bits, exponent),
+            } => Command::gen_key(
+                &path,
+                &roles,
+                &key_source,
+                KeyType::Rsa,
+                Some(bits),
+                Some(exponent),
+            ),
             Command::Sign { path, key_source } => Command::sign(&path, key_source),
         }
     }
 
     fn init(path: &PathBuf) -> Result<()> {
-        write_file(
-            path,
-            &Signed {
-                signed: Root {
-                    spec_version: crate::SPEC_VERSION.to_owned(),
-                    consistent_snapshot: true,
-                    version: NonZeroU64::new(1).unwrap(),
-                    expires: round_time(Utc::now()),
-                    keys: HashMap::new(),
-                    roles: hashmap! {
-                        RoleType::Root => role_keys!(),
-                        RoleType::Snapshot => role_keys!(),
-                        RoleType::Targets => role_keys!(),
-                        RoleType::Timestamp => role_keys!(),
-                    },
-                    _extra: HashMap::new(),
-                },
-                signatures: Vec::new(),
-            },
-        )
+        write_unsigned(path, &RootEditor::new())
     }
 
     fn bump_version(path: &PathBuf) -> Result<()> {
-        let mut root: Signed<Root> = load_file(path)?;
-        root.signed.version = NonZeroU64::new(
-            root.signed
-                .version
-                .get()
-                .checked_add(1)
-                .context(error::VersionOverflow)?,
-        )
-        .context(error::VersionZero)?;
-        clear_sigs(&mut root);
-        write_file(path, &root)
+        let mut editor = load_editor(path)?;
+        editor.bump_version().context(error::RootEditor { path })?;
+        write_unsigned(path, &editor)
     }
 
     fn expire(path: &PathBuf, time: &DateTime<Utc>) -> Result<()> {
-        let mut root: Signed<Root> = load_file(path)?;
-        root.signed.expires = round_time(*time);
-        clear_sigs(&mut root);
-        write_file(path, &root)
+        let mut editor = load_editor(path)?;
+        editor.expires(*time);
+        write_unsigned(path, &editor)
     }
 
     fn set_threshold(path: &PathBuf, role: RoleType, threshold: NonZeroU64) -> Result<()> {
-        let mut root: Signed<Root> = load_file(path)?;
-        root.signed
-            .roles
-            .entry(role)
-            .and_modify(|rk| rk.threshold = threshold)
-            .or_insert_with(|| role_keys!(threshold));
-        clear_sigs(&mut root);
-        write_file(path, &root)
+        let mut editor = load_editor(path)?;
+        editor.threshold(role, threshold);
+        write_unsigned(path, &editor)
     }
 
     #[allow(clippy::borrowed_box)]
     fn add_key(path: &PathBuf, roles: &[RoleType], key_source: &Box<dyn KeySource>) -> Result<()> {
-        let mut root: Signed<Root> = load_file(path)?;
-        let key_pair = key_source
+        let mut editor = load_editor(path)?;
+        let key = key_source
             .as_sign()
             .context(error::KeyPairFromKeySource)?
             .tuf_key();
-        let key_id = hex::encode(add_key(&mut root.signed, roles, key_pair)?);
-        clear_sigs(&mut root);
+        let key_id = hex::encode(key.key_id().context(error::KeyId)?);
+        editor
+            .add_key(key, roles)
+            .context(error::RootEditor { path })?;
         println!("{}", key_id);
-        write_file(path, &root)
+        write_unsigned(path, &editor)
     }
 
     fn remove_key(path: &PathBuf, key_id: &Decoded<Hex>, role: Option<RoleType>) -> Result<()> {
-        let mut root: Signed<Root> = load_file(path)?;
-        if let Some(role) = role {
-            if let Some(role_keys) = root.signed.roles.get_mut(&role) {
-                role_keys
-                    .keyids
-                    .iter()
-                    .position(|k| k.eq(key_id))
-                    .map(|pos| role_keys.keyids.remove(pos));
-            }
-        } else {
-            for role_keys in root.signed.roles.values_mut() {
-                role_keys
-                    .keyids
-                    .iter()
-                    .position(|k| k.eq(key_id))
-                    .map(|pos| role_keys.keyids.remove(pos));
-            }
-            root.signed.keys.remove(key_id);
-        }
-        clear_sigs(&mut root);
-        write_file(path, &root)
+        let mut editor = load_editor(path)?;
+        editor.remove_key(key_id, role);
+        write_unsigned(path, &editor)
     }
 
     #[allow(clippy::borrowed_box)]
-    fn gen_rsa_key(
+    fn gen_key(
         path: &PathBuf,
         roles: &[RoleType],
         key_source: &Box<dyn KeySource>,
-        bits: u16,
-        exponent: u32,
+        key_type: KeyType,
+        bits: Option<u16>,
+        exponent: Option<u32>,
     ) -> Result<()> {
-        let mut root: Signed<Root> = load_file(path)?;
+        let mut editor = load_editor(path)?;
 
-        // ring doesn't support RSA key generation yet
-        // https://github.com/briansmith/ring/issues/219
-        let mut command = std::process::Command::new("openssl");
-        command.args(&["genpkey", "-algorithm", "RSA", "-pkeyopt"]);
-        command.arg(format!("rsa_keygen_bits:{}", bits));
-        command.arg("-pkeyopt");
-        command.arg(format!("rsa_keygen_pubexp:{}", exponent));
-
-        let command_str = format!("{:?}", command);
-        let output = command.output().context(error::CommandExec {
-            command_str: &command_str,
-        })?;
-        ensure!(
-            output.status.success(),
-            error::CommandStatus {
-                command_str: &command_str,
-                status: output.status
+        let rng = SystemRandom::new();
+        let generated = match key_type {
+            KeyType::Ed25519 | KeyType::Ecdsa => {
+                ensure!(
+                    bits.is_none() && exponent.is_none(),
+                    error::KeyOptionNotRsa { key_type }
+                );
+                generate_key(key_type, &rng)
             }
-        );
-        let stdout =
-            String::from_utf8(output.stdout).context(error::CommandUtf8 { command_str })?;
-
-        let key_pair = parse_keypair(stdout.as_bytes()).context(error::KeyPairParse)?;
-        let key_id = hex::encode(add_key(&mut root.signed, roles, key_pair.tuf_key())?);
-        key_source
-            .write(&stdout, &key_id)
+            KeyType::Rsa => generate_rsa_key(
+                bits.unwrap_or(2048).into(),
+                exponent.unwrap_or(65537).into(),
+                &rng,
+            ),
+        }
+        .context(error::KeyGenerate { key_type })?;
+        editor
+            .add_key(generated.key.clone(), roles)
+            .context(error::RootEditor { path })?;
+        let key_id = generated
+            .write(key_source.as_ref())
             .context(error::WriteKeySource)?;
-        clear_sigs(&mut root);
         println!("{}", key_id);
-        write_file(path, &root)
+        write_unsigned(path, &editor)
     }
 
     fn sign(path: &PathBuf, key_source: Box<dyn KeySource>) -> Result<()> {
-        let root: Signed<Root> = load_file(path)?;
-
-        let signed_root = SignedRole::new(
-            root.signed.clone(),
-            &KeyHolder::Root(root.signed),
-            &[key_source],
-            &SystemRandom::new(),
-        )
-        .context(error::SignRoot { path })?;
-
-        // Quick check that root is signed by enough key IDs
-        for (roletype, rolekeys) in &signed_root.signed().signed.roles {
-            if rolekeys.threshold.get() > rolekeys.keyids.len() as u64 {
-                return Err(error::Error::UnstableRoot {
-                    role: *roletype,
-                    threshold: rolekeys.threshold.get(),
-                    actual: rolekeys.keyids.len(),
-                });
-            }
-        }
-
-        // Signature check for root
-        let threshold = signed_root
-            .signed()
-            .signed
-            .roles
-            .get(&RoleType::Root)
-            .ok_or_else(|| error::Error::UnstableRoot {
-                // The code should never reach this point
-                role: RoleType::Root,
-                threshold: 0,
-                actual: 0,
-            })?
-            .threshold
-            .get();
-        let signature_count = signed_root.signed().signatures.len();
-        if threshold > signature_count as u64 {
-            return Err(error::Error::SignatureRoot {
-                threshold,
-                signature_count,
-            });
-        }
+        let signed_root = load_editor(path)?
+            .sign(&[key_source])
+            .context(error::SignRoot { path })?;
 
         // Use `tempfile::NamedTempFile::persist` to perform an atomic file write.
         let parent = path.parent().context(error::PathParent { path })?;
@@ -331,43 +256,13 @@ impl Command {
     }
 }
 
-fn round_time(time: DateTime<Utc>) -> DateTime<Utc> {
-    // `Timelike::with_nanosecond` returns None only when passed a value >= 2_000_000_000
-    time.with_nanosecond(0).unwrap()
-}
-
-/// Removes signatures from a role. Useful if the content is updated.
-fn clear_sigs<T>(role: &mut Signed<T>) {
-    role.signatures.clear();
+/// Loads the root.json at `path` for editing.
+fn load_editor(path: &Path) -> Result<RootEditor> {
+    let root: Signed<Root> = load_file(path)?;
+    Ok(RootEditor::from_root(root.signed))
 }
 
-/// Adds a key to the root role if not already present, and adds its key ID to the specified role.
-fn add_key(root: &mut Root, role: &[RoleType], key: Key) -> Result<Decoded<Hex>> {
-    let key_id = if let Some((key_id, _)) = root
-        .keys
-        .iter()
-        .find(|(_, candidate_key)| key.eq(candidate_key))
-    {
-        key_id.clone()
-    } else {
-        // Key isn't present yet, so we need to add it
-        let key_id = key.key_id().context(error::KeyId)?;
-        ensure!(
-            !root.keys.contains_key(&key_id),
-            error::KeyDuplicate {
-                key_id: hex::encode(&key_id)
-            }
-        );
-        root.keys.insert(key_id.clone(), key);
-        key_id
-    };
-
-    for r in role {
-        let entry = root.roles.entry(*r).or_insert_with(|| role_keys!());
-        if !entry.keyids.contains(&key_id) {
-            entry.keyids.push(key_id.clone());
-        }
-    }
-
-    Ok(key_id)
+/// Writes the edited root to `path` without signatures, since any change invalidates them.
+fn write_unsigned(path: &Path, editor: &RootEditor) -> Result<()> {
+    write_file(path, &Signed::new(editor.root().clone(), Vec::new()))
 }
@@ -1,12 +1,14 @@
 // Copyright 2019 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use crate::build_targets;
+use crate::build_new_targets;
 use crate::datetime::parse_datetime;
 use crate::error::{self, Result};
+use crate::manifest::{parse_custom, Manifest};
 use crate::source::parse_key_source;
 use chrono::{DateTime, Utc};
-use snafu::ResultExt;
+use serde_json::Value;
+use snafu::{ensure, ResultExt};
 use std::fs::File;
 use std::num::NonZeroU64;
 use std::num::NonZeroUsize;
@@ -18,7 +20,7 @@ use tough::editor::targets::TargetsEditor;
 use tough::http::HttpTransport;
 use tough::key_source::KeySource;
 use tough::Transport;
-use tough::{ExpirationEnforcement, FilesystemTransport, Limits, Repository};
+use tough::{FilesystemTransport, Repository};
 use url::Url;
 
 #[derive(Debug, StructOpt)]
@@ -48,6 +50,16 @@ pub(crate) struct UpdateTargetsArgs {
     #[structopt(short = "t", long = "add-targets")]
     targets_indir: Option<PathBuf>,
 
+    /// Custom metadata for each target added, as key=json, like 'version="1.2.3"'; strings must be
+    /// quoted. May be given more than once
+    #[structopt(long = "custom", parse(try_from_str = parse_custom))]
+    custom: Vec<(String, Value)>,
+
+    /// Manifest file (JSON, or TOML if named *.toml) listing targets to add by name, with their
+    /// local paths and custom metadata
+    #[structopt(long = "targets-manifest")]
+    targets_manifest: Option<PathBuf>,
+
     /// The directory where the repository will be written
     #[structopt(short = "o", long = "outdir")]
     outdir: PathBuf,
@@ -56,6 +68,11 @@ pub(crate) struct UpdateTargetsArgs {
     #[structopt(short = "f", long = "follow")]
     follow: bool,
 
+    /// Name targets by their paths relative to the given directory, like "images/root.img",
+    /// rather than by their file names
+    #[structopt(long = "preserve-paths")]
+    preserve_paths: bool,
+
     /// Number of target hashing threads to run when adding targets
     /// (default: number of cores)
     // No default is specified in structopt here. This is because rayon
@@ -73,17 +90,22 @@ pub(crate) struct UpdateTargetsArgs {
 
 impl UpdateTargetsArgs {
     pub(crate) fn run(&self, role: &str) -> Result<()> {
+        ensure!(
+            self.custom.is_empty()
+                || self.targets_indir.is_some()
+                || self.targets_manifest.is_some(),
+            error::CustomWithoutTargets
+        );
+
         // load the repo
         let datastore = tempdir().context(error::TempDir)?;
-        let settings = tough::Settings {
-            root: File::open(&self.root).unwrap(),
-            datastore: &datastore.path(),
-            metadata_base_url: self.metadata_base_url.as_str(),
+        let settings = tough::Settings::new(
+            File::open(&self.root).unwrap(),
+            &datastore.path(),
+            self.metadata_base_url.as_str(),
             // We don't do anything with targets so we will use metadata url
-            targets_base_url: self.metadata_base_url.as_str(),
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        };
+            self.metadata_base_url.as_str(),
+        );
 
         // Load the `Repository` into the `RepositoryEditor`
         // Loading a `Repository` with different `Transport`s results in
@@ -114,10 +136,14 @@ impl UpdateTargetsArgs {
     {
         editor.version(self.version).expires(self.expires);
 
-        // If the "add-targets" argument was passed, build a list of targets
-        // and add them to the repository. If a user specifies job count we
-        // override the default, which is the number of cores.
-        if let Some(ref targets_indir) = self.targets_indir {
+        // If the "add-targets" or "targets-manifest" argument was passed, build
+        // a list of targets and add them to the repository. If a user specifies
+        // job count we override the default, which is the number of cores.
+        let manifest = match &self.targets_manifest {
+            Some(path) => Some(Manifest::load(path)?),
+            None => None,
+        };
+        if self.targets_indir.is_some() || manifest.is_some() {
             if let Some(jobs) = self.jobs {
                 rayon::ThreadPoolBuilder::new()
                     .num_threads(usize::from(jobs))
@@ -125,7 +151,13 @@ impl UpdateTargetsArgs {
                     .context(error::InitializeThreadPool)?;
             }
 
-            let new_targets = build_targets(&targets_indir, self.follow)?;
+            let new_targets = build_new_targets(
+                self.targets_indir.as_deref(),
+                self.follow,
+                self.preserve_paths,
+                manifest.as_ref(),
+                &self.custom,
+            )?;
 
             for (filename, target) in new_targets {
                 editor.add_target(&filename, target);
@@ -136,8 +168,8 @@ impl UpdateTargetsArgs {
         let signed_role = editor.sign(&self.keys).context(error::SignRepo)?;
 
         // Copy any targets that were added
+        let targets_outdir = &self.outdir.join("targets");
         if let Some(ref targets_indir) = self.targets_indir {
-            let targets_outdir = &self.outdir.join("targets");
             signed_role
                 .copy_targets(&targets_indir, &targets_outdir, self.target_path_exists)
                 .context(error::LinkTargets {
@@ -145,6 +177,11 @@ impl UpdateTargetsArgs {
                     outdir: targets_outdir,
                 })?;
         };
+        if let Some(manifest) = &manifest {
+            manifest.publish_targets(targets_outdir, |path, name| {
+                signed_role.copy_target(path, targets_outdir, self.target_path_exists, Some(name))
+            })?;
+        }
 
         // Write the metadata to the outdir
         let metadata_dir = &self.outdir.join("metadata");
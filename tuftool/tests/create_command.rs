@@ -7,7 +7,7 @@ use assert_cmd::Command;
 use chrono::{Duration, Utc};
 use std::fs::File;
 use tempfile::TempDir;
-use tough::{ExpirationEnforcement, Limits, Repository, Settings};
+use tough::{Repository, Settings};
 
 #[test]
 // Ensure we can read a repo created by the `tuftool` binary using the `tough` library
@@ -60,14 +60,12 @@ fn create_command() {
     let targets_base_url = &test_utils::dir_url(repo_dir.path().join("targets"));
     let repo = Repository::load(
         &tough::FilesystemTransport,
-        Settings {
-            root: File::open(root_json).unwrap(),
-            datastore: load_dir.as_ref(),
+        Settings::new(
+            File::open(root_json).unwrap(),
+            load_dir.as_ref(),
             metadata_base_url,
             targets_base_url,
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        ),
     )
     .unwrap();
 
@@ -95,14 +93,19 @@ fn create_command() {
     assert_eq!(repo.targets().signatures.len(), 1);
 
     // Ensure the snapshot.json file is correct
-    assert_eq!(repo.snapshot().signed.version.get(), snapshot_version);
-    assert_eq!(repo.snapshot().signed.expires, snapshot_expiration);
-    assert_eq!(repo.snapshot().signed.meta.len(), 1);
     assert_eq!(
-        repo.snapshot().signed.meta["targets.json"].version.get(),
+        repo.snapshot().unwrap().signed.version.get(),
+        snapshot_version
+    );
+    assert_eq!(repo.snapshot().unwrap().signed.expires, snapshot_expiration);
+    assert_eq!(repo.snapshot().unwrap().signed.meta.len(), 1);
+    assert_eq!(
+        repo.snapshot().unwrap().signed.meta["targets.json"]
+            .version
+            .get(),
         targets_version
     );
-    assert_eq!(repo.snapshot().signatures.len(), 1);
+    assert_eq!(repo.snapshot().unwrap().signatures.len(), 1);
 
     // Ensure the timestamp.json file is correct
     assert_eq!(repo.timestamp().signed.version.get(), timestamp_version);
@@ -112,7 +115,239 @@ fn create_command() {
         repo.timestamp().signed.meta["snapshot.json"].version.get(),
         snapshot_version
     );
-    assert_eq!(repo.snapshot().signatures.len(), 1);
+    assert_eq!(repo.snapshot().unwrap().signatures.len(), 1);
+}
+
+#[test]
+// Ensure that targets with the same file name in different directories are kept apart when they
+// are named by their paths
+fn create_command_preserve_paths() {
+    let expiration = Utc::now().checked_add_signed(Duration::days(3)).unwrap();
+    let root_json = test_utils::test_data().join("simple-rsa").join("root.json");
+    let root_key = test_utils::test_data().join("snakeoil.pem");
+    let targets_input_dir = TempDir::new().unwrap();
+    let repo_dir = TempDir::new().unwrap();
+    let load_dir = TempDir::new().unwrap();
+    for dir in &["a", "b"] {
+        std::fs::create_dir(targets_input_dir.path().join(dir)).unwrap();
+        std::fs::write(targets_input_dir.path().join(dir).join("foo.txt"), dir).unwrap();
+    }
+
+    Command::cargo_bin("tuftool")
+        .unwrap()
+        .args(&[
+            "create",
+            "--preserve-paths",
+            "-t",
+            targets_input_dir.path().to_str().unwrap(),
+            "-o",
+            repo_dir.path().to_str().unwrap(),
+            "-k",
+            root_key.to_str().unwrap(),
+            "--root",
+            root_json.to_str().unwrap(),
+            "--targets-expires",
+            expiration.to_rfc3339().as_str(),
+            "--targets-version",
+            "1",
+            "--snapshot-expires",
+            expiration.to_rfc3339().as_str(),
+            "--snapshot-version",
+            "1",
+            "--timestamp-expires",
+            expiration.to_rfc3339().as_str(),
+            "--timestamp-version",
+            "1",
+        ])
+        .assert()
+        .success();
+
+    let metadata_base_url = &test_utils::dir_url(repo_dir.path().join("metadata"));
+    let targets_base_url = &test_utils::dir_url(repo_dir.path().join("targets"));
+    let repo = Repository::load(
+        &tough::FilesystemTransport,
+        Settings::new(
+            File::open(root_json).unwrap(),
+            load_dir.as_ref(),
+            metadata_base_url,
+            targets_base_url,
+        ),
+    )
+    .unwrap();
+
+    assert_eq!(repo.targets().signed.targets.len(), 2);
+    assert_eq!(
+        test_utils::read_to_end(repo.read_target("a/foo.txt").unwrap().unwrap()),
+        &b"a"[..]
+    );
+    assert_eq!(
+        test_utils::read_to_end(repo.read_target("b/foo.txt").unwrap().unwrap()),
+        &b"b"[..]
+    );
+}
+
+#[test]
+// Ensure that targets listed in a manifest are added with their custom metadata, along with the
+// custom metadata given on the command line
+fn create_command_manifest() {
+    let expiration = Utc::now().checked_add_signed(Duration::days(3)).unwrap();
+    let root_json = test_utils::test_data().join("simple-rsa").join("root.json");
+    let root_key = test_utils::test_data().join("snakeoil.pem");
+    let targets_dir = test_utils::test_data()
+        .join("tuf-reference-impl")
+        .join("targets");
+    let manifest_dir = TempDir::new().unwrap();
+    let repo_dir = TempDir::new().unwrap();
+    let load_dir = TempDir::new().unwrap();
+    let manifest = manifest_dir.path().join("manifest.toml");
+    std::fs::write(
+        &manifest,
+        format!(
+            r#"
+[targets."images/file1.txt"]
+path = "{}"
+
+[targets."images/file1.txt".custom]
+version = "1.2.3"
+variant = "aws-k8s"
+
+[targets."file2.txt"]
+path = "{}"
+"#,
+            targets_dir.join("file1.txt").display(),
+            targets_dir.join("file2.txt").display(),
+        ),
+    )
+    .unwrap();
+
+    Command::cargo_bin("tuftool")
+        .unwrap()
+        .args(&[
+            "create",
+            "--targets-manifest",
+            manifest.to_str().unwrap(),
+            "--custom",
+            "variant=\"aws-dev\"",
+            "--custom",
+            "signed=true",
+            "-o",
+            repo_dir.path().to_str().unwrap(),
+            "-k",
+            root_key.to_str().unwrap(),
+            "--root",
+            root_json.to_str().unwrap(),
+            "--targets-expires",
+            expiration.to_rfc3339().as_str(),
+            "--targets-version",
+            "1",
+            "--snapshot-expires",
+            expiration.to_rfc3339().as_str(),
+            "--snapshot-version",
+            "1",
+            "--timestamp-expires",
+            expiration.to_rfc3339().as_str(),
+            "--timestamp-version",
+            "1",
+        ])
+        .assert()
+        .success();
+
+    let metadata_base_url = &test_utils::dir_url(repo_dir.path().join("metadata"));
+    let targets_base_url = &test_utils::dir_url(repo_dir.path().join("targets"));
+    let repo = Repository::load(
+        &tough::FilesystemTransport,
+        Settings::new(
+            File::open(root_json).unwrap(),
+            load_dir.as_ref(),
+            metadata_base_url,
+            targets_base_url,
+        ),
+    )
+    .unwrap();
+
+    // The manifest's custom metadata takes precedence over the command line's
+    let targets = &repo.targets().signed.targets;
+    assert_eq!(targets.len(), 2);
+    let custom = &targets["images/file1.txt"].custom;
+    assert_eq!(custom["version"], "1.2.3");
+    assert_eq!(custom["variant"], "aws-k8s");
+    assert_eq!(custom["signed"], true);
+    let custom = &targets["file2.txt"].custom;
+    assert_eq!(custom["variant"], "aws-dev");
+    assert_eq!(custom["signed"], true);
+    assert_eq!(
+        test_utils::read_to_end(repo.read_target("images/file1.txt").unwrap().unwrap()),
+        &b"This is an example target file."[..]
+    );
+}
+
+#[test]
+// Ensure that custom metadata that isn't JSON, and manifest targets that are also in the targets
+// directory, are rejected
+fn create_command_manifest_errors() {
+    let expiration = Utc::now().checked_add_signed(Duration::days(3)).unwrap();
+    let root_json = test_utils::test_data().join("simple-rsa").join("root.json");
+    let root_key = test_utils::test_data().join("snakeoil.pem");
+    let targets_dir = test_utils::test_data()
+        .join("tuf-reference-impl")
+        .join("targets");
+    let manifest_dir = TempDir::new().unwrap();
+    let manifest = manifest_dir.path().join("manifest.toml");
+    std::fs::write(
+        &manifest,
+        format!(
+            "[targets.\"file1.txt\"]\npath = \"{}\"\n",
+            targets_dir.join("file1.txt").display(),
+        ),
+    )
+    .unwrap();
+
+    let create = |args: &[&str]| {
+        let repo_dir = TempDir::new().unwrap();
+        let output = Command::cargo_bin("tuftool")
+            .unwrap()
+            .arg("create")
+            .args(args)
+            .args(&[
+                "-o",
+                repo_dir.path().to_str().unwrap(),
+                "-k",
+                root_key.to_str().unwrap(),
+                "--root",
+                root_json.to_str().unwrap(),
+                "--targets-expires",
+                expiration.to_rfc3339().as_str(),
+                "--targets-version",
+                "1",
+                "--snapshot-expires",
+                expiration.to_rfc3339().as_str(),
+                "--snapshot-version",
+                "1",
+                "--timestamp-expires",
+                expiration.to_rfc3339().as_str(),
+                "--timestamp-version",
+                "1",
+            ])
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        String::from_utf8(output.stderr).unwrap()
+    };
+
+    assert!(create(&[
+        "--targets-manifest",
+        manifest.to_str().unwrap(),
+        "--custom",
+        "variant=aws-dev",
+    ])
+    .contains("must be JSON"));
+    assert!(create(&[
+        "--targets-manifest",
+        manifest.to_str().unwrap(),
+        "-t",
+        targets_dir.to_str().unwrap(),
+    ])
+    .contains("'file1.txt' is in both"));
 }
 
 #[test]
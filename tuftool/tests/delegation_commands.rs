@@ -8,7 +8,7 @@ use chrono::{Duration, Utc};
 use std::fs::File;
 use std::path::Path;
 use tempfile::TempDir;
-use tough::{ExpirationEnforcement, Limits, Repository, Settings};
+use tough::{Repository, Settings};
 
 fn create_repo<P: AsRef<Path>>(repo_dir: P) {
     let timestamp_expiration = Utc::now().checked_add_signed(Duration::days(1)).unwrap();
@@ -145,14 +145,12 @@ fn create_add_role_command() {
     let updated_targets_base_url = &test_utils::dir_url(new_repo_dir.path().join("targets"));
     let repo = Repository::load(
         &tough::FilesystemTransport,
-        Settings {
-            root: File::open(&root_json).unwrap(),
-            datastore: temp_datastore.as_ref(),
-            metadata_base_url: updated_metadata_base_url,
-            targets_base_url: updated_targets_base_url,
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            File::open(&root_json).unwrap(),
+            temp_datastore.as_ref(),
+            updated_metadata_base_url,
+            updated_targets_base_url,
+        ),
     )
     .unwrap();
     // Make sure `A` is added as a role
@@ -253,14 +251,12 @@ fn create_add_role_command() {
     let updated_targets_base_url = &test_utils::dir_url(update_out.path().join("targets"));
     let repo = Repository::load(
         &tough::FilesystemTransport,
-        Settings {
-            root: File::open(root_json).unwrap(),
-            datastore: temp_datastore.as_ref(),
-            metadata_base_url: updated_metadata_base_url,
-            targets_base_url: updated_targets_base_url,
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            File::open(root_json).unwrap(),
+            temp_datastore.as_ref(),
+            updated_metadata_base_url,
+            updated_targets_base_url,
+        ),
     )
     .unwrap();
 
@@ -432,14 +428,12 @@ fn update_target_command() {
     let updated_targets_base_url = &test_utils::dir_url(update_out.path().join("targets"));
     let repo = Repository::load(
         &tough::FilesystemTransport,
-        Settings {
-            root: File::open(root_json).unwrap(),
-            datastore: temp_datastore.as_ref(),
-            metadata_base_url: updated_metadata_base_url,
-            targets_base_url: updated_targets_base_url,
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            File::open(root_json).unwrap(),
+            temp_datastore.as_ref(),
+            updated_metadata_base_url,
+            updated_targets_base_url,
+        ),
     )
     .unwrap();
 
@@ -693,14 +687,12 @@ fn add_key_command() {
     let updated_targets_base_url = &test_utils::dir_url(update_out.path().join("targets"));
     let _repo = Repository::load(
         &tough::FilesystemTransport,
-        Settings {
-            root: File::open(root_json).unwrap(),
-            datastore: temp_datastore.as_ref(),
-            metadata_base_url: updated_metadata_base_url,
-            targets_base_url: updated_targets_base_url,
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            File::open(root_json).unwrap(),
+            temp_datastore.as_ref(),
+            updated_metadata_base_url,
+            updated_targets_base_url,
+        ),
     )
     .unwrap();
 }
@@ -996,14 +988,12 @@ fn remove_role_command() {
     let updated_targets_base_url = &test_utils::dir_url(new_repo_dir.path().join("targets"));
     let repo = Repository::load(
         &tough::FilesystemTransport,
-        Settings {
-            root: File::open(&root_json).unwrap(),
-            datastore: temp_datastore.as_ref(),
-            metadata_base_url: updated_metadata_base_url,
-            targets_base_url: updated_targets_base_url,
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            File::open(&root_json).unwrap(),
+            temp_datastore.as_ref(),
+            updated_metadata_base_url,
+            updated_targets_base_url,
+        ),
     )
     .unwrap();
     // Make sure `A` is added as a role
@@ -1183,14 +1173,12 @@ fn remove_role_command() {
     let updated_targets_base_url = &test_utils::dir_url(update_out.path().join("targets"));
     let repo = Repository::load(
         &tough::FilesystemTransport,
-        Settings {
-            root: File::open(root_json).unwrap(),
-            datastore: temp_datastore.as_ref(),
-            metadata_base_url: updated_metadata_base_url,
-            targets_base_url: updated_targets_base_url,
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            File::open(root_json).unwrap(),
+            temp_datastore.as_ref(),
+            updated_metadata_base_url,
+            updated_targets_base_url,
+        ),
     )
     .unwrap();
 
@@ -1285,14 +1273,12 @@ fn remove_role_recursive_command() {
     let updated_targets_base_url = &test_utils::dir_url(new_repo_dir.path().join("targets"));
     let repo = Repository::load(
         &tough::FilesystemTransport,
-        Settings {
-            root: File::open(&root_json).unwrap(),
-            datastore: temp_datastore.as_ref(),
-            metadata_base_url: updated_metadata_base_url,
-            targets_base_url: updated_targets_base_url,
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            File::open(&root_json).unwrap(),
+            temp_datastore.as_ref(),
+            updated_metadata_base_url,
+            updated_targets_base_url,
+        ),
     )
     .unwrap();
     // Make sure `A` is added as a role
@@ -1473,14 +1459,12 @@ fn remove_role_recursive_command() {
     let updated_targets_base_url = &test_utils::dir_url(update_out.path().join("targets"));
     let repo = Repository::load(
         &tough::FilesystemTransport,
-        Settings {
-            root: File::open(root_json).unwrap(),
-            datastore: temp_datastore.as_ref(),
-            metadata_base_url: updated_metadata_base_url,
-            targets_base_url: updated_targets_base_url,
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            File::open(root_json).unwrap(),
+            temp_datastore.as_ref(),
+            updated_metadata_base_url,
+            updated_targets_base_url,
+        ),
     )
     .unwrap();
 
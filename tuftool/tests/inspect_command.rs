@@ -0,0 +1,47 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+mod test_utils;
+
+use assert_cmd::Command;
+use serde_json::Value;
+
+#[test]
+// Ensure that the inspect command prints a JSON report of every role in the repository.
+fn inspect_command_reports_roles() {
+    let repo_dir = test_utils::test_data().join("tuf-reference-impl");
+    let root_json = repo_dir.join("metadata").join("root.json");
+    let metadata_base_url = test_utils::dir_url(repo_dir.join("metadata"));
+
+    let output = Command::cargo_bin("tuftool")
+        .unwrap()
+        .args(&[
+            "inspect",
+            "-r",
+            root_json.to_str().unwrap(),
+            "--metadata-url",
+            metadata_base_url.as_str(),
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let roles = report["roles"].as_array().unwrap();
+    let names: Vec<&str> = roles
+        .iter()
+        .map(|role| role["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        vec!["root", "timestamp", "snapshot", "targets", "role1", "role2"]
+    );
+    assert_eq!(roles[5]["delegated_by"], "role1");
+    for role in roles {
+        assert!(role["keys"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|key| key["signed"] == true));
+    }
+}
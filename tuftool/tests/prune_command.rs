@@ -0,0 +1,139 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+mod test_utils;
+
+use assert_cmd::Command;
+use chrono::{Duration, Utc};
+use std::path::Path;
+use tempfile::TempDir;
+
+// Creates a repo at `version` in `repo_dir` whose targets are the named reference targets
+fn create_repo(repo_dir: &Path, version: &str, targets: &[&str]) {
+    let expiration = Utc::now().checked_add_signed(Duration::days(1)).unwrap();
+    let root_json = test_utils::test_data().join("simple-rsa").join("root.json");
+    let root_key = test_utils::test_data().join("snakeoil.pem");
+    let targets_input_dir = TempDir::new().unwrap();
+    for target in targets {
+        std::fs::copy(
+            test_utils::test_data()
+                .join("tuf-reference-impl")
+                .join("targets")
+                .join(target),
+            targets_input_dir.path().join(target),
+        )
+        .unwrap();
+    }
+
+    Command::cargo_bin("tuftool")
+        .unwrap()
+        .args(&[
+            "create",
+            "-t",
+            targets_input_dir.path().to_str().unwrap(),
+            "-o",
+            repo_dir.to_str().unwrap(),
+            "-k",
+            root_key.to_str().unwrap(),
+            "--root",
+            root_json.to_str().unwrap(),
+            "--targets-expires",
+            expiration.to_rfc3339().as_str(),
+            "--targets-version",
+            version,
+            "--snapshot-expires",
+            expiration.to_rfc3339().as_str(),
+            "--snapshot-version",
+            version,
+            "--timestamp-expires",
+            expiration.to_rfc3339().as_str(),
+            "--timestamp-version",
+            version,
+        ])
+        .assert()
+        .success();
+}
+
+// Returns the number of files in `dir` whose names end with `name`
+fn count_files(dir: &Path, name: &str) -> usize {
+    std::fs::read_dir(dir)
+        .unwrap()
+        .filter(|entry| {
+            entry
+                .as_ref()
+                .unwrap()
+                .file_name()
+                .to_str()
+                .unwrap()
+                .ends_with(name)
+        })
+        .count()
+}
+
+#[test]
+// Ensure `tuftool prune` lists stale files with --dry-run, and removes them without it
+fn prune_command() {
+    let repo_dir = TempDir::new().unwrap();
+    create_repo(repo_dir.path(), "1", &["file1.txt", "file2.txt"]);
+    create_repo(repo_dir.path(), "2", &["file1.txt"]);
+    let metadata_dir = repo_dir.path().join("metadata");
+    let targets_dir = repo_dir.path().join("targets");
+    std::fs::write(targets_dir.join("unknown.txt"), b"from another repository").unwrap();
+
+    let output = Command::cargo_bin("tuftool")
+        .unwrap()
+        .args(&[
+            "prune",
+            "-o",
+            repo_dir.path().to_str().unwrap(),
+            "--keep",
+            "1",
+            "--dry-run",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 3);
+    assert!(stdout.contains("1.targets.json"));
+    assert!(stdout.contains("file2.txt"));
+    assert!(!stdout.contains("unknown.txt"));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("unknown.txt"));
+    assert!(metadata_dir.join("1.targets.json").exists());
+    assert_eq!(count_files(&targets_dir, "file2.txt"), 1);
+
+    Command::cargo_bin("tuftool")
+        .unwrap()
+        .args(&[
+            "prune",
+            "-o",
+            repo_dir.path().to_str().unwrap(),
+            "--keep",
+            "1",
+        ])
+        .assert()
+        .success();
+    assert!(!metadata_dir.join("1.targets.json").exists());
+    assert!(!metadata_dir.join("1.snapshot.json").exists());
+    assert!(metadata_dir.join("2.targets.json").exists());
+    assert!(metadata_dir.join("1.root.json").exists());
+    assert!(metadata_dir.join("timestamp.json").exists());
+    assert_eq!(count_files(&targets_dir, "file1.txt"), 1);
+    assert_eq!(count_files(&targets_dir, "file2.txt"), 0);
+    assert!(targets_dir.join("unknown.txt").exists());
+
+    Command::cargo_bin("tuftool")
+        .unwrap()
+        .args(&[
+            "prune",
+            "-o",
+            repo_dir.path().to_str().unwrap(),
+            "--remove-unknown-targets",
+        ])
+        .assert()
+        .success();
+    assert!(!targets_dir.join("unknown.txt").exists());
+    assert_eq!(count_files(&targets_dir, "file1.txt"), 1);
+}
@@ -0,0 +1,208 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+mod test_utils;
+
+use assert_cmd::Command;
+use chrono::{DateTime, Duration, Utc};
+use std::fs::File;
+use std::path::Path;
+use tempfile::TempDir;
+use tough::{FilesystemTransport, Repository, Settings};
+
+fn create_repo<P: AsRef<Path>>(repo_dir: P, timestamp_expiration: DateTime<Utc>) {
+    let expiration = Utc::now().checked_add_signed(Duration::days(1)).unwrap();
+    let targets_input_dir = test_utils::test_data()
+        .join("tuf-reference-impl")
+        .join("targets");
+    let root_json = test_utils::test_data().join("simple-rsa").join("root.json");
+    let root_key = test_utils::test_data().join("snakeoil.pem");
+
+    // Create a repo using tuftool and the reference tuf implementation data
+    Command::cargo_bin("tuftool")
+        .unwrap()
+        .args(&[
+            "create",
+            "-t",
+            targets_input_dir.to_str().unwrap(),
+            "-o",
+            repo_dir.as_ref().to_str().unwrap(),
+            "-k",
+            root_key.to_str().unwrap(),
+            "--root",
+            root_json.to_str().unwrap(),
+            "--targets-expires",
+            expiration.to_rfc3339().as_str(),
+            "--targets-version",
+            "17",
+            "--snapshot-expires",
+            expiration.to_rfc3339().as_str(),
+            "--snapshot-version",
+            "25",
+            "--timestamp-expires",
+            timestamp_expiration.to_rfc3339().as_str(),
+            "--timestamp-version",
+            "31",
+        ])
+        .assert()
+        .success();
+}
+
+fn load_repo<'a>(
+    metadata_base_url: &'a str,
+    datastore: &'a Path,
+) -> Repository<'a, FilesystemTransport> {
+    let root_json = test_utils::test_data().join("simple-rsa").join("root.json");
+    Repository::load(
+        &FilesystemTransport,
+        Settings::new(
+            File::open(root_json).unwrap(),
+            datastore,
+            metadata_base_url,
+            metadata_base_url,
+        ),
+    )
+    .unwrap()
+}
+
+#[test]
+// Ensure `tuftool refresh` re-signs only the timestamp, and with --snapshot, the snapshot too
+fn refresh_command() {
+    let root_json = test_utils::test_data().join("simple-rsa").join("root.json");
+    let root_key = test_utils::test_data().join("snakeoil.pem");
+    let repo_dir = TempDir::new().unwrap();
+    create_repo(
+        repo_dir.path(),
+        Utc::now().checked_add_signed(Duration::days(1)).unwrap(),
+    );
+    let metadata_base_url = &test_utils::dir_url(repo_dir.path().join("metadata"));
+    let new_expiration = Utc::now().checked_add_signed(Duration::days(4)).unwrap();
+
+    // Re-sign the timestamp in place, letting its version default
+    Command::cargo_bin("tuftool")
+        .unwrap()
+        .args(&[
+            "refresh",
+            "-o",
+            repo_dir.path().to_str().unwrap(),
+            "-k",
+            root_key.to_str().unwrap(),
+            "--root",
+            root_json.to_str().unwrap(),
+            "--metadata-url",
+            metadata_base_url,
+            "--timestamp-expires",
+            new_expiration.to_rfc3339().as_str(),
+        ])
+        .assert()
+        .success();
+
+    let datastore = TempDir::new().unwrap();
+    let repo = load_repo(metadata_base_url, datastore.path());
+    assert_eq!(repo.timestamp().signed.version.get(), 32);
+    assert_eq!(repo.timestamp().signed.expires, new_expiration);
+    assert_eq!(repo.snapshot().unwrap().signed.version.get(), 25);
+    assert_eq!(repo.targets().signed.version.get(), 17);
+
+    // Re-sign the snapshot and timestamp in place
+    Command::cargo_bin("tuftool")
+        .unwrap()
+        .args(&[
+            "refresh",
+            "--snapshot",
+            "-o",
+            repo_dir.path().to_str().unwrap(),
+            "-k",
+            root_key.to_str().unwrap(),
+            "--root",
+            root_json.to_str().unwrap(),
+            "--metadata-url",
+            metadata_base_url,
+            "--snapshot-expires",
+            new_expiration.to_rfc3339().as_str(),
+            "--snapshot-version",
+            "250",
+            "--timestamp-expires",
+            new_expiration.to_rfc3339().as_str(),
+        ])
+        .assert()
+        .success();
+
+    let datastore = TempDir::new().unwrap();
+    let repo = load_repo(metadata_base_url, datastore.path());
+    assert_eq!(repo.timestamp().signed.version.get(), 33);
+    assert_eq!(repo.snapshot().unwrap().signed.version.get(), 250);
+    assert_eq!(repo.snapshot().unwrap().signed.expires, new_expiration);
+    assert_eq!(repo.targets().signed.version.get(), 17);
+    assert_eq!(repo.targets().signed.targets.len(), 3);
+}
+
+#[test]
+// Ensure `tuftool refresh --snapshot` requires a snapshot expiration
+fn refresh_command_snapshot_requires_expiration() {
+    let root_json = test_utils::test_data().join("simple-rsa").join("root.json");
+    let root_key = test_utils::test_data().join("snakeoil.pem");
+    let repo_dir = TempDir::new().unwrap();
+    create_repo(
+        repo_dir.path(),
+        Utc::now().checked_add_signed(Duration::days(1)).unwrap(),
+    );
+    let metadata_base_url = &test_utils::dir_url(repo_dir.path().join("metadata"));
+    let new_expiration = Utc::now().checked_add_signed(Duration::days(4)).unwrap();
+
+    Command::cargo_bin("tuftool")
+        .unwrap()
+        .args(&[
+            "refresh",
+            "--snapshot",
+            "-o",
+            repo_dir.path().to_str().unwrap(),
+            "-k",
+            root_key.to_str().unwrap(),
+            "--root",
+            root_json.to_str().unwrap(),
+            "--metadata-url",
+            metadata_base_url,
+            "--timestamp-expires",
+            new_expiration.to_rfc3339().as_str(),
+        ])
+        .assert()
+        .failure();
+}
+
+#[test]
+// Ensure `tuftool refresh` can replace a timestamp that has already expired
+fn refresh_command_expired_timestamp() {
+    let root_json = test_utils::test_data().join("simple-rsa").join("root.json");
+    let root_key = test_utils::test_data().join("snakeoil.pem");
+    let repo_dir = TempDir::new().unwrap();
+    create_repo(
+        repo_dir.path(),
+        Utc::now().checked_sub_signed(Duration::days(1)).unwrap(),
+    );
+    let metadata_base_url = &test_utils::dir_url(repo_dir.path().join("metadata"));
+    let new_expiration = Utc::now().checked_add_signed(Duration::days(4)).unwrap();
+
+    Command::cargo_bin("tuftool")
+        .unwrap()
+        .args(&[
+            "refresh",
+            "-o",
+            repo_dir.path().to_str().unwrap(),
+            "-k",
+            root_key.to_str().unwrap(),
+            "--root",
+            root_json.to_str().unwrap(),
+            "--metadata-url",
+            metadata_base_url,
+            "--timestamp-expires",
+            new_expiration.to_rfc3339().as_str(),
+        ])
+        .assert()
+        .success();
+
+    let datastore = TempDir::new().unwrap();
+    let repo = load_repo(metadata_base_url, datastore.path());
+    assert_eq!(repo.timestamp().signed.version.get(), 32);
+    assert_eq!(repo.timestamp().signed.expires, new_expiration);
+}
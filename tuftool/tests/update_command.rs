@@ -8,7 +8,7 @@ use chrono::{Duration, Utc};
 use std::fs::File;
 use std::path::Path;
 use tempfile::TempDir;
-use tough::{ExpirationEnforcement, Limits, Repository, Settings};
+use tough::{Repository, Settings};
 
 fn create_repo<P: AsRef<Path>>(repo_dir: P) {
     let timestamp_expiration = Utc::now().checked_add_signed(Duration::days(1)).unwrap();
@@ -108,14 +108,12 @@ fn update_command_without_new_targets() {
     let updated_targets_base_url = &test_utils::dir_url(update_out.path().join("targets"));
     let repo = Repository::load(
         &tough::FilesystemTransport,
-        Settings {
-            root: File::open(root_json).unwrap(),
-            datastore: temp_datastore.as_ref(),
-            metadata_base_url: updated_metadata_base_url,
-            targets_base_url: updated_targets_base_url,
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            File::open(root_json).unwrap(),
+            temp_datastore.as_ref(),
+            updated_metadata_base_url,
+            updated_targets_base_url,
+        ),
     )
     .unwrap();
 
@@ -125,8 +123,14 @@ fn update_command_without_new_targets() {
     // Ensure all the metadata has been updated
     assert_eq!(repo.targets().signed.version.get(), new_targets_version);
     assert_eq!(repo.targets().signed.expires, new_targets_expiration);
-    assert_eq!(repo.snapshot().signed.version.get(), new_snapshot_version);
-    assert_eq!(repo.snapshot().signed.expires, new_snapshot_expiration);
+    assert_eq!(
+        repo.snapshot().unwrap().signed.version.get(),
+        new_snapshot_version
+    );
+    assert_eq!(
+        repo.snapshot().unwrap().signed.expires,
+        new_snapshot_expiration
+    );
     assert_eq!(repo.timestamp().signed.version.get(), new_timestamp_version);
     assert_eq!(repo.timestamp().signed.expires, new_timestamp_expiration);
 }
@@ -190,14 +194,12 @@ fn update_command_with_new_targets() {
     let updated_targets_base_url = &test_utils::dir_url(update_out.path().join("targets"));
     let repo = Repository::load(
         &tough::FilesystemTransport,
-        Settings {
-            root: File::open(root_json).unwrap(),
-            datastore: temp_datastore.as_ref(),
-            metadata_base_url: updated_metadata_base_url,
-            targets_base_url: updated_targets_base_url,
-            limits: Limits::default(),
-            expiration_enforcement: ExpirationEnforcement::Safe,
-        },
+        Settings::new(
+            File::open(root_json).unwrap(),
+            temp_datastore.as_ref(),
+            updated_metadata_base_url,
+            updated_targets_base_url,
+        ),
     )
     .unwrap();
 
@@ -221,8 +223,14 @@ fn update_command_with_new_targets() {
     // Ensure all the metadata has been updated
     assert_eq!(repo.targets().signed.version.get(), new_targets_version);
     assert_eq!(repo.targets().signed.expires, new_targets_expiration);
-    assert_eq!(repo.snapshot().signed.version.get(), new_snapshot_version);
-    assert_eq!(repo.snapshot().signed.expires, new_snapshot_expiration);
+    assert_eq!(
+        repo.snapshot().unwrap().signed.version.get(),
+        new_snapshot_version
+    );
+    assert_eq!(
+        repo.snapshot().unwrap().signed.expires,
+        new_snapshot_expiration
+    );
     assert_eq!(repo.timestamp().signed.version.get(), new_timestamp_version);
     assert_eq!(repo.timestamp().signed.expires, new_timestamp_expiration);
 }
@@ -263,6 +271,47 @@ fn update_with_incorrect_key() {
         .failure();
 }
 
+#[test]
+// Ensure that custom metadata without any targets to add is rejected
+fn update_with_custom_and_no_new_targets() {
+    let root_json = test_utils::test_data().join("simple-rsa").join("root.json");
+    let root_key = test_utils::test_data().join("snakeoil.pem");
+
+    let output = Command::cargo_bin("tuftool")
+        .unwrap()
+        .args(&[
+            "update",
+            "--outdir",
+            "/outdir/does/not/matter",
+            "-k",
+            root_key.to_str().unwrap(),
+            "--root",
+            root_json.to_str().unwrap(),
+            "--metadata-url",
+            "https://metadata.url.does.not.matter",
+            "--custom",
+            "variant=\"aws-dev\"",
+            "--targets-expires",
+            "in 7 days",
+            "--targets-version",
+            "1234",
+            "--snapshot-expires",
+            "in 7 days",
+            "--snapshot-version",
+            "1234",
+            "--timestamp-expires",
+            "in 7 days",
+            "--timestamp-version",
+            "1234",
+        ])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("no targets to add"));
+}
+
 #[test]
 // Ensure we fail if no key is provided
 fn update_with_no_key() {